@@ -90,3 +90,84 @@ type CacheConfig struct {
 	// matching provider is configured.
 	OIDCAudience string `json:"oidc_audience,omitempty"`
 }
+
+// CacheInfo is the body of PUT /api/cache-info and describes the
+// nix-cache-info object nix clients read before using the cache.
+type CacheInfo struct {
+	// StoreDir is the Nix store directory the cache serves, e.g. /nix/store.
+	StoreDir string `json:"store_dir"`
+
+	// WantMassQuery lets nix query the cache for many paths at once.
+	WantMassQuery bool `json:"want_mass_query"`
+
+	// Priority orders substituters; lower values are tried first.
+	Priority int `json:"priority"`
+}
+
+// DeleteResult is returned by DELETE /api/objects/{key} for a narinfo key.
+type DeleteResult struct {
+	// Deleted lists the narinfo and the NAR, listing and realisations only it
+	// referenced: the objects removed, or that would be removed in a dry run.
+	Deleted []string `json:"deleted"`
+
+	// DryRun is set if nothing was removed.
+	DryRun bool `json:"dry_run"`
+}
+
+// DeleteConflictResponse is returned with 409 Conflict when a narinfo cannot
+// be deleted because it is pinned or still referenced.
+type DeleteConflictResponse struct {
+	Error     string   `json:"error"`
+	Pins      []string `json:"pins,omitempty"`
+	Referrers []string `json:"referrers,omitempty"`
+}
+
+// AddSignaturesRequest is the body of POST /api/signatures/{key}, which adds
+// signatures to a narinfo already in the cache, e.g. when rotating keys.
+type AddSignaturesRequest struct {
+	// Signatures are "<key name>:<base64 signature>" values for Sig lines.
+	Signatures []string `json:"signatures"`
+}
+
+// AddSignaturesResponse is returned by POST /api/signatures/{key}.
+type AddSignaturesResponse struct {
+	// Added counts the signatures the narinfo did not carry yet.
+	Added int `json:"added"`
+}
+
+// NarinfoExistsRequest is the body of POST /api/narinfo_exists, which looks
+// up many narinfos at once so a push can skip cached paths without one HEAD
+// request per path.
+type NarinfoExistsRequest struct {
+	// Hashes are store path hashes, e.g. "26xbg1ndr7hbcncrlf9nhx5is2b25d13".
+	Hashes []string `json:"hashes"`
+
+	// KeyPrefix namespaces the narinfos, as for pending closures.
+	KeyPrefix string `json:"key_prefix,omitempty"`
+}
+
+// NarinfoExistsResponse is returned by POST /api/narinfo_exists.
+type NarinfoExistsResponse struct {
+	// Present lists the requested hashes whose narinfo is in the cache.
+	Present []string `json:"present"`
+}
+
+// CompletePendingClosuresRequest is the body of POST
+// /api/pending_closures/complete, which completes several pending closures
+// at the end of a push with one request instead of one per closure.
+type CompletePendingClosuresRequest struct {
+	// IDs are the pending closure ids returned when they were created.
+	IDs []string `json:"ids"`
+}
+
+// CompletePendingClosuresResponse is returned by POST
+// /api/pending_closures/complete.
+type CompletePendingClosuresResponse struct {
+	// Completed lists the ids whose closures are now registered.
+	Completed []string `json:"completed"`
+
+	// Failed maps the ids that could not be completed to the reason.
+	// Ids in neither Completed nor Failed were not tried, e.g. because the
+	// request was cancelled, and are still pending.
+	Failed map[string]string `json:"failed,omitempty"`
+}
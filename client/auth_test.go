@@ -4,6 +4,7 @@ import (
 	"context"
 	"net/http"
 	"net/http/httptest"
+	"strings"
 	"testing"
 
 	"github.com/Mic92/niks3/client"
@@ -48,3 +49,41 @@ func TestDoServerRequestAttachesToken(t *testing.T) {
 		tok = "tok-b" // rotate for the next iteration
 	}
 }
+
+// TestUserAgent verifies requests carry the configured User-Agent with the
+// suffix appended.
+func TestUserAgent(t *testing.T) {
+	t.Parallel()
+
+	var seen string
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		seen = r.Header.Get("User-Agent")
+
+		w.WriteHeader(http.StatusOK)
+	}))
+	defer srv.Close()
+
+	c := client.NewTestClient(&http.Client{}, client.DefaultRetryConfig())
+	c.SetUserAgentSuffix("ci/42")
+
+	req, err := http.NewRequestWithContext(t.Context(), http.MethodGet, srv.URL, nil)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	resp, err := c.DoS3Request(t.Context(), req)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	_ = resp.Body.Close()
+
+	if want := client.DefaultUserAgent() + " ci/42"; seen != want {
+		t.Fatalf("User-Agent = %q, want %q", seen, want)
+	}
+
+	if !strings.HasPrefix(seen, "niks3/") {
+		t.Fatalf("User-Agent %q does not start with niks3/", seen)
+	}
+}
@@ -1,6 +1,8 @@
 package client
 
 import (
+	"bytes"
+	"cmp"
 	"compress/bzip2"
 	"errors"
 	"fmt"
@@ -8,9 +10,11 @@ import (
 	"log/slog"
 	"os"
 	"path/filepath"
+	"slices"
 	"strings"
 
 	"github.com/klauspost/compress/zstd"
+	"golang.org/x/sys/unix"
 )
 
 // GetBuildLogPath finds the build log file for a derivation path.
@@ -57,15 +61,25 @@ func GetBuildLogPath(drvPath string) (string, error) {
 	return "", nil
 }
 
+// DefaultInMemoryLogLimit is the size up to which build logs are compressed
+// in memory rather than staged in a temporary file. Most logs are far
+// smaller, so pushes of many small derivations touch the disk only for
+// unusually verbose builds.
+const DefaultInMemoryLogLimit = 4 << 20
+
 // CompressedBuildLogInfo contains information about the compressed build log.
+// The log is either held in Data or staged in TempFile.
 type CompressedBuildLogInfo struct {
-	TempFile string // Path to temporary file containing compressed log
+	TempFile string // Path to temporary file containing compressed log ("" if held in Data)
+	Data     []byte // Compressed log, for logs compressed in memory
 	Size     int64  // Size of compressed log
 }
 
 // Cleanup removes the temporary compressed log file.
 // It's safe to call multiple times.
 func (info *CompressedBuildLogInfo) Cleanup() error {
+	info.Data = nil
+
 	if info.TempFile == "" {
 		return nil
 	}
@@ -80,10 +94,30 @@ func (info *CompressedBuildLogInfo) Cleanup() error {
 	return nil
 }
 
-// CompressBuildLog reads and compresses a build log file to a temporary file.
+// removeStagingDir removes a push's log staging directory. Files are only
+// left in it if uploads were canceled before cleaning up after themselves.
+func removeStagingDir(dir string) {
+	if entries, err := os.ReadDir(dir); err == nil && len(entries) > 0 {
+		slog.Info("Removing build logs left staged by canceled uploads", "dir", dir, "count", len(entries))
+	}
+
+	if err := os.RemoveAll(dir); err != nil {
+		slog.Warn("Failed to remove staging directory", "dir", dir, "error", err)
+	}
+}
+
+// CompressBuildLog reads and compresses a build log file to a temporary file in
+// tempDir (the system default if empty).
 // It automatically decompresses .bz2 source files and recompresses with zstd.
 // Returns info about the compressed temp file. The caller must call Cleanup() when done.
-func CompressBuildLog(logPath string) (*CompressedBuildLogInfo, error) {
+func CompressBuildLog(logPath, tempDir string) (*CompressedBuildLogInfo, error) {
+	return compressBuildLog(logPath, tempDir, 0)
+}
+
+// compressBuildLog is CompressBuildLog, except that logs of at most
+// memoryLimit bytes (0 = none) are compressed into memory instead of a
+// temporary file.
+func compressBuildLog(logPath, tempDir string, memoryLimit int64) (*CompressedBuildLogInfo, error) {
 	// Open source log file
 	srcFile, err := os.Open(logPath)
 	if err != nil {
@@ -104,8 +138,24 @@ func CompressBuildLog(logPath string) (*CompressedBuildLogInfo, error) {
 		reader = io.LimitReader(bzip2.NewReader(srcFile), 1<<30) // 1GB limit
 	}
 
+	srcInfo, err := srcFile.Stat()
+	if err != nil {
+		return nil, fmt.Errorf("stat build log: %w", err)
+	}
+
+	// The source size is a good estimate for the output: a bzip2 log
+	// recompressed with zstd ends up at a similar size.
+	if memoryLimit > 0 && srcInfo.Size() <= memoryLimit {
+		data, err := compressBuildLogToMemory(reader)
+		if err != nil {
+			return nil, err
+		}
+
+		return &CompressedBuildLogInfo{Data: data, Size: int64(len(data))}, nil
+	}
+
 	// Create temporary file for compressed output
-	tempFile, err := os.CreateTemp("", "buildlog-*.zst")
+	tempFile, err := os.CreateTemp(tempDir, "buildlog-*.zst")
 	if err != nil {
 		return nil, fmt.Errorf("creating temp file: %w", err)
 	}
@@ -163,3 +213,87 @@ func CompressBuildLog(logPath string) (*CompressedBuildLogInfo, error) {
 		Size:     stat.Size(),
 	}, nil
 }
+
+// compressBuildLogToMemory compresses a small build log with zstd.
+func compressBuildLogToMemory(reader io.Reader) ([]byte, error) {
+	var buf bytes.Buffer
+
+	encoder, ok := zstdEncoderPool.Get().(*zstd.Encoder)
+	if !ok {
+		return nil, errors.New("failed to get zstd encoder from pool")
+	}
+	defer zstdEncoderPool.Put(encoder)
+
+	encoder.Reset(&buf)
+
+	if _, err := io.Copy(encoder, reader); err != nil {
+		_ = encoder.Close()
+
+		return nil, fmt.Errorf("compressing build log: %w", err)
+	}
+
+	if err := encoder.Close(); err != nil {
+		return nil, fmt.Errorf("closing zstd encoder: %w", err)
+	}
+
+	return buf.Bytes(), nil
+}
+
+// checkBuildLogStaging fails early if the compressed build logs staged at
+// once (at most `concurrent` of them) could exceed budget (0 = unlimited) or
+// the free space of tempDir's filesystem. Source log sizes serve as the
+// estimate, since zstd output is smaller than the plain text it compresses.
+// Logs of at most memoryLimit bytes are compressed in memory and not staged.
+func checkBuildLogStaging(tempDir string, budget uint64, concurrent int, memoryLimit int64, logPaths []string) error {
+	if tempDir == "" {
+		tempDir = os.TempDir()
+	}
+
+	sizes := make([]uint64, 0, len(logPaths))
+
+	for _, logPath := range logPaths {
+		info, err := os.Stat(logPath)
+		if err != nil {
+			return fmt.Errorf("checking build log size: %w", err)
+		}
+
+		if memoryLimit > 0 && info.Size() <= memoryLimit {
+			continue
+		}
+
+		sizes = append(sizes, uint64(info.Size())) //nolint:gosec // file sizes are never negative
+	}
+
+	if len(sizes) == 0 {
+		return nil
+	}
+
+	slices.SortFunc(sizes, func(a, b uint64) int { return cmp.Compare(b, a) })
+
+	if concurrent > 0 && concurrent < len(sizes) {
+		sizes = sizes[:concurrent]
+	}
+
+	var projected uint64
+	for _, size := range sizes {
+		projected += size
+	}
+
+	if budget > 0 && projected > budget {
+		return fmt.Errorf("staging build logs needs up to %s in %s, more than the %s temp dir budget",
+			FormatBytes(projected), tempDir, FormatBytes(budget))
+	}
+
+	var stat unix.Statfs_t
+	if err := unix.Statfs(tempDir, &stat); err != nil {
+		return fmt.Errorf("checking free space in %s: %w", tempDir, err)
+	}
+
+	free := uint64(stat.Bavail) * uint64(stat.Bsize) //nolint:gosec,unconvert // field types differ per platform
+	if projected > free {
+		return fmt.Errorf("staging build logs needs up to %s in %s, but only %s is free (use --temp-dir to stage elsewhere)",
+			FormatBytes(projected), tempDir, FormatBytes(free))
+	}
+
+	return nil
+}
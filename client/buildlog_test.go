@@ -0,0 +1,235 @@
+package client_test
+
+import (
+	"context"
+	"io"
+	"net/http"
+	"net/http/httptest"
+	"os"
+	"path/filepath"
+	"strings"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+	"github.com/klauspost/compress/zstd"
+)
+
+func TestCheckBuildLogStaging(t *testing.T) {
+	t.Parallel()
+
+	dir := t.TempDir()
+
+	var logPaths []string
+
+	for i, size := range []int{100, 300, 200} {
+		logPath := filepath.Join(dir, string(rune('a'+i))+".log")
+		if err := os.WriteFile(logPath, make([]byte, size), 0o600); err != nil {
+			t.Fatal(err)
+		}
+
+		logPaths = append(logPaths, logPath)
+	}
+
+	// Only the two largest logs are staged at once: 300 + 200 bytes.
+	if err := client.CheckBuildLogStaging(dir, 500, 2, 0, logPaths); err != nil {
+		t.Fatalf("expected staging to fit the budget: %v", err)
+	}
+
+	err := client.CheckBuildLogStaging(dir, 499, 2, 0, logPaths)
+	if err == nil || !strings.Contains(err.Error(), "budget") {
+		t.Fatalf("expected a budget error, got %v", err)
+	}
+
+	// Without a budget only free space matters.
+	if err := client.CheckBuildLogStaging(dir, 0, 0, 0, logPaths); err != nil {
+		t.Fatalf("expected staging to fit the free space: %v", err)
+	}
+
+	if err := client.CheckBuildLogStaging(filepath.Join(dir, "missing"), 0, 0, 0, logPaths); err == nil {
+		t.Fatal("expected an error for a missing temp dir")
+	}
+
+	// Logs compressed in memory are not staged: only the 300-byte one is.
+	if err := client.CheckBuildLogStaging(dir, 300, 2, 200, logPaths); err != nil {
+		t.Fatalf("expected only the largest log to be staged: %v", err)
+	}
+
+	if err := client.CheckBuildLogStaging(filepath.Join(dir, "missing"), 0, 0, 300, logPaths); err != nil {
+		t.Fatalf("expected nothing to be staged: %v", err)
+	}
+}
+
+// TestBuildLogCompressedInMemory checks that a small log is compressed
+// without a temp file and uploaded with the build log headers.
+func TestBuildLogCompressedInMemory(t *testing.T) {
+	t.Parallel()
+
+	content := strings.Repeat("building\n", 100)
+
+	logPath := filepath.Join(t.TempDir(), "build.log")
+	if err := os.WriteFile(logPath, []byte(content), 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	info, err := client.CompressBuildLogWithLimit(logPath, filepath.Join(t.TempDir(), "missing"), 1<<20)
+	if err != nil {
+		t.Fatalf("compressing build log: %v", err)
+	}
+
+	if info.TempFile != "" {
+		t.Fatalf("expected an in-memory log, got temp file %s", info.TempFile)
+	}
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if got := r.Header.Get("Content-Encoding"); got != "zstd" {
+			t.Errorf("Content-Encoding = %q, want zstd", got)
+		}
+
+		dec, err := zstd.NewReader(r.Body)
+		if err != nil {
+			t.Errorf("creating zstd reader: %v", err)
+
+			return
+		}
+		defer dec.Close()
+
+		body, err := io.ReadAll(dec)
+		if err != nil || string(body) != content {
+			t.Errorf("uploaded log does not decompress to the original (err %v)", err)
+		}
+
+		w.WriteHeader(http.StatusOK)
+	}))
+	defer srv.Close()
+
+	c := newTestClientWithRetries(http.DefaultClient, 0)
+
+	if err := c.UploadBuildLogToPresignedURL(t.Context(), srv.URL, info); err != nil {
+		t.Fatalf("uploading build log: %v", err)
+	}
+}
+
+// TestBuildLogOverSinglePutLimit checks that a compressed log too large for
+// one S3 PUT fails before any request is sent.
+func TestBuildLogOverSinglePutLimit(t *testing.T) {
+	t.Parallel()
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, _ *http.Request) {
+		t.Error("unexpected upload request")
+		w.WriteHeader(http.StatusOK)
+	}))
+	defer srv.Close()
+
+	logPath := filepath.Join(t.TempDir(), "huge.log.zst")
+
+	f, err := os.Create(logPath)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	// Sparse, so the test does not write 5 GiB.
+	if err := f.Truncate(5<<30 + 1); err != nil {
+		t.Fatal(err)
+	}
+
+	if err := f.Close(); err != nil {
+		t.Fatal(err)
+	}
+
+	c := newTestClientWithRetries(http.DefaultClient, 0)
+
+	err = c.UploadBuildLogToPresignedURL(context.Background(), srv.URL, &client.CompressedBuildLogInfo{TempFile: logPath, Size: 5<<30 + 1})
+	if err == nil || !strings.Contains(err.Error(), "single-PUT limit") {
+		t.Fatalf("expected single-PUT limit error, got %v", err)
+	}
+}
+
+// TestUploadPendingObjectsCanceledRemovesStagedLogs checks that a push
+// interrupted mid-upload leaves nothing behind in TempDir.
+func TestUploadPendingObjectsCanceledRemovesStagedLogs(t *testing.T) {
+	t.Parallel()
+
+	ctx, cancel := context.WithCancel(t.Context())
+	defer cancel()
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		cancel()
+		<-r.Context().Done()
+		w.WriteHeader(http.StatusOK)
+	}))
+	defer srv.Close()
+
+	logPath := filepath.Join(t.TempDir(), "build.log")
+	if err := os.WriteFile(logPath, []byte(strings.Repeat("building...\n", 1000)), 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	tempDir := t.TempDir()
+
+	c := newTestClientWithRetries(http.DefaultClient, 0)
+	c.TempDir = tempDir
+	c.InMemoryLogLimit = 0
+
+	const logKey = "log/00000000000000000000000000000000-hello.drv"
+
+	_, err := c.UploadPendingObjects(ctx, &client.UploadContext{
+		PendingObjects: map[string]client.PendingObject{
+			logKey: {Type: "build_log", PresignedURL: srv.URL + "/" + logKey},
+		},
+		LogPathsByKey: map[string]string{logKey: logPath},
+	})
+	if err == nil {
+		t.Fatal("expected the canceled upload to fail")
+	}
+
+	entries, err := os.ReadDir(tempDir)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if len(entries) != 0 {
+		t.Errorf("TempDir not cleaned up: %v", entries)
+	}
+}
+
+func TestUploadPendingObjectsKeepTemp(t *testing.T) {
+	t.Parallel()
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		_, _ = io.Copy(io.Discard, r.Body)
+		w.WriteHeader(http.StatusOK)
+	}))
+	defer srv.Close()
+
+	logPath := filepath.Join(t.TempDir(), "build.log")
+	if err := os.WriteFile(logPath, []byte("building...\n"), 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	tempDir := t.TempDir()
+
+	c := newTestClientWithRetries(http.DefaultClient, 0)
+	c.TempDir = tempDir
+	c.KeepTemp = true
+
+	const logKey = "log/00000000000000000000000000000000-hello.drv"
+
+	if _, err := c.UploadPendingObjects(t.Context(), &client.UploadContext{
+		PendingObjects: map[string]client.PendingObject{
+			logKey: {Type: "build_log", PresignedURL: srv.URL + "/" + logKey},
+		},
+		LogPathsByKey: map[string]string{logKey: logPath},
+	}); err != nil {
+		t.Fatal(err)
+	}
+
+	// The log is small enough for memory, but kept logs are always staged.
+	kept, err := filepath.Glob(filepath.Join(tempDir, "niks3-push-*", "buildlog-*.zst"))
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if len(kept) != 1 {
+		t.Fatalf("expected one kept build log, got %v", kept)
+	}
+}
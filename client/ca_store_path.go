@@ -0,0 +1,128 @@
+package client
+
+import (
+	"crypto/sha256"
+	"encoding/hex"
+	"fmt"
+	"path"
+	"slices"
+	"strings"
+)
+
+// storePathHashSize is the length in bytes of the digest a store path's
+// hash part encodes: sha256 folded to 160 bits.
+const storePathHashSize = 20
+
+// compressHash folds digest to size bytes by XORing, as Nix's compressHash.
+func compressHash(digest []byte, size int) []byte {
+	out := make([]byte, size)
+	for i, b := range digest {
+		out[i%size] ^= b
+	}
+
+	return out
+}
+
+// makeStorePath is Nix's Store::makeStorePath: the path of an object of
+// pathType (e.g. "text", "source" or "output:out", with its references
+// appended) whose inner sha256 digest is innerSHA256.
+func makeStorePath(storeDir, pathType string, innerSHA256 []byte, name string) string {
+	fingerprint := pathType + ":sha256:" + hex.EncodeToString(innerSHA256) + ":" + storeDir + ":" + name
+	sum := sha256.Sum256([]byte(fingerprint))
+
+	return storeDir + "/" + EncodeNixBase32(compressHash(sum[:], storePathHashSize)) + "-" + name
+}
+
+// makePathType appends the sorted references, and "self" for a
+// self-reference, to pathType, as Nix's makeType.
+func makePathType(pathType string, references []string, selfReference bool) string {
+	var sb strings.Builder
+
+	sb.WriteString(pathType)
+
+	for _, ref := range slices.Sorted(slices.Values(references)) {
+		sb.WriteString(":" + ref)
+	}
+
+	if selfReference {
+		sb.WriteString(":self")
+	}
+
+	return sb.String()
+}
+
+// ContentAddressedStorePath computes the store path Nix gives an object
+// named name in storeDir with the content address ca, in narinfo form
+// ("text:sha256:…", "fixed:sha256:…" or "fixed:r:<algo>:…"). references
+// are full store paths, excluding the object itself; selfReference is set
+// if it refers to itself. Git content addresses are not supported.
+func ContentAddressedStorePath(storeDir, name, ca string, references []string, selfReference bool) (string, error) {
+	if err := validateContentAddress(ca); err != nil {
+		return "", err
+	}
+
+	var (
+		method string
+		rest   string
+	)
+
+	switch {
+	case strings.HasPrefix(ca, "text:"):
+		method, rest = "text", strings.TrimPrefix(ca, "text:")
+	case strings.HasPrefix(ca, "fixed:git:"):
+		return "", fmt.Errorf("content address %q: git hashes are not supported", ca)
+	case strings.HasPrefix(ca, "fixed:r:"):
+		method, rest = "r:", strings.TrimPrefix(ca, "fixed:r:")
+	default:
+		rest = strings.TrimPrefix(ca, "fixed:")
+	}
+
+	algo, digest, err := DecodeNixHash(rest)
+	if err != nil {
+		return "", fmt.Errorf("content address %q: %w", ca, err)
+	}
+
+	switch {
+	case method == "text":
+		if algo != HashSHA256 || selfReference {
+			return "", fmt.Errorf("content address %q: text paths use sha256 and cannot refer to themselves", ca)
+		}
+
+		return makeStorePath(storeDir, makePathType("text", references, false), digest, name), nil
+	case method == "r:" && algo == HashSHA256:
+		return makeStorePath(storeDir, makePathType("source", references, selfReference), digest, name), nil
+	case len(references) > 0 || selfReference:
+		return "", fmt.Errorf("content address %q: only recursive sha256 fixed outputs can have references", ca)
+	default:
+		inner := sha256.Sum256([]byte("fixed:out:" + method + string(algo) + ":" + hex.EncodeToString(digest) + ":"))
+
+		return makeStorePath(storeDir, "output:out", inner[:], name), nil
+	}
+}
+
+// CheckContentAddressedStorePath checks that storePath is the path its
+// content address ca and references, as listed in its narinfo, produce. A
+// narinfo whose CA field or references were altered fails the check, since
+// the path would hash differently.
+func CheckContentAddressedStorePath(storePath, ca string, references []string) error {
+	base := path.Base(storePath)
+
+	_, name, ok := strings.Cut(base, "-")
+	if !ok || name == "" {
+		return fmt.Errorf("invalid store path %q", storePath)
+	}
+
+	others := slices.DeleteFunc(slices.Clone(references), func(ref string) bool { return ref == storePath })
+	selfReference := len(others) != len(references)
+
+	expected, err := ContentAddressedStorePath(path.Dir(storePath), name, ca, others, selfReference)
+	if err != nil {
+		return err
+	}
+
+	if expected != storePath {
+		return fmt.Errorf("content address %s and references produce %s, not %s", ca, expected, storePath)
+	}
+
+	return nil
+}
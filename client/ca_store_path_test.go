@@ -0,0 +1,73 @@
+package client_test
+
+import (
+	"crypto/sha256"
+	"encoding/hex"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+func TestContentAddressedStorePath(t *testing.T) {
+	t.Parallel()
+
+	textCA := func(contents string) string {
+		sum := sha256.Sum256([]byte(contents))
+
+		return "text:sha256:" + client.EncodeNixBase32(sum[:])
+	}
+
+	// nix-repl> builtins.toFile "foo" "bar"
+	foo := "/nix/store/vxjiwkjkn7x4079qvh1jkl5pn05j2aw0-foo"
+
+	sha1Foo, err := hex.DecodeString("0beec7b5ea3f0fdbc95d0dd47f3c5bc275da8a33")
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	tests := []struct {
+		name       string
+		pathName   string
+		ca         string
+		references []string
+		want       string
+	}{
+		{"text", "foo", textCA("bar"), nil, foo},
+		// nix-repl> builtins.toFile "baz" "${builtins.toFile "foo" "bar"}"
+		{"text with references", "baz", textCA(foo), []string{foo}, "/nix/store/5xd714cbfnkz02h2vbsj4fm03x3f15nf-baz"},
+		// A recursive sha1 fixed output, whose path hashes the content address.
+		{"recursive sha1", "bar", "fixed:r:sha1:" + client.EncodeNixBase32(sha1Foo), nil, "/nix/store/mp57d33657rf34lzvlbpfa1gjfv5gmpg-bar"},
+	}
+
+	for _, tt := range tests {
+		t.Run(tt.name, func(t *testing.T) {
+			t.Parallel()
+
+			got, err := client.ContentAddressedStorePath("/nix/store", tt.pathName, tt.ca, tt.references, false)
+			if err != nil {
+				t.Fatal(err)
+			}
+
+			if got != tt.want {
+				t.Errorf("got %s, want %s", got, tt.want)
+			}
+
+			if err := client.CheckContentAddressedStorePath(tt.want, tt.ca, tt.references); err != nil {
+				t.Errorf("CheckContentAddressedStorePath: %v", err)
+			}
+		})
+	}
+
+	// Tampering with the content address or the references changes the path.
+	if err := client.CheckContentAddressedStorePath(foo, textCA("baz"), nil); err == nil {
+		t.Error("accepted an altered content address")
+	}
+
+	if err := client.CheckContentAddressedStorePath(foo, textCA("bar"), []string{"/nix/store/5xd714cbfnkz02h2vbsj4fm03x3f15nf-baz"}); err == nil {
+		t.Error("accepted an added reference")
+	}
+
+	if _, err := client.ContentAddressedStorePath("/nix/store", "foo", "fixed:sha256:"+textCA("bar")[len("text:sha256:"):], []string{foo}, false); err == nil {
+		t.Error("accepted references on a flat fixed output")
+	}
+}
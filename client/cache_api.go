@@ -0,0 +1,29 @@
+package client
+
+import "context"
+
+// CacheAPI is the part of the niks3 server API that drives a push: pending
+// closures are created, their narinfos signed, then completed or aborted,
+// and presigned URLs re-minted or extended as uploads need them. Client
+// implements it over HTTP. Setting Client.API replaces it for pushes, e.g.
+// with an in-memory fake that hands out presigned URLs of a test server.
+type CacheAPI interface {
+	CreatePendingClosure(ctx context.Context, closure string, objects []ObjectWithRefs, verifyS3 bool) (*CreatePendingClosureResponse, error)
+	SignPendingClosure(ctx context.Context, closureID string, narinfos map[string]NarinfoMetadata) (map[string][]string, error)
+	CompletePendingClosure(ctx context.Context, closureID string) error
+	AbortPendingClosure(ctx context.Context, closureID string) error
+	RefreshPresignedURL(ctx context.Context, closureID, objectKey string) (string, error)
+	RequestMoreParts(ctx context.Context, objectKey, uploadID string, startPartNumber, numParts int) ([]string, error)
+	CompleteMultipartUpload(ctx context.Context, objectKey, uploadID string, parts []CompletedPart) error
+}
+
+var _ CacheAPI = (*Client)(nil)
+
+// api returns the CacheAPI pushes go through: Client.API if set, else c.
+func (c *Client) api() CacheAPI {
+	if c.API != nil {
+		return c.API
+	}
+
+	return c
+}
@@ -0,0 +1,181 @@
+package client_test
+
+import (
+	"context"
+	"errors"
+	"slices"
+	"strconv"
+	"strings"
+	"sync"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+var errNotFaked = errors.New("multipart uploads are not faked")
+
+// fakeCacheAPI is an in-memory CacheAPI whose presigned URLs point at s3URL.
+type fakeCacheAPI struct {
+	s3URL string
+
+	// editPending, if set, changes the pending objects of each response.
+	editPending func(map[string]client.PendingObject)
+
+	mu        sync.Mutex
+	created   int
+	completed []string
+	aborted   []string
+}
+
+func (f *fakeCacheAPI) CreatePendingClosure(_ context.Context, _ string, objects []client.ObjectWithRefs, _ bool) (*client.CreatePendingClosureResponse, error) {
+	f.mu.Lock()
+	defer f.mu.Unlock()
+
+	f.created++
+	resp := &client.CreatePendingClosureResponse{ID: strconv.Itoa(f.created), PendingObjects: make(map[string]client.PendingObject)}
+
+	for _, obj := range objects {
+		resp.PendingObjects[obj.Key] = client.PendingObject{Type: string(obj.Type), PresignedURL: f.s3URL + "/" + obj.Key}
+	}
+
+	if f.editPending != nil {
+		f.editPending(resp.PendingObjects)
+	}
+
+	return resp, nil
+}
+
+func (f *fakeCacheAPI) SignPendingClosure(_ context.Context, _ string, narinfos map[string]client.NarinfoMetadata) (map[string][]string, error) {
+	signatures := make(map[string][]string, len(narinfos))
+	for key := range narinfos {
+		signatures[key] = []string{"test-1:c2lnbmF0dXJl"}
+	}
+
+	return signatures, nil
+}
+
+func (f *fakeCacheAPI) CompletePendingClosure(_ context.Context, closureID string) error {
+	f.mu.Lock()
+	defer f.mu.Unlock()
+
+	f.completed = append(f.completed, closureID)
+
+	return nil
+}
+
+func (f *fakeCacheAPI) AbortPendingClosure(_ context.Context, closureID string) error {
+	f.mu.Lock()
+	defer f.mu.Unlock()
+
+	f.aborted = append(f.aborted, closureID)
+
+	return nil
+}
+
+func (f *fakeCacheAPI) RefreshPresignedURL(_ context.Context, _, objectKey string) (string, error) {
+	return f.s3URL + "/" + objectKey, nil
+}
+
+func (f *fakeCacheAPI) RequestMoreParts(context.Context, string, string, int, int) ([]string, error) {
+	return nil, errNotFaked
+}
+
+func (f *fakeCacheAPI) CompleteMultipartUpload(context.Context, string, string, []client.CompletedPart) error {
+	return errNotFaked
+}
+
+// TestPushWithFakeCacheAPI runs a whole push against an in-memory CacheAPI,
+// with only the presigned uploads going over HTTP.
+func TestPushWithFakeCacheAPI(t *testing.T) {
+	t.Parallel()
+
+	m := newMockCache(t)
+	store := newTestStore(t, map[string]string{"0ha1dhmx807czjczmwy078s4r9s254il-hello": "hello\n"})
+
+	opts := store.PushOptions()
+	opts.SkipExisting = false
+
+	stats, err := client.Push(context.Background(), m.Client(t), store.Paths, opts)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if !slices.Equal(stats.Succeeded, store.Paths) {
+		t.Errorf("succeeded = %v, want %v", stats.Succeeded, store.Paths)
+	}
+
+	if len(m.API.completed) != 1 || len(m.API.aborted) != 0 {
+		t.Errorf("completed %v and aborted %v closures, want one completed", m.API.completed, m.API.aborted)
+	}
+
+	if _, ok := m.Object("0ha1dhmx807czjczmwy078s4r9s254il.narinfo"); !ok {
+		t.Errorf("expected a narinfo upload, got %v", m.Keys())
+	}
+}
+
+// TestPushIgnoresUnrequestedPendingObjects checks that pending objects the
+// client did not ask for are not uploaded.
+func TestPushIgnoresUnrequestedPendingObjects(t *testing.T) {
+	t.Parallel()
+
+	m := newMockCache(t)
+	store := newTestStore(t, harnessStore)
+
+	const extraKey = "2ha1dhmx807czjczmwy078s4r9s254il.narinfo"
+
+	m.API.editPending = func(pending map[string]client.PendingObject) {
+		pending[extraKey] = client.PendingObject{Type: string(client.ObjectTypeNarinfo), PresignedURL: m.S3.URL + "/" + extraKey}
+	}
+
+	opts := store.PushOptions()
+	opts.SkipExisting = false
+
+	stats, err := client.Push(context.Background(), m.Client(t), store.Paths, opts)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if !slices.Equal(stats.Succeeded, store.Paths) {
+		t.Errorf("succeeded = %v, want %v", stats.Succeeded, store.Paths)
+	}
+
+	if _, ok := m.Object(extraKey); ok {
+		t.Errorf("unrequested object %s was uploaded", extraKey)
+	}
+}
+
+// TestPushRejectsMismatchedPendingObjects checks that a pending object
+// returned with another type than requested fails the push before anything
+// is uploaded, and that the pending closures are aborted.
+func TestPushRejectsMismatchedPendingObjects(t *testing.T) {
+	t.Parallel()
+
+	m := newMockCache(t)
+	store := newTestStore(t, map[string]string{"0ha1dhmx807czjczmwy078s4r9s254il-hello": "hello\n"})
+	key := narinfoKey(t, store.Paths[0])
+
+	m.API.editPending = func(pending map[string]client.PendingObject) {
+		obj := pending[key]
+		obj.Type = string(client.ObjectTypeNAR)
+		pending[key] = obj
+	}
+
+	opts := store.PushOptions()
+	opts.SkipExisting = false
+
+	_, err := client.Push(context.Background(), m.Client(t), store.Paths, opts)
+	if err == nil || !strings.Contains(err.Error(), "requested as narinfo") {
+		t.Fatalf("expected a type mismatch error, got %v", err)
+	}
+
+	if keys := m.Keys(); len(keys) != 0 {
+		t.Errorf("objects were uploaded despite the mismatch: %v", keys)
+	}
+
+	m.API.mu.Lock()
+	defer m.API.mu.Unlock()
+
+	if len(m.API.aborted) != 1 {
+		t.Errorf("aborted %v pending closures, want 1", m.API.aborted)
+	}
+}
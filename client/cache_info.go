@@ -0,0 +1,50 @@
+package client
+
+import (
+	"bytes"
+	"context"
+	"encoding/json"
+	"fmt"
+	"log/slog"
+	"net/http"
+
+	"github.com/Mic92/niks3/api"
+)
+
+// PutCacheInfo replaces the cache's nix-cache-info. An empty StoreDir uses
+// the client's store directory. The server renders and writes the object;
+// clients cannot upload it directly.
+func (c *Client) PutCacheInfo(ctx context.Context, info api.CacheInfo) error {
+	if info.StoreDir == "" {
+		info.StoreDir = c.effectiveStoreDir()
+	}
+
+	jsonData, err := json.Marshal(info)
+	if err != nil {
+		return fmt.Errorf("marshaling request: %w", err)
+	}
+
+	reqURL := c.baseURL.JoinPath("api/cache-info")
+
+	req, err := http.NewRequestWithContext(ctx, http.MethodPut, reqURL.String(), bytes.NewReader(jsonData))
+	if err != nil {
+		return fmt.Errorf("creating request: %w", err)
+	}
+
+	req.Header.Set("Content-Type", "application/json")
+
+	resp, err := c.DoServerRequest(ctx, req)
+	if err != nil {
+		return fmt.Errorf("sending request: %w", err)
+	}
+
+	defer deferCloseBody(resp)
+
+	if err := checkResponse(resp, http.StatusOK, http.StatusNoContent); err != nil {
+		return err
+	}
+
+	slog.Debug("Updated nix-cache-info", "store_dir", info.StoreDir, "priority", info.Priority)
+
+	return nil
+}
@@ -5,16 +5,23 @@ import (
 	"context"
 	"crypto/tls"
 	"crypto/x509"
+	"encoding/pem"
 	"fmt"
 	"io"
 	"log/slog"
+	"net"
 	"net/http"
 	"net/http/httputil"
 	"net/url"
 	"os"
+	"path/filepath"
 	"slices"
+	"strings"
+	"sync"
+	"time"
 
 	"github.com/Mic92/niks3/ratelimit"
+	"golang.org/x/sync/semaphore"
 )
 
 // compressionZstd is the algorithm name used in Content-Encoding headers
@@ -27,13 +34,53 @@ type Client struct {
 	tokenSource             TokenSource
 	httpClient              *http.Client
 	MaxConcurrentNARUploads int                            // Maximum number of concurrent uploads (0 = unlimited)
+	MaxConcurrentRequests   int                            // Maximum number of concurrent pending closure requests (0 = unlimited)
 	NixEnv                  []string                       // Optional environment variables for nix commands (for testing)
 	Retry                   RetryConfig                    // Retry configuration for HTTP requests
 	storeDir                string                         // Cached Nix store directory (e.g., "/nix/store")
 	VerifyS3Integrity       bool                           // Enable S3 integrity checking when creating pending closures
 	DebugHTTP               bool                           // Enable HTTP request/response debug logging
+	UserAgent               string                         // User-Agent header for requests to the server and S3 ("" = Go's default)
+	RequestTimeout          time.Duration                  // Per-attempt timeout for server API calls, including the response body (0 = none)
+	TransferTimeout         time.Duration                  // Per-attempt timeout for NAR, log and listing transfers (0 = none)
 	S3RateLimiter           *ratelimit.AdaptiveRateLimiter // Rate limiter for S3 presigned URL uploads
+	MaxUploadRate           int64                          // Bytes per second shared by all S3 uploads (0 = unlimited)
 	ServerRateLimiter       *ratelimit.AdaptiveRateLimiter // Rate limiter for niks3 server API calls
+	NarOptions              NarOptions                     // NAR serialization options (case hack)
+	Compression             Compression                    // NAR compression (zstd, xz, br or none)
+	CompressionLevel        int                            // Compression level for NARs (0 = default)
+	ZstdWindowLog           int                            // zstd window log for NARs, for long-distance matches (0 = the level's default)
+	CompressionWorkers      int                            // zstd workers for large NARs (0 = GOMAXPROCS)
+	CompressionJobs         int                            // NARs compressed at once across all uploads (0 = GOMAXPROCS)
+	MinCompressionRatio     float64                        // Store NARs uncompressed if a sample compresses worse than this (0 = always compress)
+	SkipExisting            bool                           // Skip closures whose narinfos are all already cached
+	WriteListings           bool                           // Upload a .ls listing of every NAR's file tree alongside it
+	IncludeDerivations      bool                           // Also push the .drv closures of the pushed paths' derivers
+	ChecksumUploads         bool                           // Send Content-MD5 (and x-amz-checksum-sha256 for NARs) so S3 rejects corrupted bodies
+	TempDir                 string                         // Directory for staging compressed build logs ("" = system default)
+	TempDirBudget           uint64                         // Maximum bytes staged in TempDir at once (0 = unlimited)
+	KeepTemp                bool                           // Keep staged logs and copies of uploaded NARs and narinfos for debugging
+	InMemoryLogLimit        int64                          // Build logs up to this size are compressed in memory, not in TempDir (0 = always stage)
+	Store                   string                         // Nix store URI to read paths from, e.g. "ssh-ng://builder" ("" = local store)
+	PathInfoFile            string                         // Read path info from this `nix path-info --recursive --json` dump instead of nix
+	PathInfoCache           string                         // Directory caching nix path info between runs, by store path hash ("" = no cache)
+	PathInfoCacheTTL        time.Duration                  // Age after which cached path info is queried again (0 = kept while the path is in the store)
+	OnEvent                 func(PushEvent)                // Called with push progress events, concurrently from upload goroutines
+	DryRun                  bool                           // Report what a push would upload without creating pending closures
+	NarinfoOutput           io.Writer                      // Write the narinfos a push would upload here instead of uploading (nil = upload)
+	ContinueOnError         bool                           // Upload what can be uploaded and report failed paths instead of aborting
+	Exclude                 []string                       // Store paths to leave out of pushed closures
+	NoRecursive             bool                           // Push only the given paths; their references are not uploaded
+	KeyPrefix               string                         // Upload every object below this bucket prefix ("" = bucket root); see ValidateKeyPrefix
+	StateFile               string                         // Checkpoint file that lets an interrupted push resume ("" = none)
+	NARCache                *NARCache                      // Compressed NARs reused by pushes to several caches (nil = compress per upload)
+	API                     CacheAPI                       // Pending closure API used by pushes (nil = this client's HTTP API)
+	pushState               *pushState                     // Opened from StateFile for the duration of a push
+	keepDir                 string                         // Directory KeepTemp retains files in during an upload
+	compressionSemOnce      sync.Once                      // Creates compressionSem from CompressionJobs on first use
+	compressionSem          *semaphore.Weighted            // Bounds concurrent NAR compressions
+	uploadLimiterOnce       sync.Once                      // Creates uploadLimiter from MaxUploadRate on first use
+	uploadLimiter           *ratelimit.BandwidthLimiter    // Throttles S3 upload bodies (nil = unlimited)
 }
 
 // loggingTransport wraps an http.RoundTripper to log requests and responses.
@@ -100,9 +147,19 @@ func NewClient(ctx context.Context, serverURL, authToken string) (*Client, error
 // auth token via ts before each server request. Use this for short-lived
 // tokens (OIDC, vault) that need to refresh during a long-running upload.
 func NewClientWithTokenSource(ctx context.Context, serverURL string, ts TokenSource) (*Client, error) {
-	baseURL, err := url.Parse(serverURL)
+	return NewClientWithHTTPClient(ctx, &http.Client{
+		Timeout: 0, // No timeout for streaming uploads
+	}, serverURL, ts)
+}
+
+// NewClientWithHTTPClient is like NewClientWithTokenSource, but sends the
+// requests to the server and to S3 through a copy of httpClient, e.g. one
+// with a tuned transport or the client of an httptest server. The TLS, proxy
+// and timeout setters change the copy, never httpClient itself.
+func NewClientWithHTTPClient(ctx context.Context, httpClient *http.Client, serverURL string, ts TokenSource) (*Client, error) {
+	baseURL, err := parseServerURL(serverURL)
 	if err != nil {
-		return nil, fmt.Errorf("parsing server URL: %w", err)
+		return nil, err
 	}
 
 	// Get the Nix store directory at startup
@@ -111,20 +168,56 @@ func NewClientWithTokenSource(ctx context.Context, serverURL string, ts TokenSou
 		return nil, fmt.Errorf("getting store directory: %w", err)
 	}
 
+	// ownTransport modifies an *http.Transport in place, so give the client
+	// its own.
+	hc := *httpClient
+	if t, ok := hc.Transport.(*http.Transport); ok {
+		hc.Transport = t.Clone()
+	}
+
 	return &Client{
-		baseURL:     baseURL,
-		tokenSource: ts,
-		httpClient: &http.Client{
-			Timeout: 0, // No timeout for streaming uploads
-		},
+		baseURL:                 baseURL,
+		tokenSource:             ts,
+		httpClient:              &hc,
 		MaxConcurrentNARUploads: 16,
+		MaxConcurrentRequests:   8,
 		Retry:                   DefaultRetryConfig(),
 		storeDir:                storeDir,
 		S3RateLimiter:           ratelimit.NewAdaptiveRateLimiter(0, "s3"),
 		ServerRateLimiter:       ratelimit.NewAdaptiveRateLimiter(0, "server"),
+		NarOptions:              DefaultNarOptions(),
+		Compression:             CompressionZstd,
+		SkipExisting:            true,
+		WriteListings:           true,
+		InMemoryLogLimit:        DefaultInMemoryLogLimit,
+		UserAgent:               DefaultUserAgent(),
 	}, nil
 }
 
+// parseServerURL parses a --server-url value, which must be an http or https
+// URL with a host. Anything else would only fail later, when the first
+// request URL is built from it.
+func parseServerURL(serverURL string) (*url.URL, error) {
+	baseURL, err := url.Parse(serverURL)
+	if err != nil {
+		return nil, fmt.Errorf("parsing server URL %q: %w", serverURL, err)
+	}
+
+	if baseURL.Scheme != "http" && baseURL.Scheme != "https" {
+		if !strings.Contains(serverURL, "://") {
+			return nil, fmt.Errorf("server URL %q has no http:// or https:// scheme, did you mean https://%s?", serverURL, serverURL)
+		}
+
+		return nil, fmt.Errorf("server URL %q must use http or https, not %q", serverURL, baseURL.Scheme)
+	}
+
+	if baseURL.Host == "" {
+		return nil, fmt.Errorf("server URL %q has no host", serverURL)
+	}
+
+	return baseURL, nil
+}
+
 // SetDebugHTTP enables or disables HTTP request/response logging.
 // When enabled, wraps the HTTP client transport with a logging transport.
 func (c *Client) SetDebugHTTP(enabled bool) {
@@ -147,10 +240,29 @@ func (c *Client) SetDebugHTTP(enabled bool) {
 	}
 }
 
+// SetStoreDir overrides the detected Nix store directory, e.g. for a store
+// at /var/nix/store. The nix commands the client runs get NIX_STORE_DIR set
+// to the same directory.
+func (c *Client) SetStoreDir(dir string) {
+	dir = filepath.Clean(dir)
+	c.storeDir = dir
+
+	env := c.NixEnv
+	if len(env) == 0 {
+		env = os.Environ()
+	}
+
+	env = slices.DeleteFunc(slices.Clone(env), func(e string) bool {
+		return strings.HasPrefix(e, "NIX_STORE_DIR=")
+	})
+
+	c.NixEnv = append(env, "NIX_STORE_DIR="+dir)
+}
+
 // SetClientTLS configures the HTTP client TLS settings. certFile/keyFile
 // add a client certificate for mTLS; either both or neither must be set.
-// If caFile is non-empty the server certificate is verified against that
-// CA; otherwise the system certificate pool is used.
+// If caFile is non-empty its certificates are trusted in addition to the
+// system certificate pool, e.g. for an internal CA of a TLS-inspecting proxy.
 func (c *Client) SetClientTLS(certFile, keyFile, caFile string) error {
 	tlsConfig := &tls.Config{
 		MinVersion: tls.VersionTLS12,
@@ -166,22 +278,132 @@ func (c *Client) SetClientTLS(certFile, keyFile, caFile string) error {
 	}
 
 	if caFile != "" {
-		caCert, err := os.ReadFile(caFile)
+		certPool, err := loadCertPool(caFile)
 		if err != nil {
-			return fmt.Errorf("loading CA certificate: %w", err)
+			return err
+		}
+
+		tlsConfig.RootCAs = certPool
+	}
+
+	transport := c.ownTransport()
+	if transport.TLSClientConfig != nil {
+		tlsConfig.InsecureSkipVerify = transport.TLSClientConfig.InsecureSkipVerify
+	}
+
+	transport.TLSClientConfig = tlsConfig
+
+	return nil
+}
+
+// loadCertPool returns the system certificate pool with the certificates of
+// the PEM file at caFile added. Unlike x509.CertPool.AppendCertsFromPEM, it
+// fails on any block that is not a parseable certificate, so a wrong file
+// is reported instead of showing up later as an unknown authority.
+func loadCertPool(caFile string) (*x509.CertPool, error) {
+	data, err := os.ReadFile(caFile)
+	if err != nil {
+		return nil, fmt.Errorf("loading CA certificate: %w", err)
+	}
+
+	certPool, err := x509.SystemCertPool()
+	if err != nil {
+		slog.Warn("Failed to load system certificate pool, trusting only the given CA", "error", err)
+
+		certPool = x509.NewCertPool()
+	}
+
+	found := false
+
+	for {
+		var block *pem.Block
+
+		block, data = pem.Decode(data)
+		if block == nil {
+			break
 		}
 
-		certPool := x509.NewCertPool()
-		if !certPool.AppendCertsFromPEM(caCert) {
-			return fmt.Errorf("parsing CA certificate %q: no PEM certificates found", caFile)
+		if block.Type != "CERTIFICATE" {
+			return nil, fmt.Errorf("parsing CA certificate %q: unexpected PEM block %q", caFile, block.Type)
 		}
 
-		tlsConfig.RootCAs = certPool
+		cert, err := x509.ParseCertificate(block.Bytes)
+		if err != nil {
+			return nil, fmt.Errorf("parsing CA certificate %q: %w", caFile, err)
+		}
+
+		certPool.AddCert(cert)
+
+		found = true
+	}
+
+	if !found {
+		return nil, fmt.Errorf("parsing CA certificate %q: no PEM certificates found", caFile)
+	}
+
+	return certPool, nil
+}
+
+// SetProxy sends all requests, to the server and to S3, through the proxy
+// at proxyURL (http, https or socks5). Without it, the HTTPS_PROXY,
+// HTTP_PROXY and NO_PROXY environment variables are honored.
+func (c *Client) SetProxy(proxyURL string) error {
+	u, err := url.Parse(proxyURL)
+	if err != nil {
+		return fmt.Errorf("parsing proxy URL: %w", err)
+	}
+
+	if u.Scheme == "" || u.Host == "" {
+		return fmt.Errorf("invalid proxy URL %q: expected scheme://host[:port]", proxyURL)
+	}
+
+	c.ownTransport().Proxy = http.ProxyURL(u)
+
+	return nil
+}
+
+// SetInsecureSkipVerify disables verification of server certificates, for
+// testing against servers with self-signed certificates. Never use it in
+// production: it makes the connection open to interception.
+func (c *Client) SetInsecureSkipVerify() {
+	transport := c.ownTransport()
+	if transport.TLSClientConfig == nil {
+		transport.TLSClientConfig = &tls.Config{MinVersion: tls.VersionTLS12}
+	}
+
+	transport.TLSClientConfig.InsecureSkipVerify = true //nolint:gosec // G402: opt-in via --insecure, for testing only
+}
+
+// SetConnectTimeout bounds how long establishing a connection (dial and TLS
+// handshake) may take, so an unreachable server fails fast even when
+// RequestTimeout or TransferTimeout are long or unset. 0 keeps the default
+// of http.DefaultTransport (30s).
+func (c *Client) SetConnectTimeout(timeout time.Duration) {
+	if timeout <= 0 {
+		return
+	}
+
+	transport := c.ownTransport()
+	transport.DialContext = (&net.Dialer{Timeout: timeout, KeepAlive: 30 * time.Second}).DialContext
+	transport.TLSHandshakeTimeout = timeout
+}
+
+// ownTransport returns the client's *http.Transport for modification. The
+// first call replaces http.DefaultTransport with a clone, so we never mutate
+// the shared default. If SetDebugHTTP has already wrapped the transport in a
+// loggingTransport, the wrapper is preserved around the new transport.
+func (c *Client) ownTransport() *http.Transport {
+	lt, wrapped := c.httpClient.Transport.(*loggingTransport)
+
+	current := c.httpClient.Transport
+	if wrapped {
+		current = lt.transport
+	}
+
+	if t, ok := current.(*http.Transport); ok && t != http.DefaultTransport {
+		return t
 	}
 
-	// Build a fresh transport so we never mutate http.DefaultTransport. If
-	// SetDebugHTTP has already wrapped the transport in a loggingTransport,
-	// preserve that wrapper around the new transport.
 	var newTransport *http.Transport
 	if dt, ok := http.DefaultTransport.(*http.Transport); ok {
 		newTransport = dt.Clone()
@@ -189,15 +411,13 @@ func (c *Client) SetClientTLS(certFile, keyFile, caFile string) error {
 		newTransport = &http.Transport{}
 	}
 
-	newTransport.TLSClientConfig = tlsConfig
-
-	if lt, ok := c.httpClient.Transport.(*loggingTransport); ok {
+	if wrapped {
 		lt.transport = newTransport
 	} else {
 		c.httpClient.Transport = newTransport
 	}
 
-	return nil
+	return newTransport
 }
 
 func deferCloseBody(resp *http.Response) {
@@ -236,6 +456,7 @@ func (c *Client) putBytes(ctx context.Context, url string, data []byte) (*http.R
 
 	req.ContentLength = int64(len(data))
 	req.Header.Set("Content-Type", "application/octet-stream")
+	c.setContentMD5(req, data)
 
 	resp, err := c.DoS3Request(ctx, req)
 	if err != nil {
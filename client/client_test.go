@@ -0,0 +1,91 @@
+package client_test
+
+import (
+	"context"
+	"net/http"
+	"net/http/httptest"
+	"strings"
+	"testing"
+	"time"
+
+	"github.com/Mic92/niks3/client"
+)
+
+// TestNewClientRejectsInvalidServerURL checks that server URLs without an
+// http(s) scheme or a host are rejected up front, before nix is run, with an
+// error naming the value.
+func TestNewClientRejectsInvalidServerURL(t *testing.T) {
+	t.Parallel()
+
+	tests := []struct {
+		serverURL string
+		want      string
+	}{
+		{"niks3", `server URL "niks3" has no http:// or https:// scheme, did you mean https://niks3?`},
+		{"cache.example.com:5751", `did you mean https://cache.example.com:5751?`},
+		{"ftp://cache.example.com", `server URL "ftp://cache.example.com" must use http or https, not "ftp"`},
+		{"https://", `server URL "https://" has no host`},
+	}
+
+	for _, tt := range tests {
+		t.Run(tt.serverURL, func(t *testing.T) {
+			t.Parallel()
+
+			_, err := client.NewClient(context.Background(), tt.serverURL, "token")
+			if err == nil || !strings.Contains(err.Error(), tt.want) {
+				t.Errorf("got error %v, want one containing %q", err, tt.want)
+			}
+		})
+	}
+}
+
+// TestNewClientWithHTTPClient checks that an injected http.Client is used for
+// server requests: the TLS test server is only trusted by its own client.
+func TestNewClientWithHTTPClient(t *testing.T) {
+	t.Parallel()
+
+	var auth string
+
+	srv := httptest.NewTLSServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		auth = r.Header.Get("Authorization")
+
+		w.WriteHeader(http.StatusOK)
+	}))
+	defer srv.Close()
+
+	httpClient := srv.Client()
+
+	transport, ok := httpClient.Transport.(*http.Transport)
+	if !ok {
+		t.Fatalf("unexpected transport %T", httpClient.Transport)
+	}
+
+	handshakeTimeout := transport.TLSHandshakeTimeout
+
+	c, err := client.NewClientWithHTTPClient(t.Context(), httpClient, srv.URL, client.StaticToken("secret"))
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	c.SetConnectTimeout(time.Second)
+
+	req, err := http.NewRequestWithContext(t.Context(), http.MethodGet, srv.URL, nil)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	resp, err := c.DoServerRequest(t.Context(), req)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	_ = resp.Body.Close()
+
+	if auth != "Bearer secret" {
+		t.Errorf("Authorization = %q, want the client's token", auth)
+	}
+
+	if transport.TLSHandshakeTimeout != handshakeTimeout {
+		t.Error("SetConnectTimeout modified the injected client's transport")
+	}
+}
@@ -0,0 +1,242 @@
+package client
+
+import (
+	"context"
+	"errors"
+	"fmt"
+	"io"
+	"runtime"
+	"sync"
+
+	"github.com/klauspost/compress/zstd"
+	"golang.org/x/sync/semaphore"
+)
+
+// Bounds for --compression-level, matching the zstd, xz and brotli CLIs.
+// 0 means the encoder's default.
+const (
+	MinCompressionLevel = 1
+	MaxZstdLevel        = 22
+	MaxXzLevel          = 9
+	MaxBrotliLevel      = 11
+)
+
+// Compression selects how NARs are compressed before upload. The value is
+// written verbatim to the narinfo Compression field.
+type Compression string
+
+const (
+	CompressionZstd   Compression = compressionZstd
+	CompressionXz     Compression = "xz"
+	CompressionBrotli Compression = "br"
+	CompressionNone   Compression = "none"
+)
+
+// ParseCompression validates a --compression value.
+func ParseCompression(s string) (Compression, error) {
+	switch Compression(s) {
+	case CompressionZstd, "":
+		return CompressionZstd, nil
+	case CompressionXz:
+		return CompressionXz, nil
+	case CompressionBrotli:
+		return CompressionBrotli, nil
+	case CompressionNone:
+		return CompressionNone, nil
+	default:
+		return "", fmt.Errorf("unknown compression %q (expected zstd, xz, br or none)", s)
+	}
+}
+
+// Bounds for --zstd-long. Windows above 2^27 (128 MiB) are rejected by
+// libzstd decoders unless raised explicitly, which Nix does not do, so a
+// cache written with them could not be substituted from.
+const (
+	MinZstdWindowLog     = 10
+	MaxZstdWindowLog     = 27
+	DefaultZstdWindowLog = MaxZstdWindowLog
+)
+
+// ValidateZstdWindowLog checks a --zstd-long window log. 0 keeps the
+// window the compression level selects.
+func ValidateZstdWindowLog(windowLog int) error {
+	if windowLog == 0 || (windowLog >= MinZstdWindowLog && windowLog <= MaxZstdWindowLog) {
+		return nil
+	}
+
+	return fmt.Errorf("zstd window log %d out of range (expected %d-%d)", windowLog, MinZstdWindowLog, MaxZstdWindowLog)
+}
+
+// ValidateCompressionLevel checks a --compression-level for c: a zstd
+// level, an xz preset or a brotli quality. 0 selects the default level.
+func ValidateCompressionLevel(c Compression, level int) error {
+	maxLevel := MaxZstdLevel
+
+	switch c {
+	case CompressionZstd, CompressionNone:
+	case CompressionXz:
+		maxLevel = MaxXzLevel
+	case CompressionBrotli:
+		maxLevel = MaxBrotliLevel
+	}
+
+	if level == 0 || (level >= MinCompressionLevel && level <= maxLevel) {
+		return nil
+	}
+
+	return fmt.Errorf("%s compression level %d out of range (expected %d-%d, or 0 for default)", c, level, MinCompressionLevel, maxLevel)
+}
+
+// multithreadNARThreshold is the NarSize above which a NAR is compressed
+// with several zstd workers. Smaller NARs already run in parallel across
+// MaxConcurrentNARUploads, so one worker each avoids oversubscribing the CPU.
+const multithreadNARThreshold = 64 << 20
+
+// zstdPoolKey identifies an encoder configuration in zstdPools.
+type zstdPoolKey struct {
+	level     zstd.EncoderLevel
+	windowLog int
+	workers   int
+}
+
+// zstdPools holds one encoder pool per non-default configuration so a
+// custom level or worker count still reuses encoders across NARs.
+var zstdPools sync.Map //nolint:gochecknoglobals // encoder pools are process-wide like zstdEncoderPool
+
+// zstdPool returns the encoder pool for a zstd level (0 = default), window
+// log (0 = the level's default) and worker count (0 = GOMAXPROCS). None
+// affects NarHash, only the compressed bytes.
+func zstdPool(level, windowLog, workers int) *sync.Pool {
+	key := zstdPoolKey{level: zstd.SpeedDefault, windowLog: windowLog}
+	if level != 0 {
+		key.level = zstd.EncoderLevelFromZstd(level)
+	}
+
+	if workers > 0 && workers != runtime.GOMAXPROCS(0) {
+		key.workers = workers
+	}
+
+	// zstdEncoderPool is SpeedDefault with the library's default concurrency.
+	if key == (zstdPoolKey{level: zstd.SpeedDefault}) {
+		return &zstdEncoderPool
+	}
+
+	if pool, ok := zstdPools.Load(key); ok {
+		return pool.(*sync.Pool) //nolint:forcetypeassert // only *sync.Pool is stored
+	}
+
+	opts := []zstd.EOption{zstd.WithEncoderLevel(key.level)}
+	if key.workers > 0 {
+		opts = append(opts, zstd.WithEncoderConcurrency(key.workers))
+	}
+
+	if key.windowLog > 0 {
+		// Each encoder keeps a window-sized history per worker.
+		opts = append(opts, zstd.WithWindowSize(1<<key.windowLog))
+	}
+
+	pool, _ := zstdPools.LoadOrStore(key, &sync.Pool{
+		New: func() any {
+			encoder, err := zstd.NewWriter(nil, opts...)
+			if err != nil {
+				// This should never happen with nil writer
+				panic(fmt.Sprintf("failed to create zstd encoder: %v", err))
+			}
+
+			return encoder
+		},
+	})
+
+	return pool.(*sync.Pool) //nolint:forcetypeassert // only *sync.Pool is stored
+}
+
+// narCompressionWorkers picks the zstd worker count for a NAR of narSize
+// bytes: CompressionWorkers (or GOMAXPROCS) above multithreadNARThreshold,
+// a single worker below it.
+func (c *Client) narCompressionWorkers(narSize uint64) int {
+	if narSize < multithreadNARThreshold {
+		return 1
+	}
+
+	if c.CompressionWorkers > 0 {
+		return c.CompressionWorkers
+	}
+
+	return runtime.GOMAXPROCS(0)
+}
+
+// acquireCompressionSlot blocks until fewer than CompressionJobs NARs are
+// being compressed across all upload workers. The returned function gives
+// the slot back and may be called more than once.
+func (c *Client) acquireCompressionSlot(ctx context.Context) (func(), error) {
+	c.compressionSemOnce.Do(func() {
+		jobs := c.CompressionJobs
+		if jobs <= 0 {
+			jobs = runtime.GOMAXPROCS(0)
+		}
+
+		c.compressionSem = semaphore.NewWeighted(int64(jobs))
+	})
+
+	if err := c.compressionSem.Acquire(ctx, 1); err != nil {
+		return nil, fmt.Errorf("waiting for a compression slot: %w", err)
+	}
+
+	return sync.OnceFunc(func() { c.compressionSem.Release(1) }), nil
+}
+
+// narExtension returns the object key suffix for NARs with this compression,
+// using the extensions Nix itself writes (e.g. .nar.xz for xz). The server
+// only accepts the suffixes listed in its narRe.
+func (c Compression) narExtension() string {
+	switch c {
+	case CompressionNone:
+		return ".nar"
+	case CompressionZstd:
+		return ".nar.zst"
+	case CompressionXz:
+		return ".nar.xz"
+	case "bzip2":
+		return ".nar.bz2"
+	case CompressionBrotli:
+		return ".nar.br"
+	default:
+		return ".nar." + string(c)
+	}
+}
+
+// nopWriteCloser adapts an io.Writer for uncompressed uploads.
+type nopWriteCloser struct {
+	io.Writer
+}
+
+func (nopWriteCloser) Close() error { return nil }
+
+// newNARCompressor wraps w with the encoder for c at the given level
+// (0 = default), zstd window log (0 = the level's default) and worker
+// count. The window log and workers only apply to zstd. The returned
+// release function hands pooled encoders back and must be called after
+// Close.
+func newNARCompressor(c Compression, level, windowLog, workers int, w io.Writer) (io.WriteCloser, func(), error) {
+	switch c {
+	case CompressionNone:
+		return nopWriteCloser{w}, func() {}, nil
+	case CompressionZstd:
+		pool := zstdPool(level, windowLog, workers)
+
+		encoder, ok := pool.Get().(*zstd.Encoder)
+		if !ok {
+			return nil, nil, errors.New("failed to get zstd encoder from pool")
+		}
+
+		encoder.Reset(w)
+
+		return encoder, func() { pool.Put(encoder) }, nil
+	case CompressionXz:
+		return newXzCompressor(w, level)
+	case CompressionBrotli:
+		return newBrotliCompressor(w, level)
+	default:
+		return nil, nil, fmt.Errorf("unsupported compression %q", c)
+	}
+}
@@ -0,0 +1,253 @@
+package client
+
+import (
+	"bytes"
+	"context"
+	"errors"
+	"fmt"
+	"io"
+	"log/slog"
+	"os/exec"
+	"strconv"
+	"strings"
+)
+
+// cliCompressor pipes everything written to it through an external
+// compression tool such as xz or brotli and writes the tool's output to w. Like nix
+// itself, niks3 relies on the tool found in PATH rather than bundling an
+// encoder for every format Nix understands.
+type cliCompressor struct {
+	cmd    *exec.Cmd
+	stdin  io.WriteCloser
+	stderr bytes.Buffer
+	done   bool
+	err    error
+}
+
+// newCLICompressor starts name with args, writing its output to w.
+func newCLICompressor(w io.Writer, name string, args ...string) (*cliCompressor, error) {
+	// The process ends when its stdin is closed (Close) or it is killed
+	// (abort), so it is not tied to a request context.
+	c := &cliCompressor{cmd: exec.CommandContext(context.Background(), name, args...)}
+
+	c.cmd.Stdout = w
+	c.cmd.Stderr = &c.stderr
+
+	stdin, err := c.cmd.StdinPipe()
+	if err != nil {
+		return nil, fmt.Errorf("creating %s stdin pipe: %w", name, err)
+	}
+
+	if err := c.cmd.Start(); err != nil {
+		return nil, fmt.Errorf("starting %s (is it installed and in PATH?): %w", name, err)
+	}
+
+	c.stdin = stdin
+
+	return c, nil
+}
+
+func (c *cliCompressor) Write(p []byte) (int, error) {
+	n, err := c.stdin.Write(p)
+	if err != nil {
+		return n, fmt.Errorf("writing to %s: %w", c.cmd.Args[0], err)
+	}
+
+	return n, nil
+}
+
+// Close ends the input and waits until the tool has written all of its
+// output to the underlying writer.
+func (c *cliCompressor) Close() error {
+	if c.done {
+		return c.err
+	}
+
+	c.done = true
+
+	if err := c.stdin.Close(); err != nil {
+		c.err = fmt.Errorf("closing %s stdin: %w", c.cmd.Args[0], err)
+		c.kill()
+
+		return c.err
+	}
+
+	if err := c.cmd.Wait(); err != nil {
+		c.err = fmt.Errorf("command failed: %s\nstderr: %s\nerror: %w",
+			strings.Join(c.cmd.Args, " "), c.stderr.String(), err)
+	}
+
+	return c.err
+}
+
+// abort stops the tool if Close was never called, e.g. because serializing
+// the NAR failed halfway.
+func (c *cliCompressor) abort() {
+	if c.done {
+		return
+	}
+
+	c.done = true
+	c.kill()
+}
+
+func (c *cliCompressor) kill() {
+	if err := c.cmd.Process.Kill(); err != nil {
+		slog.Debug("Failed to kill compressor", "command", c.cmd.Args[0], "error", err)
+	}
+
+	_ = c.cmd.Wait()
+}
+
+// newXzCompressor compresses to w with `xz`, at preset level (0 = the xz
+// default).
+func newXzCompressor(w io.Writer, level int) (io.WriteCloser, func(), error) {
+	args := []string{"--compress", "--stdout"}
+	if level != 0 {
+		args = append(args, "-"+strconv.Itoa(level))
+	}
+
+	encoder, err := newCLICompressor(w, "xz", args...)
+	if err != nil {
+		return nil, nil, err
+	}
+
+	return encoder, encoder.abort, nil
+}
+
+// newBrotliCompressor compresses to w with `brotli`, at quality level (0 =
+// the brotli default).
+func newBrotliCompressor(w io.Writer, level int) (io.WriteCloser, func(), error) {
+	args := []string{"--stdout"}
+	if level != 0 {
+		args = append(args, "--quality="+strconv.Itoa(level))
+	}
+
+	encoder, err := newCLICompressor(w, "brotli", args...)
+	if err != nil {
+		return nil, nil, err
+	}
+
+	return encoder, encoder.abort, nil
+}
+
+// cliDecompressor reads the output of an external decompression tool that
+// is fed from r.
+type cliDecompressor struct {
+	cmd    *exec.Cmd
+	stdout io.ReadCloser
+	stderr bytes.Buffer
+	// fed is closed once all of r was copied to the tool, or copying failed.
+	fed  chan struct{}
+	done bool
+	err  error
+}
+
+// newCLIDecompressor starts name with args, feeding it r.
+func newCLIDecompressor(r io.Reader, name string, args ...string) (*cliDecompressor, error) {
+	// Like cliCompressor, the process is ended by EOF or abort.
+	d := &cliDecompressor{cmd: exec.CommandContext(context.Background(), name, args...), fed: make(chan struct{})}
+
+	d.cmd.Stderr = &d.stderr
+
+	stdin, err := d.cmd.StdinPipe()
+	if err != nil {
+		return nil, fmt.Errorf("creating %s stdin pipe: %w", name, err)
+	}
+
+	stdout, err := d.cmd.StdoutPipe()
+	if err != nil {
+		return nil, fmt.Errorf("creating %s stdout pipe: %w", name, err)
+	}
+
+	if err := d.cmd.Start(); err != nil {
+		return nil, fmt.Errorf("starting %s (is it installed and in PATH?): %w", name, err)
+	}
+
+	d.stdout = stdout
+
+	// Not cmd.Stdin: Wait would block on a stalled r even after a kill.
+	go func() {
+		defer close(d.fed)
+
+		if _, err := io.Copy(stdin, r); err != nil {
+			// A corrupt stream makes the tool exit early; Read reports that.
+			slog.Debug("Stopped feeding decompressor", "command", name, "error", err)
+		}
+
+		if err := stdin.Close(); err != nil {
+			slog.Debug("Failed to close decompressor stdin", "command", name, "error", err)
+		}
+	}()
+
+	return d, nil
+}
+
+// Read returns decompressed bytes. At EOF it waits for the tool and reports
+// a failed exit, so a truncated or corrupt stream is never silently cut short.
+func (d *cliDecompressor) Read(p []byte) (int, error) {
+	n, err := d.stdout.Read(p)
+	if errors.Is(err, io.EOF) {
+		if waitErr := d.wait(); waitErr != nil {
+			return n, waitErr
+		}
+	}
+
+	return n, err //nolint:wrapcheck // io.EOF must reach the caller unwrapped
+}
+
+func (d *cliDecompressor) wait() error {
+	if d.done {
+		return d.err
+	}
+
+	d.done = true
+
+	if err := d.cmd.Wait(); err != nil {
+		d.err = fmt.Errorf("command failed: %s\nstderr: %s\nerror: %w",
+			strings.Join(d.cmd.Args, " "), d.stderr.String(), err)
+	}
+
+	if d.err == nil {
+		// Callers may read the rest of r once the NAR is done (verify keeps
+		// a copy of the download), so r must no longer be in use.
+		<-d.fed
+	}
+
+	return d.err
+}
+
+// abort stops the tool if the stream was not read to EOF.
+func (d *cliDecompressor) abort() {
+	if d.done {
+		return
+	}
+
+	d.done = true
+
+	if err := d.cmd.Process.Kill(); err != nil {
+		slog.Debug("Failed to kill decompressor", "command", d.cmd.Args[0], "error", err)
+	}
+
+	_ = d.cmd.Wait()
+}
+
+// newXzDecompressor decompresses r with `xz`.
+func newXzDecompressor(r io.Reader) (io.Reader, func(), error) {
+	decoder, err := newCLIDecompressor(r, "xz", "--decompress", "--stdout")
+	if err != nil {
+		return nil, nil, err
+	}
+
+	return decoder, decoder.abort, nil
+}
+
+// newBrotliDecompressor decompresses r with `brotli`.
+func newBrotliDecompressor(r io.Reader) (io.Reader, func(), error) {
+	decoder, err := newCLIDecompressor(r, "brotli", "--decompress", "--stdout")
+	if err != nil {
+		return nil, nil, err
+	}
+
+	return decoder, decoder.abort, nil
+}
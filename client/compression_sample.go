@@ -0,0 +1,118 @@
+package client
+
+import (
+	"context"
+	"errors"
+	"fmt"
+	"log/slog"
+	"runtime"
+
+	"golang.org/x/sync/errgroup"
+)
+
+// compressionSampleSize is how much of the start of each NAR is compressed
+// to estimate whether compressing the whole NAR is worthwhile.
+const compressionSampleSize = 1 << 20
+
+// errSampleFull stops NAR serialization once the sample is complete.
+var errSampleFull = errors.New("compression sample complete")
+
+// sampleWriter keeps the first limit bytes written to it and then fails.
+type sampleWriter struct {
+	buf   []byte
+	limit int
+}
+
+func (s *sampleWriter) Write(p []byte) (int, error) {
+	if room := s.limit - len(s.buf); len(p) > room {
+		s.buf = append(s.buf, p[:room]...)
+
+		return room, errSampleFull
+	}
+
+	s.buf = append(s.buf, p...)
+
+	return len(p), nil
+}
+
+// sampleCompressionRatio compresses the first compressionSampleSize bytes of
+// storePath's NAR with the client's compression settings and returns the
+// uncompressed size divided by the compressed size.
+func (c *Client) sampleCompressionRatio(ctx context.Context, storePath string) (float64, error) {
+	sample := &sampleWriter{limit: compressionSampleSize}
+
+	if _, _, err := c.dumpNAR(ctx, sample, storePath); err != nil && !errors.Is(err, errSampleFull) {
+		return 0, fmt.Errorf("sampling NAR of %s: %w", storePath, err)
+	}
+
+	return compressionRatio(c.Compression, c.CompressionLevel, sample.buf)
+}
+
+// compressionRatio returns len(data) divided by its compressed size.
+func compressionRatio(compression Compression, level int, data []byte) (float64, error) {
+	counter := &countingWriter{}
+
+	// The sample is far smaller than any window, so ZstdWindowLog is moot.
+	encoder, release, err := newNARCompressor(compression, level, 0, 1, counter)
+	if err != nil {
+		return 0, err
+	}
+	defer release()
+
+	if _, err := encoder.Write(data); err != nil {
+		return 0, fmt.Errorf("compressing sample: %w", err)
+	}
+
+	if err := encoder.Close(); err != nil {
+		return 0, fmt.Errorf("closing %s encoder: %w", compression, err)
+	}
+
+	return float64(len(data)) / float64(max(counter.n, 1)), nil
+}
+
+// skipIncompressibleNARs samples every NAR in pathInfos and marks those whose
+// sample compresses worse than MinCompressionRatio to be stored
+// uncompressed. Sampling failures keep the default compression; the upload
+// reports the underlying problem.
+func (c *Client) skipIncompressibleNARs(ctx context.Context, pathInfos map[string]*PathInfo) {
+	jobs := c.CompressionJobs
+	if jobs <= 0 {
+		jobs = runtime.GOMAXPROCS(0)
+	}
+
+	var g errgroup.Group
+
+	g.SetLimit(jobs)
+
+	for _, pathInfo := range pathInfos {
+		g.Go(func() error {
+			ratio, err := c.sampleCompressionRatio(ctx, pathInfo.Path)
+			if err != nil {
+				slog.Debug("Failed to sample NAR compression", "path", pathInfo.Path, "error", err)
+
+				return nil
+			}
+
+			if ratio < c.MinCompressionRatio {
+				slog.Debug("Storing NAR uncompressed", "path", pathInfo.Path, "ratio", ratio)
+				pathInfo.compression = CompressionNone
+			}
+
+			return nil
+		})
+	}
+
+	_ = g.Wait()
+
+	uncompressed := 0
+
+	for _, pathInfo := range pathInfos {
+		if pathInfo.compression == CompressionNone {
+			uncompressed++
+		}
+	}
+
+	if uncompressed > 0 {
+		slog.Info(fmt.Sprintf("Storing %d NARs uncompressed (compression ratio below %.2f)", uncompressed, c.MinCompressionRatio))
+	}
+}
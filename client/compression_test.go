@@ -0,0 +1,335 @@
+package client_test
+
+import (
+	"bytes"
+	"context"
+	"crypto/rand"
+	"errors"
+	"io"
+	"net/http"
+	"os"
+	"os/exec"
+	"path/filepath"
+	"slices"
+	"strconv"
+	"testing"
+	"time"
+
+	"github.com/Mic92/niks3/client"
+)
+
+func TestParseCompression(t *testing.T) {
+	t.Parallel()
+
+	tests := []struct {
+		in      string
+		want    client.Compression
+		wantErr bool
+	}{
+		{"", client.CompressionZstd, false},
+		{"zstd", client.CompressionZstd, false},
+		{"none", client.CompressionNone, false},
+		{"xz", client.CompressionXz, false},
+		{"br", client.CompressionBrotli, false},
+		{"gzip", "", true},
+	}
+
+	for _, tc := range tests {
+		got, err := client.ParseCompression(tc.in)
+		if (err != nil) != tc.wantErr {
+			t.Fatalf("ParseCompression(%q) error = %v, wantErr %v", tc.in, err, tc.wantErr)
+		}
+
+		if got != tc.want {
+			t.Errorf("ParseCompression(%q) = %q, want %q", tc.in, got, tc.want)
+		}
+	}
+}
+
+func TestNARKeySuffix(t *testing.T) {
+	t.Parallel()
+
+	const narHash = "sha256:1b8m03r63zqhnjf7l5wnldhh7c134ap5vpj0850ymkq1iyzicy5s"
+
+	zst, err := client.GetNARKey(narHash, client.CompressionZstd)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if zst != "nar/1b8m03r63zqhnjf7l5wnldhh7c134ap5vpj0850ymkq1iyzicy5s.nar.zst" {
+		t.Errorf("unexpected zstd key %q", zst)
+	}
+
+	plain, err := client.GetNARKey(narHash, client.CompressionNone)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if plain != "nar/1b8m03r63zqhnjf7l5wnldhh7c134ap5vpj0850ymkq1iyzicy5s.nar" {
+		t.Errorf("unexpected uncompressed key %q", plain)
+	}
+
+	xz, err := client.GetNARKey(narHash, client.CompressionXz)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if xz != "nar/1b8m03r63zqhnjf7l5wnldhh7c134ap5vpj0850ymkq1iyzicy5s.nar.xz" {
+		t.Errorf("unexpected xz key %q", xz)
+	}
+}
+
+func TestCLICompressorRoundTrip(t *testing.T) {
+	t.Parallel()
+
+	nar := bytes.Repeat([]byte("nix-archive-1\x00(type regular contents hello)"), 4096)
+
+	for _, tc := range []struct {
+		compression client.Compression
+		tool        string
+		level       int
+	}{
+		{client.CompressionXz, "xz", 0},
+		{client.CompressionXz, "xz", client.MaxXzLevel},
+		{client.CompressionBrotli, "brotli", 0},
+		{client.CompressionBrotli, "brotli", 5},
+	} {
+		t.Run(string(tc.compression)+"-"+strconv.Itoa(tc.level), func(t *testing.T) {
+			t.Parallel()
+
+			if _, err := exec.LookPath(tc.tool); err != nil {
+				t.Skip(tc.tool + " not found in PATH")
+			}
+
+			var compressed bytes.Buffer
+
+			encoder, release, err := client.NewNARCompressor(tc.compression, tc.level, 0, 1, &compressed)
+			if err != nil {
+				t.Fatal(err)
+			}
+			defer release()
+
+			if _, err := encoder.Write(nar); err != nil {
+				t.Fatal(err)
+			}
+
+			if err := encoder.Close(); err != nil {
+				t.Fatal(err)
+			}
+
+			if compressed.Len() >= len(nar) {
+				t.Errorf("output is %d bytes, not smaller than the %d byte input", compressed.Len(), len(nar))
+			}
+
+			got, err := decompressNAR(t, string(tc.compression), compressed.Bytes())
+			if err != nil {
+				t.Fatal(err)
+			}
+
+			if !bytes.Equal(got, nar) {
+				t.Errorf("round trip returned %d bytes, want the original %d", len(got), len(nar))
+			}
+
+			// A truncated download must fail instead of yielding a short NAR.
+			if _, err := decompressNAR(t, string(tc.compression), compressed.Bytes()[:compressed.Len()/2]); err == nil {
+				t.Error("truncated stream decompressed without error")
+			}
+		})
+	}
+}
+
+func decompressNAR(t *testing.T, compression string, data []byte) ([]byte, error) {
+	t.Helper()
+
+	r, release, err := client.NARDecompressor(compression, bytes.NewReader(data))
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer release()
+
+	return io.ReadAll(r) //nolint:wrapcheck // test helper
+}
+
+func TestValidateCompressionLevel(t *testing.T) {
+	t.Parallel()
+
+	valid := map[client.Compression][]int{
+		client.CompressionZstd:   {0, client.MinCompressionLevel, 3, 19, client.MaxZstdLevel},
+		client.CompressionXz:     {0, client.MinCompressionLevel, 6, client.MaxXzLevel},
+		client.CompressionBrotli: {0, client.MinCompressionLevel, 5, client.MaxBrotliLevel},
+	}
+
+	invalid := map[client.Compression][]int{
+		client.CompressionZstd:   {-1, client.MaxZstdLevel + 1, 100},
+		client.CompressionXz:     {-1, client.MaxXzLevel + 1, client.MaxZstdLevel},
+		client.CompressionBrotli: {-1, client.MaxBrotliLevel + 1, client.MaxZstdLevel},
+	}
+
+	for compression, levels := range valid {
+		for _, level := range levels {
+			if err := client.ValidateCompressionLevel(compression, level); err != nil {
+				t.Errorf("%s level %d rejected: %v", compression, level, err)
+			}
+		}
+	}
+
+	for compression, levels := range invalid {
+		for _, level := range levels {
+			if err := client.ValidateCompressionLevel(compression, level); err == nil {
+				t.Errorf("%s level %d accepted", compression, level)
+			}
+		}
+	}
+}
+
+func TestValidateZstdWindowLog(t *testing.T) {
+	t.Parallel()
+
+	for _, windowLog := range []int{0, client.MinZstdWindowLog, 24, client.MaxZstdWindowLog} {
+		if err := client.ValidateZstdWindowLog(windowLog); err != nil {
+			t.Errorf("window log %d rejected: %v", windowLog, err)
+		}
+	}
+
+	for _, windowLog := range []int{-1, client.MinZstdWindowLog - 1, client.MaxZstdWindowLog + 1} {
+		if err := client.ValidateZstdWindowLog(windowLog); err == nil {
+			t.Errorf("window log %d accepted", windowLog)
+		}
+	}
+}
+
+// TestZstdLongWindowRoundTrip compresses data whose repeats are further
+// apart than the default 8 MiB window with the largest window --zstd-long
+// writes, and checks that the pull decoder accepts it.
+func TestZstdLongWindowRoundTrip(t *testing.T) {
+	t.Parallel()
+
+	chunk := make([]byte, 9<<20)
+	if _, err := rand.Read(chunk); err != nil {
+		t.Fatal(err)
+	}
+
+	data := append(slices.Clone(chunk), chunk...)
+
+	var compressed bytes.Buffer
+
+	encoder, release, err := client.NewNARCompressor(client.CompressionZstd, 0, client.MaxZstdWindowLog, 1, &compressed)
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer release()
+
+	if _, err := encoder.Write(data); err != nil {
+		t.Fatal(err)
+	}
+
+	if err := encoder.Close(); err != nil {
+		t.Fatal(err)
+	}
+
+	r, closeDecoder, err := client.NARDecompressor(string(client.CompressionZstd), &compressed)
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer closeDecoder()
+
+	got, err := io.ReadAll(r)
+	if err != nil {
+		t.Fatalf("decompressing: %v", err)
+	}
+
+	if !bytes.Equal(got, data) {
+		t.Fatal("round trip changed the data")
+	}
+}
+
+func TestNARKeySuffixPerCompression(t *testing.T) {
+	t.Parallel()
+
+	const narHash = "sha256:1b8m03r63zqhnjf7l5wnldhh7c134ap5vpj0850ymkq1iyzicy5s"
+
+	for compression, suffix := range map[client.Compression]string{
+		client.CompressionNone:   ".nar",
+		client.CompressionZstd:   ".nar.zst",
+		client.CompressionXz:     ".nar.xz",
+		"bzip2":                  ".nar.bz2",
+		client.CompressionBrotli: ".nar.br",
+	} {
+		key, err := client.GetNARKey(narHash, compression)
+		if err != nil {
+			t.Fatal(err)
+		}
+
+		// Keyed by NAR content hash, never by store path basename
+		if want := "nar/1b8m03r63zqhnjf7l5wnldhh7c134ap5vpj0850ymkq1iyzicy5s" + suffix; key != want {
+			t.Errorf("%s: key = %q, want %q", compression, key, want)
+		}
+	}
+}
+
+func TestCompressionJobsLimit(t *testing.T) {
+	t.Parallel()
+
+	c := newTestClientWithRetries(http.DefaultClient, 0)
+	c.CompressionJobs = 1
+
+	release, err := c.AcquireCompressionSlot(context.Background())
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	ctx, cancel := context.WithTimeout(context.Background(), 20*time.Millisecond)
+	defer cancel()
+
+	if _, err := c.AcquireCompressionSlot(ctx); !errors.Is(err, context.DeadlineExceeded) {
+		t.Fatalf("expected the second slot to block with CompressionJobs=1, got %v", err)
+	}
+
+	// Releasing twice must not free a slot that is not held.
+	release()
+	release()
+
+	release, err = c.AcquireCompressionSlot(context.Background())
+	if err != nil {
+		t.Fatalf("slot not available after release: %v", err)
+	}
+
+	release()
+}
+
+func TestSampleCompressionRatio(t *testing.T) {
+	t.Parallel()
+
+	c := newTestClientWithRetries(http.DefaultClient, 0)
+
+	random := make([]byte, 2<<20)
+	if _, err := rand.Read(random); err != nil {
+		t.Fatal(err)
+	}
+
+	tests := []struct {
+		name     string
+		contents []byte
+		minRatio float64
+		maxRatio float64
+	}{
+		{"random", random, 0, 1.1},
+		{"zeros", make([]byte, 2<<20), 10, 0},
+	}
+
+	for _, tc := range tests {
+		dir := t.TempDir()
+		if err := os.WriteFile(filepath.Join(dir, "data"), tc.contents, 0o644); err != nil { //nolint:gosec // test fixture
+			t.Fatal(err)
+		}
+
+		ratio, err := c.SampleCompressionRatio(context.Background(), dir)
+		if err != nil {
+			t.Fatalf("%s: %v", tc.name, err)
+		}
+
+		if ratio < tc.minRatio || (tc.maxRatio > 0 && ratio > tc.maxRatio) {
+			t.Errorf("%s: ratio %.2f outside [%.2f, %.2f]", tc.name, ratio, tc.minRatio, tc.maxRatio)
+		}
+	}
+}
@@ -0,0 +1,112 @@
+package client
+
+import (
+	"context"
+	"encoding/json"
+	"fmt"
+	"log/slog"
+	"net/http"
+	"strings"
+
+	"github.com/Mic92/niks3/api"
+)
+
+// maxReferrersShown bounds the referrers a DeleteError names; a widely used
+// dependency can have thousands.
+const maxReferrersShown = 5
+
+// DeleteError is returned when the server refuses to delete a path because
+// it is pinned or still referenced by other paths.
+type DeleteError struct {
+	StorePath string
+	Message   string
+	Pins      []string
+	Referrers []string
+}
+
+func (e *DeleteError) Error() string {
+	switch {
+	case len(e.Pins) > 0:
+		return fmt.Sprintf("cannot delete %s: pinned by %s; delete the pins first", e.StorePath, strings.Join(e.Pins, ", "))
+	case len(e.Referrers) > 0:
+		shown := e.Referrers[:min(len(e.Referrers), maxReferrersShown)]
+
+		msg := fmt.Sprintf("cannot delete %s: still referenced by %s", e.StorePath, strings.Join(shown, ", "))
+		if more := len(e.Referrers) - len(shown); more > 0 {
+			msg += fmt.Sprintf(" and %d more", more)
+		}
+
+		return msg + "; delete those first"
+	default:
+		return fmt.Sprintf("cannot delete %s: %s", e.StorePath, e.Message)
+	}
+}
+
+// DeletePath asks the server to delete the narinfo of storePath, a store
+// path or its hash, below KeyPrefix. The NAR, listing and realisations only
+// that narinfo referenced go with it. The server refuses paths that are
+// pinned or referenced by other paths with a *DeleteError. With dryRun
+// nothing is deleted and the result lists what would be.
+func (c *Client) DeletePath(ctx context.Context, storePath string, dryRun bool) (*api.DeleteResult, error) {
+	hash, err := GetStorePathHash(storePath)
+	if err != nil {
+		return nil, err
+	}
+
+	reqURL := c.baseURL.JoinPath("api/objects", c.wireKey(hash+".narinfo"))
+
+	if dryRun {
+		reqURL.RawQuery = "dry-run=true"
+	}
+
+	req, err := http.NewRequestWithContext(ctx, http.MethodDelete, reqURL.String(), http.NoBody)
+	if err != nil {
+		return nil, fmt.Errorf("creating request: %w", err)
+	}
+
+	resp, err := c.DoServerRequest(ctx, req)
+	if err != nil {
+		return nil, fmt.Errorf("sending request: %w", err)
+	}
+
+	defer deferCloseBody(resp)
+
+	switch resp.StatusCode {
+	case http.StatusNotFound:
+		return nil, fmt.Errorf("%s is not in the cache", storePath)
+	case http.StatusConflict:
+		var conflict api.DeleteConflictResponse
+		if err := json.NewDecoder(resp.Body).Decode(&conflict); err != nil {
+			return nil, fmt.Errorf("deleting %s refused (failed to parse response): %w", storePath, err)
+		}
+
+		referrers := make([]string, len(conflict.Referrers))
+		for i, key := range conflict.Referrers {
+			referrers[i] = c.localKey(key)
+		}
+
+		return nil, &DeleteError{
+			StorePath: storePath,
+			Message:   conflict.Error,
+			Pins:      conflict.Pins,
+			Referrers: referrers,
+		}
+	}
+
+	if err := checkResponse(resp, http.StatusOK); err != nil {
+		return nil, fmt.Errorf("deleting %s: %w", storePath, err)
+	}
+
+	var result api.DeleteResult
+	if err := json.NewDecoder(resp.Body).Decode(&result); err != nil {
+		return nil, fmt.Errorf("parsing response: %w", err)
+	}
+
+	for i, key := range result.Deleted {
+		result.Deleted[i] = c.localKey(key)
+	}
+
+	slog.Debug("Deleted path", "store_path", storePath, "objects", result.Deleted, "dry_run", result.DryRun)
+
+	return &result, nil
+}
@@ -0,0 +1,95 @@
+package client_test
+
+import (
+	"context"
+	"encoding/json"
+	"errors"
+	"net/http"
+	"net/http/httptest"
+	"slices"
+	"strings"
+	"testing"
+
+	"github.com/Mic92/niks3/api"
+	"github.com/Mic92/niks3/client"
+)
+
+func TestDeletePath(t *testing.T) {
+	t.Parallel()
+
+	const (
+		hash      = "0ha1dhmx807czjczmwy078s4r9s254il"
+		storePath = "/nix/store/" + hash + "-hello"
+	)
+
+	var gotPath, gotQuery string
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if r.Method != http.MethodDelete {
+			http.Error(w, "unexpected request", http.StatusBadRequest)
+
+			return
+		}
+
+		gotPath, gotQuery = r.URL.Path, r.URL.RawQuery
+
+		_ = json.NewEncoder(w).Encode(api.DeleteResult{
+			Deleted: []string{"team-a/" + hash + ".narinfo", "team-a/nar/x.nar.zst"},
+			DryRun:  r.URL.Query().Get("dry-run") == "true",
+		})
+	}))
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	c.KeyPrefix = "team-a"
+
+	result, err := c.DeletePath(context.Background(), storePath, true)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if gotPath != "/api/objects/team-a/"+hash+".narinfo" || gotQuery != "dry-run=true" {
+		t.Errorf("requested %s?%s", gotPath, gotQuery)
+	}
+
+	if !result.DryRun || !slices.Equal(result.Deleted, []string{hash + ".narinfo", "nar/x.nar.zst"}) {
+		t.Errorf("result = %+v", result)
+	}
+
+	if _, err := c.DeletePath(context.Background(), "/nix/store/not-a-path", false); err == nil {
+		t.Error("expected an error for an invalid store path")
+	}
+}
+
+func TestDeletePathReferenced(t *testing.T) {
+	t.Parallel()
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, _ *http.Request) {
+		referrers := []string{"a.narinfo", "b.narinfo", "c.narinfo", "d.narinfo", "e.narinfo", "f.narinfo", "g.narinfo"}
+
+		w.Header().Set("Content-Type", "application/json")
+		w.WriteHeader(http.StatusConflict)
+		_ = json.NewEncoder(w).Encode(api.DeleteConflictResponse{Error: "still referenced", Referrers: referrers})
+	}))
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	_, err = c.DeletePath(context.Background(), "0ha1dhmx807czjczmwy078s4r9s254il", false)
+
+	var deleteErr *client.DeleteError
+	if !errors.As(err, &deleteErr) {
+		t.Fatalf("expected a *DeleteError, got %v", err)
+	}
+
+	if len(deleteErr.Referrers) != 7 || !strings.Contains(err.Error(), "e.narinfo and 2 more") {
+		t.Errorf("unexpected error: %v", err)
+	}
+}
@@ -0,0 +1,99 @@
+package client
+
+import (
+	"context"
+	"crypto/sha256"
+	"errors"
+	"fmt"
+	"io/fs"
+	"log/slog"
+	"maps"
+	"os"
+	"slices"
+	"strings"
+)
+
+// addDerivations adds the derivations of topLevelPaths and their closures,
+// i.e. the .drv files of the whole build graph, to a push. Derivations that
+// are no longer in the store are skipped with a warning.
+func (c *Client) addDerivations(ctx context.Context, topLevelPaths []string, pathInfos map[string]*PathInfo) ([]string, map[string]*PathInfo, error) {
+	seen := make(map[string]bool)
+	drvPaths := make([]string, 0, len(topLevelPaths))
+
+	for _, storePath := range topLevelPaths {
+		info := pathInfos[storePath]
+		if info == nil || knownDeriver(info.Deriver) == nil {
+			continue
+		}
+
+		drvPath := *info.Deriver
+		if seen[drvPath] || pathInfos[drvPath] != nil {
+			continue
+		}
+
+		seen[drvPath] = true
+
+		if c.PathInfoFile == "" && c.effectiveStore() == "" {
+			if _, err := os.Lstat(drvPath); errors.Is(err, fs.ErrNotExist) {
+				slog.Warn("Derivation is not in the store, not pushing it", "drv_path", drvPath, "store_path", storePath)
+
+				continue
+			}
+		}
+
+		drvPaths = append(drvPaths, drvPath)
+	}
+
+	if len(drvPaths) == 0 {
+		return topLevelPaths, pathInfos, nil
+	}
+
+	drvInfos, err := c.getPathInfo(ctx, drvPaths)
+	if err != nil {
+		return nil, nil, fmt.Errorf("getting path info of derivations: %w", err)
+	}
+
+	merged := maps.Clone(pathInfos)
+
+	for drvPath, info := range drvInfos {
+		if _, ok := merged[drvPath]; ok {
+			continue
+		}
+
+		if err := c.fillDerivationCA(drvPath, info); err != nil {
+			return nil, nil, err
+		}
+
+		merged[drvPath] = info
+	}
+
+	slog.Debug("Added derivations", "top_level", len(drvPaths), "paths", len(merged)-len(pathInfos))
+
+	return append(slices.Clone(topLevelPaths), drvPaths...), merged, nil
+}
+
+// fillDerivationCA sets the content address of a .drv path if nix did not
+// report one, as `nix-store --dump-db` does not. Derivations are text
+// content-addressed by the SHA-256 of their contents, and narinfos without
+// a CA field would make Nix treat them as input-addressed.
+func (c *Client) fillDerivationCA(storePath string, info *PathInfo) error {
+	if info.CA != nil || !strings.HasSuffix(storePath, ".drv") {
+		return nil
+	}
+
+	if c.effectiveStore() != "" {
+		slog.Warn("Cannot compute content address of a derivation in a remote store", "drv_path", storePath)
+
+		return nil
+	}
+
+	data, err := os.ReadFile(storePath)
+	if err != nil {
+		return fmt.Errorf("reading derivation %s: %w", storePath, err)
+	}
+
+	sum := sha256.Sum256(data)
+	info.CA = &ContentAddress{raw: "text:sha256:" + EncodeNixBase32(sum[:])}
+
+	return nil
+}
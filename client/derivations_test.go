@@ -0,0 +1,96 @@
+package client_test
+
+import (
+	"crypto/sha256"
+	"os"
+	"path/filepath"
+	"slices"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+func TestAddDerivations(t *testing.T) {
+	t.Parallel()
+
+	const (
+		storePath = "/nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1"
+		drvPath   = "/nix/store/8ha1dhmx807czjczmwy078s4r9s254il-hello-2.12.1.drv"
+		depDrv    = "/nix/store/0ha1dhmx807czjczmwy078s4r9s254il-bash-5.2.drv"
+		drvCA     = "text:sha256:1b8m03r63zqhnjf7l5wnldhh7c134ap5vpj0850ymkq1iyzicy5s"
+	)
+
+	dump := filepath.Join(t.TempDir(), "path-info.json")
+	if err := os.WriteFile(dump, []byte(`{
+		"`+storePath+`": {
+			"narHash": "sha256-FePFYIlMuycIXPZbWi7LGEiMmZSX9FMbaQenWBzm1Sc=",
+			"narSize": 226560,
+			"references": [],
+			"deriver": "`+drvPath+`"
+		},
+		"`+drvPath+`": {
+			"narHash": "sha256-FePFYIlMuycIXPZbWi7LGEiMmZSX9FMbaQenWBzm1Sc=",
+			"narSize": 1024,
+			"references": ["`+depDrv+`"],
+			"ca": "`+drvCA+`"
+		},
+		"`+depDrv+`": {
+			"narHash": "sha256-FePFYIlMuycIXPZbWi7LGEiMmZSX9FMbaQenWBzm1Sc=",
+			"narSize": 512,
+			"references": [],
+			"ca": "`+drvCA+`"
+		}
+	}`), 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	all, err := client.LoadPathInfoFile(dump)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	c := client.NewTestClientWithStoreDir("/nix/store")
+	c.PathInfoFile = dump
+
+	topLevel, pathInfos, err := c.AddDerivations(t.Context(), []string{storePath}, map[string]*client.PathInfo{storePath: all[storePath]})
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if !slices.Equal(topLevel, []string{storePath, drvPath}) {
+		t.Errorf("top-level paths = %v", topLevel)
+	}
+
+	for _, p := range []string{storePath, drvPath, depDrv} {
+		if pathInfos[p] == nil {
+			t.Errorf("%s is missing from the push", p)
+		}
+	}
+
+	if got := pathInfos[drvPath].CA.String(); got != drvCA {
+		t.Errorf("CA = %q, want %q", got, drvCA)
+	}
+}
+
+func TestFillDerivationCA(t *testing.T) {
+	t.Parallel()
+
+	contents := []byte(`Derive([("out","/nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1","","")],[],[],"x86_64-linux","/bin/sh",[],[])`)
+
+	drvPath := filepath.Join(t.TempDir(), "8ha1dhmx807czjczmwy078s4r9s254il-hello-2.12.1.drv")
+	if err := os.WriteFile(drvPath, contents, 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	c := client.NewTestClientWithStoreDir("/nix/store")
+	info := &client.PathInfo{}
+
+	if err := c.FillDerivationCA(drvPath, info); err != nil {
+		t.Fatal(err)
+	}
+
+	sum := sha256.Sum256(contents)
+	if want := "text:sha256:" + client.EncodeNixBase32(sum[:]); info.CA == nil || info.CA.String() != want {
+		t.Errorf("CA = %v, want %s", info.CA, want)
+	}
+}
@@ -0,0 +1,386 @@
+package client
+
+import (
+	"context"
+	"errors"
+	"fmt"
+	"log/slog"
+	"maps"
+	"net/url"
+	"slices"
+	"strconv"
+	"strings"
+	"sync"
+	"sync/atomic"
+	"time"
+
+	"github.com/Mic92/niks3/server/signing"
+	"github.com/minio/minio-go/v7"
+	"github.com/minio/minio-go/v7/pkg/credentials"
+)
+
+const (
+	// directS3URLExpiry is how long presigned URLs minted by DirectS3 stay
+	// valid, the same as the server's.
+	directS3URLExpiry = 5 * time.Hour
+
+	// directS3PartSize mirrors the server: NARs up to one part are uploaded
+	// with a single PUT, larger ones as multipart uploads.
+	directS3PartSize = 10 * 1024 * 1024
+
+	// directS3InitialParts is the number of part URLs handed out per
+	// multipart upload; the uploader asks for more as it needs them.
+	directS3InitialParts = 10
+)
+
+// DirectS3Config configures a DirectS3 backend.
+type DirectS3Config struct {
+	Endpoint     string         // S3 endpoint, host[:port] or an http(s):// URL ("" = s3.amazonaws.com)
+	Bucket       string         // Bucket the cache lives in
+	Region       string         // Bucket region ("" = asked from S3)
+	AccessKey    string         // Access key ID ("" = IAM instance credentials)
+	SecretKey    string         // Secret access key
+	SessionToken string         // Session token for temporary credentials ("" = none)
+	KeyPrefix    string         // Upload every object below this bucket prefix ("" = bucket root)
+	StoreDir     string         // Written to nix-cache-info if the bucket has none ("" = leave it missing)
+	SigningKeys  []*signing.Key // Keys narinfos are signed with (none = unsigned narinfos)
+}
+
+// DirectS3 implements CacheAPI against an S3 bucket without a niks3 server,
+// for users who only have S3 credentials. It presigns the PUTs and
+// multipart parts the server would hand out with SigV4 itself and signs
+// narinfos with local keys. Pending closures exist only in memory: there is
+// no database, so closures are not registered, pins and garbage collection
+// are unavailable, and a narinfo is visible as soon as it is uploaded.
+// Objects already in the bucket are left out of pending closures.
+type DirectS3 struct {
+	s3     *minio.Client
+	config DirectS3Config
+
+	cacheInfoOnce sync.Once
+	nextClosureID atomic.Int64
+
+	mu        sync.Mutex
+	multipart map[string]map[string]string // Closure ID to the upload IDs of its unfinished multipart uploads, by S3 key
+}
+
+var _ CacheAPI = (*DirectS3)(nil)
+
+// NewDirectS3 creates a DirectS3 backend from cfg.
+func NewDirectS3(cfg DirectS3Config) (*DirectS3, error) {
+	if cfg.Bucket == "" {
+		return nil, errors.New("an S3 bucket is required")
+	}
+
+	if err := ValidateKeyPrefix(cfg.KeyPrefix); err != nil {
+		return nil, err
+	}
+
+	endpoint, secure, err := parseS3Endpoint(cfg.Endpoint)
+	if err != nil {
+		return nil, err
+	}
+
+	var creds *credentials.Credentials
+	if cfg.AccessKey != "" {
+		creds = credentials.NewStaticV4(cfg.AccessKey, cfg.SecretKey, cfg.SessionToken)
+	} else {
+		creds = credentials.NewIAM("")
+	}
+
+	s3, err := minio.New(endpoint, &minio.Options{
+		Creds:  creds,
+		Secure: secure,
+		Region: cfg.Region,
+	})
+	if err != nil {
+		return nil, fmt.Errorf("creating S3 client: %w", err)
+	}
+
+	return &DirectS3{
+		s3:        s3,
+		config:    cfg,
+		multipart: make(map[string]map[string]string),
+	}, nil
+}
+
+// parseS3Endpoint splits endpoint into the host minio expects and whether
+// to use TLS. A bare host uses TLS.
+func parseS3Endpoint(endpoint string) (string, bool, error) {
+	if endpoint == "" {
+		return "s3.amazonaws.com", true, nil
+	}
+
+	if !strings.Contains(endpoint, "://") {
+		return endpoint, true, nil
+	}
+
+	u, err := url.Parse(endpoint)
+	if err != nil {
+		return "", false, fmt.Errorf("invalid S3 endpoint %q: %w", endpoint, err)
+	}
+
+	if (u.Scheme != "http" && u.Scheme != "https") || u.Host == "" || strings.Trim(u.Path, "/") != "" {
+		return "", false, fmt.Errorf("invalid S3 endpoint %q: expected http(s)://host[:port]", endpoint)
+	}
+
+	return u.Host, u.Scheme == "https", nil
+}
+
+// URL returns the endpoint presigned URLs point at, e.g. for
+// NewClientWithTokenSource.
+func (d *DirectS3) URL() string {
+	return d.s3.EndpointURL().String()
+}
+
+// key returns the bucket key of objectKey, below KeyPrefix.
+func (d *DirectS3) key(objectKey string) string {
+	if d.config.KeyPrefix == "" {
+		return objectKey
+	}
+
+	return d.config.KeyPrefix + "/" + objectKey
+}
+
+// ObjectExists reports whether objectKey, below KeyPrefix, is in the bucket.
+// Client.ObjectExists uses it instead of asking a server.
+func (d *DirectS3) ObjectExists(ctx context.Context, objectKey string) (bool, error) {
+	_, err := d.s3.StatObject(ctx, d.config.Bucket, d.key(objectKey), minio.StatObjectOptions{})
+	if err == nil {
+		return true, nil
+	}
+
+	if minio.ToErrorResponse(err).Code == minio.NoSuchKey {
+		return false, nil
+	}
+
+	return false, fmt.Errorf("checking %s in S3: %w", objectKey, err)
+}
+
+// CreatePendingClosure hands out upload URLs for the objects that are not
+// in the bucket yet. verifyS3 is implied: the bucket is the only record of
+// what is cached.
+func (d *DirectS3) CreatePendingClosure(ctx context.Context, closure string, objects []ObjectWithRefs, _ bool) (*CreatePendingClosureResponse, error) {
+	d.cacheInfoOnce.Do(func() { d.ensureCacheInfo(ctx) })
+
+	id := strconv.FormatInt(d.nextClosureID.Add(1), 10)
+
+	resp := &CreatePendingClosureResponse{
+		ID:             id,
+		StartedAt:      time.Now().UTC().Format(time.RFC3339),
+		PendingObjects: make(map[string]PendingObject, len(objects)),
+	}
+
+	for _, obj := range objects {
+		exists, err := d.ObjectExists(ctx, obj.Key)
+		if err != nil {
+			_ = d.AbortPendingClosure(ctx, id)
+
+			return nil, err
+		}
+
+		if exists {
+			continue
+		}
+
+		pending, err := d.pendingObject(ctx, id, obj)
+		if err != nil {
+			_ = d.AbortPendingClosure(ctx, id)
+
+			return nil, err
+		}
+
+		resp.PendingObjects[obj.Key] = pending
+	}
+
+	slog.Debug("Created pending closure in S3", "closure", closure, "id", id, "pending_objects", len(resp.PendingObjects))
+
+	return resp, nil
+}
+
+// pendingObject presigns the upload of obj: a single PUT, or a multipart
+// upload for NARs larger than one part or of unknown size.
+func (d *DirectS3) pendingObject(ctx context.Context, closureID string, obj ObjectWithRefs) (PendingObject, error) {
+	if obj.Type != ObjectTypeNAR || (obj.NarSize != nil && *obj.NarSize > 0 && *obj.NarSize <= directS3PartSize) {
+		presignedURL, err := d.RefreshPresignedURL(ctx, closureID, obj.Key)
+		if err != nil {
+			return PendingObject{}, err
+		}
+
+		return PendingObject{Type: string(obj.Type), PresignedURL: presignedURL}, nil
+	}
+
+	core := minio.Core{Client: d.s3}
+
+	uploadID, err := core.NewMultipartUpload(ctx, d.config.Bucket, d.key(obj.Key), minio.PutObjectOptions{
+		ContentType: "application/octet-stream",
+	})
+	if err != nil {
+		return PendingObject{}, fmt.Errorf("starting multipart upload of %s: %w", obj.Key, err)
+	}
+
+	d.mu.Lock()
+
+	if d.multipart[closureID] == nil {
+		d.multipart[closureID] = make(map[string]string)
+	}
+
+	d.multipart[closureID][obj.Key] = uploadID
+	d.mu.Unlock()
+
+	partURLs, err := d.RequestMoreParts(ctx, obj.Key, uploadID, 1, directS3InitialParts)
+	if err != nil {
+		return PendingObject{}, err
+	}
+
+	return PendingObject{
+		Type:          string(ObjectTypeNAR),
+		MultipartInfo: &MultipartUploadInfo{UploadID: uploadID, PartURLs: partURLs},
+	}, nil
+}
+
+// ensureCacheInfo writes a default nix-cache-info if the bucket has none,
+// as the server does on startup. Failures only warn: the push itself does
+// not need it.
+func (d *DirectS3) ensureCacheInfo(ctx context.Context) {
+	if d.config.StoreDir == "" {
+		return
+	}
+
+	exists, err := d.ObjectExists(ctx, "nix-cache-info")
+	if err != nil || exists {
+		return
+	}
+
+	info := fmt.Sprintf("StoreDir: %s\nWantMassQuery: 1\nPriority: 30\n", d.config.StoreDir)
+
+	if _, err := d.s3.PutObject(ctx, d.config.Bucket, d.key("nix-cache-info"), strings.NewReader(info), int64(len(info)),
+		minio.PutObjectOptions{ContentType: "text/x-nix-cache-info"}); err != nil {
+		slog.Warn("Failed to write nix-cache-info", "error", err)
+
+		return
+	}
+
+	slog.Info("Created nix-cache-info", "store_dir", d.config.StoreDir)
+}
+
+// SignPendingClosure signs the narinfos with the configured keys.
+func (d *DirectS3) SignPendingClosure(_ context.Context, _ string, narinfos map[string]NarinfoMetadata) (map[string][]string, error) {
+	signatures := make(map[string][]string, len(narinfos))
+
+	for key, meta := range narinfos {
+		sigs := []string{}
+
+		if len(d.config.SigningKeys) > 0 {
+			var err error
+
+			sigs, err = signing.SignNarinfo(d.config.SigningKeys, &signing.NarInfo{
+				StorePath:  meta.StorePath,
+				NarHash:    meta.NarHash,
+				NarSize:    meta.NarSize,
+				References: meta.References,
+			})
+			if err != nil {
+				return nil, fmt.Errorf("signing %s: %w", key, err)
+			}
+		}
+
+		signatures[key] = sigs
+	}
+
+	return signatures, nil
+}
+
+// CompletePendingClosure forgets the closure. Its objects are already in
+// the bucket; there is nothing to register.
+func (d *DirectS3) CompletePendingClosure(_ context.Context, closureID string) error {
+	d.mu.Lock()
+	defer d.mu.Unlock()
+
+	delete(d.multipart, closureID)
+
+	return nil
+}
+
+// AbortPendingClosure aborts the closure's unfinished multipart uploads.
+// Objects already uploaded stay in the bucket.
+func (d *DirectS3) AbortPendingClosure(ctx context.Context, closureID string) error {
+	d.mu.Lock()
+	uploads := d.multipart[closureID]
+	delete(d.multipart, closureID)
+	d.mu.Unlock()
+
+	core := minio.Core{Client: d.s3}
+
+	var errs []error
+
+	for _, key := range slices.Sorted(maps.Keys(uploads)) {
+		err := core.AbortMultipartUpload(ctx, d.config.Bucket, d.key(key), uploads[key])
+		if err != nil && minio.ToErrorResponse(err).Code != minio.NoSuchUpload {
+			errs = append(errs, fmt.Errorf("aborting multipart upload of %s: %w", key, err))
+		}
+	}
+
+	return errors.Join(errs...)
+}
+
+// RefreshPresignedURL presigns a PUT of objectKey.
+func (d *DirectS3) RefreshPresignedURL(ctx context.Context, _ string, objectKey string) (string, error) {
+	presignedURL, err := d.s3.PresignedPutObject(ctx, d.config.Bucket, d.key(objectKey), directS3URLExpiry)
+	if err != nil {
+		return "", fmt.Errorf("presigning %s: %w", objectKey, err)
+	}
+
+	return presignedURL.String(), nil
+}
+
+// RequestMoreParts presigns numParts part uploads starting at
+// startPartNumber.
+func (d *DirectS3) RequestMoreParts(ctx context.Context, objectKey, uploadID string, startPartNumber, numParts int) ([]string, error) {
+	partURLs := make([]string, numParts)
+
+	for i := range numParts {
+		partNumber := startPartNumber + i
+
+		params := make(url.Values)
+		params.Set("uploadId", uploadID)
+		params.Set("partNumber", strconv.Itoa(partNumber))
+
+		presignedURL, err := d.s3.Presign(ctx, "PUT", d.config.Bucket, d.key(objectKey), directS3URLExpiry, params)
+		if err != nil {
+			return nil, fmt.Errorf("presigning part %d of %s: %w", partNumber, objectKey, err)
+		}
+
+		partURLs[i] = presignedURL.String()
+	}
+
+	return partURLs, nil
+}
+
+// CompleteMultipartUpload completes a multipart upload from its parts.
+func (d *DirectS3) CompleteMultipartUpload(ctx context.Context, objectKey, uploadID string, parts []CompletedPart) error {
+	completeParts := make([]minio.CompletePart, len(parts))
+	for i, part := range parts {
+		completeParts[i] = minio.CompletePart{PartNumber: part.PartNumber, ETag: part.ETag}
+	}
+
+	slices.SortFunc(completeParts, func(a, b minio.CompletePart) int { return a.PartNumber - b.PartNumber })
+
+	core := minio.Core{Client: d.s3}
+
+	if _, err := core.CompleteMultipartUpload(ctx, d.config.Bucket, d.key(objectKey), uploadID, completeParts, minio.PutObjectOptions{}); err != nil {
+		return fmt.Errorf("completing multipart upload of %s: %w", objectKey, err)
+	}
+
+	d.mu.Lock()
+
+	for _, uploads := range d.multipart {
+		if uploads[objectKey] == uploadID {
+			delete(uploads, objectKey)
+		}
+	}
+
+	d.mu.Unlock()
+
+	return nil
+}
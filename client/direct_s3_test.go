@@ -0,0 +1,139 @@
+package client_test
+
+import (
+	"context"
+	"io"
+	"net/http"
+	"net/http/httptest"
+	"strconv"
+	"strings"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/Mic92/niks3/client"
+	"github.com/Mic92/niks3/server/signing"
+)
+
+func TestPushDirectS3(t *testing.T) {
+	t.Parallel()
+
+	var (
+		mu      sync.Mutex
+		objects = make(map[string][]byte)
+		puts    int
+	)
+
+	s3 := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		mu.Lock()
+		defer mu.Unlock()
+
+		key := strings.TrimPrefix(r.URL.Path, "/")
+
+		switch r.Method {
+		case http.MethodHead:
+			body, ok := objects[key]
+			if !ok {
+				w.WriteHeader(http.StatusNotFound)
+
+				return
+			}
+
+			w.Header().Set("ETag", `"etag"`)
+			w.Header().Set("Content-Length", strconv.Itoa(len(body)))
+			w.Header().Set("Last-Modified", time.Now().UTC().Format(http.TimeFormat))
+		case http.MethodPut:
+			if r.URL.Query().Get("X-Amz-Signature") == "" && r.Header.Get("Authorization") == "" {
+				http.Error(w, "unsigned request", http.StatusForbidden)
+
+				return
+			}
+
+			body, err := io.ReadAll(r.Body)
+			if err != nil {
+				http.Error(w, err.Error(), http.StatusBadRequest)
+
+				return
+			}
+
+			objects[key] = body
+			puts++
+
+			w.Header().Set("ETag", `"etag"`)
+		default:
+			t.Errorf("unexpected S3 request %s %s", r.Method, r.URL)
+			http.Error(w, "unexpected request", http.StatusBadRequest)
+		}
+	}))
+	defer s3.Close()
+
+	key, err := signing.GenerateKey("test-1", nil)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	store := newTestStore(t, harnessStore)
+
+	direct, err := client.NewDirectS3(client.DirectS3Config{
+		Endpoint:    s3.URL,
+		Bucket:      "cache",
+		Region:      "us-east-1",
+		AccessKey:   "access",
+		SecretKey:   "secret",
+		KeyPrefix:   "team-a",
+		StoreDir:    store.Dir,
+		SigningKeys: []*signing.Key{key},
+	})
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	c, err := client.NewTestClientForServer(direct.URL())
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	opts := store.PushOptions()
+	opts.API = direct
+
+	if _, err := client.Push(context.Background(), c, store.Paths, opts); err != nil {
+		t.Fatal(err)
+	}
+
+	mu.Lock()
+	_, hasCacheInfo := objects["cache/team-a/nix-cache-info"]
+	narinfos := make(map[string][]byte, len(store.Paths))
+
+	for _, storePath := range store.Paths {
+		narinfos[storePath] = objects["cache/team-a/"+narinfoKey(t, storePath)]
+	}
+
+	firstPuts := puts
+	mu.Unlock()
+
+	if !hasCacheInfo {
+		t.Error("nix-cache-info was not written")
+	}
+
+	for storePath, body := range narinfos {
+		if body == nil {
+			t.Fatalf("no narinfo uploaded for %s", storePath)
+		}
+
+		if narinfo := string(decompressZstd(t, body)); !strings.Contains(narinfo, "\nSig: test-1:") {
+			t.Errorf("narinfo of %s is not signed:\n%s", storePath, narinfo)
+		}
+	}
+
+	// Everything is in the bucket now, so a second push uploads nothing.
+	if _, err := client.Push(context.Background(), c, store.Paths, opts); err != nil {
+		t.Fatal(err)
+	}
+
+	mu.Lock()
+	defer mu.Unlock()
+
+	if puts != firstPuts {
+		t.Errorf("second push uploaded %d objects, want 0", puts-firstPuts)
+	}
+}
@@ -0,0 +1,157 @@
+package client
+
+import (
+	"context"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"io"
+	"net/http"
+	"os"
+	"os/exec"
+	"strings"
+
+	"github.com/Mic92/niks3/api"
+	"golang.org/x/sys/unix"
+)
+
+// DoctorCheck is the outcome of one `niks3 doctor` check. Err is nil if the
+// check passed; Hint then stays empty and Detail says what was found.
+type DoctorCheck struct {
+	Name   string
+	Detail string
+	Err    error
+	Hint   string
+}
+
+func passed(name, detail string) DoctorCheck {
+	return DoctorCheck{Name: name, Detail: detail}
+}
+
+func failed(name string, err error, hint string) DoctorCheck {
+	return DoctorCheck{Name: name, Err: err, Hint: hint}
+}
+
+// Doctor runs every environment check in order. The auth check is skipped
+// if the server cannot be reached, since it would only fail the same way.
+func (c *Client) Doctor(ctx context.Context) []DoctorCheck {
+	checks := []DoctorCheck{c.CheckNix(ctx)}
+
+	server := c.CheckServer(ctx)
+	checks = append(checks, server)
+
+	if server.Err == nil {
+		checks = append(checks, c.CheckAuth(ctx))
+	}
+
+	return append(checks, CheckStoreDir(c.effectiveStoreDir()))
+}
+
+// CheckNix checks that nix is on PATH and accepts the nix-command
+// experimental feature every nix invocation of the client enables. Nix
+// before 2.4 passes if nix-store, which is used instead, is available.
+func (c *Client) CheckNix(ctx context.Context) DoctorCheck {
+	const name = "nix"
+
+	if !detectNixCLI().unified {
+		if _, err := exec.LookPath("nix-store"); err != nil {
+			return failed(name, err, "install Nix or add it to PATH; push reads path info with `nix path-info`")
+		}
+
+		return passed(name, "no unified nix CLI, reading the store with nix-store")
+	}
+
+	cmd := exec.CommandContext(ctx, "nix", "--extra-experimental-features", "nix-command", "eval", "--raw", "--expr", "builtins.nixVersion")
+	if len(c.NixEnv) > 0 {
+		cmd.Env = c.NixEnv
+	}
+
+	output, err := cmd.CombinedOutput()
+	if err != nil {
+		return failed(name, fmt.Errorf("%w: %s", err, strings.TrimSpace(string(output))),
+			"nix must support the nix-command experimental feature (Nix 2.4 or later)")
+	}
+
+	return passed(name, "version "+strings.TrimSpace(string(output)))
+}
+
+// CheckServer checks that the server URL points at a niks3 server by
+// fetching its unauthenticated cache configuration.
+func (c *Client) CheckServer(ctx context.Context) DoctorCheck {
+	const name = "server"
+
+	reqURL := c.baseURL.JoinPath("api/cache-config")
+
+	req, err := http.NewRequestWithContext(ctx, http.MethodGet, reqURL.String(), nil)
+	if err != nil {
+		return failed(name, fmt.Errorf("creating request: %w", err), "check --server-url")
+	}
+
+	resp, err := c.DoServerRequest(ctx, req)
+	if err != nil {
+		return failed(name, err, "check --server-url and that the server is running and reachable from here")
+	}
+
+	defer deferCloseBody(resp)
+
+	if err := checkResponse(resp, http.StatusOK); err != nil {
+		return failed(name, err, "the URL answers but is not a niks3 server; check --server-url, including any path prefix")
+	}
+
+	var cfg api.CacheConfig
+	if err := json.NewDecoder(resp.Body).Decode(&cfg); err != nil {
+		return failed(name, fmt.Errorf("decoding /api/cache-config: %w", err),
+			"the URL answers but is not a niks3 server; check --server-url, including any path prefix")
+	}
+
+	detail := fmt.Sprintf("%s, %d signing keys", c.baseURL, len(cfg.PublicKeys))
+	if cfg.SubstituterURL != "" {
+		detail += ", substituter " + cfg.SubstituterURL
+	}
+
+	return passed(name, detail)
+}
+
+// CheckAuth checks that the server accepts the auth token, using a
+// read-only object lookup that needs authentication.
+func (c *Client) CheckAuth(ctx context.Context) DoctorCheck {
+	const name = "auth"
+
+	_, err := c.ObjectExists(ctx, "nix-cache-info")
+
+	var statusErr *HTTPStatusError
+	if errors.As(err, &statusErr) && (statusErr.StatusCode == http.StatusUnauthorized || statusErr.StatusCode == http.StatusForbidden) {
+		return failed(name, err, "the token was rejected; check --auth-token-path/--auth-token-script or NIKS3_AUTH_TOKEN_FILE")
+	}
+
+	if err != nil {
+		return failed(name, err, "the server failed to answer an authenticated request; check its logs")
+	}
+
+	return passed(name, "token accepted")
+}
+
+// CheckStoreDir checks that the Nix store directory can be read, which
+// push needs to serialize paths. Whether it is writable is only reported:
+// with a nix daemon, users normally cannot write to it.
+func CheckStoreDir(storeDir string) DoctorCheck {
+	name := "store " + storeDir
+
+	f, err := os.Open(storeDir)
+	if err != nil {
+		return failed(name, err, "set --store-dir or NIX_STORE_DIR if the store is not at "+storeDir)
+	}
+	defer func() { _ = f.Close() }()
+
+	// Reading one entry proves the directory is listable without walking
+	// a store of possibly millions of paths.
+	if _, err := f.Readdirnames(1); err != nil && !errors.Is(err, io.EOF) {
+		return failed(name, err, "the store directory must be readable by this user")
+	}
+
+	if unix.Access(storeDir, unix.W_OK) == nil {
+		return passed(name, "readable, writable")
+	}
+
+	return passed(name, "readable, read-only (fine for push)")
+}
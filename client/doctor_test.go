@@ -0,0 +1,111 @@
+package client_test
+
+import (
+	"context"
+	"net/http"
+	"net/http/httptest"
+	"path/filepath"
+	"strings"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+func newDoctorClient(t *testing.T, handler http.HandlerFunc) *client.Client {
+	t.Helper()
+
+	srv := httptest.NewServer(handler)
+	t.Cleanup(srv.Close)
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	c.Retry.MaxRetries = 0
+
+	return c
+}
+
+func TestCheckServer(t *testing.T) {
+	t.Parallel()
+
+	c := newDoctorClient(t, func(w http.ResponseWriter, r *http.Request) {
+		if r.URL.Path != "/api/cache-config" {
+			http.NotFound(w, r)
+
+			return
+		}
+
+		_, _ = w.Write([]byte(`{"substituter_url":"https://cache.example.com","public_keys":["cache.example.com-1:AAAA"]}`))
+	})
+
+	check := c.CheckServer(context.Background())
+	if check.Err != nil {
+		t.Fatalf("expected pass, got %v", check.Err)
+	}
+
+	if !strings.Contains(check.Detail, "1 signing keys") || !strings.Contains(check.Detail, "https://cache.example.com") {
+		t.Errorf("unexpected detail %q", check.Detail)
+	}
+}
+
+func TestCheckServerNotNiks3(t *testing.T) {
+	t.Parallel()
+
+	// e.g. a reverse proxy's landing page
+	c := newDoctorClient(t, func(w http.ResponseWriter, _ *http.Request) {
+		_, _ = w.Write([]byte("<html>welcome</html>"))
+	})
+
+	if check := c.CheckServer(context.Background()); check.Err == nil || check.Hint == "" {
+		t.Fatalf("expected failure with a hint, got %+v", check)
+	}
+}
+
+func TestCheckAuth(t *testing.T) {
+	t.Parallel()
+
+	for _, tc := range []struct {
+		status int
+		ok     bool
+	}{
+		{http.StatusNotFound, true},
+		{http.StatusNoContent, true},
+		{http.StatusUnauthorized, false},
+		{http.StatusForbidden, false},
+	} {
+		c := newDoctorClient(t, func(w http.ResponseWriter, r *http.Request) {
+			if r.Method != http.MethodHead || r.URL.Path != "/api/objects/nix-cache-info" {
+				http.Error(w, "unexpected request", http.StatusBadRequest)
+
+				return
+			}
+
+			w.WriteHeader(tc.status)
+		})
+
+		check := c.CheckAuth(context.Background())
+		if (check.Err == nil) != tc.ok {
+			t.Errorf("status %d: got %+v, want ok=%v", tc.status, check, tc.ok)
+		}
+
+		if !tc.ok && !strings.Contains(check.Hint, "token") {
+			t.Errorf("status %d: hint %q does not mention the token", tc.status, check.Hint)
+		}
+	}
+}
+
+func TestCheckStoreDir(t *testing.T) {
+	t.Parallel()
+
+	dir := t.TempDir()
+
+	if check := client.CheckStoreDir(dir); check.Err != nil {
+		t.Errorf("expected %s to pass, got %v", dir, check.Err)
+	}
+
+	if check := client.CheckStoreDir(filepath.Join(dir, "missing")); check.Err == nil {
+		t.Error("expected a missing store directory to fail")
+	}
+}
@@ -0,0 +1,47 @@
+package client
+
+import (
+	"fmt"
+	"log/slog"
+	"maps"
+	"slices"
+)
+
+// planUpload splits pathInfos into the paths a push would upload and those
+// the cache already has, and sums the uncompressed NAR size of the former.
+func planUpload(pathInfos map[string]*PathInfo, cached map[string]bool) ([]string, []string, uint64) {
+	var (
+		toUpload, present []string
+		narBytes          uint64
+	)
+
+	for _, storePath := range slices.Sorted(maps.Keys(pathInfos)) {
+		if cached[storePath] {
+			present = append(present, storePath)
+
+			continue
+		}
+
+		toUpload = append(toUpload, storePath)
+		narBytes += pathInfos[storePath].NarSize
+	}
+
+	return toUpload, present, narBytes
+}
+
+// reportDryRun logs what a push would upload. cached is nil when the cache
+// was not queried, in which case every path counts as missing.
+func reportDryRun(pathInfos map[string]*PathInfo, cached map[string]bool, result *PrepareClosuresResult) {
+	toUpload, present, narBytes := planUpload(pathInfos, cached)
+
+	for _, storePath := range present {
+		slog.Info("Already in cache", "store_path", storePath)
+	}
+
+	for _, storePath := range toUpload {
+		slog.Info("Would upload", "store_path", storePath, "nar_size", FormatBytes(pathInfos[storePath].NarSize))
+	}
+
+	slog.Info(fmt.Sprintf("Dry run: would upload %d paths (%s uncompressed), %d already in the cache; %d objects in %d closures",
+		len(toUpload), FormatBytes(narBytes), len(present), countClosureObjects(result.Closures), len(result.Closures)))
+}
@@ -0,0 +1,38 @@
+package client_test
+
+import (
+	"slices"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+func TestPlanUpload(t *testing.T) {
+	t.Parallel()
+
+	pathInfos := map[string]*client.PathInfo{
+		"/nix/store/a-app": {NarSize: 100},
+		"/nix/store/b-lib": {NarSize: 20},
+		"/nix/store/c-doc": {NarSize: 3},
+	}
+
+	toUpload, present, narBytes := client.PlanUpload(pathInfos, map[string]bool{"/nix/store/b-lib": true})
+
+	if want := []string{"/nix/store/a-app", "/nix/store/c-doc"}; !slices.Equal(toUpload, want) {
+		t.Errorf("toUpload = %v, want %v", toUpload, want)
+	}
+
+	if want := []string{"/nix/store/b-lib"}; !slices.Equal(present, want) {
+		t.Errorf("present = %v, want %v", present, want)
+	}
+
+	if narBytes != 103 {
+		t.Errorf("narBytes = %d, want 103", narBytes)
+	}
+
+	// Without a cache query every path counts as missing.
+	toUpload, present, _ = client.PlanUpload(pathInfos, nil)
+	if len(toUpload) != 3 || len(present) != 0 {
+		t.Errorf("expected all paths to be uploaded without a cache query, got %v / %v", toUpload, present)
+	}
+}
@@ -0,0 +1,55 @@
+package client
+
+// PushEvent reports push progress to Client.OnEvent. It is one of
+// PathDiscovered, CompressionStarted, NarUploaded, NarinfoUploaded and
+// ClosureCompleted; use a type switch to tell them apart.
+type PushEvent interface {
+	pushEvent()
+}
+
+// PathDiscovered is sent once per store path that is going to be pushed,
+// after paths already in the cache have been skipped. All PathDiscovered
+// events are sent before the first upload starts, so summing NarSize gives
+// the total of the push up front.
+type PathDiscovered struct {
+	StorePath string
+	NarSize   uint64 // Uncompressed NAR size
+}
+
+// CompressionStarted is sent when a NAR starts being serialized and
+// compressed.
+type CompressionStarted struct {
+	StorePath string
+	Bytes     uint64 // Uncompressed NAR size
+}
+
+// NarUploaded is sent when a NAR and its listing have been uploaded.
+type NarUploaded struct {
+	StorePath      string
+	FileHash       string // Hash of the compressed NAR, "sha256:<nix32>"
+	CompressedSize uint64
+}
+
+// NarinfoUploaded is sent when the signed narinfo of a path has been
+// uploaded, which is the last object uploaded for a path.
+type NarinfoUploaded struct {
+	StorePath string
+}
+
+// ClosureCompleted is sent when the server has accepted a finished closure.
+type ClosureCompleted struct {
+	NarinfoKey string // Narinfo key of the closure's top-level path
+}
+
+func (PathDiscovered) pushEvent()     {}
+func (CompressionStarted) pushEvent() {}
+func (NarUploaded) pushEvent()        {}
+func (NarinfoUploaded) pushEvent()    {}
+func (ClosureCompleted) pushEvent()   {}
+
+// emit passes ev to c.OnEvent if one is set.
+func (c *Client) emit(ev PushEvent) {
+	if c.OnEvent != nil {
+		c.OnEvent(ev)
+	}
+}
@@ -0,0 +1,119 @@
+package client_test
+
+import (
+	"context"
+	"encoding/json"
+	"fmt"
+	"io"
+	"net/http"
+	"net/http/httptest"
+	"sync"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+// TestUploadNAREmitsEvents checks that uploading a NAR reports the start of
+// compression with the NAR size and the upload with the compressed digest.
+func TestUploadNAREmitsEvents(t *testing.T) {
+	t.Parallel()
+
+	src := t.TempDir()
+	makeMixedTree(t, src)
+
+	_, digest, err := client.DumpPathWithDigest(io.Discard, src)
+	if err != nil {
+		t.Fatalf("DumpPathWithDigest: %v", err)
+	}
+
+	var pathInfo client.PathInfo
+	if err := json.Unmarshal(fmt.Appendf(nil, `{"narHash":%q,"narSize":%d}`, digest.NarHash, digest.NarSize), &pathInfo); err != nil {
+		t.Fatal(err)
+	}
+
+	pathInfo.Path = src
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if r.Method != http.MethodPut {
+			http.Error(w, "unexpected request", http.StatusBadRequest)
+
+			return
+		}
+
+		w.WriteHeader(http.StatusOK)
+	}))
+	defer srv.Close()
+
+	c := newTestClientWithRetries(srv.Client(), 0)
+
+	var (
+		mu     sync.Mutex
+		events []client.PushEvent
+	)
+
+	c.OnEvent = func(ev client.PushEvent) {
+		mu.Lock()
+		defer mu.Unlock()
+
+		events = append(events, ev)
+	}
+
+	fileDigest, err := c.UploadNARWithListing(context.Background(), "nar/test.nar.zst",
+		client.PendingObject{Type: "nar", PresignedURL: srv.URL + "/nar"}, &pathInfo)
+	if err != nil {
+		t.Fatalf("UploadNARWithListing: %v", err)
+	}
+
+	want := []client.PushEvent{
+		client.CompressionStarted{StorePath: src, Bytes: digest.NarSize},
+		client.NarUploaded{StorePath: src, FileHash: fileDigest.FileHash, CompressedSize: fileDigest.FileSize},
+	}
+
+	if len(events) != len(want) {
+		t.Fatalf("expected %d events, got %d: %+v", len(want), len(events), events)
+	}
+
+	for i := range want {
+		if events[i] != want[i] {
+			t.Errorf("event %d = %+v, want %+v", i, events[i], want[i])
+		}
+	}
+}
+
+// TestPushEmitsNarSizes checks that PathDiscovered carries each path's NAR
+// size, so progress output can sum the total before uploads start.
+func TestPushEmitsNarSizes(t *testing.T) {
+	t.Parallel()
+
+	m := newMockCache(t)
+	store := newTestStore(t, harnessStore)
+
+	var (
+		mu         sync.Mutex
+		discovered = make(map[string]uint64)
+	)
+
+	opts := store.PushOptions()
+	opts.OnEvent = func(ev client.PushEvent) {
+		mu.Lock()
+		defer mu.Unlock()
+
+		if ev, ok := ev.(client.PathDiscovered); ok {
+			discovered[ev.StorePath] = ev.NarSize
+		}
+	}
+
+	if _, err := client.Push(context.Background(), m.Client(t), store.Paths, opts); err != nil {
+		t.Fatal(err)
+	}
+
+	if len(discovered) != len(store.Paths) {
+		t.Fatalf("discovered %d paths, want %d: %v", len(discovered), len(store.Paths), discovered)
+	}
+
+	for _, storePath := range store.Paths {
+		if got, want := discovered[storePath], uint64(len(store.NARs[storePath])); got != want {
+			t.Errorf("PathDiscovered NarSize of %s = %d, want %d", storePath, got, want)
+		}
+	}
+}
@@ -0,0 +1,62 @@
+package client
+
+import (
+	"fmt"
+	"log/slog"
+)
+
+// excludePaths drops the paths in c.Exclude from a push. Excluded paths may
+// still be referenced by the narinfos of paths that are pushed; only their
+// own NAR and narinfo are left out. Paths that are only reachable through
+// an excluded path are dropped as well, since no pushed closure contains
+// them. Excluded top-level paths are skipped with a warning.
+func (c *Client) excludePaths(topLevelPaths []string, pathInfos map[string]*PathInfo) ([]string, map[string]*PathInfo, error) {
+	resolved, err := resolveSymlinks(c.Exclude, c.effectiveStoreDir())
+	if err != nil {
+		return nil, nil, fmt.Errorf("resolving excluded paths: %w", err)
+	}
+
+	excluded := make(map[string]bool, len(resolved))
+	for _, storePath := range resolved {
+		excluded[storePath] = true
+	}
+
+	remaining := make([]string, 0, len(topLevelPaths))
+	kept := make(map[string]*PathInfo, len(pathInfos))
+
+	var visit func(string)
+
+	visit = func(storePath string) {
+		if excluded[storePath] || kept[storePath] != nil {
+			return
+		}
+
+		pathInfo, ok := pathInfos[storePath]
+		if !ok {
+			return
+		}
+
+		kept[storePath] = pathInfo
+
+		for _, ref := range pathInfo.References {
+			visit(ref)
+		}
+	}
+
+	for _, topLevelPath := range topLevelPaths {
+		if excluded[topLevelPath] {
+			slog.Warn("Not pushing excluded path", "store_path", topLevelPath)
+
+			continue
+		}
+
+		remaining = append(remaining, topLevelPath)
+		visit(topLevelPath)
+	}
+
+	if dropped := len(pathInfos) - len(kept); dropped > 0 {
+		slog.Info(fmt.Sprintf("Excluded %d paths from the push", dropped))
+	}
+
+	return remaining, kept, nil
+}
@@ -0,0 +1,50 @@
+package client_test
+
+import (
+	"maps"
+	"slices"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+func TestExcludePaths(t *testing.T) {
+	t.Parallel()
+
+	const (
+		app    = "/nix/store/00000000000000000000000000000000-app"
+		lib    = "/nix/store/11111111111111111111111111111111-lib"
+		secret = "/nix/store/22222222222222222222222222222222-secret"
+		// Only reachable through secret.
+		secretDep = "/nix/store/33333333333333333333333333333333-secret-dep"
+		tool      = "/nix/store/44444444444444444444444444444444-tool"
+	)
+
+	pathInfos := map[string]*client.PathInfo{
+		app:       {References: []string{lib, secret}},
+		lib:       {},
+		secret:    {References: []string{secretDep}},
+		secretDep: {},
+		tool:      {References: []string{lib}},
+	}
+
+	c := client.NewTestClientWithStoreDir("/nix/store")
+
+	topLevel, kept, err := c.ExcludePaths([]string{secret, tool}, []string{app, tool}, pathInfos)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if !slices.Equal(topLevel, []string{app}) {
+		t.Errorf("top-level paths = %v, want [%s]", topLevel, app)
+	}
+
+	if got := slices.Sorted(maps.Keys(kept)); !slices.Equal(got, []string{app, lib}) {
+		t.Errorf("kept paths = %v, want [%s %s]", got, app, lib)
+	}
+
+	// The narinfo of app still references the excluded path.
+	if !slices.Contains(kept[app].References, secret) {
+		t.Errorf("references of app lost %s: %v", secret, kept[app].References)
+	}
+}
@@ -12,6 +12,12 @@ import (
 // ParsePathInfoJSON exports parsePathInfoJSON for testing.
 var ParsePathInfoJSON = parsePathInfoJSON //nolint:gochecknoglobals // test-only re-export
 
+// ClosureFromPathInfos exports closureFromPathInfos for testing.
+var ClosureFromPathInfos = closureFromPathInfos //nolint:gochecknoglobals // test-only re-export
+
+// PlanUpload exports planUpload for testing.
+var PlanUpload = planUpload //nolint:gochecknoglobals // test-only re-export
+
 // ShellSplit re-exports shellSplit for the external test package.
 var ShellSplit = shellSplit //nolint:gochecknoglobals // test-only re-export
 
@@ -42,6 +48,8 @@ func NewTestClientWithToken(httpClient *http.Client, retry RetryConfig, ts Token
 		Retry:             retry,
 		S3RateLimiter:     ratelimit.NewAdaptiveRateLimiter(0, "s3-test"),
 		ServerRateLimiter: ratelimit.NewAdaptiveRateLimiter(0, "server-test"),
+		Compression:       CompressionZstd,
+		WriteListings:     true,
 	}
 }
 
@@ -67,3 +75,132 @@ func NewTestClientForServer(serverURL string) (*Client, error) {
 func (c *Client) UploadMultipart(ctx context.Context, r io.Reader, info *MultipartUploadInfo, objectKey string, partSize int) error {
 	return c.uploadMultipart(ctx, r, info, objectKey, partSize)
 }
+
+// GetNARKey re-exports getNARKey for the external test package.
+var GetNARKey = getNARKey //nolint:gochecknoglobals // test-only re-export
+
+// NewNARCompressor re-exports newNARCompressor for the external test package.
+var NewNARCompressor = newNARCompressor //nolint:gochecknoglobals // test-only re-export
+
+// NARDecompressor re-exports narDecompressor for the external test package.
+var NARDecompressor = narDecompressor //nolint:gochecknoglobals // test-only re-export
+
+// NewFileDigestWriter re-exports newFileDigestWriter for the external test package.
+var NewFileDigestWriter = newFileDigestWriter //nolint:gochecknoglobals // test-only re-export
+
+// GenerateNarinfoContent re-exports generateNarinfoContent for the external test package.
+var GenerateNarinfoContent = generateNarinfoContent //nolint:gochecknoglobals // test-only re-export
+
+// NewNarinfoMetadata re-exports newNarinfoMetadata for the external test package.
+var NewNarinfoMetadata = newNarinfoMetadata //nolint:gochecknoglobals // test-only re-export
+
+// DropCachedClosures re-exports dropCachedClosures for the external test package.
+var DropCachedClosures = dropCachedClosures //nolint:gochecknoglobals // test-only re-export
+
+// CheckBuildLogStaging re-exports checkBuildLogStaging for the external test package.
+var CheckBuildLogStaging = checkBuildLogStaging //nolint:gochecknoglobals // test-only re-export
+
+// CompressBuildLogWithLimit re-exports compressBuildLog for the external test package.
+var CompressBuildLogWithLimit = compressBuildLog //nolint:gochecknoglobals // test-only re-export
+
+// CountClosureObjects re-exports countClosureObjects for the external test package.
+var CountClosureObjects = countClosureObjects //nolint:gochecknoglobals // test-only re-export
+
+// UploadNARWithListing re-exports uploadNARWithListing, without a listing
+// task, for the external test package.
+func (c *Client) UploadNARWithListing(ctx context.Context, key string, obj PendingObject, pathInfo *PathInfo) (*FileDigest, error) {
+	return c.uploadNARWithListing(ctx, uploadTask{key: key, obj: obj}, nil, pathInfo)
+}
+
+// AcquireCompressionSlot re-exports acquireCompressionSlot for the external test package.
+func (c *Client) AcquireCompressionSlot(ctx context.Context) (func(), error) {
+	return c.acquireCompressionSlot(ctx)
+}
+
+// SampleCompressionRatio re-exports sampleCompressionRatio for the external test package.
+func (c *Client) SampleCompressionRatio(ctx context.Context, storePath string) (float64, error) {
+	return c.sampleCompressionRatio(ctx, storePath)
+}
+
+// UploadBytesWithPresignedURL uploads data like a pending closure object of
+// closureID, refreshing an expired presigned URL once.
+func (c *Client) UploadBytesWithPresignedURL(ctx context.Context, closureID, key, presignedURL string, data []byte) error {
+	obj := PendingObject{PresignedURL: presignedURL, closureID: closureID}
+
+	return c.withPresignedURL(ctx, key, obj, func(presignedURL string) error {
+		return c.UploadBytesToPresignedURLWithHeaders(ctx, presignedURL, data, nil)
+	})
+}
+
+// SkipCompletedObjects drops the pending objects the push state file at
+// stateFile records as uploaded, like a resumed push does.
+func (c *Client) SkipCompletedObjects(ctx context.Context, stateFile string, pendingObjects map[string]PendingObject) ([]string, error) {
+	state, err := loadPushState(stateFile)
+	if err != nil {
+		return nil, err
+	}
+	defer state.close()
+
+	c.pushState = state
+	defer func() { c.pushState = nil }()
+
+	return c.skipCompletedObjects(ctx, pendingObjects)
+}
+
+// ExcludePaths re-exports excludePaths with exclude as c.Exclude.
+func (c *Client) ExcludePaths(exclude, topLevelPaths []string, pathInfos map[string]*PathInfo) ([]string, map[string]*PathInfo, error) {
+	c.Exclude = exclude
+
+	return c.excludePaths(topLevelPaths, pathInfos)
+}
+
+// NixRemoteStore re-exports nixRemoteStore for the external test package.
+var NixRemoteStore = nixRemoteStore //nolint:gochecknoglobals // test-only re-export
+
+// ParseNixVersion reports the version and whether the unified CLI is
+// usable according to `nix --version` output.
+func ParseNixVersion(output string) (string, bool) {
+	cli := parseNixVersion(output)
+
+	return cli.version, cli.unified
+}
+
+// ParseValidityRegistration parses `nix-store --dump-db` output.
+func ParseValidityRegistration(data []byte) (map[string]*PathInfo, error) {
+	result := make(map[string]*PathInfo)
+
+	return result, parseValidityRegistration(data, result)
+}
+
+// BuildManifest re-exports buildManifest for the external test package.
+var BuildManifest = buildManifest //nolint:gochecknoglobals // test-only re-export
+
+// AddDerivations re-exports addDerivations for the external test package.
+func (c *Client) AddDerivations(ctx context.Context, topLevelPaths []string, pathInfos map[string]*PathInfo) ([]string, map[string]*PathInfo, error) {
+	return c.addDerivations(ctx, topLevelPaths, pathInfos)
+}
+
+// FillDerivationCA re-exports fillDerivationCA for the external test package.
+func (c *Client) FillDerivationCA(storePath string, info *PathInfo) error {
+	return c.fillDerivationCA(storePath, info)
+}
+
+// SetNarDigests sets the NARs the stats record as uploaded.
+func (s *UploadStats) SetNarDigests(digests map[string]*FileDigest) {
+	s.narDigests = digests
+}
+
+// SetNarSizes sets the uncompressed sizes of the NARs from SetNarDigests.
+func (s *UploadStats) SetNarSizes(sizes map[string]uint64) {
+	s.narSizes = sizes
+}
+
+// GetPathInfo exposes getPathInfo for testing.
+func (c *Client) GetPathInfo(ctx context.Context, storePaths []string) (map[string]*PathInfo, error) {
+	return c.getPathInfo(ctx, storePaths)
+}
+
+// CompleteAllPendingClosures exposes completePendingClosures for testing.
+func (c *Client) CompleteAllPendingClosures(ctx context.Context, closureIDs []string) ([]string, error) {
+	return c.completePendingClosures(ctx, closureIDs)
+}
@@ -0,0 +1,329 @@
+package client_test
+
+import (
+	"bytes"
+	"context"
+	"fmt"
+	"io"
+	"maps"
+	"net/http"
+	"net/http/httptest"
+	"os"
+	"path/filepath"
+	"slices"
+	"strings"
+	"sync"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+// mockCache is a niks3 deployment for push tests without a real server or
+// S3: a fakeCacheAPI for the control plane, an S3 server that records the
+// bodies of presigned PUTs, and a server answering the HEAD
+// /api/objects/<key> requests of SkipExisting from the recorded objects.
+type mockCache struct {
+	API    *fakeCacheAPI
+	S3     *httptest.Server
+	Server *httptest.Server
+
+	mu      sync.Mutex
+	objects map[string][]byte
+}
+
+func newMockCache(t *testing.T) *mockCache {
+	t.Helper()
+
+	m := &mockCache{objects: make(map[string][]byte)}
+
+	m.S3 = httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if r.Method != http.MethodPut {
+			t.Errorf("unexpected S3 request %s %s", r.Method, r.URL.Path)
+			http.Error(w, "unexpected request", http.StatusBadRequest)
+
+			return
+		}
+
+		body, err := io.ReadAll(r.Body)
+		if err != nil {
+			http.Error(w, err.Error(), http.StatusBadRequest)
+
+			return
+		}
+
+		m.Put(strings.TrimPrefix(r.URL.Path, "/"), body)
+	}))
+	t.Cleanup(m.S3.Close)
+
+	m.Server = httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		key, ok := strings.CutPrefix(r.URL.Path, "/api/objects/")
+		if r.Method != http.MethodHead || !ok {
+			t.Errorf("unexpected server request %s %s", r.Method, r.URL.Path)
+			w.WriteHeader(http.StatusInternalServerError)
+
+			return
+		}
+
+		if _, exists := m.Object(key); exists {
+			w.WriteHeader(http.StatusNoContent)
+		} else {
+			w.WriteHeader(http.StatusNotFound)
+		}
+	}))
+	t.Cleanup(m.Server.Close)
+
+	m.API = &fakeCacheAPI{s3URL: m.S3.URL}
+
+	return m
+}
+
+// Client returns a client for the mock cache.
+func (m *mockCache) Client(t *testing.T) *client.Client {
+	t.Helper()
+
+	c, err := client.NewTestClientForServer(m.Server.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	c.API = m.API
+
+	return c
+}
+
+// Put stores an object as if it had been uploaded.
+func (m *mockCache) Put(key string, body []byte) {
+	m.mu.Lock()
+	defer m.mu.Unlock()
+
+	m.objects[key] = body
+}
+
+// Object returns the body of an uploaded object.
+func (m *mockCache) Object(key string) ([]byte, bool) {
+	m.mu.Lock()
+	defer m.mu.Unlock()
+
+	body, ok := m.objects[key]
+
+	return body, ok
+}
+
+// Keys returns the keys of all objects, sorted.
+func (m *mockCache) Keys() []string {
+	m.mu.Lock()
+	defer m.mu.Unlock()
+
+	return slices.Sorted(maps.Keys(m.objects))
+}
+
+// Narinfo returns the decompressed narinfo uploaded for storePath.
+func (m *mockCache) Narinfo(t *testing.T, storePath string) string {
+	t.Helper()
+
+	body, ok := m.Object(narinfoKey(t, storePath))
+	if !ok {
+		t.Fatalf("no narinfo uploaded for %s, objects: %v", storePath, m.Keys())
+	}
+
+	return string(decompressZstd(t, body))
+}
+
+// testStore is a directory of regular files standing in for a Nix store,
+// with a path-info file describing them.
+type testStore struct {
+	Dir          string
+	Paths        []string          // Sorted store paths
+	NARs         map[string][]byte // Store path to its NAR serialization
+	PathInfoFile string
+}
+
+// newTestStore creates a store with one file per entry of files, keyed by
+// the store path's base name.
+func newTestStore(t *testing.T, files map[string]string) *testStore {
+	t.Helper()
+
+	ts := &testStore{Dir: filepath.Join(t.TempDir(), "store"), NARs: make(map[string][]byte)}
+	if err := os.MkdirAll(ts.Dir, 0o750); err != nil {
+		t.Fatal(err)
+	}
+
+	infos := make([]string, 0, len(files))
+
+	for _, name := range slices.Sorted(maps.Keys(files)) {
+		storePath := filepath.Join(ts.Dir, name)
+		if err := os.WriteFile(storePath, []byte(files[name]), 0o600); err != nil {
+			t.Fatal(err)
+		}
+
+		var nar bytes.Buffer
+
+		_, digest, err := client.DumpPathWithDigest(&nar, storePath)
+		if err != nil {
+			t.Fatalf("DumpPathWithDigest: %v", err)
+		}
+
+		ts.Paths = append(ts.Paths, storePath)
+		ts.NARs[storePath] = nar.Bytes()
+		infos = append(infos, fmt.Sprintf(`%q: {"narHash": %q, "narSize": %d, "references": []}`, storePath, digest.NarHash, digest.NarSize))
+	}
+
+	ts.PathInfoFile = filepath.Join(t.TempDir(), "path-info.json")
+	if err := os.WriteFile(ts.PathInfoFile, []byte("{"+strings.Join(infos, ",")+"}"), 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	return ts
+}
+
+// PushOptions returns push options reading paths from the store's
+// path-info file.
+func (ts *testStore) PushOptions() client.PushOptions {
+	opts := client.DefaultPushOptions()
+	opts.PathInfoFile = ts.PathInfoFile
+	opts.StoreDir = ts.Dir
+
+	return opts
+}
+
+func narinfoKey(t *testing.T, storePath string) string {
+	t.Helper()
+
+	hash, err := client.GetStorePathHash(storePath)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	return hash + ".narinfo"
+}
+
+func decompressZstd(t *testing.T, data []byte) []byte {
+	t.Helper()
+
+	r, closeDecoder, err := client.NARDecompressor(string(client.CompressionZstd), bytes.NewReader(data))
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer closeDecoder()
+
+	out, err := io.ReadAll(r)
+	if err != nil {
+		t.Fatalf("decompressing: %v", err)
+	}
+
+	return out
+}
+
+// narinfoField returns the value of a `Name: value` line of a narinfo.
+func narinfoField(narinfo, name string) string {
+	for line := range strings.Lines(narinfo) {
+		if value, ok := strings.CutPrefix(line, name+": "); ok {
+			return strings.TrimSpace(value)
+		}
+	}
+
+	return ""
+}
+
+var harnessStore = map[string]string{ //nolint:gochecknoglobals // test fixture
+	"0ha1dhmx807czjczmwy078s4r9s254il-hello": "hello\n",
+	"1ha1dhmx807czjczmwy078s4r9s254il-world": "world\n",
+}
+
+// TestMockCachePushUploadsObjects checks the object keys a push writes and
+// that their bodies decode to the paths' narinfos and NARs.
+func TestMockCachePushUploadsObjects(t *testing.T) {
+	t.Parallel()
+
+	m := newMockCache(t)
+	store := newTestStore(t, harnessStore)
+
+	opts := store.PushOptions()
+	opts.SkipExisting = false
+
+	if _, err := client.Push(context.Background(), m.Client(t), store.Paths, opts); err != nil {
+		t.Fatal(err)
+	}
+
+	for _, storePath := range store.Paths {
+		narinfo := m.Narinfo(t, storePath)
+
+		if got := narinfoField(narinfo, "StorePath"); got != storePath {
+			t.Errorf("narinfo StorePath = %q, want %q", got, storePath)
+		}
+
+		if got := narinfoField(narinfo, "Sig"); got != "test-1:c2lnbmF0dXJl" {
+			t.Errorf("narinfo Sig = %q, want the fake signature", got)
+		}
+
+		narKey := narinfoField(narinfo, "URL")
+		if !strings.HasPrefix(narKey, "nar/") || narinfoField(narinfo, "Compression") != "zstd" {
+			t.Fatalf("narinfo of %s names NAR %q with compression %q", storePath, narKey, narinfoField(narinfo, "Compression"))
+		}
+
+		body, ok := m.Object(narKey)
+		if !ok {
+			t.Fatalf("NAR %s of %s was not uploaded, objects: %v", narKey, storePath, m.Keys())
+		}
+
+		if !bytes.Equal(decompressZstd(t, body), store.NARs[storePath]) {
+			t.Errorf("uploaded NAR %s does not decompress to the NAR of %s", narKey, storePath)
+		}
+	}
+
+	var narinfos, nars int
+
+	for _, key := range m.Keys() {
+		switch {
+		case strings.HasSuffix(key, ".narinfo"):
+			narinfos++
+		case strings.HasPrefix(key, "nar/"):
+			nars++
+		}
+	}
+
+	if narinfos != len(store.Paths) || nars != len(store.Paths) {
+		t.Errorf("uploaded %d narinfos and %d NARs, want %d of each: %v", narinfos, nars, len(store.Paths), m.Keys())
+	}
+}
+
+// TestMockCacheSkipExisting checks that SkipExisting leaves out paths whose
+// narinfo is already in the cache, and that a repeated push uploads nothing.
+func TestMockCacheSkipExisting(t *testing.T) {
+	t.Parallel()
+
+	m := newMockCache(t)
+	store := newTestStore(t, harnessStore)
+	cachedPath, newPath := store.Paths[0], store.Paths[1]
+
+	m.Put(narinfoKey(t, cachedPath), []byte("cached"))
+
+	if _, err := client.Push(context.Background(), m.Client(t), store.Paths, store.PushOptions()); err != nil {
+		t.Fatal(err)
+	}
+
+	if body, _ := m.Object(narinfoKey(t, cachedPath)); string(body) != "cached" {
+		t.Errorf("narinfo of cached path %s was overwritten", cachedPath)
+	}
+
+	if got := narinfoField(m.Narinfo(t, newPath), "StorePath"); got != newPath {
+		t.Errorf("narinfo StorePath = %q, want %q", got, newPath)
+	}
+
+	keys := m.Keys()
+
+	if _, err := client.Push(context.Background(), m.Client(t), store.Paths, store.PushOptions()); err != nil {
+		t.Fatal(err)
+	}
+
+	if after := m.Keys(); !slices.Equal(after, keys) {
+		t.Errorf("repeated push uploaded objects: %v, before %v", after, keys)
+	}
+
+	m.API.mu.Lock()
+	defer m.API.mu.Unlock()
+
+	if m.API.created != 1 {
+		t.Errorf("created %d pending closures, want 1 for the uncached path", m.API.created)
+	}
+}
@@ -0,0 +1,90 @@
+package client
+
+import (
+	"context"
+	"fmt"
+	"io"
+	"maps"
+	"slices"
+)
+
+// PathReport describes one store path as a push would see it: the path
+// info read from nix and the narinfo that would be uploaded for it.
+type PathReport struct {
+	StorePath  string   `json:"store_path"` // Resolved store path
+	Hash       string   `json:"hash"`       // Store path hash, the narinfo's object key without ".narinfo"
+	NarHash    string   `json:"nar_hash"`
+	NarSize    uint64   `json:"nar_size"`
+	References []string `json:"references"`
+	Deriver    *string  `json:"deriver,omitempty"`
+	NarKey     string   `json:"nar_key"`
+	Narinfo    string   `json:"narinfo"` // Without FileHash/FileSize and the server's signature
+}
+
+// InspectPath reports what a push of path would upload for it, without
+// contacting the server. Symlinks such as ./result are resolved first.
+func (c *Client) InspectPath(ctx context.Context, path string) (*PathReport, error) {
+	resolved, err := resolveSymlinks([]string{path}, c.effectiveStoreDir())
+	if err != nil {
+		return nil, fmt.Errorf("resolving symlinks: %w", err)
+	}
+
+	storePath := resolved[0]
+
+	pathInfos, err := c.getPathInfo(ctx, resolved)
+	if err != nil {
+		return nil, fmt.Errorf("getting path info: %w", err)
+	}
+
+	pathInfo, ok := pathInfos[storePath]
+	if !ok {
+		return nil, fmt.Errorf("no path info for %s", storePath)
+	}
+
+	hash, err := GetStorePathHash(storePath)
+	if err != nil {
+		return nil, fmt.Errorf("getting store path hash: %w", err)
+	}
+
+	meta, err := newNarinfoMetadata(pathInfo, pathInfo.narCompression(c.Compression), nil)
+	if err != nil {
+		return nil, err
+	}
+
+	return &PathReport{
+		StorePath:  storePath,
+		Hash:       hash,
+		NarHash:    meta.NarHash,
+		NarSize:    meta.NarSize,
+		References: meta.References,
+		Deriver:    meta.Deriver,
+		NarKey:     meta.URL,
+		Narinfo:    generateNarinfoContent(meta, nil),
+	}, nil
+}
+
+// printNarinfos writes the narinfo a push would upload for each path of
+// pathInfos to w, sorted by store path and separated by blank lines. They
+// are built like in a real upload but lack FileHash/FileSize, which need
+// the compressed NAR, and the server's signatures.
+func (c *Client) printNarinfos(w io.Writer, pathInfos map[string]*PathInfo) error {
+	for i, storePath := range slices.Sorted(maps.Keys(pathInfos)) {
+		pathInfo := pathInfos[storePath]
+
+		meta, err := newNarinfoMetadata(pathInfo, pathInfo.narCompression(c.Compression), nil)
+		if err != nil {
+			return err
+		}
+
+		separator := ""
+		if i > 0 {
+			separator = "\n"
+		}
+
+		if _, err := fmt.Fprint(w, separator+generateNarinfoContent(meta, nil)); err != nil {
+			return fmt.Errorf("writing narinfo of %s: %w", storePath, err)
+		}
+	}
+
+	return nil
+}
@@ -0,0 +1,54 @@
+package client
+
+import (
+	"log/slog"
+	"os"
+	"path"
+	"path/filepath"
+)
+
+// keepFile creates name in the directory KeepTemp retains for this push.
+// It returns nil if files are not kept or the file cannot be created; a
+// debugging aid must not fail the push.
+func (c *Client) keepFile(name string) *os.File {
+	if c.keepDir == "" {
+		return nil
+	}
+
+	f, err := os.Create(filepath.Join(c.keepDir, path.Base(name)))
+	if err != nil {
+		slog.Warn("Failed to create kept file", "name", name, "error", err)
+
+		return nil
+	}
+
+	slog.Info("Keeping staged file", "path", f.Name())
+
+	return f
+}
+
+// keepBytes writes data to name in the KeepTemp directory, if any.
+func (c *Client) keepBytes(name string, data []byte) {
+	f := c.keepFile(name)
+	if f == nil {
+		return
+	}
+
+	_, err := f.Write(data)
+	closeKeptFile(f)
+
+	if err != nil {
+		slog.Warn("Failed to write kept file", "path", f.Name(), "error", err)
+	}
+}
+
+// closeKeptFile closes a file returned by keepFile; nil is a no-op.
+func closeKeptFile(f *os.File) {
+	if f == nil {
+		return
+	}
+
+	if err := f.Close(); err != nil {
+		slog.Warn("Failed to close kept file", "path", f.Name(), "error", err)
+	}
+}
@@ -0,0 +1,76 @@
+package client
+
+import (
+	"fmt"
+	"strings"
+)
+
+// ValidateKeyPrefix checks a KeyPrefix the way the server does: one or more
+// slash-separated segments of letters, digits, '.', '_' and '-', each
+// starting with a letter or digit, at most 256 bytes in all.
+func ValidateKeyPrefix(prefix string) error {
+	if prefix == "" {
+		return nil
+	}
+
+	if len(prefix) > 256 {
+		return fmt.Errorf("key prefix is longer than 256 bytes: %q", prefix)
+	}
+
+	for segment := range strings.SplitSeq(prefix, "/") {
+		if segment == "" || !isAlphanumeric(segment[0]) ||
+			strings.Trim(segment, "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789._-") != "" {
+			return fmt.Errorf("invalid key prefix %q: segments may only contain letters, digits, '.', '_' and '-' and must start with a letter or digit", prefix)
+		}
+	}
+
+	return nil
+}
+
+func isAlphanumeric(b byte) bool {
+	return b >= 'a' && b <= 'z' || b >= 'A' && b <= 'Z' || b >= '0' && b <= '9'
+}
+
+// wireKey returns the key of an object in the bucket, which is key below
+// c.KeyPrefix. Keys inside the client stay unprefixed; only requests to the
+// server carry the prefix.
+func (c *Client) wireKey(key string) string {
+	if c.KeyPrefix == "" {
+		return key
+	}
+
+	return c.KeyPrefix + "/" + key
+}
+
+// localKey is the inverse of wireKey, for keys in server responses.
+func (c *Client) localKey(key string) string {
+	if c.KeyPrefix == "" {
+		return key
+	}
+
+	return strings.TrimPrefix(key, c.KeyPrefix+"/")
+}
+
+// wireObjects returns objects with their keys and references below
+// c.KeyPrefix.
+func (c *Client) wireObjects(objects []ObjectWithRefs) []ObjectWithRefs {
+	if c.KeyPrefix == "" {
+		return objects
+	}
+
+	prefixed := make([]ObjectWithRefs, len(objects))
+
+	for i, obj := range objects {
+		obj.Key = c.wireKey(obj.Key)
+
+		refs := make([]string, len(obj.Refs))
+		for j, ref := range obj.Refs {
+			refs[j] = c.wireKey(ref)
+		}
+
+		obj.Refs = refs
+		prefixed[i] = obj
+	}
+
+	return prefixed
+}
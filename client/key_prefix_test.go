@@ -0,0 +1,132 @@
+package client_test
+
+import (
+	"context"
+	"encoding/json"
+	"net/http"
+	"net/http/httptest"
+	"slices"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+func TestValidateKeyPrefix(t *testing.T) {
+	t.Parallel()
+
+	for _, prefix := range []string{"", "team-a", "org/team_a/v1.2"} {
+		if err := client.ValidateKeyPrefix(prefix); err != nil {
+			t.Errorf("ValidateKeyPrefix(%q) = %v, want nil", prefix, err)
+		}
+	}
+
+	for _, prefix := range []string{"/team", "team/", "a//b", "..", ".hidden", "-x", "my team", "a/../b"} {
+		if err := client.ValidateKeyPrefix(prefix); err == nil {
+			t.Errorf("ValidateKeyPrefix(%q) = nil, want an error", prefix)
+		}
+	}
+}
+
+// TestKeyPrefixOnTheWire checks that keys are sent to the server below the
+// key prefix and come back from it without.
+func TestKeyPrefixOnTheWire(t *testing.T) {
+	t.Parallel()
+
+	var (
+		created struct {
+			Closure   string                  `json:"closure"`
+			Objects   []client.ObjectWithRefs `json:"objects"`
+			KeyPrefix string                  `json:"key_prefix"`
+		}
+		signed struct {
+			Narinfos map[string]client.NarinfoMetadata `json:"narinfos"`
+		}
+		headPath string
+	)
+
+	mux := http.NewServeMux()
+	mux.HandleFunc("POST /api/pending_closures", func(w http.ResponseWriter, r *http.Request) {
+		if err := json.NewDecoder(r.Body).Decode(&created); err != nil {
+			http.Error(w, err.Error(), http.StatusBadRequest)
+
+			return
+		}
+
+		pending := make(map[string]client.PendingObject)
+		for _, obj := range created.Objects {
+			pending[obj.Key] = client.PendingObject{Type: string(obj.Type), PresignedURL: "http://s3/" + obj.Key}
+		}
+
+		_ = json.NewEncoder(w).Encode(client.CreatePendingClosureResponse{ID: "1", PendingObjects: pending})
+	})
+	mux.HandleFunc("POST /api/pending_closures/1/sign", func(w http.ResponseWriter, r *http.Request) {
+		if err := json.NewDecoder(r.Body).Decode(&signed); err != nil {
+			http.Error(w, err.Error(), http.StatusBadRequest)
+
+			return
+		}
+
+		signatures := make(map[string][]string)
+		for key := range signed.Narinfos {
+			signatures[key] = []string{"test-1:sig"}
+		}
+
+		_ = json.NewEncoder(w).Encode(map[string]any{"signatures": signatures})
+	})
+	mux.HandleFunc("HEAD /api/objects/{key...}", func(w http.ResponseWriter, r *http.Request) {
+		headPath = r.URL.Path
+		w.WriteHeader(http.StatusNotFound)
+	})
+
+	srv := httptest.NewServer(mux)
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	c.KeyPrefix = "team-a"
+
+	const (
+		narinfoKey = "26xbg1ndr7hbcncrlf9nhx5is2b25d13.narinfo"
+		narKey     = "nar/1ngi2dxw1f7khrrjamzkkdai393lwcm8s78gvs1ag8k3n82w7bvp.nar.zst"
+	)
+
+	resp, err := c.CreatePendingClosure(context.Background(), narinfoKey, []client.ObjectWithRefs{
+		{Key: narinfoKey, Type: client.ObjectTypeNarinfo, Refs: []string{narKey}},
+		{Key: narKey, Type: client.ObjectTypeNAR, Refs: []string{}},
+	}, false)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if created.KeyPrefix != "team-a" || created.Closure != "team-a/"+narinfoKey {
+		t.Errorf("sent closure %q with key_prefix %q", created.Closure, created.KeyPrefix)
+	}
+
+	if len(created.Objects) != 2 || created.Objects[0].Key != "team-a/"+narinfoKey || !slices.Equal(created.Objects[0].Refs, []string{"team-a/" + narKey}) {
+		t.Errorf("objects were not sent below the key prefix: %+v", created.Objects)
+	}
+
+	if obj, ok := resp.PendingObjects[narKey]; !ok || obj.PresignedURL != "http://s3/team-a/"+narKey {
+		t.Errorf("pending objects not returned under unprefixed keys: %+v", resp.PendingObjects)
+	}
+
+	signatures, err := c.SignPendingClosure(context.Background(), "1", map[string]client.NarinfoMetadata{narinfoKey: {}})
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if _, ok := signed.Narinfos["team-a/"+narinfoKey]; !ok || len(signatures[narinfoKey]) != 1 {
+		t.Errorf("sent narinfos %v, got signatures %v", signed.Narinfos, signatures)
+	}
+
+	if _, err := c.ObjectExists(context.Background(), narinfoKey); err != nil {
+		t.Fatal(err)
+	}
+
+	if headPath != "/api/objects/team-a/"+narinfoKey {
+		t.Errorf("ObjectExists requested %q", headPath)
+	}
+}
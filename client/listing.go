@@ -1,6 +1,7 @@
 package client
 
 import (
+	"errors"
 	"fmt"
 	"os"
 	"path/filepath"
@@ -11,7 +12,12 @@ import (
 // without serializing the NAR. This is much faster for deduplicated NARs
 // where we only need the listing structure.
 func GenerateListingOnly(path string) (*NarListing, error) {
-	entry, err := generateListingEntry(path)
+	return DefaultNarOptions().GenerateListingOnly(path)
+}
+
+// GenerateListingOnly is like the package-level GenerateListingOnly but uses o.
+func (o NarOptions) GenerateListingOnly(path string) (*NarListing, error) {
+	entry, err := generateListingEntry(path, o, true)
 	if err != nil {
 		return nil, err
 	}
@@ -19,7 +25,9 @@ func GenerateListingOnly(path string) (*NarListing, error) {
 	return &NarListing{Version: 1, Root: entry}, nil
 }
 
-func generateListingEntry(path string) (NarListingEntry, error) {
+// generateListingEntry lists path. Special files below the root come back
+// as errSpecialFileSkipped with NarOptions.SkipSpecialFiles.
+func generateListingEntry(path string, opts NarOptions, root bool) (NarListingEntry, error) {
 	info, err := os.Lstat(path)
 	if err != nil {
 		return NarListingEntry{}, fmt.Errorf("stat %s: %w", path, err)
@@ -30,11 +38,11 @@ func generateListingEntry(path string) (NarListingEntry, error) {
 	case mode.IsRegular():
 		return generateRegularFileListing(path, info)
 	case mode.IsDir():
-		return generateDirectoryListing(path)
+		return generateDirectoryListing(path, opts)
 	case mode&os.ModeSymlink != 0:
 		return generateSymlinkListing(path)
 	default:
-		return NarListingEntry{}, fmt.Errorf("unsupported file type for %s: %v", path, mode)
+		return NarListingEntry{}, opts.specialFile(path, mode, root)
 	}
 }
 
@@ -59,7 +67,7 @@ func generateRegularFileListing(_ string, info os.FileInfo) (NarListingEntry, er
 	return entry, nil
 }
 
-func generateDirectoryListing(path string) (NarListingEntry, error) {
+func generateDirectoryListing(path string, opts NarOptions) (NarListingEntry, error) {
 	entries, err := os.ReadDir(path)
 	if err != nil {
 		return NarListingEntry{}, fmt.Errorf("reading directory %s: %w", path, err)
@@ -75,11 +83,15 @@ func generateDirectoryListing(path string) (NarListingEntry, error) {
 	for _, entry := range entries {
 		name := entry.Name()
 		// Strip case hack suffix on macOS (must match NAR serialization)
-		narName := stripCaseHackSuffix(name)
+		narName := stripCaseHackSuffix(name, opts.CaseHack)
 
 		entryPath := filepath.Join(path, name)
 
-		listingEntry, err := generateListingEntry(entryPath)
+		listingEntry, err := generateListingEntry(entryPath, opts, false)
+		if errors.Is(err, errSpecialFileSkipped) {
+			continue
+		}
+
 		if err != nil {
 			return NarListingEntry{}, err
 		}
@@ -2,6 +2,7 @@ package client_test
 
 import (
 	"bytes"
+	"io"
 	"os"
 	"os/exec"
 	"path/filepath"
@@ -68,37 +69,100 @@ func TestCaseHackSuffix(t *testing.T) {
 			ourNAR.Len(), len(nixNAR))
 	}
 
-	// Verify listing structure
-	listing, err := client.GenerateListingOnly(testDir)
-	if err != nil {
-		t.Fatalf("GenerateListingOnly failed: %v", err)
+	// Verify listing structure for both case hack modes explicitly, rather
+	// than only the one implied by the current platform.
+	for _, caseHack := range []bool{false, true} {
+		opts := client.NarOptions{CaseHack: caseHack}
+
+		listing, err := opts.GenerateListingOnly(testDir)
+		if err != nil {
+			t.Fatalf("GenerateListingOnly(caseHack=%v) failed: %v", caseHack, err)
+		}
+
+		entries := listing.Root.Entries
+		if len(entries) != 2 {
+			t.Fatalf("caseHack=%v: expected 2 entries, got %d", caseHack, len(entries))
+		}
+
+		expectedName := "README~nix~case~hack~"
+		unexpectedName := "README"
+
+		if caseHack {
+			expectedName = "README"
+			unexpectedName = "README~nix~case~hack~"
+		}
+
+		if _, ok := entries[expectedName]; !ok {
+			t.Errorf("caseHack=%v: expected '%s' entry", caseHack, expectedName)
+		}
+
+		if _, ok := entries[unexpectedName]; ok {
+			t.Errorf("caseHack=%v: should not have '%s' entry", caseHack, unexpectedName)
+		}
+
+		// Normal file should be unchanged in both modes
+		if _, ok := entries["normal.txt"]; !ok {
+			t.Errorf("caseHack=%v: expected 'normal.txt' entry", caseHack)
+		}
+
+		// The NAR writer must agree with the listing walker.
+		dumpListing, err := opts.DumpPathWithListing(io.Discard, testDir)
+		if err != nil {
+			t.Fatalf("DumpPathWithListing(caseHack=%v) failed: %v", caseHack, err)
+		}
+
+		if _, ok := dumpListing.Root.Entries[expectedName]; !ok {
+			t.Errorf("caseHack=%v: NAR listing missing '%s' entry", caseHack, expectedName)
+		}
+	}
+
+	if got, want := client.DefaultNarOptions().CaseHack, runtime.GOOS == "darwin"; got != want {
+		t.Errorf("DefaultNarOptions().CaseHack = %v, want %v", got, want)
+	}
+}
+
+// TestRestoreCaseHackCollision checks that restoring with the case hack on
+// renames case-insensitive collisions and that dumping strips the suffix
+// again, yielding the original NAR.
+func TestRestoreCaseHackCollision(t *testing.T) {
+	t.Parallel()
+
+	src := filepath.Join(t.TempDir(), "src")
+	if err := os.Mkdir(src, 0o755); err != nil {
+		t.Fatalf("mkdir: %v", err)
 	}
 
-	entries := listing.Root.Entries
-	if len(entries) != 2 {
-		t.Fatalf("Expected 2 entries, got %d", len(entries))
+	// Build a NAR with "README" and "readme" without relying on the host
+	// filesystem being case-sensitive: dump with the case hack on from a
+	// tree that already carries the suffix.
+	for _, name := range []string{"README", "readme~nix~case~hack~1"} {
+		if err := os.WriteFile(filepath.Join(src, name), []byte(name), 0o600); err != nil {
+			t.Fatalf("write %s: %v", name, err)
+		}
 	}
 
-	// Determine expected name based on platform
-	expectedName := "README~nix~case~hack~"
-	unexpectedName := "README"
+	opts := client.NarOptions{CaseHack: true}
+
+	var original bytes.Buffer
+	if _, err := opts.DumpPathWithListing(&original, src); err != nil {
+		t.Fatalf("DumpPathWithListing: %v", err)
+	}
 
-	if runtime.GOOS == "darwin" {
-		expectedName = "README"
-		unexpectedName = "README~nix~case~hack~"
+	dest := filepath.Join(t.TempDir(), "dest")
+	if err := opts.RestorePath(bytes.NewReader(original.Bytes()), dest); err != nil {
+		t.Fatalf("RestorePath: %v", err)
 	}
 
-	// Verify case hack handling matches platform
-	if _, ok := entries[expectedName]; !ok {
-		t.Errorf("Expected '%s' entry", expectedName)
+	if _, err := os.Stat(filepath.Join(dest, "readme~nix~case~hack~1")); err != nil {
+		t.Fatalf("expected case-hacked name after restore: %v", err)
 	}
 
-	if _, ok := entries[unexpectedName]; ok {
-		t.Errorf("Should not have '%s' entry", unexpectedName)
+	var redumped bytes.Buffer
+	if _, err := opts.DumpPathWithListing(&redumped, dest); err != nil {
+		t.Fatalf("DumpPathWithListing (restored): %v", err)
 	}
 
-	// Normal file should be unchanged on all platforms
-	if _, ok := entries["normal.txt"]; !ok {
-		t.Error("Expected 'normal.txt' entry")
+	if !bytes.Equal(original.Bytes(), redumped.Bytes()) {
+		t.Fatal("NAR mismatch after case-hack round trip")
 	}
 }
@@ -0,0 +1,61 @@
+package client
+
+import (
+	"slices"
+	"strings"
+)
+
+// ManifestEntry describes one store path of a push, for `push --manifest`.
+// Skipped paths were not uploaded by this push because the cache already
+// had them; their NAR fields are only known if the push prepared them.
+type ManifestEntry struct {
+	StorePath  string `json:"store_path"`
+	NarinfoKey string `json:"narinfo_key"`
+	NarKey     string `json:"nar_key,omitempty"`
+	NarSize    uint64 `json:"nar_size"`
+	FileSize   uint64 `json:"file_size,omitempty"` // Compressed NAR size, if this push uploaded the NAR
+	FileHash   string `json:"file_hash,omitempty"` // Compressed NAR hash, if this push uploaded the NAR
+	Skipped    bool   `json:"skipped"`
+}
+
+// buildManifest lists every path of pathInfos, sorted by store path.
+// narKeyToHash comes from PrepareClosures (nil if nothing was prepared),
+// uploaded are the paths whose narinfo this push uploaded, and narDigests
+// the compressed NARs it uploaded by store path.
+func buildManifest(pathInfos map[string]*PathInfo, narKeyToHash map[string]string, uploaded []string, narDigests map[string]*FileDigest) []ManifestEntry {
+	narKeys := make(map[string]string, len(narKeyToHash))
+	for narKey, hash := range narKeyToHash {
+		narKeys[hash] = narKey
+	}
+
+	manifest := make([]ManifestEntry, 0, len(pathInfos))
+
+	for storePath, pathInfo := range pathInfos {
+		hash, err := GetStorePathHash(storePath)
+		if err != nil {
+			// Unreachable: PrepareClosures or the cache query rejected it.
+			continue
+		}
+
+		entry := ManifestEntry{
+			StorePath:  storePath,
+			NarinfoKey: hash + ".narinfo",
+			NarKey:     narKeys[hash],
+			NarSize:    pathInfo.NarSize,
+			Skipped:    !slices.Contains(uploaded, storePath),
+		}
+
+		if digest := narDigests[storePath]; digest != nil {
+			entry.FileSize = digest.FileSize
+			entry.FileHash = digest.FileHash
+		}
+
+		manifest = append(manifest, entry)
+	}
+
+	slices.SortFunc(manifest, func(a, b ManifestEntry) int {
+		return strings.Compare(a.StorePath, b.StorePath)
+	})
+
+	return manifest
+}
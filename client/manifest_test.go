@@ -0,0 +1,54 @@
+package client_test
+
+import (
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+func TestBuildManifest(t *testing.T) {
+	t.Parallel()
+
+	uploadedPath := "/nix/store/0123456789abcdfghijklmnpqrsvwxyz-hello"
+	skippedPath := "/nix/store/00000000000000000000000000000000-glibc"
+
+	pathInfos := map[string]*client.PathInfo{
+		uploadedPath: {NarSize: 1024},
+		skippedPath:  {NarSize: 4096},
+	}
+	narKeyToHash := map[string]string{
+		"nar/abc.nar.zst": "0123456789abcdfghijklmnpqrsvwxyz",
+	}
+	digests := map[string]*client.FileDigest{
+		uploadedPath: {FileHash: "sha256:deadbeef", FileSize: 512},
+	}
+
+	manifest := client.BuildManifest(pathInfos, narKeyToHash, []string{uploadedPath}, digests)
+
+	want := []client.ManifestEntry{
+		{
+			StorePath:  skippedPath,
+			NarinfoKey: "00000000000000000000000000000000.narinfo",
+			NarSize:    4096,
+			Skipped:    true,
+		},
+		{
+			StorePath:  uploadedPath,
+			NarinfoKey: "0123456789abcdfghijklmnpqrsvwxyz.narinfo",
+			NarKey:     "nar/abc.nar.zst",
+			NarSize:    1024,
+			FileSize:   512,
+			FileHash:   "sha256:deadbeef",
+		},
+	}
+
+	if len(manifest) != len(want) {
+		t.Fatalf("got %d entries, want %d: %+v", len(manifest), len(want), manifest)
+	}
+
+	for i := range want {
+		if manifest[i] != want[i] {
+			t.Errorf("entry %d: got %+v, want %+v", i, manifest[i], want[i])
+		}
+	}
+}
@@ -35,54 +35,50 @@ func compressWithZstd(data []byte) ([]byte, error) {
 	return compressed.Bytes(), nil
 }
 
-// uploadLog uploads a build log.
-func (c *Client) uploadLog(ctx context.Context, task uploadTask, logPathsByKey map[string]string) error {
-	// Get the local log path
-	logPath, ok := logPathsByKey[task.key]
-	if !ok {
-		// Log was requested by server but not found locally - this shouldn't happen
-		// but we'll log a warning and continue rather than failing the entire upload
-		slog.Warn("Build log not found", "key", task.key)
-
-		return nil // Don't fail the entire upload
+// uploadLog uploads the build log at logPath, staging it in stagingDir if
+// it is too large to compress in memory. It reports false if the log was
+// skipped.
+func (c *Client) uploadLog(ctx context.Context, task uploadTask, logPath, stagingDir string) (bool, error) {
+	// Compress the log, into memory if it is small enough and not kept
+	memoryLimit := c.InMemoryLogLimit
+	if c.KeepTemp {
+		memoryLimit = 0
 	}
 
-	// Compress the log to a temporary file
-	compressedInfo, err := CompressBuildLog(logPath)
+	compressedInfo, err := compressBuildLog(logPath, stagingDir, memoryLimit)
 	if err != nil {
 		slog.Warn("Failed to compress build log", "key", task.key, "log_path", logPath, "error", err)
 
-		return nil // Don't fail the entire upload
+		return false, nil // Don't fail the entire upload
 	}
 
 	defer func() {
+		if c.KeepTemp {
+			slog.Info("Keeping staged file", "path", compressedInfo.TempFile, "key", task.key)
+
+			return
+		}
+
 		if cleanupErr := compressedInfo.Cleanup(); cleanupErr != nil {
 			slog.Warn("Failed to cleanup compressed build log", "key", task.key, "error", cleanupErr)
 		}
 	}()
 
 	// Upload the compressed log
-	if err := c.UploadBuildLogToPresignedURL(ctx, task.obj.PresignedURL, compressedInfo); err != nil {
-		return fmt.Errorf("uploading build log %s: %w", task.key, err)
+	err = c.withPresignedURL(ctx, task.key, task.obj, func(presignedURL string) error {
+		return c.UploadBuildLogToPresignedURL(ctx, presignedURL, compressedInfo)
+	})
+	if err != nil {
+		return false, fmt.Errorf("uploading build log %s: %w", task.key, err)
 	}
 
 	slog.Debug("Uploaded build log", "key", task.key)
 
-	return nil
+	return true, nil
 }
 
 // uploadRealisation uploads a realisation (.doi) file for CA derivations.
-func (c *Client) uploadRealisation(ctx context.Context, task uploadTask, realisationsByKey map[string]*RealisationInfo) error {
-	// Get the realisation info
-	realisationInfo, ok := realisationsByKey[task.key]
-	if !ok {
-		// Realisation was requested by server but not found locally - this shouldn't happen
-		// but we'll log a warning and continue rather than failing the entire upload
-		slog.Warn("Realisation not found", "key", task.key)
-
-		return nil // Don't fail the entire upload
-	}
-
+func (c *Client) uploadRealisation(ctx context.Context, task uploadTask, realisationInfo *RealisationInfo) error {
 	// Marshal realisation to JSON
 	jsonData, err := json.Marshal(realisationInfo)
 	if err != nil {
@@ -100,7 +96,10 @@ func (c *Client) uploadRealisation(ctx context.Context, task uploadTask, realisa
 		"Content-Encoding": compressionZstd,
 	}
 
-	if err := c.UploadBytesToPresignedURLWithHeaders(ctx, task.obj.PresignedURL, compressed, headers); err != nil {
+	err = c.withPresignedURL(ctx, task.key, task.obj, func(presignedURL string) error {
+		return c.UploadBytesToPresignedURLWithHeaders(ctx, presignedURL, compressed, headers)
+	})
+	if err != nil {
 		return fmt.Errorf("uploading realisation %s: %w", task.key, err)
 	}
 
@@ -21,6 +21,11 @@ const (
 	// gives no slack right at a boundary. The headroom only costs a
 	// slightly bigger part size.
 	targetMaxParts = 9000
+	// maxSimpleUploadSize is S3's limit for a single PUT. NARs that could
+	// exceed it are always uploaded in parts (the server only hands out a
+	// single presigned URL for NARs that fit into one part); other objects
+	// are checked against it before uploading.
+	maxSimpleUploadSize = 5 * 1024 * 1024 * 1024
 )
 
 // partSizeForNAR returns a part size that keeps the compressed upload under
@@ -43,8 +48,8 @@ func partSizeForNAR(narSize uint64) int {
 	return int(size)
 }
 
-// formatBytes formats bytes in human-readable form (KB/MB/GB).
-func formatBytes(bytes uint64) string {
+// FormatBytes formats bytes in human-readable form (KB/MB/GB).
+func FormatBytes(bytes uint64) string {
 	const unit = 1024
 	if bytes < unit {
 		return fmt.Sprintf("%dB", bytes)
@@ -105,9 +110,21 @@ func (c *Client) supersededByPeer(ctx context.Context, objectKey string, err err
 	return exists
 }
 
-// ObjectExists reports whether objectKey is already present in S3.
+// objectChecker is implemented by CacheAPIs that can look up objects
+// themselves, such as DirectS3.
+type objectChecker interface {
+	ObjectExists(ctx context.Context, objectKey string) (bool, error)
+}
+
+// ObjectExists reports whether objectKey, below KeyPrefix, is already present
+// in S3. If Client.API can look objects up, it is asked instead of the
+// server.
 func (c *Client) ObjectExists(ctx context.Context, objectKey string) (bool, error) {
-	reqURL := c.baseURL.JoinPath("api/objects", objectKey)
+	if checker, ok := c.API.(objectChecker); ok {
+		return checker.ObjectExists(ctx, objectKey) //nolint:wrapcheck // the checker names the key
+	}
+
+	reqURL := c.baseURL.JoinPath("api/objects", c.wireKey(objectKey))
 
 	req, err := http.NewRequestWithContext(ctx, http.MethodHead, reqURL.String(), nil)
 	if err != nil {
@@ -169,7 +186,7 @@ func (c *Client) RequestMoreParts(ctx context.Context, objectKey, uploadID strin
 	reqURL := c.baseURL.JoinPath("api/multipart/request-parts")
 
 	reqBody := requestMorePartsRequest{
-		ObjectKey:       objectKey,
+		ObjectKey:       c.wireKey(objectKey),
 		UploadID:        uploadID,
 		StartPartNumber: startPartNumber,
 		NumParts:        numParts,
@@ -228,7 +245,7 @@ func (c *Client) CompleteMultipartUpload(ctx context.Context, objectKey, uploadI
 	reqURL := c.baseURL.JoinPath("api/multipart/complete")
 
 	reqBody := completeMultipartRequest{
-		ObjectKey: objectKey,
+		ObjectKey: c.wireKey(objectKey),
 		UploadID:  uploadID,
 		Parts:     parts,
 	}
@@ -283,7 +300,7 @@ func (c *Client) uploadMultipart(ctx context.Context, r io.Reader, multipartInfo
 				"current_part", partNumber,
 				"requesting", additionalParts)
 
-			newPartURLs, err := c.RequestMoreParts(ctx, objectKey, multipartInfo.UploadID, partNumber, additionalParts)
+			newPartURLs, err := c.api().RequestMoreParts(ctx, objectKey, multipartInfo.UploadID, partNumber, additionalParts)
 			if err != nil {
 				return fmt.Errorf("requesting more parts at part %d: %w", partNumber, err)
 			}
@@ -339,7 +356,7 @@ func (c *Client) uploadMultipart(ctx context.Context, r io.Reader, multipartInfo
 	}
 
 	// Complete the multipart upload
-	err := c.CompleteMultipartUpload(ctx, objectKey, multipartInfo.UploadID, completedParts)
+	err := c.api().CompleteMultipartUpload(ctx, objectKey, multipartInfo.UploadID, completedParts)
 	if err != nil {
 		if c.supersededByPeer(ctx, objectKey, err) {
 			return ErrUploadSuperseded
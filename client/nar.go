@@ -1,10 +1,12 @@
 package client
 
 import (
+	"crypto/sha256"
 	"encoding/binary"
 	"encoding/json"
 	"errors"
 	"fmt"
+	"hash"
 	"io"
 	"log/slog"
 	"os"
@@ -13,6 +15,8 @@ import (
 	"slices"
 	"strings"
 	"sync"
+
+	"golang.org/x/sync/errgroup"
 )
 
 const (
@@ -20,9 +24,6 @@ const (
 	caseHackSuffix  = "~nix~case~hack~"
 )
 
-//nolint:gochecknoglobals // useCaseHack is platform-specific runtime constant
-var useCaseHack = runtime.GOOS == "darwin"
-
 var copyBufferPool = sync.Pool{ //nolint:gochecknoglobals
 	New: func() any {
 		// 128KB buffer for efficient large file reads
@@ -52,14 +53,96 @@ var (
 	targetEncoded          = encodeStaticString("target")
 )
 
-// stripCaseHackSuffix removes the case hack suffix from filenames on macOS.
-func stripCaseHackSuffix(name string) string {
-	if !useCaseHack {
+// NarOptions controls platform-dependent details of NAR (de)serialization.
+type NarOptions struct {
+	// CaseHack strips the "~nix~case~hack~" suffix from names when dumping
+	// and adds it to case-insensitive collisions when restoring. Nix enables
+	// this by default on macOS only.
+	CaseHack bool
+
+	// SkipSpecialFiles leaves fifos, sockets and device nodes out of the
+	// NAR with a warning, as if they were not there, instead of failing.
+	// Nix cannot archive them, so a path it registered only contains them
+	// if they were added afterwards.
+	SkipSpecialFiles bool
+}
+
+// DefaultNarOptions returns the options Nix uses on the current platform.
+func DefaultNarOptions() NarOptions {
+	return NarOptions{CaseHack: runtime.GOOS == "darwin"}
+}
+
+// ParseCaseHackMode maps a --case-hack value ("auto", "on", "off") to
+// whether the case hack is enabled. "auto" picks the platform default.
+func ParseCaseHackMode(mode string) (bool, error) {
+	switch mode {
+	case "auto", "":
+		return DefaultNarOptions().CaseHack, nil
+	case "on":
+		return true, nil
+	case "off":
+		return false, nil
+	default:
+		return false, fmt.Errorf("invalid case hack mode %q (expected auto, on, or off)", mode)
+	}
+}
+
+// ParseSpecialFileMode maps an --on-special-file value ("fail", "skip") to
+// whether special files are skipped.
+func ParseSpecialFileMode(mode string) (bool, error) {
+	switch mode {
+	case "fail", "":
+		return false, nil
+	case "skip":
+		return true, nil
+	default:
+		return false, fmt.Errorf("invalid special file mode %q (expected fail or skip)", mode)
+	}
+}
+
+// errSpecialFileSkipped reports a special file SkipSpecialFiles left out.
+var errSpecialFileSkipped = errors.New("special file skipped")
+
+// specialFile handles an entry that is neither a regular file, a directory
+// nor a symlink, which NARs cannot represent. It fails the serialization
+// or, with SkipSpecialFiles, returns errSpecialFileSkipped for entries
+// below root; root itself cannot be skipped.
+func (o NarOptions) specialFile(path string, mode os.FileMode, root bool) error {
+	var kind string
+
+	switch {
+	case mode&os.ModeNamedPipe != 0:
+		kind = "fifo"
+	case mode&os.ModeSocket != 0:
+		kind = "socket"
+	case mode&os.ModeCharDevice != 0:
+		kind = "character device"
+	case mode&os.ModeDevice != 0:
+		kind = "block device"
+	default:
+		kind = "file of type " + mode.Type().String()
+	}
+
+	if o.SkipSpecialFiles && !root {
+		slog.Warn("Skipping special file, NARs cannot contain it", "path", path, "type", kind)
+
+		return errSpecialFileSkipped
+	}
+
+	return fmt.Errorf("cannot serialize %s: it is a %s, and NARs only hold regular files, directories and symlinks", path, kind)
+}
+
+// stripCaseHackSuffix removes the case hack suffix from filenames when the
+// case hack is enabled. Like Nix, everything from the suffix onwards is
+// dropped, including the collision counter RestorePath appends
+// ("~nix~case~hack~1").
+func stripCaseHackSuffix(name string, caseHack bool) string {
+	if !caseHack {
 		return name
 	}
 
-	if strings.HasSuffix(name, caseHackSuffix) {
-		return name[:len(name)-len(caseHackSuffix)]
+	if i := strings.Index(name, caseHackSuffix); i >= 0 {
+		return name[:i]
 	}
 
 	return name
@@ -269,16 +352,130 @@ type narNode struct {
 	children   []*narNode
 }
 
+// NarDigest is the hash and size of a serialized NAR, computed while it is
+// written so our own serialization can be checked against what Nix reports.
+type NarDigest struct {
+	NarHash string // nix32 format, e.g. "sha256:1b2c..."
+	NarSize uint64
+}
+
+// Check compares the digest against the NarHash/NarSize reported by Nix.
+// expectedHash may be in SRI or nix32 format.
+func (d *NarDigest) Check(expectedHash string, expectedSize uint64) error {
+	if d.NarSize != expectedSize {
+		return fmt.Errorf("NAR size mismatch: serialized %d bytes, nix reports %d", d.NarSize, expectedSize)
+	}
+
+	expected, err := ConvertHashToNix32(expectedHash)
+	if err != nil {
+		return fmt.Errorf("converting expected NAR hash: %w", err)
+	}
+
+	if d.NarHash != expected {
+		return fmt.Errorf("NAR hash mismatch: serialized %s, nix reports %s", d.NarHash, expected)
+	}
+
+	return nil
+}
+
+// FileDigest is the hash and size of a NAR as uploaded (after compression),
+// written to the narinfo FileHash/FileSize fields.
+type FileDigest struct {
+	FileHash string // nix32 format, e.g. "sha256:1b2c..."
+	FileSize uint64
+}
+
+// fileDigestWriter hashes and counts the bytes written through it.
+type fileDigestWriter struct {
+	w    io.Writer
+	h    hash.Hash
+	algo HashAlgorithm
+	n    uint64
+}
+
+// newFileDigestWriter hashes with sha256, which uploads always use.
+func newFileDigestWriter(w io.Writer) *fileDigestWriter {
+	return &fileDigestWriter{w: w, h: sha256.New(), algo: HashSHA256}
+}
+
+// newFileDigestWriterFor hashes with algo, to check a FileHash an
+// existing narinfo declares.
+func newFileDigestWriterFor(w io.Writer, algo HashAlgorithm) (*fileDigestWriter, error) {
+	h, err := algo.New()
+	if err != nil {
+		return nil, err
+	}
+
+	return &fileDigestWriter{w: w, h: h, algo: algo}, nil
+}
+
+func (f *fileDigestWriter) Write(p []byte) (int, error) {
+	n, err := f.w.Write(p)
+	f.h.Write(p[:n])
+	f.n += uint64(n) //nolint:gosec // n is never negative
+
+	return n, err //nolint:wrapcheck // pass through the underlying writer's error
+}
+
+// Digest returns the FileHash/FileSize of everything written so far.
+func (f *fileDigestWriter) Digest() *FileDigest {
+	return &FileDigest{
+		FileHash: string(f.algo) + ":" + EncodeNixBase32(f.h.Sum(nil)),
+		FileSize: f.n,
+	}
+}
+
 // DumpPathWithListing serializes a path to NAR format and returns the directory listing.
 // The listing is compatible with Nix's .ls format.
 //
 // Serialization runs in two passes: a fast metadata-only walk builds the tree,
 // then the write pass streams it to w while a worker pool prefetches small
 // file contents ahead of the writer.
+//
+// It uses DefaultNarOptions; see NarOptions.DumpPathWithListing to override.
 func DumpPathWithListing(w io.Writer, path string) (*NarListing, error) {
-	root, err := walkPath(path)
+	return DefaultNarOptions().DumpPathWithListing(w, path)
+}
+
+// DumpPathWithListing is like the package-level DumpPathWithListing but uses o.
+func (o NarOptions) DumpPathWithListing(w io.Writer, path string) (*NarListing, error) {
+	listing, _, err := dumpPath(w, path, nil, o)
+
+	return listing, err
+}
+
+// DumpPathWithDigest is like DumpPathWithListing but also hashes the
+// uncompressed NAR as it is written. The hasher sees the same buffers as w,
+// so no extra copy of the NAR is made.
+func DumpPathWithDigest(w io.Writer, path string) (*NarListing, *NarDigest, error) {
+	return DefaultNarOptions().DumpPathWithDigest(w, path)
+}
+
+// DumpPathWithDigest is like the package-level DumpPathWithDigest but uses o.
+func (o NarOptions) DumpPathWithDigest(w io.Writer, path string) (*NarListing, *NarDigest, error) {
+	h := sha256.New()
+
+	listing, size, err := dumpPath(w, path, h, o)
 	if err != nil {
-		return nil, err
+		return nil, nil, err
+	}
+
+	return listing, &NarDigest{
+		NarHash: "sha256:" + EncodeNixBase32(h.Sum(nil)),
+		NarSize: size,
+	}, nil
+}
+
+// dumpPath serializes path to w and returns the listing and the number of
+// NAR bytes written. If h is non-nil every write is also fed to it.
+func dumpPath(w io.Writer, path string, h hash.Hash, opts NarOptions) (*NarListing, uint64, error) {
+	root, err := walkPath(path, opts)
+	if err != nil {
+		return nil, 0, err
+	}
+
+	if h != nil {
+		w = io.MultiWriter(w, h)
 	}
 
 	pf := newPrefetcher(0)
@@ -313,47 +510,100 @@ func DumpPathWithListing(w io.Writer, path string) (*NarListing, error) {
 	if err := nw.writeStatic(narVersionMagicEncoded); err != nil {
 		drain()
 
-		return nil, err
+		return nil, 0, err
 	}
 
 	if err := nw.writeStatic(openParenEncoded); err != nil {
 		drain()
 
-		return nil, err
+		return nil, 0, err
 	}
 
 	entry, err := writeNode(nw, pf, root)
 	if err != nil {
 		drain()
 
-		return nil, err
+		return nil, 0, err
 	}
 
 	if err := nw.writeStatic(closeParenEncoded); err != nil {
 		drain()
 
-		return nil, err
+		return nil, 0, err
 	}
 
 	<-enqueueDone
 
-	return &NarListing{Version: 1, Root: entry}, nil
+	return &NarListing{Version: 1, Root: entry}, nw.offset, nil
+}
+
+// The walk that builds the narNode tree is dominated by lstat/readlink/
+// readdir syscalls, which for trees with tens of thousands of small files
+// take longer than writing the NAR. Directories are walked concurrently in
+// chunks of sorted entries; each child is stored at its index, so the tree,
+// and with it the NAR, is the same as a serial walk would produce.
+const (
+	// walkConcurrency bounds the goroutines walking the tree at once.
+	walkConcurrency = 16
+
+	// walkChunkSize is the number of entries of one directory handled by
+	// one goroutine, keeping goroutine overhead small next to the syscalls.
+	walkChunkSize = 64
+)
+
+// treeWalker walks a path with bounded concurrency. A task that finds no
+// free slot runs inline in its caller, so nested directories never wait on
+// their parents for a slot.
+type treeWalker struct {
+	g    errgroup.Group
+	opts NarOptions
 }
 
 // walkPath builds the narNode tree for a path using only metadata syscalls.
-func walkPath(path string) (*narNode, error) {
+func walkPath(path string, opts NarOptions) (*narNode, error) {
 	info, err := os.Lstat(path)
 	if err != nil {
 		return nil, fmt.Errorf("stat %s: %w", path, err)
 	}
 
-	return walkNode(path, "", info.Mode(), info)
+	tw := &treeWalker{opts: opts}
+	tw.g.SetLimit(walkConcurrency)
+
+	root, err := tw.walkNode(path, "", info.Mode(), info)
+
+	// Children of directories are filled in by goroutines that may still
+	// be running, even if walkNode itself failed.
+	if waitErr := tw.g.Wait(); err == nil {
+		err = waitErr
+	}
+
+	if err != nil {
+		return nil, err
+	}
+
+	if opts.SkipSpecialFiles {
+		pruneSkipped(root)
+	}
+
+	return root, nil
+}
+
+// pruneSkipped drops the nil children skipped special files leave behind.
+func pruneSkipped(n *narNode) {
+	if n.kind != 'd' {
+		return
+	}
+
+	n.children = slices.DeleteFunc(n.children, func(c *narNode) bool { return c == nil })
+	for _, child := range n.children {
+		pruneSkipped(child)
+	}
 }
 
 // walkNode classifies a single filesystem entry and recurses into
 // directories. info may be nil for directories and symlinks (they don't need
 // it); the mode tells us which branch to take.
-func walkNode(path, name string, mode os.FileMode, info os.FileInfo) (*narNode, error) {
+func (tw *treeWalker) walkNode(path, name string, mode os.FileMode, info os.FileInfo) (*narNode, error) {
 	switch {
 	case mode.IsRegular():
 		if info == nil {
@@ -369,7 +619,7 @@ func walkNode(path, name string, mode os.FileMode, info os.FileInfo) (*narNode,
 		}, nil
 
 	case mode.IsDir():
-		return walkDirectory(path, name)
+		return tw.walkDirectory(path, name)
 
 	case mode&os.ModeSymlink != 0:
 		target, err := os.Readlink(path)
@@ -380,11 +630,17 @@ func walkNode(path, name string, mode os.FileMode, info os.FileInfo) (*narNode,
 		return &narNode{name: name, path: path, kind: 'l', target: target}, nil
 
 	default:
-		return nil, fmt.Errorf("unsupported file type for %s: %v", path, mode)
+		if err := tw.opts.specialFile(path, mode, name == ""); !errors.Is(err, errSpecialFileSkipped) {
+			return nil, err
+		}
+
+		return nil, nil //nolint:nilnil // skipped entries are pruned by walkPath
 	}
 }
 
-func walkDirectory(path, name string) (*narNode, error) {
+// walkDirectory returns the node for a directory. Its children are only
+// complete once the walker's group has been waited for.
+func (tw *treeWalker) walkDirectory(path, name string) (*narNode, error) {
 	entries, err := os.ReadDir(path)
 	if err != nil {
 		return nil, fmt.Errorf("reading directory %s: %w", path, err)
@@ -395,52 +651,72 @@ func walkDirectory(path, name string) (*narNode, error) {
 		return strings.Compare(a.Name(), b.Name())
 	})
 
-	node := &narNode{name: name, path: path, kind: 'd', children: make([]*narNode, 0, len(entries))}
+	node := &narNode{name: name, path: path, kind: 'd', children: make([]*narNode, len(entries))}
 
-	for _, entry := range entries {
-		entryName := entry.Name()
-		narName := stripCaseHackSuffix(entryName)
-		childPath := filepath.Join(path, entryName)
+	for start := 0; start < len(entries); start += walkChunkSize {
+		end := min(start+walkChunkSize, len(entries))
 
-		// Use entry.Type() to avoid an extra stat syscall when possible.
-		// Fall back to entry.Info() if Type() returns DT_UNKNOWN.
-		entryType := entry.Type()
+		task := func() error {
+			for i := start; i < end; i++ {
+				child, err := tw.walkEntry(path, entries[i])
+				if err != nil {
+					return err
+				}
 
-		var (
-			info os.FileInfo
-			mode os.FileMode
-		)
-
-		switch {
-		case entryType.IsRegular():
-			// Regular files need FileInfo for size and permissions.
-			info, err = entry.Info()
-			if err != nil {
-				return nil, fmt.Errorf("getting info for %s: %w", childPath, err)
+				node.children[i] = child
 			}
 
-			mode = info.Mode()
-		case entryType.IsDir() || entryType&os.ModeSymlink != 0:
-			mode = entryType
-		default:
-			// DT_UNKNOWN: fall back to lstat.
-			info, err = entry.Info()
-			if err != nil {
-				return nil, fmt.Errorf("getting info for %s: %w", childPath, err)
+			return nil
+		}
+
+		if !tw.g.TryGo(task) {
+			if err := task(); err != nil {
+				return nil, err
 			}
+		}
+	}
+
+	return node, nil
+}
+
+// walkEntry stats one directory entry and walks it.
+func (tw *treeWalker) walkEntry(dir string, entry os.DirEntry) (*narNode, error) {
+	entryName := entry.Name()
+	narName := stripCaseHackSuffix(entryName, tw.opts.CaseHack)
+	childPath := filepath.Join(dir, entryName)
 
-			mode = info.Mode()
+	// Use entry.Type() to avoid an extra stat syscall when possible.
+	// Fall back to entry.Info() if Type() returns DT_UNKNOWN.
+	entryType := entry.Type()
+
+	var (
+		info os.FileInfo
+		mode os.FileMode
+		err  error
+	)
+
+	switch {
+	case entryType.IsRegular():
+		// Regular files need FileInfo for size and permissions.
+		info, err = entry.Info()
+		if err != nil {
+			return nil, fmt.Errorf("getting info for %s: %w", childPath, err)
 		}
 
-		child, err := walkNode(childPath, narName, mode, info)
+		mode = info.Mode()
+	case entryType.IsDir() || entryType&os.ModeSymlink != 0:
+		mode = entryType
+	default:
+		// DT_UNKNOWN: fall back to lstat.
+		info, err = entry.Info()
 		if err != nil {
-			return nil, err
+			return nil, fmt.Errorf("getting info for %s: %w", childPath, err)
 		}
 
-		node.children = append(node.children, child)
+		mode = info.Mode()
 	}
 
-	return node, nil
+	return tw.walkNode(childPath, narName, mode, info)
 }
 
 // shouldPrefetch reports whether a regular file is read ahead by the worker
@@ -25,6 +25,34 @@ func BenchmarkDumpPathLargeClosure(b *testing.B) {
 	}
 }
 
+// BenchmarkDumpPathManySmallFiles dumps a node_modules-style tree, where
+// walking the tree costs more than reading the files.
+func BenchmarkDumpPathManySmallFiles(b *testing.B) {
+	b.ReportAllocs()
+
+	root := b.TempDir()
+
+	for pkg := range 200 {
+		dir := filepath.Join(root, "node_modules", fmt.Sprintf("pkg-%03d", pkg), "lib")
+		if err := os.MkdirAll(dir, 0o755); err != nil {
+			b.Fatalf("creating directory %q: %v", dir, err)
+		}
+
+		for file := range 100 {
+			filePath := filepath.Join(dir, fmt.Sprintf("mod-%02d.js", file))
+			if err := os.WriteFile(filePath, []byte("module.exports = {};\n"), 0o600); err != nil {
+				b.Fatalf("creating file %q: %v", filePath, err)
+			}
+		}
+	}
+
+	for b.Loop() {
+		if _, err := client.DumpPathWithListing(io.Discard, root); err != nil {
+			b.Fatalf("DumpPathWithListing failed: %v", err)
+		}
+	}
+}
+
 func makeBenchmarkTree(tb testing.TB, root string) {
 	tb.Helper()
 
@@ -0,0 +1,196 @@
+package client
+
+import (
+	"context"
+	"fmt"
+	"io"
+	"os"
+	"path"
+	"path/filepath"
+	"sync"
+)
+
+// NARCache keeps compressed NARs on disk so that pushes of the same paths to
+// several caches compress each NAR once. Entries are keyed by NAR object
+// key, which names the NAR hash and compression, so every push sharing a
+// NARCache must use the same compression settings. It is safe for
+// concurrent use.
+type NARCache struct {
+	dir string
+
+	mu      sync.Mutex
+	entries map[string]*narCacheEntry
+}
+
+// narCacheEntry is a compressed NAR in a NARCache. mu is held while the NAR
+// is compressed, so concurrent users of a key wait for one compression.
+type narCacheEntry struct {
+	mu      sync.Mutex
+	path    string
+	listing *NarListing
+	digest  *FileDigest // nil until the NAR has been compressed successfully
+}
+
+// NewNARCache creates a NARCache in a new directory under tempDir ("" = the
+// system default). Close removes it.
+func NewNARCache(tempDir string) (*NARCache, error) {
+	dir, err := os.MkdirTemp(tempDir, "niks3-nars-*")
+	if err != nil {
+		return nil, fmt.Errorf("creating NAR cache directory: %w", err)
+	}
+
+	return &NARCache{dir: dir, entries: make(map[string]*narCacheEntry)}, nil
+}
+
+// Close removes the compressed NARs.
+func (nc *NARCache) Close() error {
+	if err := os.RemoveAll(nc.dir); err != nil {
+		return fmt.Errorf("removing NAR cache directory: %w", err)
+	}
+
+	return nil
+}
+
+func (nc *NARCache) entry(objectKey string) *narCacheEntry {
+	nc.mu.Lock()
+	defer nc.mu.Unlock()
+
+	e, ok := nc.entries[objectKey]
+	if !ok {
+		e = &narCacheEntry{path: filepath.Join(nc.dir, path.Base(objectKey))}
+		nc.entries[objectKey] = e
+	}
+
+	return e
+}
+
+// cachedNAR returns the compressed NAR of pathInfo from c.NARCache,
+// compressing it on first use. A failed compression is not remembered, so
+// the next push tries again.
+func (c *Client) cachedNAR(ctx context.Context, pathInfo *PathInfo, objectKey string) (*narCacheEntry, error) {
+	e := c.NARCache.entry(objectKey)
+
+	e.mu.Lock()
+	defer e.mu.Unlock()
+
+	if e.digest != nil {
+		return e, nil
+	}
+
+	listing, digest, err := c.compressNARToFile(ctx, pathInfo, e.path)
+	if err != nil {
+		return nil, err
+	}
+
+	e.listing, e.digest = listing, digest
+
+	return e, nil
+}
+
+// compressNARToFile writes the compressed NAR of pathInfo to name. The file
+// is removed again if compressing fails.
+func (c *Client) compressNARToFile(ctx context.Context, pathInfo *PathInfo, name string) (*NarListing, *FileDigest, error) {
+	f, err := os.Create(name)
+	if err != nil {
+		return nil, nil, fmt.Errorf("creating compressed NAR: %w", err)
+	}
+
+	listing, fileDigest, err := c.writeCompressedNAR(ctx, pathInfo, f)
+	if closeErr := f.Close(); closeErr != nil && err == nil {
+		err = fmt.Errorf("closing compressed NAR: %w", closeErr)
+	}
+
+	if err != nil {
+		_ = os.Remove(name)
+
+		return nil, nil, err
+	}
+
+	return listing, fileDigest, nil
+}
+
+// writeCompressedNAR compresses the NAR of pathInfo to w, checking it
+// against pathInfo's NarHash and NarSize like a direct upload does.
+func (c *Client) writeCompressedNAR(ctx context.Context, pathInfo *PathInfo, w io.Writer) (*NarListing, *FileDigest, error) {
+	releaseSlot, err := c.acquireCompressionSlot(ctx)
+	if err != nil {
+		return nil, nil, err
+	}
+	defer releaseSlot()
+
+	fileWriter := newFileDigestWriter(w)
+
+	encoder, release, err := newNARCompressor(pathInfo.narCompression(c.Compression), c.CompressionLevel, c.ZstdWindowLog, c.narCompressionWorkers(pathInfo.NarSize), fileWriter)
+	if err != nil {
+		return nil, nil, err
+	}
+	defer release()
+
+	listing, digest, err := c.dumpNAR(ctx, encoder, pathInfo.Path)
+	if err != nil {
+		return nil, nil, fmt.Errorf("serializing NAR: %w", err)
+	}
+
+	if err := encoder.Close(); err != nil {
+		return nil, nil, fmt.Errorf("closing %s encoder: %w", pathInfo.narCompression(c.Compression), err)
+	}
+
+	if err := digest.Check(pathInfo.NarHash.String(), pathInfo.NarSize); err != nil {
+		return nil, nil, fmt.Errorf("verifying NAR for %s: %w", pathInfo.Path, err)
+	}
+
+	return listing, fileWriter.Digest(), nil
+}
+
+// uploadCachedNAR uploads the NAR of pathInfo from c.NARCache, with a
+// single PUT or a multipart upload like CompressAndUploadNAR.
+func (c *Client) uploadCachedNAR(ctx context.Context, pathInfo *PathInfo, obj PendingObject, objectKey string) (*NarListing, *FileDigest, error) {
+	e, err := c.cachedNAR(ctx, pathInfo, objectKey)
+	if err != nil {
+		return nil, nil, err
+	}
+
+	if obj.MultipartInfo != nil {
+		f, err := os.Open(e.path)
+		if err != nil {
+			return nil, nil, fmt.Errorf("opening compressed NAR: %w", err)
+		}
+
+		defer func() { _ = f.Close() }()
+
+		var r io.Reader = f
+
+		if kept := c.keepFile(objectKey); kept != nil {
+			defer closeKeptFile(kept)
+
+			r = io.TeeReader(f, kept)
+		}
+
+		if err := c.uploadMultipart(ctx, r, obj.MultipartInfo, objectKey, partSizeForNAR(pathInfo.NarSize)); err != nil {
+			return nil, nil, err
+		}
+
+		return e.listing, e.digest, nil
+	}
+
+	data, err := os.ReadFile(e.path)
+	if err != nil {
+		return nil, nil, fmt.Errorf("reading compressed NAR: %w", err)
+	}
+
+	c.keepBytes(objectKey, data)
+
+	headers, err := narChecksumHeaders(c.ChecksumUploads, e.digest, objectKey)
+	if err != nil {
+		return nil, nil, err
+	}
+
+	err = c.withPresignedURL(ctx, objectKey, obj, func(presignedURL string) error {
+		return c.UploadBytesToPresignedURLWithHeaders(ctx, presignedURL, data, headers)
+	})
+	if err != nil {
+		return nil, nil, fmt.Errorf("uploading NAR %s: %w", objectKey, err)
+	}
+
+	return e.listing, e.digest, nil
+}
@@ -0,0 +1,95 @@
+package client_test
+
+import (
+	"bytes"
+	"context"
+	"encoding/json"
+	"fmt"
+	"io"
+	"net/http"
+	"net/http/httptest"
+	"os"
+	"path/filepath"
+	"sync"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+// TestNARCacheCompressesOnce uploads one NAR to two servers through a shared
+// NARCache and checks that the second upload reuses the first compression.
+func TestNARCacheCompressesOnce(t *testing.T) {
+	t.Parallel()
+
+	src := t.TempDir()
+	makeMixedTree(t, src)
+
+	_, digest, err := client.DumpPathWithDigest(io.Discard, src)
+	if err != nil {
+		t.Fatalf("DumpPathWithDigest: %v", err)
+	}
+
+	var pathInfo client.PathInfo
+	if err := json.Unmarshal(fmt.Appendf(nil, `{"narHash":%q,"narSize":%d}`, digest.NarHash, digest.NarSize), &pathInfo); err != nil {
+		t.Fatal(err)
+	}
+
+	pathInfo.Path = src
+
+	var (
+		mu     sync.Mutex
+		bodies [][]byte
+	)
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		body, err := io.ReadAll(r.Body)
+		if err != nil {
+			http.Error(w, err.Error(), http.StatusBadRequest)
+
+			return
+		}
+
+		mu.Lock()
+		bodies = append(bodies, body)
+		mu.Unlock()
+	}))
+	defer srv.Close()
+
+	narCache, err := client.NewNARCache(t.TempDir())
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	defer func() { _ = narCache.Close() }()
+
+	obj := client.PendingObject{Type: "nar", PresignedURL: srv.URL + "/nar"}
+
+	primary := newTestClientWithRetries(srv.Client(), 0)
+	primary.NARCache = narCache
+
+	first, err := primary.UploadNARWithListing(context.Background(), "nar/test.nar.zst", obj, &pathInfo)
+	if err != nil {
+		t.Fatalf("first upload: %v", err)
+	}
+
+	// Compressing again would now fail the NarHash check.
+	if err := os.WriteFile(filepath.Join(src, "added-later"), []byte("x"), 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	mirror := newTestClientWithRetries(srv.Client(), 0)
+	mirror.NARCache = narCache
+
+	second, err := mirror.UploadNARWithListing(context.Background(), "nar/test.nar.zst", obj, &pathInfo)
+	if err != nil {
+		t.Fatalf("second upload: %v", err)
+	}
+
+	if *first != *second {
+		t.Errorf("digests differ: %+v vs %+v", first, second)
+	}
+
+	if len(bodies) != 2 || !bytes.Equal(bodies[0], bodies[1]) {
+		t.Errorf("expected two identical uploads, got %d", len(bodies))
+	}
+}
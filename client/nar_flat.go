@@ -0,0 +1,107 @@
+package client
+
+import (
+	"bytes"
+	"fmt"
+	"io"
+	"os"
+	"strings"
+)
+
+// DumpFlat writes the contents of the regular file at path to w, without
+// NAR framing, and returns their sha256 and size. FileHash is then the flat
+// hash `nix hash file` prints, which `fixed:sha256:` content addresses of
+// flat fixed-output paths declare.
+func DumpFlat(w io.Writer, path string) (*FileDigest, error) {
+	return dumpFlat(w, path, HashSHA256)
+}
+
+// dumpFlat is DumpFlat hashing with algo.
+func dumpFlat(w io.Writer, path string, algo HashAlgorithm) (*FileDigest, error) {
+	info, err := os.Lstat(path)
+	if err != nil {
+		return nil, fmt.Errorf("stat %s: %w", path, err)
+	}
+
+	if !info.Mode().IsRegular() {
+		return nil, fmt.Errorf("%s is not a regular file and has no flat serialization", path)
+	}
+
+	f, err := os.Open(path)
+	if err != nil {
+		return nil, fmt.Errorf("opening %s: %w", path, err)
+	}
+	defer func() { _ = f.Close() }()
+
+	dw, err := newFileDigestWriterFor(w, algo)
+	if err != nil {
+		return nil, err
+	}
+
+	if _, err := io.Copy(dw, f); err != nil {
+		return nil, fmt.Errorf("reading %s: %w", path, err)
+	}
+
+	return dw.Digest(), nil
+}
+
+// VerifyContentAddress checks the store path at path against its content
+// address ca, as found in narinfo CA fields: `fixed:r:` hashes the NAR
+// serialization, `fixed:` and `text:` the flat file contents. Git hashes
+// (`fixed:git:`) are not supported.
+func VerifyContentAddress(path, ca string) error {
+	var (
+		rest      string
+		recursive bool
+	)
+
+	switch {
+	case strings.HasPrefix(ca, "fixed:git:"):
+		return fmt.Errorf("content address %q: git hashes are not supported", ca)
+	case strings.HasPrefix(ca, "fixed:r:"):
+		rest, recursive = strings.TrimPrefix(ca, "fixed:r:"), true
+	case strings.HasPrefix(ca, "fixed:"):
+		rest = strings.TrimPrefix(ca, "fixed:")
+	case strings.HasPrefix(ca, "text:"):
+		rest = strings.TrimPrefix(ca, "text:")
+	default:
+		return fmt.Errorf("content address %q has no text: or fixed: prefix", ca)
+	}
+
+	algo, expected, err := DecodeNixHash(rest)
+	if err != nil {
+		return fmt.Errorf("content address %q: %w", ca, err)
+	}
+
+	var actual []byte
+
+	if recursive {
+		h, err := algo.New()
+		if err != nil {
+			return err
+		}
+
+		if _, _, err := dumpPath(io.Discard, path, h, DefaultNarOptions()); err != nil {
+			return err
+		}
+
+		actual = h.Sum(nil)
+	} else {
+		digest, err := dumpFlat(io.Discard, path, algo)
+		if err != nil {
+			return err
+		}
+
+		_, actual, err = DecodeNixHash(digest.FileHash)
+		if err != nil {
+			return err
+		}
+	}
+
+	if !bytes.Equal(actual, expected) {
+		return fmt.Errorf("content address mismatch for %s: contents hash to %s:%s, CA declares %s",
+			path, algo, EncodeNixBase32(actual), ca)
+	}
+
+	return nil
+}
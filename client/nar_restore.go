@@ -0,0 +1,293 @@
+package client
+
+import (
+	"bufio"
+	"encoding/binary"
+	"errors"
+	"fmt"
+	"io"
+	"log/slog"
+	"os"
+	"path/filepath"
+	"strconv"
+	"strings"
+)
+
+// maxNarStringLen bounds non-content strings (tags, names, symlink targets)
+// so a corrupt length prefix cannot make us allocate gigabytes.
+const maxNarStringLen = 64 * 1024
+
+// narReader reads the framing written by narWriter.
+type narReader struct {
+	r       *bufio.Reader
+	opts    NarOptions
+	scratch [8]byte
+	offset  uint64 // bytes of framing consumed; file contents are added by the caller
+}
+
+func (nr *narReader) readUint64() (uint64, error) {
+	if _, err := io.ReadFull(nr.r, nr.scratch[:]); err != nil {
+		return 0, fmt.Errorf("reading uint64: %w", err)
+	}
+
+	nr.offset += 8
+
+	return binary.LittleEndian.Uint64(nr.scratch[:]), nil
+}
+
+// skipPadding consumes the zero padding after n bytes of payload and rejects
+// non-zero padding, which Nix treats as a corrupt archive.
+func (nr *narReader) skipPadding(n uint64) error {
+	padding := (8 - (n % 8)) % 8
+	if padding == 0 {
+		return nil
+	}
+
+	if _, err := io.ReadFull(nr.r, nr.scratch[:padding]); err != nil {
+		return fmt.Errorf("reading padding: %w", err)
+	}
+
+	nr.offset += padding
+
+	for _, b := range nr.scratch[:padding] {
+		if b != 0 {
+			return errors.New("non-zero padding in NAR")
+		}
+	}
+
+	return nil
+}
+
+func (nr *narReader) readString() (string, error) {
+	n, err := nr.readUint64()
+	if err != nil {
+		return "", err
+	}
+
+	if n > maxNarStringLen {
+		return "", fmt.Errorf("NAR string too long: %d bytes", n)
+	}
+
+	buf := make([]byte, n)
+	if _, err := io.ReadFull(nr.r, buf); err != nil {
+		return "", fmt.Errorf("reading string content: %w", err)
+	}
+
+	nr.offset += n
+
+	if err := nr.skipPadding(n); err != nil {
+		return "", err
+	}
+
+	return string(buf), nil
+}
+
+func (nr *narReader) expect(want string) error {
+	got, err := nr.readString()
+	if err != nil {
+		return err
+	}
+
+	if got != want {
+		return fmt.Errorf("expected %q in NAR, got %q", want, got)
+	}
+
+	return nil
+}
+
+// RestorePath deserializes a NAR from r and recreates it at dest, which must
+// not exist yet. It is the inverse of DumpPathWithListing: files, directories,
+// symlinks, and the executable bit are restored. When the case hack is
+// enabled, names that collide case-insensitively get the Nix case-hack suffix
+// so they survive on case-insensitive filesystems.
+func RestorePath(r io.Reader, dest string) error {
+	return DefaultNarOptions().RestorePath(r, dest)
+}
+
+// RestorePath is like the package-level RestorePath but uses o.
+func (o NarOptions) RestorePath(r io.Reader, dest string) error {
+	nr := &narReader{r: bufio.NewReaderSize(r, 128*1024), opts: o}
+
+	if err := nr.expect(narVersionMagic); err != nil {
+		return fmt.Errorf("not a NAR archive: %w", err)
+	}
+
+	if err := nr.expect("("); err != nil {
+		return err
+	}
+
+	return restoreNode(nr, dest)
+}
+
+// restoreNode restores one node whose opening "(" has already been consumed.
+// It consumes the node's closing ")".
+func restoreNode(nr *narReader, path string) error {
+	if err := nr.expect("type"); err != nil {
+		return err
+	}
+
+	kind, err := nr.readString()
+	if err != nil {
+		return err
+	}
+
+	switch kind {
+	case "regular":
+		return restoreRegularFile(nr, path)
+	case "directory":
+		return restoreDirectory(nr, path)
+	case "symlink":
+		return restoreSymlink(nr, path)
+	default:
+		return fmt.Errorf("unknown NAR node type %q at %s", kind, path)
+	}
+}
+
+func restoreRegularFile(nr *narReader, path string) error {
+	tag, err := nr.readString()
+	if err != nil {
+		return err
+	}
+
+	var mode os.FileMode = 0o644
+
+	if tag == "executable" {
+		if err := nr.expect(""); err != nil {
+			return err
+		}
+
+		mode = 0o755
+
+		if tag, err = nr.readString(); err != nil {
+			return err
+		}
+	}
+
+	if tag != "contents" {
+		return fmt.Errorf("expected \"contents\" for %s, got %q", path, tag)
+	}
+
+	size, err := nr.readUint64()
+	if err != nil {
+		return err
+	}
+
+	f, err := os.OpenFile(path, os.O_WRONLY|os.O_CREATE|os.O_EXCL, mode)
+	if err != nil {
+		return fmt.Errorf("creating file %s: %w", path, err)
+	}
+
+	if _, err := io.CopyN(f, nr.r, int64(size)); err != nil { //nolint:gosec // size comes from the archive; CopyN fails on short input
+		if closeErr := f.Close(); closeErr != nil {
+			slog.Error("Failed to close file", "path", path, "error", closeErr)
+		}
+
+		return fmt.Errorf("writing file %s: %w", path, err)
+	}
+
+	if err := f.Close(); err != nil {
+		return fmt.Errorf("closing file %s: %w", path, err)
+	}
+
+	if err := nr.skipPadding(size); err != nil {
+		return err
+	}
+
+	return nr.expect(")")
+}
+
+func restoreDirectory(nr *narReader, path string) error {
+	if err := os.Mkdir(path, 0o755); err != nil {
+		return fmt.Errorf("creating directory %s: %w", path, err)
+	}
+
+	var (
+		prevName string
+		// Lower-cased names seen so far and how often each collided, used
+		// to number case-hack suffixes the same way Nix does.
+		collisions = make(map[string]int)
+	)
+
+	for {
+		tag, err := nr.readString()
+		if err != nil {
+			return err
+		}
+
+		if tag == ")" {
+			return nil
+		}
+
+		if tag != "entry" {
+			return fmt.Errorf("expected \"entry\" in directory %s, got %q", path, tag)
+		}
+
+		if err := nr.expect("("); err != nil {
+			return err
+		}
+
+		if err := nr.expect("name"); err != nil {
+			return err
+		}
+
+		name, err := nr.readString()
+		if err != nil {
+			return err
+		}
+
+		if name == "" || name == "." || name == ".." || strings.ContainsAny(name, "/\x00") {
+			return fmt.Errorf("invalid entry name %q in directory %s", name, path)
+		}
+
+		if prevName != "" && name <= prevName {
+			return fmt.Errorf("entries in directory %s are not sorted: %q after %q", path, name, prevName)
+		}
+
+		prevName = name
+
+		diskName := name
+
+		if nr.opts.CaseHack {
+			lower := strings.ToLower(name)
+			if n, ok := collisions[lower]; ok {
+				collisions[lower] = n + 1
+				diskName = name + caseHackSuffix + strconv.Itoa(n+1)
+			} else {
+				collisions[lower] = 0
+			}
+		}
+
+		if err := nr.expect("node"); err != nil {
+			return err
+		}
+
+		if err := nr.expect("("); err != nil {
+			return err
+		}
+
+		if err := restoreNode(nr, filepath.Join(path, diskName)); err != nil {
+			return err
+		}
+
+		if err := nr.expect(")"); err != nil {
+			return err
+		}
+	}
+}
+
+func restoreSymlink(nr *narReader, path string) error {
+	if err := nr.expect("target"); err != nil {
+		return err
+	}
+
+	target, err := nr.readString()
+	if err != nil {
+		return err
+	}
+
+	if err := os.Symlink(target, path); err != nil {
+		return fmt.Errorf("creating symlink %s: %w", path, err)
+	}
+
+	return nr.expect(")")
+}
@@ -0,0 +1,81 @@
+package client_test
+
+import (
+	"bytes"
+	"encoding/binary"
+	"os"
+	"path/filepath"
+	"strings"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+// TestRestorePathRoundTrip dumps a tree, restores it elsewhere, and checks
+// that dumping the restored tree yields a byte-identical NAR.
+func TestRestorePathRoundTrip(t *testing.T) {
+	t.Parallel()
+
+	src := t.TempDir()
+	makeMixedTree(t, src)
+
+	var original bytes.Buffer
+	if _, err := client.DumpPathWithListing(&original, src); err != nil {
+		t.Fatalf("DumpPathWithListing: %v", err)
+	}
+
+	dest := filepath.Join(t.TempDir(), "restored")
+	if err := client.RestorePath(bytes.NewReader(original.Bytes()), dest); err != nil {
+		t.Fatalf("RestorePath: %v", err)
+	}
+
+	var redumped bytes.Buffer
+	if _, err := client.DumpPathWithListing(&redumped, dest); err != nil {
+		t.Fatalf("DumpPathWithListing (restored): %v", err)
+	}
+
+	if !bytes.Equal(original.Bytes(), redumped.Bytes()) {
+		t.Fatalf("NAR mismatch after round trip: original=%d bytes, restored=%d bytes", original.Len(), redumped.Len())
+	}
+
+	info, err := os.Stat(filepath.Join(dest, "exec"))
+	if err != nil {
+		t.Fatalf("stat exec: %v", err)
+	}
+
+	if info.Mode()&0o111 == 0 {
+		t.Fatal("executable bit was not restored")
+	}
+}
+
+// narString encodes s with NAR length framing and padding.
+func narString(s string) []byte {
+	buf := make([]byte, 8, 8+len(s)+8)
+	binary.LittleEndian.PutUint64(buf, uint64(len(s)))
+	buf = append(buf, s...)
+
+	return append(buf, make([]byte, (8-len(s)%8)%8)...)
+}
+
+func TestRestorePathRejectsUnknownType(t *testing.T) {
+	t.Parallel()
+
+	var nar bytes.Buffer
+	for _, s := range []string{"nix-archive-1", "(", "type", "fifo", ")"} {
+		nar.Write(narString(s))
+	}
+
+	err := client.RestorePath(&nar, filepath.Join(t.TempDir(), "out"))
+	if err == nil || !strings.Contains(err.Error(), `unknown NAR node type "fifo"`) {
+		t.Fatalf("expected unknown node type error, got %v", err)
+	}
+}
+
+func TestRestorePathRejectsBadMagic(t *testing.T) {
+	t.Parallel()
+
+	err := client.RestorePath(bytes.NewReader(narString("not-a-nar")), filepath.Join(t.TempDir(), "out"))
+	if err == nil || !strings.Contains(err.Error(), "not a NAR archive") {
+		t.Fatalf("expected bad magic error, got %v", err)
+	}
+}
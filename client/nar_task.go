@@ -7,37 +7,51 @@ import (
 	"log/slog"
 )
 
-// uploadNARWithListing uploads a NAR and its listing.
+// uploadNARWithListing uploads a NAR and its listing. It returns the
+// FileHash/FileSize for the narinfo, or nil if a peer uploaded the NAR first.
 func (c *Client) uploadNARWithListing(
 	ctx context.Context,
 	narTask uploadTask,
 	lsTask *uploadTask,
 	pathInfo *PathInfo,
-) error {
+) (*FileDigest, error) {
 	if pathInfo == nil {
-		return fmt.Errorf("missing PathInfo for NAR %s", narTask.key)
+		return nil, fmt.Errorf("missing PathInfo for NAR %s", narTask.key)
 	}
 
-	listing, err := c.CompressAndUploadNAR(ctx, pathInfo.Path, pathInfo.NarSize, narTask.obj, narTask.key)
+	c.emit(CompressionStarted{StorePath: pathInfo.Path, Bytes: pathInfo.NarSize})
+
+	listing, fileDigest, err := c.CompressAndUploadNAR(ctx, pathInfo, narTask.obj, narTask.key)
 	if err != nil {
 		if errors.Is(err, ErrUploadSuperseded) {
 			// A peer already uploaded this NAR (and its listing); nothing to do.
+			// Deduplicated NARs have no FileHash/FileSize, which Nix accepts
+			// since both fields are optional.
 			slog.Debug("Skipping NAR superseded by concurrent upload", "key", narTask.key)
 
-			return nil
+			return nil, nil //nolint:nilnil // superseded NARs have no digest
 		}
 
-		return fmt.Errorf("uploading NAR %s: %w", narTask.key, err)
+		return nil, fmt.Errorf("uploading NAR %s: %w", narTask.key, err)
+	}
+
+	if fileDigest == nil {
+		return nil, fmt.Errorf("uploading NAR %s: no compressed file hash recorded for %s", narTask.key, pathInfo.Path)
 	}
 
 	// Upload listing immediately in same goroutine
 	if lsTask != nil && listing != nil {
-		if err := c.UploadListingToPresignedURL(ctx, lsTask.obj.PresignedURL, listing); err != nil {
-			return fmt.Errorf("uploading listing %s: %w", lsTask.key, err)
+		err := c.withPresignedURL(ctx, lsTask.key, lsTask.obj, func(presignedURL string) error {
+			return c.UploadListingToPresignedURL(ctx, presignedURL, listing)
+		})
+		if err != nil {
+			return nil, fmt.Errorf("uploading listing %s: %w", lsTask.key, err)
 		}
 
 		slog.Debug("Uploaded listing", "key", lsTask.key)
 	}
 
-	return nil
+	c.emit(NarUploaded{StorePath: pathInfo.Path, FileHash: fileDigest.FileHash, CompressedSize: fileDigest.FileSize})
+
+	return fileDigest, nil
 }
@@ -3,11 +3,16 @@ package client_test
 import (
 	"bytes"
 	"crypto/rand"
+	"crypto/sha256"
+	"encoding/base64"
 	"errors"
 	"fmt"
 	"os"
 	"os/exec"
 	"path/filepath"
+	"reflect"
+	"strings"
+	"syscall"
 	"testing"
 
 	"github.com/Mic92/niks3/client"
@@ -72,6 +77,21 @@ func makeMixedTree(t *testing.T, root string) {
 	if err := os.Symlink("/nonexistent/target", filepath.Join(root, "abs-link")); err != nil {
 		t.Fatalf("symlink: %v", err)
 	}
+
+	// A directory wide enough to be walked in several concurrent chunks,
+	// with subdirectories spread across them.
+	wide := filepath.Join(root, "wide")
+	mkdir(wide)
+
+	for i := range 300 {
+		if i%50 == 0 {
+			sub := filepath.Join(wide, fmt.Sprintf("e-%03d-dir", i))
+			mkdir(sub)
+			write(filepath.Join(sub, "index.js"), 30+i, 0o644)
+		}
+
+		write(filepath.Join(wide, fmt.Sprintf("e-%03d", i)), i%17, 0o644)
+	}
 }
 
 // TestDumpPathMatchesNix compares our NAR serialization byte-for-byte against
@@ -220,3 +240,184 @@ func TestDumpPathWriterError(t *testing.T) {
 		}
 	}
 }
+
+// TestDumpPathWithDigest checks that the inline digest matches a sha256 over
+// the emitted bytes and that Check rejects a mismatching size or hash.
+func TestDumpPathWithDigest(t *testing.T) {
+	t.Parallel()
+
+	tmp := t.TempDir()
+	makeMixedTree(t, tmp)
+
+	var nar bytes.Buffer
+
+	_, digest, err := client.DumpPathWithDigest(&nar, tmp)
+	if err != nil {
+		t.Fatalf("DumpPathWithDigest: %v", err)
+	}
+
+	if digest.NarSize != uint64(nar.Len()) {
+		t.Fatalf("NarSize = %d, want %d", digest.NarSize, nar.Len())
+	}
+
+	sum := sha256.Sum256(nar.Bytes())
+	sri := "sha256-" + base64.StdEncoding.EncodeToString(sum[:])
+
+	if err := digest.Check(sri, uint64(nar.Len())); err != nil {
+		t.Fatalf("Check with matching SRI hash: %v", err)
+	}
+
+	if err := digest.Check(sri, uint64(nar.Len())+1); err == nil {
+		t.Fatal("Check accepted a mismatching size")
+	}
+
+	other := sha256.Sum256([]byte("other"))
+	if err := digest.Check("sha256-"+base64.StdEncoding.EncodeToString(other[:]), uint64(nar.Len())); err == nil {
+		t.Fatal("Check accepted a mismatching hash")
+	}
+}
+
+// TestDumpFlat checks that the flat dump is the bare file contents and that
+// VerifyContentAddress checks flat and recursive content addresses.
+func TestDumpFlat(t *testing.T) {
+	t.Parallel()
+
+	tmp := t.TempDir()
+	f := filepath.Join(tmp, "source.tar.gz")
+	contents := bytes.Repeat([]byte("fixed output\n"), 1000)
+
+	if err := os.WriteFile(f, contents, 0o600); err != nil {
+		t.Fatalf("write: %v", err)
+	}
+
+	var out bytes.Buffer
+
+	digest, err := client.DumpFlat(&out, f)
+	if err != nil {
+		t.Fatalf("DumpFlat: %v", err)
+	}
+
+	if !bytes.Equal(out.Bytes(), contents) {
+		t.Fatalf("DumpFlat wrote %d bytes, want the %d bytes of the file", out.Len(), len(contents))
+	}
+
+	sum := sha256.Sum256(contents)
+	flatHash := "sha256:" + client.EncodeNixBase32(sum[:])
+
+	if digest.FileHash != flatHash || digest.FileSize != uint64(len(contents)) {
+		t.Fatalf("digest = %+v, want %s and %d bytes", digest, flatHash, len(contents))
+	}
+
+	_, narDigest, err := client.DumpPathWithDigest(&bytes.Buffer{}, f)
+	if err != nil {
+		t.Fatalf("DumpPathWithDigest: %v", err)
+	}
+
+	for _, ca := range []string{"fixed:" + flatHash, "text:" + flatHash, "fixed:r:" + narDigest.NarHash} {
+		if err := client.VerifyContentAddress(f, ca); err != nil {
+			t.Errorf("VerifyContentAddress(%s): %v", ca, err)
+		}
+	}
+
+	for _, ca := range []string{"fixed:" + narDigest.NarHash, "fixed:r:" + flatHash, "fixed:git:" + flatHash, flatHash} {
+		if err := client.VerifyContentAddress(f, ca); err == nil {
+			t.Errorf("VerifyContentAddress(%s) accepted a mismatching content address", ca)
+		}
+	}
+
+	if _, err := client.DumpFlat(&out, tmp); err == nil {
+		t.Error("DumpFlat accepted a directory")
+	}
+}
+
+// TestDumpPathSpecialFiles checks that a fifo fails the dump by default and
+// that SkipSpecialFiles produces the NAR and listing of the tree without it.
+func TestDumpPathSpecialFiles(t *testing.T) {
+	t.Parallel()
+
+	withFifo := filepath.Join(t.TempDir(), "root")
+	without := filepath.Join(t.TempDir(), "root")
+
+	for _, dir := range []string{withFifo, without} {
+		if err := os.MkdirAll(filepath.Join(dir, "sub"), 0o755); err != nil {
+			t.Fatalf("mkdir: %v", err)
+		}
+
+		for _, name := range []string{"a", "sub/c"} {
+			if err := os.WriteFile(filepath.Join(dir, name), []byte(name), 0o600); err != nil {
+				t.Fatalf("write: %v", err)
+			}
+		}
+	}
+
+	for _, name := range []string{"b.fifo", "sub/b.fifo"} {
+		if err := syscall.Mkfifo(filepath.Join(withFifo, name), 0o600); err != nil {
+			t.Skipf("mkfifo: %v", err)
+		}
+	}
+
+	if _, err := client.DumpPathWithListing(&bytes.Buffer{}, withFifo); err == nil || !strings.Contains(err.Error(), "is a fifo") {
+		t.Fatalf("expected an error naming the fifo, got %v", err)
+	}
+
+	skip := client.NarOptions{SkipSpecialFiles: true}
+
+	var got, want bytes.Buffer
+
+	gotListing, err := skip.DumpPathWithListing(&got, withFifo)
+	if err != nil {
+		t.Fatalf("DumpPathWithListing with SkipSpecialFiles: %v", err)
+	}
+
+	wantListing, err := skip.DumpPathWithListing(&want, without)
+	if err != nil {
+		t.Fatalf("DumpPathWithListing: %v", err)
+	}
+
+	if !bytes.Equal(got.Bytes(), want.Bytes()) {
+		t.Error("NAR with the fifos skipped differs from the NAR of the tree without them")
+	}
+
+	if !reflect.DeepEqual(gotListing, wantListing) {
+		t.Errorf("listing = %+v, want %+v", gotListing, wantListing)
+	}
+
+	onlyListing, err := skip.GenerateListingOnly(withFifo)
+	if err != nil {
+		t.Fatalf("GenerateListingOnly: %v", err)
+	}
+
+	if !reflect.DeepEqual(onlyListing, wantListing) {
+		t.Errorf("GenerateListingOnly = %+v, want %+v", onlyListing, wantListing)
+	}
+
+	if _, err := skip.DumpPathWithListing(&bytes.Buffer{}, filepath.Join(withFifo, "b.fifo")); err == nil {
+		t.Error("SkipSpecialFiles skipped the root of the dump")
+	}
+}
+
+// TestDumpPathListingMatchesNAR parses the NAR of a tree walked in
+// concurrent chunks and checks that the entries come out sorted (ListNAR
+// rejects unsorted directories) and match the listing from the dump.
+func TestDumpPathListingMatchesNAR(t *testing.T) {
+	t.Parallel()
+
+	tmp := t.TempDir()
+	makeMixedTree(t, tmp)
+
+	var nar bytes.Buffer
+
+	listing, err := client.DumpPathWithListing(&nar, tmp)
+	if err != nil {
+		t.Fatalf("DumpPathWithListing: %v", err)
+	}
+
+	parsed, err := client.ListNAR(&nar)
+	if err != nil {
+		t.Fatalf("ListNAR: %v", err)
+	}
+
+	if !reflect.DeepEqual(parsed, listing) {
+		t.Fatal("listing parsed from the NAR differs from the dump's listing")
+	}
+}
@@ -3,7 +3,7 @@ package client
 import (
 	"bytes"
 	"context"
-	"errors"
+	"encoding/base64"
 	"fmt"
 	"io"
 	"log/slog"
@@ -31,129 +31,223 @@ var zstdEncoderPool = sync.Pool{ //nolint:gochecknoglobals // sync.Pool should b
 // compressAndSimpleUploadNAR uploads a small NAR with a single presigned PUT.
 // The compressed NAR is stored as opaque bytes with no Content-Encoding (like multipart part upload);
 // nix-daemon decompresses it per the narinfo Compression field.
-func (c *Client) compressAndSimpleUploadNAR(ctx context.Context, storePath, presignedURL, objectKey string) (*NarListing, error) {
-	encoder, ok := zstdEncoderPool.Get().(*zstd.Encoder)
-	if !ok {
-		return nil, errors.New("failed to get zstd encoder from pool")
-	}
-	defer zstdEncoderPool.Put(encoder)
-
+func (c *Client) compressAndSimpleUploadNAR(ctx context.Context, pathInfo *PathInfo, obj PendingObject, objectKey string) (*NarListing, *FileDigest, error) {
 	var buf bytes.Buffer
 
-	encoder.Reset(&buf)
+	fileWriter := newFileDigestWriter(&buf)
+
+	releaseSlot, err := c.acquireCompressionSlot(ctx)
+	if err != nil {
+		return nil, nil, err
+	}
+	defer releaseSlot()
+
+	encoder, release, err := newNARCompressor(pathInfo.narCompression(c.Compression), c.CompressionLevel, c.ZstdWindowLog, c.narCompressionWorkers(pathInfo.NarSize), fileWriter)
+	if err != nil {
+		return nil, nil, err
+	}
+	defer release()
 
-	listing, err := DumpPathWithListing(encoder, storePath)
+	listing, digest, err := c.dumpNAR(ctx, encoder, pathInfo.Path)
 	if err != nil {
-		return nil, fmt.Errorf("serializing NAR: %w", err)
+		return nil, nil, fmt.Errorf("serializing NAR: %w", err)
 	}
 
 	if err := encoder.Close(); err != nil {
-		return nil, fmt.Errorf("closing zstd encoder: %w", err)
+		return nil, nil, fmt.Errorf("closing %s encoder: %w", pathInfo.narCompression(c.Compression), err)
 	}
 
-	if err := c.UploadBytesToPresignedURLWithHeaders(ctx, presignedURL, buf.Bytes(), nil); err != nil {
-		return nil, fmt.Errorf("uploading NAR %s: %w", objectKey, err)
+	// The NAR is fully compressed in memory; let another path compress
+	// while this one uploads.
+	releaseSlot()
+
+	c.keepBytes(objectKey, buf.Bytes())
+
+	// Refuse to upload a NAR that disagrees with what nix registered.
+	if err := digest.Check(pathInfo.NarHash.String(), pathInfo.NarSize); err != nil {
+		return nil, nil, fmt.Errorf("verifying NAR for %s: %w", pathInfo.Path, err)
+	}
+
+	fileDigest := fileWriter.Digest()
+
+	headers, err := narChecksumHeaders(c.ChecksumUploads, fileDigest, objectKey)
+	if err != nil {
+		return nil, nil, err
 	}
 
-	return listing, nil
+	err = c.withPresignedURL(ctx, objectKey, obj, func(presignedURL string) error {
+		return c.UploadBytesToPresignedURLWithHeaders(ctx, presignedURL, buf.Bytes(), headers)
+	})
+	if err != nil {
+		return nil, nil, fmt.Errorf("uploading NAR %s: %w", objectKey, err)
+	}
+
+	return listing, fileDigest, nil
+}
+
+// narChecksumHeaders returns the x-amz-checksum-sha256 header for a NAR
+// upload if enabled. The SHA-256 of the compressed NAR is already known as
+// its FileHash.
+func narChecksumHeaders(enabled bool, fileDigest *FileDigest, objectKey string) (map[string]string, error) {
+	if !enabled {
+		return nil, nil //nolint:nilnil // no headers to send
+	}
+
+	_, sum, err := DecodeNixHash(fileDigest.FileHash)
+	if err != nil {
+		return nil, fmt.Errorf("decoding FileHash of %s: %w", objectKey, err)
+	}
+
+	return map[string]string{"x-amz-checksum-sha256": base64.StdEncoding.EncodeToString(sum)}, nil
 }
 
 // CompressAndUploadNAR compresses a NAR and uploads it.
 // Small NARs are sent with a single presigned PUT, larger ones via multipart upload.
-// It also generates a directory listing during serialization.
-func (c *Client) CompressAndUploadNAR(ctx context.Context, storePath string, narSize uint64, obj PendingObject, objectKey string) (*NarListing, error) {
-	name := filepath.Base(storePath)
-	slog.Info(fmt.Sprintf("Uploading %s (%s)", name, formatBytes(narSize)))
+// It also generates a directory listing during serialization. The serialized
+// NAR is hashed on the fly and checked against pathInfo's NarHash/NarSize
+// before the upload is finalized. The returned FileDigest describes the
+// compressed bytes as stored. With a NARCache, the NAR is compressed into it
+// once and uploaded from there.
+func (c *Client) CompressAndUploadNAR(ctx context.Context, pathInfo *PathInfo, obj PendingObject, objectKey string) (*NarListing, *FileDigest, error) {
+	name := filepath.Base(pathInfo.Path)
+	slog.Info(fmt.Sprintf("Uploading %s (%s)", name, FormatBytes(pathInfo.NarSize)))
 
 	var (
-		listing *NarListing
-		err     error
+		listing    *NarListing
+		fileDigest *FileDigest
+		err        error
 	)
 
-	if obj.MultipartInfo != nil {
-		listing, err = c.compressAndMultipartUploadNAR(ctx, storePath, narSize, obj.MultipartInfo, objectKey)
-	} else {
-		listing, err = c.compressAndSimpleUploadNAR(ctx, storePath, obj.PresignedURL, objectKey)
+	switch {
+	case c.NARCache != nil:
+		listing, fileDigest, err = c.uploadCachedNAR(ctx, pathInfo, obj, objectKey)
+	case obj.MultipartInfo != nil:
+		listing, fileDigest, err = c.compressAndMultipartUploadNAR(ctx, pathInfo, obj.MultipartInfo, objectKey)
+	default:
+		listing, fileDigest, err = c.compressAndSimpleUploadNAR(ctx, pathInfo, obj, objectKey)
 	}
 
 	if err != nil {
-		return nil, err
+		return nil, nil, err
 	}
 
-	slog.Debug("Uploaded NAR", "object_key", objectKey)
+	slog.Debug("Uploaded NAR", "object_key", objectKey, "nar_size", pathInfo.NarSize, "file_size", fileDigest.FileSize,
+		"compression_ratio", compressionRatio(pathInfo.NarSize, fileDigest.FileSize))
 
-	return listing, nil
+	return listing, fileDigest, nil
 }
 
 // compressAndMultipartUploadNAR streams a compressed NAR through a multipart upload.
-func (c *Client) compressAndMultipartUploadNAR(ctx context.Context, storePath string, narSize uint64, multipartInfo *MultipartUploadInfo, objectKey string) (*NarListing, error) {
+func (c *Client) compressAndMultipartUploadNAR(ctx context.Context, pathInfo *PathInfo, multipartInfo *MultipartUploadInfo, objectKey string) (*NarListing, *FileDigest, error) {
+	// Compression feeds the upload through a pipe, so the slot is held for
+	// the whole upload.
+	releaseSlot, err := c.acquireCompressionSlot(ctx)
+	if err != nil {
+		return nil, nil, err
+	}
+
 	// Create a pipe for streaming: NAR serialization -> zstd compression -> hash/size tracking
 	pr, pw := io.Pipe()
 
-	// Channels to receive errors and listing from the compression goroutine
-	errChan := make(chan error, 1)
-	listingChan := make(chan *NarListing, 1)
+	var out io.Writer = pw
+
+	kept := c.keepFile(objectKey)
+	if kept != nil {
+		out = io.MultiWriter(pw, kept)
+	}
+
+	// The compression goroutine owns the digest writer and hands back the
+	// digest with its result once the encoder is closed.
+	resultChan := make(chan narStreamResult, 1)
 
-	// Start compression in goroutine
 	go func() {
+		defer releaseSlot()
+		defer closeKeptFile(kept)
+
 		defer func() {
 			if err := pw.Close(); err != nil {
 				slog.Error("Failed to close pipe writer", "error", err)
 			}
 		}()
 
-		// Get encoder from pool and reset it to write to pipe
-		encoder, ok := zstdEncoderPool.Get().(*zstd.Encoder)
-		if !ok {
-			pw.CloseWithError(errors.New("failed to get zstd encoder from pool"))
+		listing, fileDigest, err := c.compressNARTo(ctx, pathInfo, out)
+		if err != nil {
+			// Fail the pipe before EOF so the multipart upload is never
+			// completed with a NAR that was cut short or disagrees with nix.
+			pw.CloseWithError(err)
+		}
 
-			errChan <- errors.New("failed to get zstd encoder from pool")
+		resultChan <- narStreamResult{listing: listing, fileDigest: fileDigest, err: err}
+	}()
 
-			listingChan <- nil
+	err = c.uploadMultipart(ctx, pr, multipartInfo, objectKey, partSizeForNAR(pathInfo.NarSize))
+	// If upload failed, signal compressor to stop and wait for it to exit
+	if err != nil {
+		_ = pw.CloseWithError(err)
 
-			return
-		}
-		defer zstdEncoderPool.Put(encoder)
+		<-resultChan // drain to prevent goroutine leak
 
-		encoder.Reset(pw)
+		return nil, nil, err
+	}
 
-		defer func() {
-			if err := encoder.Close(); err != nil {
-				slog.Error("Failed to close zstd encoder", "error", err)
-			}
-		}()
+	// Check for compression errors
+	result := <-resultChan
+	if result.err != nil {
+		return nil, nil, result.err
+	}
 
-		// Serialize NAR with listing directly to the compressed stream
-		listing, err := DumpPathWithListing(encoder, storePath)
-		if err != nil {
-			pw.CloseWithError(fmt.Errorf("serializing NAR: %w", err))
+	return result.listing, result.fileDigest, nil
+}
+
+// narStreamResult is what the goroutine compressing a streamed NAR reports
+// when it is done.
+type narStreamResult struct {
+	listing    *NarListing
+	fileDigest *FileDigest
+	err        error
+}
+
+// compressNARTo serializes and compresses pathInfo's NAR into out, checks it
+// against the NarHash/NarSize nix reported, and returns its listing and the
+// digest of the compressed bytes written to out.
+func (c *Client) compressNARTo(ctx context.Context, pathInfo *PathInfo, out io.Writer) (*NarListing, *FileDigest, error) {
+	fileWriter := newFileDigestWriter(out)
 
-			errChan <- err
+	// Get encoder (pooled for zstd) writing through the digest writer
+	encoder, release, err := newNARCompressor(pathInfo.narCompression(c.Compression), c.CompressionLevel, c.ZstdWindowLog, c.narCompressionWorkers(pathInfo.NarSize), fileWriter)
+	if err != nil {
+		return nil, nil, err
+	}
+	defer release()
 
-			listingChan <- nil
+	encoderClosed := false
 
+	defer func() {
+		if encoderClosed {
 			return
 		}
 
-		errChan <- nil
-
-		listingChan <- listing
+		if err := encoder.Close(); err != nil {
+			slog.Error("Failed to close NAR encoder", "compression", pathInfo.narCompression(c.Compression), "error", err)
+		}
 	}()
 
-	err := c.uploadMultipart(ctx, pr, multipartInfo, objectKey, partSizeForNAR(narSize))
-	// If upload failed, signal compressor to stop and wait for it to exit
+	// Serialize NAR with listing directly to the compressed stream
+	listing, digest, err := c.dumpNAR(ctx, encoder, pathInfo.Path)
 	if err != nil {
-		_ = pw.CloseWithError(err)
-
-		<-errChan // drain to prevent goroutine leak
+		return nil, nil, fmt.Errorf("serializing NAR: %w", err)
+	}
 
-		return nil, err
+	if err := digest.Check(pathInfo.NarHash.String(), pathInfo.NarSize); err != nil {
+		return nil, nil, fmt.Errorf("verifying NAR for %s: %w", pathInfo.Path, err)
 	}
 
-	// Check for compression errors
-	if compressErr := <-errChan; compressErr != nil {
-		return nil, compressErr
+	// Flush the final frame so fileWriter has seen every byte.
+	encoderClosed = true
+
+	if err := encoder.Close(); err != nil {
+		return nil, nil, fmt.Errorf("closing %s encoder: %w", pathInfo.narCompression(c.Compression), err)
 	}
 
-	return <-listingChan, nil
+	return listing, fileWriter.Digest(), nil
 }
@@ -4,12 +4,65 @@ import (
 	"bytes"
 	"errors"
 	"fmt"
-	"sort"
+	"path"
+	"slices"
+	"strconv"
 	"strings"
 
 	"github.com/klauspost/compress/zstd"
 )
 
+// newNarinfoMetadata is the single place narinfo fields are derived from a
+// PathInfo. fileDigest may be nil for NARs that were already in the cache.
+func newNarinfoMetadata(pathInfo *PathInfo, compression Compression, fileDigest *FileDigest) (*NarinfoMetadata, error) {
+	// Convert NarHash to Nix32 format for the narinfo
+	narHash, err := ConvertHashToNix32(pathInfo.NarHash.String())
+	if err != nil {
+		return nil, fmt.Errorf("converting NAR hash for %s: %w", pathInfo.Path, err)
+	}
+
+	// Use NarHash-based key for URL (content-based deduplication)
+	narURL, err := getNARKey(pathInfo.NarHash.String(), compression)
+	if err != nil {
+		return nil, fmt.Errorf("getting NAR key for %s: %w", pathInfo.Path, err)
+	}
+
+	meta := &NarinfoMetadata{
+		StorePath:   pathInfo.Path,
+		URL:         narURL,
+		Compression: string(compression),
+		NarHash:     narHash,
+		NarSize:     pathInfo.NarSize,
+		References:  slices.Compact(slices.Sorted(slices.Values(pathInfo.References))),
+		Deriver:     knownDeriver(pathInfo.Deriver),
+		Signatures:  pathInfo.Signatures,
+	}
+
+	if fileDigest != nil {
+		// An uploaded NAR always has both; a narinfo with FileSize: 0 or
+		// an empty FileHash is rejected by Nix, so refuse to write it.
+		if fileDigest.FileHash == "" || fileDigest.FileSize == 0 {
+			return nil, fmt.Errorf("missing compressed file hash or size for %s", pathInfo.Path)
+		}
+
+		meta.FileHash = fileDigest.FileHash
+		meta.FileSize = fileDigest.FileSize
+	}
+
+	if pathInfo.CA != nil {
+		if ca := pathInfo.CA.String(); ca != "" {
+			// Nix rejects a narinfo with a malformed CA field outright.
+			if err := validateContentAddress(ca); err != nil {
+				return nil, fmt.Errorf("invalid content address for %s: %w", pathInfo.Path, err)
+			}
+
+			meta.CA = &ca
+		}
+	}
+
+	return meta, nil
+}
+
 // generateNarinfoContent creates narinfo file content from metadata and signatures.
 // This is used after receiving signatures from the server to generate the final narinfo.
 func generateNarinfoContent(meta *NarinfoMetadata, signatures []string) string {
@@ -24,6 +77,12 @@ func generateNarinfoContent(meta *NarinfoMetadata, signatures []string) string {
 	// Compression
 	fmt.Fprintf(&sb, "Compression: %s\n", meta.Compression)
 
+	// Compressed file hash and size (only known for NARs uploaded in this run)
+	if meta.FileHash != "" {
+		fmt.Fprintf(&sb, "FileHash: %s\n", meta.FileHash)
+		fmt.Fprintf(&sb, "FileSize: %d\n", meta.FileSize)
+	}
+
 	// NAR hash and size (uncompressed)
 	fmt.Fprintf(&sb, "NarHash: %s\n", meta.NarHash)
 	fmt.Fprintf(&sb, "NarSize: %d\n", meta.NarSize)
@@ -31,15 +90,13 @@ func generateNarinfoContent(meta *NarinfoMetadata, signatures []string) string {
 	// References (must have space after colon, even if empty)
 	fmt.Fprint(&sb, "References:")
 
-	// Sort references for deterministic output
-	sortedRefs := make([]string, len(meta.References))
-	copy(sortedRefs, meta.References)
-	sort.Strings(sortedRefs)
+	// Sort and deduplicate references for deterministic output, in the
+	// order the signing fingerprint uses
+	sortedRefs := slices.Compact(slices.Sorted(slices.Values(meta.References)))
 
 	for _, ref := range sortedRefs {
-		// Remove /nix/store/ prefix
-		refName := strings.TrimPrefix(ref, "/nix/store/")
-		fmt.Fprintf(&sb, " %s", refName)
+		// Strip the store directory, whatever it is
+		fmt.Fprintf(&sb, " %s", path.Base(ref))
 	}
 
 	// Always add a space after "References:" even if empty
@@ -49,22 +106,24 @@ func generateNarinfoContent(meta *NarinfoMetadata, signatures []string) string {
 
 	fmt.Fprint(&sb, "\n")
 
-	// Deriver (optional)
-	if meta.Deriver != nil {
-		deriverName := strings.TrimPrefix(*meta.Deriver, "/nix/store/")
-		fmt.Fprintf(&sb, "Deriver: %s\n", deriverName)
+	// Deriver (optional; Nix omits it rather than writing unknown-deriver)
+	if deriver := knownDeriver(meta.Deriver); deriver != nil {
+		fmt.Fprintf(&sb, "Deriver: %s\n", path.Base(*deriver))
 	}
 
-	// Signatures (passed as parameter from signing process)
-	if len(signatures) > 0 {
-		// Sort signatures for deterministic output
-		sortedSigs := make([]string, len(signatures))
-		copy(sortedSigs, signatures)
-		sort.Strings(sortedSigs)
+	// System (optional)
+	if meta.System != "" {
+		fmt.Fprintf(&sb, "System: %s\n", meta.System)
+	}
 
-		for _, sig := range sortedSigs {
-			fmt.Fprintf(&sb, "Sig: %s\n", sig)
-		}
+	// Signatures: the ones Nix already holds for the path (meta.Signatures)
+	// plus those from the signing process, one Sig line each. Sorted and
+	// deduplicated for deterministic output.
+	sortedSigs := slices.Concat(meta.Signatures, signatures)
+	slices.Sort(sortedSigs)
+
+	for _, sig := range slices.Compact(sortedSigs) {
+		fmt.Fprintf(&sb, "Sig: %s\n", sig)
 	}
 
 	// CA (optional)
@@ -75,6 +134,102 @@ func generateNarinfoContent(meta *NarinfoMetadata, signatures []string) string {
 	return sb.String()
 }
 
+// ParseNarinfo parses narinfo content as served by a binary cache. References
+// and Deriver are expanded to full store paths using StorePath's directory,
+// and every Sig line is collected into Signatures. Unknown fields are ignored
+// like Nix does; StorePath, URL, NarHash and NarSize are required.
+func ParseNarinfo(content string) (*NarinfoMetadata, error) {
+	meta := &NarinfoMetadata{}
+
+	var (
+		refNames []string
+		deriver  string
+		haveSize bool
+	)
+
+	for lineNo, line := range strings.Split(content, "\n") {
+		if line == "" {
+			continue
+		}
+
+		key, value, ok := strings.Cut(line, ": ")
+		if !ok {
+			// "References:" may be written without a trailing space
+			if line != "References:" {
+				return nil, fmt.Errorf("narinfo line %d: missing ': ' separator: %q", lineNo+1, line)
+			}
+
+			continue
+		}
+
+		var err error
+
+		switch key {
+		case "StorePath":
+			meta.StorePath = value
+		case "URL":
+			meta.URL = value
+		case "Compression":
+			meta.Compression = value
+		case "FileHash":
+			meta.FileHash = value
+		case "FileSize":
+			meta.FileSize, err = strconv.ParseUint(value, 10, 64)
+		case "NarHash":
+			meta.NarHash = value
+		case "NarSize":
+			meta.NarSize, err = strconv.ParseUint(value, 10, 64)
+			haveSize = true
+		case "References":
+			refNames = strings.Fields(value)
+		case "Deriver":
+			if value != "unknown-deriver" {
+				deriver = value
+			}
+		case "System":
+			meta.System = value
+		case "Sig":
+			meta.Signatures = append(meta.Signatures, value)
+		case "CA":
+			meta.CA = &value
+		}
+
+		if err != nil {
+			return nil, fmt.Errorf("narinfo line %d: invalid %s: %w", lineNo+1, key, err)
+		}
+	}
+
+	switch {
+	case meta.StorePath == "":
+		return nil, errors.New("narinfo is missing StorePath")
+	case meta.URL == "":
+		return nil, errors.New("narinfo is missing URL")
+	case meta.NarHash == "":
+		return nil, errors.New("narinfo is missing NarHash")
+	case !haveSize:
+		return nil, errors.New("narinfo is missing NarSize")
+	}
+
+	// Nix defaults to bzip2 when the field is absent
+	if meta.Compression == "" {
+		meta.Compression = "bzip2"
+	}
+
+	storeDir := path.Dir(meta.StorePath)
+
+	meta.References = make([]string, 0, len(refNames))
+	for _, name := range refNames {
+		meta.References = append(meta.References, storeDir+"/"+name)
+	}
+
+	if deriver != "" {
+		deriverPath := storeDir + "/" + deriver
+		meta.Deriver = &deriverPath
+	}
+
+	return meta, nil
+}
+
 // CompressNarinfo compresses narinfo content using zstd with encoder pooling.
 func CompressNarinfo(content string) ([]byte, error) {
 	var buf bytes.Buffer
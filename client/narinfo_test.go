@@ -0,0 +1,485 @@
+package client_test
+
+import (
+	"bytes"
+	"context"
+	"encoding/json"
+	"os"
+	"path/filepath"
+	"slices"
+	"strings"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+	"github.com/Mic92/niks3/server/signing"
+)
+
+func TestFileDigestIsNix32(t *testing.T) {
+	t.Parallel()
+
+	var buf bytes.Buffer
+
+	w := client.NewFileDigestWriter(&buf)
+	if _, err := w.Write([]byte("hello world")); err != nil {
+		t.Fatal(err)
+	}
+
+	digest := w.Digest()
+
+	hashPart, ok := strings.CutPrefix(digest.FileHash, "sha256:")
+	if !ok {
+		t.Fatalf("FileHash %q lacks sha256: prefix", digest.FileHash)
+	}
+
+	// nix-base32 of a sha256 digest is always 52 characters; base64 would be 44.
+	if len(hashPart) != 52 {
+		t.Errorf("FileHash should be 52 nix32 chars, got %d: %q", len(hashPart), hashPart)
+	}
+
+	if hashPart != "1sfdxziarxw8j3p80lvswgpq9i7smdyxmmsj5sjhhgjdjfwjfkdr" {
+		t.Errorf("unexpected FileHash %q", digest.FileHash)
+	}
+
+	if digest.FileSize != uint64(len("hello world")) || buf.String() != "hello world" {
+		t.Errorf("FileSize = %d, buffer = %q", digest.FileSize, buf.String())
+	}
+}
+
+func TestGenerateNarinfoContentFileHash(t *testing.T) {
+	t.Parallel()
+
+	meta := &client.NarinfoMetadata{
+		StorePath:   "/nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1",
+		URL:         "nar/1mkvday29m2qxg1fnbv8xh9s6151bh8a2xzhh0k86j7lqhyfwibh.nar.zst",
+		Compression: "zstd",
+		FileHash:    "sha256:1sfdxziarxw8j3p80lvswgpq9i7smdyxmmsj5sjhhgjdjfwjfkdr",
+		FileSize:    11,
+		NarHash:     "sha256:1mkvday29m2qxg1fnbv8xh9s6151bh8a2xzhh0k86j7lqhyfwibh",
+		NarSize:     226560,
+	}
+
+	content := client.GenerateNarinfoContent(meta, nil)
+
+	if !strings.Contains(content, "Compression: zstd\nFileHash: sha256:1sfdxziarxw8j3p80lvswgpq9i7smdyxmmsj5sjhhgjdjfwjfkdr\nFileSize: 11\nNarHash:") {
+		t.Errorf("FileHash/FileSize missing or misplaced:\n%s", content)
+	}
+
+	meta.FileHash = ""
+
+	if content := client.GenerateNarinfoContent(meta, nil); strings.Contains(content, "FileHash") || strings.Contains(content, "FileSize") {
+		t.Errorf("deduplicated NAR should omit FileHash/FileSize:\n%s", content)
+	}
+}
+
+func TestNarinfoMetadataRoundTrip(t *testing.T) {
+	t.Parallel()
+
+	// A non-default store directory must not leak into References/Deriver.
+	pathInfos, err := client.ParsePathInfoJSON([]byte(`{
+		"/custom/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1": {
+			"narHash": "sha256-FePFYIlMuycIXPZbWi7LGEiMmZSX9FMbaQenWBzm1Sc=",
+			"narSize": 226560,
+			"references": [
+				"/custom/store/sl141d1g77wvhr050ah87lcyz2czdxa3-glibc-2.40-36",
+				"/custom/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1"
+			],
+			"deriver": "/custom/store/8ha1dhmx807czjczmwy078s4r9s254il-hello-2.12.1.drv",
+			"signatures": ["cache.example.com-1:sig"],
+			"ca": "fixed:r:sha256:1mkvday29m2qxg1fnbv8xh9s6151bh8a2xzhh0k86j7lqhyfwibh"
+		}
+	}`))
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	pathInfo := pathInfos["/custom/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1"]
+	if pathInfo == nil {
+		t.Fatal("path info not parsed")
+	}
+
+	meta, err := client.NewNarinfoMetadata(pathInfo, client.CompressionZstd, nil)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	content := client.GenerateNarinfoContent(meta, []string{"cache.example.com-2:b", "cache.example.com-1:a"})
+
+	for _, want := range []string{
+		"StorePath: /custom/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1\n",
+		"Compression: zstd\n",
+		"NarSize: 226560\n",
+		"References: 26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1 sl141d1g77wvhr050ah87lcyz2czdxa3-glibc-2.40-36\n",
+		"Deriver: 8ha1dhmx807czjczmwy078s4r9s254il-hello-2.12.1.drv\n",
+		// Signatures Nix already had are kept next to the new ones
+		"Sig: cache.example.com-1:a\nSig: cache.example.com-1:sig\nSig: cache.example.com-2:b\n",
+		"CA: fixed:r:sha256:1mkvday29m2qxg1fnbv8xh9s6151bh8a2xzhh0k86j7lqhyfwibh\n",
+	} {
+		if !strings.Contains(content, want) {
+			t.Errorf("narinfo missing %q:\n%s", want, content)
+		}
+	}
+
+	if !strings.HasPrefix(meta.NarHash, "sha256:") || len(meta.NarHash) != len("sha256:")+52 {
+		t.Errorf("NarHash not in nix32 format: %q", meta.NarHash)
+	}
+
+	if !strings.HasSuffix(meta.URL, ".nar.zst") {
+		t.Errorf("URL should match compression, got %q", meta.URL)
+	}
+
+	// A NAR uploaded without a recorded digest must not produce a narinfo
+	// with FileSize: 0 and an empty FileHash.
+	_, err = client.NewNarinfoMetadata(pathInfo, client.CompressionZstd, &client.FileDigest{})
+	if err == nil || !strings.Contains(err.Error(), pathInfo.Path) {
+		t.Errorf("expected an error naming %s, got %v", pathInfo.Path, err)
+	}
+}
+
+func TestParseNarinfoRoundTrip(t *testing.T) {
+	t.Parallel()
+
+	deriver := "/nix/store/8ha1dhmx807czjczmwy078s4r9s254il-hello-2.12.1.drv"
+	ca := "fixed:r:sha256:1mkvday29m2qxg1fnbv8xh9s6151bh8a2xzhh0k86j7lqhyfwibh"
+
+	meta := &client.NarinfoMetadata{
+		StorePath:   "/nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1",
+		URL:         "nar/1mkvday29m2qxg1fnbv8xh9s6151bh8a2xzhh0k86j7lqhyfwibh.nar.zst",
+		Compression: "zstd",
+		FileHash:    "sha256:1sfdxziarxw8j3p80lvswgpq9i7smdyxmmsj5sjhhgjdjfwjfkdr",
+		FileSize:    11,
+		NarHash:     "sha256:1mkvday29m2qxg1fnbv8xh9s6151bh8a2xzhh0k86j7lqhyfwibh",
+		NarSize:     226560,
+		References: []string{
+			"/nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1",
+			"/nix/store/sl141d1g77wvhr050ah87lcyz2czdxa3-glibc-2.40-36",
+		},
+		Deriver: &deriver,
+		System:  "x86_64-linux",
+		CA:      &ca,
+	}
+	sigs := []string{"cache.example.com-1:a", "cache.example.com-2:b"}
+
+	parsed, err := client.ParseNarinfo(client.GenerateNarinfoContent(meta, sigs))
+	if err != nil {
+		t.Fatalf("ParseNarinfo: %v", err)
+	}
+
+	if parsed.StorePath != meta.StorePath || parsed.URL != meta.URL || parsed.Compression != meta.Compression ||
+		parsed.FileHash != meta.FileHash || parsed.FileSize != meta.FileSize ||
+		parsed.NarHash != meta.NarHash || parsed.NarSize != meta.NarSize || parsed.System != meta.System {
+		t.Errorf("scalar fields differ after round trip:\n got %+v\nwant %+v", parsed, meta)
+	}
+
+	if strings.Join(parsed.References, ",") != strings.Join(meta.References, ",") {
+		t.Errorf("References = %v, want %v", parsed.References, meta.References)
+	}
+
+	if parsed.Deriver == nil || *parsed.Deriver != deriver {
+		t.Errorf("Deriver = %v, want %q", parsed.Deriver, deriver)
+	}
+
+	if parsed.CA == nil || *parsed.CA != ca {
+		t.Errorf("CA = %v, want %q", parsed.CA, ca)
+	}
+
+	if strings.Join(parsed.Signatures, ",") != strings.Join(sigs, ",") {
+		t.Errorf("Signatures = %v, want %v", parsed.Signatures, sigs)
+	}
+}
+
+func TestParseNarinfoErrors(t *testing.T) {
+	t.Parallel()
+
+	valid := "StorePath: /nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1\n" +
+		"URL: nar/x.nar\n" +
+		"NarHash: sha256:1mkvday29m2qxg1fnbv8xh9s6151bh8a2xzhh0k86j7lqhyfwibh\n" +
+		"NarSize: 1\n" +
+		"References: \n"
+
+	meta, err := client.ParseNarinfo(valid)
+	if err != nil {
+		t.Fatalf("ParseNarinfo(minimal): %v", err)
+	}
+
+	if meta.Compression != "bzip2" || len(meta.References) != 0 {
+		t.Errorf("unexpected defaults: compression %q, references %v", meta.Compression, meta.References)
+	}
+
+	for _, field := range []string{"StorePath", "URL", "NarHash", "NarSize"} {
+		var kept []string
+
+		for line := range strings.SplitSeq(valid, "\n") {
+			if !strings.HasPrefix(line, field+":") {
+				kept = append(kept, line)
+			}
+		}
+
+		if _, err := client.ParseNarinfo(strings.Join(kept, "\n")); err == nil {
+			t.Errorf("expected error when %s is missing", field)
+		}
+	}
+
+	if _, err := client.ParseNarinfo(valid + "NarSize: many\n"); err == nil {
+		t.Error("expected error for non-numeric NarSize")
+	}
+}
+
+func TestGenerateNarinfoContentMultipleSigs(t *testing.T) {
+	t.Parallel()
+
+	meta := &client.NarinfoMetadata{
+		StorePath:   "/nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1",
+		URL:         "nar/x.nar.zst",
+		Compression: "zstd",
+		NarHash:     "sha256:1mkvday29m2qxg1fnbv8xh9s6151bh8a2xzhh0k86j7lqhyfwibh",
+		NarSize:     1,
+		Signatures:  []string{"cache.nixos.org-1:upstream", "niks3-1:dup"},
+	}
+
+	content := client.GenerateNarinfoContent(meta, []string{"niks3-1:dup", "niks3-2:new"})
+
+	want := "Sig: cache.nixos.org-1:upstream\nSig: niks3-1:dup\nSig: niks3-2:new\n"
+	if !strings.Contains(content, want) {
+		t.Errorf("expected one sorted Sig line per distinct signature:\n%s", content)
+	}
+
+	if n := strings.Count(content, "Sig: "); n != 3 {
+		t.Errorf("expected 3 Sig lines, got %d", n)
+	}
+}
+
+func TestInspectPath(t *testing.T) {
+	t.Parallel()
+
+	const storePath = "/nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1"
+
+	dump := filepath.Join(t.TempDir(), "path-info.json")
+	if err := os.WriteFile(dump, []byte(`{
+		"`+storePath+`": {
+			"narHash": "sha256-FePFYIlMuycIXPZbWi7LGEiMmZSX9FMbaQenWBzm1Sc=",
+			"narSize": 226560,
+			"references": ["`+storePath+`"],
+			"deriver": "/nix/store/8ha1dhmx807czjczmwy078s4r9s254il-hello-2.12.1.drv"
+		}
+	}`), 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	c := client.NewTestClientWithStoreDir("/nix/store")
+	c.PathInfoFile = dump
+	c.Compression = client.CompressionZstd
+
+	report, err := c.InspectPath(context.Background(), storePath)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if report.Hash != "26xbg1ndr7hbcncrlf9nhx5is2b25d13" || report.NarSize != 226560 {
+		t.Errorf("unexpected report: %+v", report)
+	}
+
+	if !strings.HasPrefix(report.NarKey, "nar/") || !strings.HasSuffix(report.NarKey, ".nar.zst") {
+		t.Errorf("NarKey = %q", report.NarKey)
+	}
+
+	for _, line := range []string{
+		"StorePath: " + storePath + "\n",
+		"URL: " + report.NarKey + "\n",
+		"NarHash: " + report.NarHash + "\n",
+		"References: 26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1\n",
+		"Deriver: 8ha1dhmx807czjczmwy078s4r9s254il-hello-2.12.1.drv\n",
+	} {
+		if !strings.Contains(report.Narinfo, line) {
+			t.Errorf("narinfo lacks %q:\n%s", line, report.Narinfo)
+		}
+	}
+
+	if strings.Contains(report.Narinfo, "FileHash") {
+		t.Errorf("narinfo has FileHash before compression:\n%s", report.Narinfo)
+	}
+}
+
+func TestNarinfoReferencesDeduplicated(t *testing.T) {
+	t.Parallel()
+
+	const (
+		hello = "/nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1"
+		glibc = "/nix/store/sl141d1g77wvhr050ah87lcyz2czdxa3-glibc-2.40-36"
+	)
+
+	pathInfos, err := client.ParsePathInfoJSON([]byte(`{
+		"` + hello + `": {
+			"narHash": "sha256-FePFYIlMuycIXPZbWi7LGEiMmZSX9FMbaQenWBzm1Sc=",
+			"narSize": 226560,
+			"references": ["` + hello + `", "` + glibc + `", "` + glibc + `"]
+		}
+	}`))
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	meta, err := client.NewNarinfoMetadata(pathInfos[hello], client.CompressionZstd, nil)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	// Sorted as in the signing fingerprint; the self-reference stays, as Nix
+	// lists it too.
+	if want := []string{hello, glibc}; !slices.Equal(meta.References, want) {
+		t.Errorf("References = %v, want %v", meta.References, want)
+	}
+
+	content := client.GenerateNarinfoContent(meta, nil)
+	if !strings.Contains(content, "References: 26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1 sl141d1g77wvhr050ah87lcyz2czdxa3-glibc-2.40-36\n") {
+		t.Errorf("unexpected References line:\n%s", content)
+	}
+
+	result, err := client.PrepareClosures(context.Background(), []string{hello}, pathInfos, nil, client.CompressionZstd, true)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	for _, obj := range result.Closures[0].Objects {
+		if obj.Type != client.ObjectTypeNarinfo {
+			continue
+		}
+
+		// GC refs: glibc's narinfo once, no self-reference
+		var narinfoRefs []string
+
+		for _, ref := range obj.Refs {
+			if strings.HasSuffix(ref, ".narinfo") {
+				narinfoRefs = append(narinfoRefs, ref)
+			}
+		}
+
+		if want := []string{"sl141d1g77wvhr050ah87lcyz2czdxa3.narinfo"}; !slices.Equal(narinfoRefs, want) {
+			t.Errorf("narinfo object refs = %v, want %v", narinfoRefs, want)
+		}
+	}
+}
+
+// TestNarinfoContentAddress pushes the content-addressed paths of
+// testdata/path-info-ca.json through narinfo generation: each must carry a
+// CA line in nix32 form and a signature that verifies over the parsed
+// narinfo, as the CA field is not part of the fingerprint.
+func TestNarinfoContentAddress(t *testing.T) {
+	t.Parallel()
+
+	pathInfos, err := client.LoadPathInfoFile(filepath.Join("testdata", "path-info-ca.json"))
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	key, err := signing.GenerateKey("cache.example.com-1", nil)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	publicKeyString, err := key.PublicKey()
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	publicKey, err := signing.ParsePublicKey(publicKeyString)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	wantCA := map[string]string{
+		"/nix/store/0ndc7w3hb1hj2qr4j1d0wsz6mlsr7lxv-hello-2.12.1.drv":    "text:sha256:1llq1f8p8n1sxm775wxv9gjky6ar5xa0kn9i2r5z2z5928hzz9rl",
+		"/nix/store/3vwm8qz0s9mjy1lbxjc5r1lf2jjbg9ap-hello-2.12.1.tar.gz": "fixed:sha512:13q9cgih0lj1wmaxfv9vhzgvac6hcywq6jym0d70jxiqwq2p6brhwry1664w9hlml3y8ykczbnxrmm2k4lmcsm2idzngmal0jbwrija",
+		"/nix/store/1b9p07z77phvv2hf6gm9f28syh6ym98a-hello-src":          "fixed:r:sha256:0gsyc3g0w8wacg97wwm1iirigsl96k36iijdxiadn8cqcjmx18y1",
+	}
+
+	if len(pathInfos) != len(wantCA) {
+		t.Fatalf("fixture has %d paths, want %d", len(pathInfos), len(wantCA))
+	}
+
+	for storePath, ca := range wantCA {
+		meta, err := client.NewNarinfoMetadata(pathInfos[storePath], client.CompressionZstd, nil)
+		if err != nil {
+			t.Fatalf("%s: %v", storePath, err)
+		}
+
+		sigs, err := signing.SignNarinfo([]*signing.Key{key}, &signing.NarInfo{
+			StorePath:  meta.StorePath,
+			NarHash:    meta.NarHash,
+			NarSize:    meta.NarSize,
+			References: meta.References,
+		})
+		if err != nil {
+			t.Fatalf("%s: signing: %v", storePath, err)
+		}
+
+		content := client.GenerateNarinfoContent(meta, sigs)
+		if !strings.Contains(content, "CA: "+ca+"\n") {
+			t.Errorf("%s: narinfo lacks CA %s:\n%s", storePath, ca, content)
+		}
+
+		parsed, err := client.ParseNarinfo(content)
+		if err != nil {
+			t.Fatalf("%s: %v", storePath, err)
+		}
+
+		ok, err := signing.VerifyNarinfo([]*signing.PublicKey{publicKey}, &signing.NarInfo{
+			StorePath:  parsed.StorePath,
+			NarHash:    parsed.NarHash,
+			NarSize:    parsed.NarSize,
+			References: parsed.References,
+		}, parsed.Signatures)
+		if err != nil || !ok {
+			t.Errorf("%s: signature does not verify (err %v)", storePath, err)
+		}
+	}
+}
+
+func TestNarinfoRejectsMalformedContentAddress(t *testing.T) {
+	t.Parallel()
+
+	pathInfos, err := client.ParsePathInfoJSON([]byte(`{
+		"/nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1": {
+			"narHash": "sha256-FePFYIlMuycIXPZbWi7LGEiMmZSX9FMbaQenWBzm1Sc=",
+			"narSize": 226560,
+			"references": [],
+			"ca": "fixed:r:sha256:1abc"
+		}
+	}`))
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	_, err = client.NewNarinfoMetadata(pathInfos["/nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1"], client.CompressionZstd, nil)
+	if err == nil || !strings.Contains(err.Error(), "invalid content address") {
+		t.Fatalf("expected an invalid content address error, got %v", err)
+	}
+}
+
+func TestNarinfoOmitsUnknownDeriver(t *testing.T) {
+	t.Parallel()
+
+	const storePath = "/nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1"
+
+	for _, deriver := range []string{"", "unknown-deriver", "/nix/store/unknown-deriver"} {
+		pathInfo := &client.PathInfo{Path: storePath, NarSize: 226560, Deriver: &deriver}
+		if err := json.Unmarshal([]byte(`"sha256-FePFYIlMuycIXPZbWi7LGEiMmZSX9FMbaQenWBzm1Sc="`), &pathInfo.NarHash); err != nil {
+			t.Fatal(err)
+		}
+
+		meta, err := client.NewNarinfoMetadata(pathInfo, client.CompressionZstd, nil)
+		if err != nil {
+			t.Fatal(err)
+		}
+
+		if meta.Deriver != nil {
+			t.Errorf("deriver %q: metadata keeps Deriver %q", deriver, *meta.Deriver)
+		}
+
+		// Metadata built elsewhere, e.g. by ParseNarinfo, is filtered too.
+		meta.Deriver = &deriver
+
+		if content := client.GenerateNarinfoContent(meta, nil); strings.Contains(content, "Deriver:") {
+			t.Errorf("deriver %q: narinfo has a Deriver line:\n%s", deriver, content)
+		}
+	}
+}
@@ -0,0 +1,281 @@
+package client
+
+import (
+	"bufio"
+	"bytes"
+	"context"
+	"encoding/base64"
+	"encoding/hex"
+	"errors"
+	"fmt"
+	"log/slog"
+	"os"
+	"os/exec"
+	"slices"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+)
+
+// nixCLI is what the installed Nix offers. Nix before 2.4 has no unified
+// `nix` command (or no --extra-experimental-features to enable it), so
+// path info and NARs are read with nix-store instead.
+type nixCLI struct {
+	version string // e.g. "2.18.1"; empty if nix is not on PATH
+	unified bool   // `nix --extra-experimental-features nix-command` works
+}
+
+// detectNixCLI inspects the nix on PATH once per process.
+var detectNixCLI = sync.OnceValue(func() nixCLI { //nolint:gochecknoglobals // detection result cached for the process lifetime
+	if _, err := exec.LookPath("nix"); err != nil {
+		slog.Debug("nix not found on PATH, using nix-store")
+
+		return nixCLI{}
+	}
+
+	ctx, cancel := context.WithTimeout(context.Background(), 30*time.Second)
+	defer cancel()
+
+	output, err := exec.CommandContext(ctx, "nix", "--version").Output()
+	if err != nil {
+		slog.Debug("nix --version failed, using nix-store", "error", err)
+
+		return nixCLI{}
+	}
+
+	cli := parseNixVersion(string(output))
+	slog.Debug("Detected nix", "version", cli.version, "unified_cli", cli.unified)
+
+	return cli
+})
+
+// parseNixVersion parses `nix --version` output such as "nix (Nix) 2.18.1"
+// or "nix (Lix, like Nix) 2.91.1". An unrecognized version is assumed to
+// be recent.
+func parseNixVersion(output string) nixCLI {
+	fields := strings.Fields(output)
+	if len(fields) == 0 {
+		return nixCLI{unified: true}
+	}
+
+	version := fields[len(fields)-1]
+
+	parts := strings.SplitN(version, ".", 3)
+	if len(parts) < 2 {
+		return nixCLI{version: version, unified: true}
+	}
+
+	major, errMajor := strconv.Atoi(parts[0])
+	// Pre-releases look like "2.4pre20210601_5985b8b".
+	minorDigits, _, _ := strings.Cut(parts[1], "pre")
+	minor, errMinor := strconv.Atoi(minorDigits)
+
+	if errMajor != nil || errMinor != nil {
+		return nixCLI{version: version, unified: true}
+	}
+
+	return nixCLI{version: version, unified: major > 2 || (major == 2 && minor >= 4)}
+}
+
+// lookupNixEnv returns key from nixEnv if set, else from the process
+// environment, the same way commands started with nixEnv would see it.
+func lookupNixEnv(nixEnv []string, key string) string {
+	if len(nixEnv) == 0 {
+		return os.Getenv(key)
+	}
+
+	for _, env := range slices.Backward(nixEnv) {
+		if value, ok := strings.CutPrefix(env, key+"="); ok {
+			return value
+		}
+	}
+
+	return ""
+}
+
+// nixRemoteStore returns the store NIX_REMOTE selects if its paths cannot
+// be read from the local filesystem, e.g. "ssh-ng://builder" or
+// "local?root=/mnt". Nix commands honor NIX_REMOTE by themselves; this is
+// for the places that would otherwise read store paths directly.
+func nixRemoteStore(nixEnv []string) string {
+	remote := lookupNixEnv(nixEnv, "NIX_REMOTE")
+
+	switch {
+	case remote == "", remote == "auto", remote == "daemon", remote == "local", strings.HasPrefix(remote, "unix://"):
+		return ""
+	default:
+		return remote
+	}
+}
+
+// effectiveStore returns the store paths are read from: c.Store, else a
+// non-local NIX_REMOTE, else "" for the local store.
+func (c *Client) effectiveStore() string {
+	if c.Store != "" {
+		return c.Store
+	}
+
+	return nixRemoteStore(c.NixEnv)
+}
+
+// nixStoreCommand builds a nix-store invocation for Nix without the
+// unified CLI.
+func nixStoreCommand(ctx context.Context, nixEnv []string, storeURI string, args ...string) *exec.Cmd {
+	if storeURI != "" {
+		args = append([]string{"--store", storeURI}, args...)
+	}
+
+	cmd := exec.CommandContext(ctx, "nix-store", args...)
+	if len(nixEnv) > 0 {
+		cmd.Env = nixEnv
+	}
+
+	return cmd
+}
+
+// queryPathInfoNixStore is queryPathInfo for Nix before 2.4: the closure
+// comes from `nix-store --query --requisites` and its path info from
+// `nix-store --dump-db`. Signatures and content addresses are not part of
+// that output and stay unset.
+func queryPathInfoNixStore(ctx context.Context, storePaths []string, nixEnv []string, storeURI string, recursive bool) (map[string]*PathInfo, error) {
+	paths := storePaths
+
+	if recursive {
+		requisites, err := runNixStore(ctx, nixEnv, storeURI, append([]string{"--query", "--requisites"}, storePaths...)...)
+		if err != nil {
+			return nil, err
+		}
+
+		paths = strings.Fields(string(requisites))
+	}
+
+	result := make(map[string]*PathInfo)
+
+	// Chunked like realisation queries to stay below ARG_MAX.
+	for chunk := range slices.Chunk(paths, 1000) {
+		dump, err := runNixStore(ctx, nixEnv, storeURI, append([]string{"--dump-db"}, chunk...)...)
+		if err != nil {
+			return nil, err
+		}
+
+		if err := parseValidityRegistration(dump, result); err != nil {
+			return nil, err
+		}
+	}
+
+	return result, nil
+}
+
+func runNixStore(ctx context.Context, nixEnv []string, storeURI string, args ...string) ([]byte, error) {
+	cmd := nixStoreCommand(ctx, nixEnv, storeURI, args...)
+
+	var stderr bytes.Buffer
+
+	cmd.Stderr = &stderr
+
+	output, err := cmd.Output()
+	if err != nil {
+		return nil, fmt.Errorf("command failed: %s\nstderr: %s\nerror: %w", strings.Join(cmd.Args, " "), stderr.Bytes(), err)
+	}
+
+	return output, nil
+}
+
+// parseValidityRegistration parses `nix-store --dump-db` output into
+// result. Each path is listed as its store path, NAR hash, NAR size,
+// deriver (possibly empty), number of references and the references, one
+// per line.
+func parseValidityRegistration(data []byte, result map[string]*PathInfo) error {
+	scanner := bufio.NewScanner(bytes.NewReader(data))
+
+	next := func() (string, error) {
+		if !scanner.Scan() {
+			if err := scanner.Err(); err != nil {
+				return "", fmt.Errorf("reading nix-store --dump-db output: %w", err)
+			}
+
+			return "", errors.New("nix-store --dump-db output is truncated")
+		}
+
+		return scanner.Text(), nil
+	}
+
+	for scanner.Scan() {
+		storePath := scanner.Text()
+		if storePath == "" {
+			continue
+		}
+
+		var lines [4]string // hash, size, deriver, reference count
+
+		for i := range lines {
+			line, err := next()
+			if err != nil {
+				return err
+			}
+
+			lines[i] = line
+		}
+
+		narHash, err := narHashFromRegistration(lines[0])
+		if err != nil {
+			return fmt.Errorf("NAR hash of %s: %w", storePath, err)
+		}
+
+		narSize, err := strconv.ParseUint(lines[1], 10, 64)
+		if err != nil {
+			return fmt.Errorf("NAR size of %s: %w", storePath, err)
+		}
+
+		refCount, err := strconv.Atoi(lines[3])
+		if err != nil || refCount < 0 {
+			return fmt.Errorf("invalid reference count %q for %s", lines[3], storePath)
+		}
+
+		info := &PathInfo{Path: storePath, NarHash: narHash, NarSize: narSize, References: make([]string, 0, refCount)}
+
+		if lines[2] != "" {
+			deriver := lines[2]
+			info.Deriver = &deriver
+		}
+
+		for range refCount {
+			ref, err := next()
+			if err != nil {
+				return err
+			}
+
+			info.References = append(info.References, ref)
+		}
+
+		result[storePath] = info
+	}
+
+	if err := scanner.Err(); err != nil {
+		return fmt.Errorf("reading nix-store --dump-db output: %w", err)
+	}
+
+	return nil
+}
+
+// narHashFromRegistration converts the "sha256:<base16>" hash of a
+// validity registration into the SRI form `nix path-info` reports.
+func narHashFromRegistration(s string) (Hash, error) {
+	algo, value, ok := strings.Cut(s, ":")
+	if !ok || algo != "sha256" {
+		return Hash{}, fmt.Errorf("unsupported hash %q", s)
+	}
+
+	if len(value) != 2*32 {
+		// Already nix32, which ConvertHashToNix32 passes through.
+		return Hash{algorithm: algo, hash: s}, nil
+	}
+
+	digest, err := hex.DecodeString(value)
+	if err != nil {
+		return Hash{}, fmt.Errorf("decoding %q: %w", s, err)
+	}
+
+	return Hash{algorithm: algo, hash: algo + "-" + base64.StdEncoding.EncodeToString(digest)}, nil
+}
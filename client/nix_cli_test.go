@@ -0,0 +1,97 @@
+package client_test
+
+import (
+	"slices"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+func TestParseNixVersion(t *testing.T) {
+	t.Parallel()
+
+	tests := []struct {
+		output  string
+		version string
+		unified bool
+	}{
+		{"nix (Nix) 2.18.1\n", "2.18.1", true},
+		{"nix (Lix, like Nix) 2.91.1\n", "2.91.1", true},
+		{"nix (Nix) 2.4pre20210601_5985b8b\n", "2.4pre20210601_5985b8b", true},
+		{"nix-env (Nix) 2.3.16\n", "2.3.16", false},
+		{"nix (Nix) 3.0.0\n", "3.0.0", true},
+	}
+
+	for _, tt := range tests {
+		version, unified := client.ParseNixVersion(tt.output)
+		if version != tt.version || unified != tt.unified {
+			t.Errorf("ParseNixVersion(%q) = %q, %v; want %q, %v", tt.output, version, unified, tt.version, tt.unified)
+		}
+	}
+}
+
+func TestParseValidityRegistration(t *testing.T) {
+	t.Parallel()
+
+	const (
+		hello = "/nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1"
+		glibc = "/nix/store/sl141d1g77wvhr050ah87lcyz2czdxa3-glibc-2.40-36"
+	)
+
+	dump := hello + "\n" +
+		"sha256:15e3c560894cbb27085cf65b5a2ecb18488c999497f4531b6907a7581ce6d527\n" +
+		"226560\n" +
+		"/nix/store/8ha1dhmx807czjczmwy078s4r9s254il-hello-2.12.1.drv\n" +
+		"2\n" +
+		glibc + "\n" +
+		hello + "\n" +
+		glibc + "\n" +
+		"sha256:15e3c560894cbb27085cf65b5a2ecb18488c999497f4531b6907a7581ce6d527\n" +
+		"1024\n" +
+		"\n" +
+		"0\n"
+
+	pathInfos, err := client.ParseValidityRegistration([]byte(dump))
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	info := pathInfos[hello]
+	if info == nil {
+		t.Fatalf("missing %s in %v", hello, pathInfos)
+	}
+
+	// The same hash nix path-info reports as SRI.
+	if got := info.NarHash.String(); got != "sha256-FePFYIlMuycIXPZbWi7LGEiMmZSX9FMbaQenWBzm1Sc=" {
+		t.Errorf("NarHash = %q", got)
+	}
+
+	if info.NarSize != 226560 || info.Deriver == nil || !slices.Equal(info.References, []string{glibc, hello}) {
+		t.Errorf("unexpected path info %+v", info)
+	}
+
+	if lib := pathInfos[glibc]; lib == nil || lib.Deriver != nil || len(lib.References) != 0 {
+		t.Errorf("unexpected path info for %s: %+v", glibc, lib)
+	}
+
+	if _, err := client.ParseValidityRegistration([]byte(hello + "\nsha256:00\n")); err == nil {
+		t.Error("expected truncated output to be rejected")
+	}
+}
+
+func TestNixRemoteStore(t *testing.T) {
+	t.Parallel()
+
+	for remote, want := range map[string]string{
+		"":                       "",
+		"daemon":                 "",
+		"auto":                   "",
+		"unix:///run/nix/socket": "",
+		"ssh-ng://builder":       "ssh-ng://builder",
+		"local?root=/mnt":        "local?root=/mnt",
+	} {
+		if got := client.NixRemoteStore([]string{"PATH=/bin", "NIX_REMOTE=" + remote}); got != want {
+			t.Errorf("NIX_REMOTE=%q: got %q, want %q", remote, got, want)
+		}
+	}
+}
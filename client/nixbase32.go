@@ -1,8 +1,13 @@
 package client
 
 import (
+	"crypto/md5"  //nolint:gosec // md5 is a hash algorithm old narinfos may declare
+	"crypto/sha1" //nolint:gosec // sha1 is a hash algorithm old narinfos may declare
+	"crypto/sha256"
+	"crypto/sha512"
 	"encoding/base64"
 	"fmt"
+	"hash"
 	"strings"
 )
 
@@ -48,6 +53,108 @@ func EncodeNixBase32(input []byte) string {
 	return string(result)
 }
 
+// DecodeNixBase32 decodes a string produced by EncodeNixBase32.
+// This implementation is based on Nix's BaseNix32::decode in src/libutil/base-nix-32.cc.
+func DecodeNixBase32(s string) ([]byte, error) {
+	length := len(s) * 5 / 8
+
+	// Reject lengths EncodeNixBase32 can never produce
+	if (length == 0 && s != "") || (length > 0 && (length*8-1)/5+1 != len(s)) {
+		return nil, fmt.Errorf("invalid nix base32 length %d", len(s))
+	}
+
+	result := make([]byte, length)
+
+	for n := range len(s) {
+		c := s[len(s)-n-1]
+
+		idx := strings.IndexByte(nixBase32Alphabet, c)
+		if idx < 0 {
+			return nil, fmt.Errorf("invalid character %q in nix base32 string", c)
+		}
+
+		digit := byte(idx) //nolint:gosec // idx is 0-31
+
+		b := n * 5
+		i := b / 8
+		j := b % 8
+
+		result[i] |= digit << j
+
+		carry := digit >> (8 - j)
+		if i+1 < length {
+			result[i+1] |= carry
+		} else if carry != 0 {
+			return nil, fmt.Errorf("invalid nix base32 string %q: excess bits", s)
+		}
+	}
+
+	return result, nil
+}
+
+// HashAlgorithm is a hash algorithm Nix supports, as declared by the
+// "algo:" prefix of narinfo hash fields. Uploads always use HashSHA256;
+// the others appear in older caches and content addresses.
+type HashAlgorithm string
+
+const (
+	HashMD5    HashAlgorithm = "md5"
+	HashSHA1   HashAlgorithm = "sha1"
+	HashSHA256 HashAlgorithm = "sha256"
+	HashSHA512 HashAlgorithm = "sha512"
+)
+
+// hashSizes maps the hash algorithms Nix supports to their digest sizes.
+var hashSizes = map[HashAlgorithm]int{ //nolint:gochecknoglobals // read-only lookup table
+	HashMD5:    md5.Size,
+	HashSHA1:   sha1.Size,
+	HashSHA256: sha256.Size,
+	HashSHA512: sha512.Size,
+}
+
+// New returns a hasher computing the algorithm's digest.
+func (a HashAlgorithm) New() (hash.Hash, error) {
+	switch a {
+	case HashMD5:
+		return md5.New(), nil //nolint:gosec // md5 is only used to check hashes old caches declared
+	case HashSHA1:
+		return sha1.New(), nil //nolint:gosec // sha1 is only used to check hashes old caches declared
+	case HashSHA256:
+		return sha256.New(), nil
+	case HashSHA512:
+		return sha512.New(), nil
+	default:
+		return nil, fmt.Errorf("unsupported hash algorithm %q", string(a))
+	}
+}
+
+// DecodeNixHash splits a hash in "algo:nix32" form, as found in narinfo
+// NarHash/FileHash fields, and decodes the digest.
+func DecodeNixHash(s string) (HashAlgorithm, []byte, error) {
+	name, value, ok := strings.Cut(s, ":")
+	if !ok {
+		return "", nil, fmt.Errorf("hash %q is not in algo:value form", s)
+	}
+
+	algo := HashAlgorithm(name)
+
+	size, ok := hashSizes[algo]
+	if !ok {
+		return "", nil, fmt.Errorf("unsupported hash algorithm %q", algo)
+	}
+
+	digest, err := DecodeNixBase32(value)
+	if err != nil {
+		return "", nil, err
+	}
+
+	if len(digest) != size {
+		return "", nil, fmt.Errorf("%s hash has %d bytes, expected %d", algo, len(digest), size)
+	}
+
+	return algo, digest, nil
+}
+
 // ConvertHashToNix32 converts a hash from SRI format (sha256-base64) or
 // Nix32 format (sha256:nix32) to Nix32 format (sha256:nix32).
 // If the hash is already in Nix32 format, it returns it unchanged.
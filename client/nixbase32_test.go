@@ -1,7 +1,9 @@
 package client_test
 
 import (
+	"bytes"
 	"crypto/sha256"
+	"crypto/sha512"
 	"encoding/hex"
 	"testing"
 
@@ -111,3 +113,90 @@ func TestConvertHashToNix32(t *testing.T) {
 		})
 	}
 }
+
+func TestDecodeNixBase32RoundTrip(t *testing.T) {
+	t.Parallel()
+
+	hash := sha256.Sum256([]byte("test"))
+
+	for _, input := range [][]byte{
+		nil,
+		{0x00},
+		{0xff},
+		[]byte("hello world"),
+		hash[:],
+	} {
+		encoded := client.EncodeNixBase32(input)
+
+		decoded, err := client.DecodeNixBase32(encoded)
+		if err != nil {
+			t.Fatalf("DecodeNixBase32(%q): %v", encoded, err)
+		}
+
+		if !bytes.Equal(decoded, input) {
+			t.Errorf("round trip of %x gave %x", input, decoded)
+		}
+	}
+
+	decoded, err := client.DecodeNixBase32("020ay2q1av2xs4n842rb3d7vz8qms1dcb87a5yd6azaci20x11lz")
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if hex.EncodeToString(decoded) != "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08" {
+		t.Errorf("unexpected decoding %x", decoded)
+	}
+}
+
+func TestDecodeNixBase32Invalid(t *testing.T) {
+	t.Parallel()
+
+	for _, s := range []string{
+		// no byte encodes to a single character
+		"0",
+		// not a length EncodeNixBase32 produces
+		"000",
+		// 'e' is not in the alphabet
+		"020ay2q1av2xs4n842rb3d7vz8qms1dcb87a5yd6azaci20x11le",
+		// high bits overflow 32 bytes
+		"z20ay2q1av2xs4n842rb3d7vz8qms1dcb87a5yd6azaci20x11lz",
+	} {
+		if _, err := client.DecodeNixBase32(s); err == nil {
+			t.Errorf("DecodeNixBase32(%q) succeeded, want error", s)
+		}
+	}
+}
+
+func TestDecodeNixHash(t *testing.T) {
+	t.Parallel()
+
+	algo, digest, err := client.DecodeNixHash("sha256:020ay2q1av2xs4n842rb3d7vz8qms1dcb87a5yd6azaci20x11lz")
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if algo != "sha256" || len(digest) != sha256.Size {
+		t.Errorf("got %s with %d bytes", algo, len(digest))
+	}
+
+	sha512Sum := sha512.Sum512([]byte("niks3"))
+
+	algo, digest, err = client.DecodeNixHash("sha512:" + client.EncodeNixBase32(sha512Sum[:]))
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if algo != client.HashSHA512 || !bytes.Equal(digest, sha512Sum[:]) {
+		t.Errorf("got %s with %d bytes", algo, len(digest))
+	}
+
+	for _, s := range []string{
+		"020ay2q1av2xs4n842rb3d7vz8qms1dcb87a5yd6azaci20x11lz",
+		"sha384:020ay2q1av2xs4n842rb3d7vz8qms1dcb87a5yd6azaci20x11lz",
+		"sha512:020ay2q1av2xs4n842rb3d7vz8qms1dcb87a5yd6azaci20x11lz",
+	} {
+		if _, _, err := client.DecodeNixHash(s); err == nil {
+			t.Errorf("DecodeNixHash(%q) succeeded, want error", s)
+		}
+	}
+}
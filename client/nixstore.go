@@ -1,14 +1,22 @@
 package client
 
 import (
+	"bytes"
 	"context"
+	"encoding/base64"
+	"encoding/hex"
 	"encoding/json"
-	"errors"
 	"fmt"
+	"io"
+	"maps"
 	"os"
 	"os/exec"
 	"path/filepath"
+	"slices"
 	"strings"
+	"sync"
+
+	"golang.org/x/sync/errgroup"
 )
 
 // GetStoreDir determines the Nix store directory path.
@@ -19,16 +27,14 @@ import (
 // Returns the store directory (e.g., "/nix/store").
 func GetStoreDir(ctx context.Context, nixEnv []string) (string, error) {
 	// First check NIX_STORE_DIR environment variable
-	if len(nixEnv) > 0 {
-		for _, env := range nixEnv {
-			if after, ok := strings.CutPrefix(env, "NIX_STORE_DIR="); ok {
-				return after, nil
-			}
-		}
-	} else if storeDir := os.Getenv("NIX_STORE_DIR"); storeDir != "" {
+	if storeDir := lookupNixEnv(nixEnv, "NIX_STORE_DIR"); storeDir != "" {
 		return storeDir, nil
 	}
 
+	if !detectNixCLI().unified {
+		return "/nix/store", nil
+	}
+
 	// Try to query nix command
 	cmd := exec.CommandContext(ctx, "nix", "--extra-experimental-features", "nix-command", "eval", "--raw", "--expr", "builtins.storeDir")
 	if len(nixEnv) > 0 {
@@ -114,6 +120,78 @@ func (h *Hash) String() string {
 	return h.hash
 }
 
+// nix32 returns the hash as "algo:nix32", the form narinfo fields use.
+// Unlike ConvertHashToNix32 it accepts every algorithm Nix supports and
+// the base16 format of the structured JSON.
+func (h *Hash) nix32() (string, error) {
+	algo, value := h.algorithm, h.hash
+
+	if h.format == "" {
+		// Old string format: "algo:nix32" or SRI "algo-base64"
+		if a, v, ok := strings.Cut(value, ":"); ok {
+			return a + ":" + v, nil
+		}
+
+		a, v, ok := strings.Cut(value, "-")
+		if !ok {
+			return "", fmt.Errorf("hash %q has no algorithm", value)
+		}
+
+		algo, value = a, v
+	}
+
+	if _, ok := hashSizes[HashAlgorithm(algo)]; !ok {
+		return "", fmt.Errorf("unsupported hash algorithm %q", algo)
+	}
+
+	var (
+		digest []byte
+		err    error
+	)
+
+	switch h.format {
+	case "", "base64", "sri":
+		digest, err = base64.StdEncoding.DecodeString(strings.TrimPrefix(value, algo+"-"))
+	case "base16":
+		digest, err = hex.DecodeString(value)
+	case "nix32":
+		return algo + ":" + value, nil
+	default:
+		return "", fmt.Errorf("unsupported hash format %q", h.format)
+	}
+
+	if err != nil {
+		return "", fmt.Errorf("decoding %s hash: %w", algo, err)
+	}
+
+	return algo + ":" + EncodeNixBase32(digest), nil
+}
+
+// validateContentAddress checks that ca is a content address Nix accepts
+// in a narinfo: a method prefix followed by a full-length nix32 hash.
+func validateContentAddress(ca string) error {
+	var rest string
+
+	switch {
+	case strings.HasPrefix(ca, "text:"):
+		rest = strings.TrimPrefix(ca, "text:")
+	case strings.HasPrefix(ca, "fixed:r:"):
+		rest = strings.TrimPrefix(ca, "fixed:r:")
+	case strings.HasPrefix(ca, "fixed:git:"):
+		rest = strings.TrimPrefix(ca, "fixed:git:")
+	case strings.HasPrefix(ca, "fixed:"):
+		rest = strings.TrimPrefix(ca, "fixed:")
+	default:
+		return fmt.Errorf("content address %q has no text: or fixed: prefix", ca)
+	}
+
+	if _, _, err := DecodeNixHash(rest); err != nil {
+		return fmt.Errorf("content address %q: %w", ca, err)
+	}
+
+	return nil
+}
+
 // ContentAddress represents a Nix content address.
 // It supports both the old string format (e.g., "fixed:r:sha256:abc...")
 // and the new structured format from Nix 2.33+.
@@ -172,13 +250,12 @@ func (ca *ContentAddress) String() string {
 	//   "nar"  -> "fixed:r:"
 	//   "git"  -> "fixed:git:"
 	if ca.method != "" {
-		// Convert hash from SRI format to nix32 format for narinfo
-		hashStr := ca.hash.String()
-
-		nix32Hash, err := ConvertHashToNix32(hashStr)
+		// Convert hash to nix32 format for narinfo. Fixed-output paths
+		// may use any algorithm, e.g. sha512 or sha1 for git.
+		nix32Hash, err := ca.hash.nix32()
 		if err != nil {
-			// Fall back to original format if conversion fails
-			nix32Hash = hashStr
+			// Fall back to original format; NewNarinfoMetadata rejects it
+			nix32Hash = ca.hash.String()
 		}
 
 		switch ca.method {
@@ -210,6 +287,33 @@ type PathInfo struct {
 	Deriver    *string         `json:"deriver,omitempty"`
 	Signatures []string        `json:"signatures,omitempty"`
 	CA         *ContentAddress `json:"ca,omitempty"`
+
+	// compression overrides the client's NAR compression for this path,
+	// e.g. when sampling showed its contents are already compressed.
+	compression Compression
+}
+
+// unknownDeriver is what Nix reports as the deriver of paths it did not
+// build itself, e.g. ones added with `nix-store --add`.
+const unknownDeriver = "unknown-deriver"
+
+// knownDeriver returns deriver, or nil if it is unset, empty or Nix's
+// unknown-deriver sentinel.
+func knownDeriver(deriver *string) *string {
+	if deriver == nil || *deriver == "" || filepath.Base(*deriver) == unknownDeriver {
+		return nil
+	}
+
+	return deriver
+}
+
+// narCompression returns the compression this path's NAR is uploaded with.
+func (p *PathInfo) narCompression(def Compression) Compression {
+	if p.compression != "" {
+		return p.compression
+	}
+
+	return def
 }
 
 // RealisationInfo represents Nix realisation information for CA derivations.
@@ -220,10 +324,97 @@ type RealisationInfo struct {
 	DependentRealisations map[string]string `json:"dependentRealisations,omitempty"` //nolint:tagliatelle
 }
 
+// DefaultPathInfoChunkSize is how many store paths GetPathInfoRecursive
+// passes to a single `nix path-info` invocation, keeping argv well below
+// ARG_MAX when pushing whole profiles.
+const DefaultPathInfoChunkSize = 256
+
+// pathInfoQueryJobs is how many `nix path-info` chunks run at once.
+const pathInfoQueryJobs = 4
+
 // GetPathInfoRecursive queries Nix for path info including all dependencies.
 func GetPathInfoRecursive(ctx context.Context, storePaths []string, nixEnv []string) (map[string]*PathInfo, error) {
-	args := make([]string, 0, 6+len(storePaths))
-	args = append(args, "--extra-experimental-features", "nix-command", "path-info", "--recursive", "--json", "--")
+	return GetPathInfoRecursiveChunked(ctx, storePaths, nixEnv, DefaultPathInfoChunkSize)
+}
+
+// GetPathInfoRecursiveChunked is GetPathInfoRecursive with an explicit number
+// of input paths per `nix path-info` call. Closures of different chunks
+// overlap; the merged map holds each store path once.
+func GetPathInfoRecursiveChunked(ctx context.Context, storePaths []string, nixEnv []string, chunkSize int) (map[string]*PathInfo, error) {
+	return getPathInfo(ctx, storePaths, nixEnv, "", chunkSize, true)
+}
+
+// GetPathInfoRecursiveFromStore is GetPathInfoRecursive against the Nix store
+// at storeURI (e.g. "ssh-ng://builder"). An empty storeURI queries the local
+// store.
+func GetPathInfoRecursiveFromStore(ctx context.Context, storePaths []string, nixEnv []string, storeURI string) (map[string]*PathInfo, error) {
+	return getPathInfo(ctx, storePaths, nixEnv, storeURI, DefaultPathInfoChunkSize, true)
+}
+
+// GetPathInfoFromStore is GetPathInfoRecursiveFromStore without the
+// dependencies: the result holds only storePaths, whose References may name
+// paths that are not in it.
+func GetPathInfoFromStore(ctx context.Context, storePaths []string, nixEnv []string, storeURI string) (map[string]*PathInfo, error) {
+	return getPathInfo(ctx, storePaths, nixEnv, storeURI, DefaultPathInfoChunkSize, false)
+}
+
+func getPathInfo(ctx context.Context, storePaths []string, nixEnv []string, storeURI string, chunkSize int, recursive bool) (map[string]*PathInfo, error) {
+	if chunkSize <= 0 {
+		chunkSize = DefaultPathInfoChunkSize
+	}
+
+	var mu sync.Mutex
+
+	result := make(map[string]*PathInfo)
+
+	// Chunks are independent nix invocations, so a few run at once.
+	g, ctx := errgroup.WithContext(ctx)
+	g.SetLimit(pathInfoQueryJobs)
+
+	for chunk := range slices.Chunk(storePaths, chunkSize) {
+		g.Go(func() error {
+			pathInfos, err := queryPathInfo(ctx, chunk, nixEnv, storeURI, recursive)
+			if err != nil {
+				return err
+			}
+
+			mu.Lock()
+			maps.Copy(result, pathInfos)
+			mu.Unlock()
+
+			return nil
+		})
+	}
+
+	if err := g.Wait(); err != nil {
+		return nil, err //nolint:wrapcheck // errors of queryPathInfo, already wrapped
+	}
+
+	return result, nil
+}
+
+// queryPathInfo runs a single `nix path-info [--recursive]` for storePaths.
+// The JSON is decoded while nix is still writing it, so the whole output is
+// never held in memory at once.
+func queryPathInfo(ctx context.Context, storePaths []string, nixEnv []string, storeURI string, recursive bool) (map[string]*PathInfo, error) {
+	if !detectNixCLI().unified {
+		return queryPathInfoNixStore(ctx, storePaths, nixEnv, storeURI, recursive)
+	}
+
+	args := make([]string, 0, 8+len(storePaths))
+	args = append(args, "--extra-experimental-features", "nix-command", "path-info")
+
+	if recursive {
+		args = append(args, "--recursive")
+	}
+
+	args = append(args, "--json")
+
+	if storeURI != "" {
+		args = append(args, "--store", storeURI)
+	}
+
+	args = append(args, "--")
 	args = append(args, storePaths...)
 
 	cmd := exec.CommandContext(ctx, "nix", args...)
@@ -231,74 +422,189 @@ func GetPathInfoRecursive(ctx context.Context, storePaths []string, nixEnv []str
 		cmd.Env = nixEnv
 	}
 
-	output, err := cmd.Output()
+	var stderr bytes.Buffer
+
+	cmd.Stderr = &stderr
+
+	cmdStr := "nix " + strings.Join(args, " ")
+
+	stdout, err := cmd.StdoutPipe()
+	if err != nil {
+		return nil, fmt.Errorf("creating stdout pipe: %w", err)
+	}
+
+	if err := cmd.Start(); err != nil {
+		return nil, fmt.Errorf("command failed: %s\nerror: %w", cmdStr, err)
+	}
+
+	pathInfos, parseErr := decodePathInfoJSON(stdout)
+	if parseErr != nil {
+		// Let nix finish writing so Wait does not block on a full pipe.
+		_, _ = io.Copy(io.Discard, stdout)
+	}
+
+	if err := cmd.Wait(); err != nil {
+		return nil, fmt.Errorf("command failed: %s\nstderr: %s\nerror: %w", cmdStr, stderr.Bytes(), err)
+	}
+
+	if parseErr != nil {
+		return nil, parseErr
+	}
+
+	return pathInfos, nil
+}
+
+// LoadPathInfoFile reads a file holding the output of
+// `nix path-info --recursive --json`, so closures can be pushed without
+// querying nix. Both the Nix and the Lix layout are accepted; see
+// testdata/path-info.json for an example.
+func LoadPathInfoFile(path string) (map[string]*PathInfo, error) {
+	data, err := os.ReadFile(path)
+	if err != nil {
+		return nil, fmt.Errorf("reading path info file: %w", err)
+	}
+
+	pathInfos, err := parsePathInfoJSON(data)
 	if err != nil {
-		cmdStr := "nix " + strings.Join(args, " ")
+		return nil, fmt.Errorf("%s: %w", path, err)
+	}
+
+	return pathInfos, nil
+}
+
+// closureFromPathInfos returns the closures of storePaths taken from a
+// precomputed path info map. The map may hold more than these closures, but
+// every requested path and every reference must be present.
+func closureFromPathInfos(storePaths []string, all map[string]*PathInfo) (map[string]*PathInfo, error) {
+	result := make(map[string]*PathInfo)
+	queue := slices.Clone(storePaths)
 
-		var exitErr *exec.ExitError
-		if errors.As(err, &exitErr) {
-			return nil, fmt.Errorf("command failed: %s\nstderr: %s\nerror: %w", cmdStr, exitErr.Stderr, err)
+	for len(queue) > 0 {
+		path := queue[len(queue)-1]
+		queue = queue[:len(queue)-1]
+
+		if _, ok := result[path]; ok {
+			continue
 		}
 
-		return nil, fmt.Errorf("command failed: %s\nerror: %w", cmdStr, err)
+		info, ok := all[path]
+		if !ok {
+			return nil, fmt.Errorf("%s is missing from the path info", path)
+		}
+
+		result[path] = info
+		queue = append(queue, info.References...)
 	}
 
-	return parsePathInfoJSON(output)
+	return result, nil
+}
+
+// pathsFromPathInfos returns the path info of storePaths alone from a
+// precomputed path info map, for pushes without their closures.
+func pathsFromPathInfos(storePaths []string, all map[string]*PathInfo) (map[string]*PathInfo, error) {
+	result := make(map[string]*PathInfo, len(storePaths))
+
+	for _, path := range storePaths {
+		info, ok := all[path]
+		if !ok {
+			return nil, fmt.Errorf("%s is missing from the path info", path)
+		}
+
+		result[path] = info
+	}
+
+	return result, nil
 }
 
 // parsePathInfoJSON parses the JSON output of `nix path-info --json`.
-// It supports both Nix format (object keyed by store path) and
-// Lix format (array of objects with a "path" field).
 func parsePathInfoJSON(output []byte) (map[string]*PathInfo, error) {
-	// Try Nix format first: object keyed by store path
-	var pathInfos map[string]*PathInfo
-	if err := json.Unmarshal(output, &pathInfos); err == nil {
-		for path, info := range pathInfos {
+	return decodePathInfoJSON(bytes.NewReader(output))
+}
+
+// decodePathInfoJSON stream-decodes the JSON output of `nix path-info --json`
+// one entry at a time. It supports both Nix format (object keyed by store
+// path) and Lix format (array of objects with a "path" field).
+func decodePathInfoJSON(r io.Reader) (map[string]*PathInfo, error) {
+	dec := json.NewDecoder(r)
+
+	tok, err := dec.Token()
+	if err != nil {
+		return nil, fmt.Errorf("parsing nix path-info output: %w", err)
+	}
+
+	result := make(map[string]*PathInfo)
+
+	switch tok {
+	case json.Delim('{'):
+		// Nix format: object keyed by store path
+		for dec.More() {
+			keyTok, err := dec.Token()
+			if err != nil {
+				return nil, fmt.Errorf("parsing nix path-info output: %w", err)
+			}
+
+			path, _ := keyTok.(string)
+
+			var info *PathInfo
+			if err := dec.Decode(&info); err != nil {
+				return nil, fmt.Errorf("parsing nix path-info output for %s: %w", path, err)
+			}
+
+			// Nix reports paths that are not valid in the store as null
+			if info == nil {
+				return nil, fmt.Errorf("%s is not a valid store path", path)
+			}
+
 			info.Path = path
+			result[path] = info
 		}
+	case json.Delim('['):
+		// Lix format: array of objects with "path" field
+		type lixPathInfo struct {
+			PathInfo
 
-		return pathInfos, nil
-	}
+			Path string `json:"path"`
+		}
 
-	// Try Lix format: array of objects with "path" field
-	type lixPathInfo struct {
-		PathInfo
+		for dec.More() {
+			lixInfo := &lixPathInfo{}
+			if err := dec.Decode(lixInfo); err != nil {
+				return nil, fmt.Errorf("parsing nix path-info output: %w", err)
+			}
 
-		Path string `json:"path"`
+			lixInfo.PathInfo.Path = lixInfo.Path
+			result[lixInfo.Path] = &lixInfo.PathInfo
+		}
+	default:
+		return nil, fmt.Errorf("parsing nix path-info output: unexpected %v", tok)
 	}
 
-	var lixInfos []lixPathInfo
-	if err := json.Unmarshal(output, &lixInfos); err != nil {
+	// Consume the closing delimiter so truncated output is rejected.
+	if _, err := dec.Token(); err != nil {
 		return nil, fmt.Errorf("parsing nix path-info output: %w", err)
 	}
 
-	result := make(map[string]*PathInfo, len(lixInfos))
-	for i := range lixInfos {
-		lixInfos[i].PathInfo.Path = lixInfos[i].Path
-		result[lixInfos[i].Path] = &lixInfos[i].PathInfo
-	}
-
 	return result, nil
 }
 
+// storePathHashLen is the length of the nix32-encoded 160-bit hash of a
+// store path.
+const storePathHashLen = 32
+
 // GetStorePathHash extracts the hash from a store path.
-// e.g., "/nix/store/abc123-name" -> "abc123".
+// e.g., "/nix/store/abc123-name" -> "abc123". A bare hash is returned as is.
 func GetStorePathHash(storePath string) (string, error) {
 	base := filepath.Base(storePath)
 
-	// Require at least hash and name separated by hyphen
-	parts := strings.SplitN(base, "-", 2)
-	if len(parts) < 2 {
+	hash, _, hasName := strings.Cut(base, "-")
+	if !hasName && len(base) != storePathHashLen {
 		return "", fmt.Errorf("invalid store path format (missing hyphen): %s", storePath)
 	}
 
-	hash := parts[0]
-
 	// Validate hash length (Nix uses 32-character base32-encoded hashes)
 	// This is the length of base32-encoded 160-bit (20-byte) hashes
-	const expectedHashLen = 32
-	if len(hash) != expectedHashLen {
-		return "", fmt.Errorf("invalid hash length %d (expected %d): %s", len(hash), expectedHashLen, storePath)
+	if len(hash) != storePathHashLen {
+		return "", fmt.Errorf("invalid hash length %d (expected %d): %s", len(hash), storePathHashLen, storePath)
 	}
 
 	// Validate hash charset (Nix base32: 0-9 and a-z except e,o,t,u)
@@ -311,6 +617,32 @@ func GetStorePathHash(storePath string) (string, error) {
 	return hash, nil
 }
 
+// isStorePathHash reports whether s looks like the hash part of a store path.
+func isStorePathHash(s string) bool {
+	return len(s) == storePathHashLen && strings.Trim(s, nixBase32Alphabet) == ""
+}
+
+// storePathNameChars are the characters Nix allows in the name part of a
+// store path.
+const storePathNameChars = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789+-._?="
+
+// checkStoreObjectName checks that name, the last element of a store path,
+// has the <hash>-<name> shape Nix gives every store object.
+func checkStoreObjectName(name string) error {
+	hash, rest, hasName := strings.Cut(name, "-")
+
+	switch {
+	case !hasName || !isStorePathHash(hash):
+		return fmt.Errorf("expected <%d-character nix32 hash>-<name>, got %q", storePathHashLen, name)
+	case rest == "" || strings.HasPrefix(rest, "."):
+		return fmt.Errorf("invalid name %q after the hash", rest)
+	case strings.Trim(rest, storePathNameChars) != "":
+		return fmt.Errorf("name %q contains characters Nix does not allow in store paths", rest)
+	}
+
+	return nil
+}
+
 // QueryRealisations queries realisations from Nix's local database using `nix realisation info`.
 // It only queries paths that have the CA field set, as non-CA paths don't have realisations.
 // Returns a map from realisation key ("realisations/<id>.doi") to RealisationInfo.
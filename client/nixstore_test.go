@@ -1,7 +1,12 @@
 package client_test
 
 import (
+	"context"
 	"encoding/json"
+	"fmt"
+	"os"
+	"path/filepath"
+	"strings"
 	"testing"
 
 	"github.com/Mic92/niks3/client"
@@ -22,6 +27,12 @@ func TestGetStorePathHash(t *testing.T) {
 			wantHash:  "8ha1dhmx807czjczmwy078s4r9s254il",
 			wantErr:   false,
 		},
+		{
+			name:      "bare hash is returned as is",
+			storePath: "8ha1dhmx807czjczmwy078s4r9s254il",
+			wantHash:  "8ha1dhmx807czjczmwy078s4r9s254il",
+			wantErr:   false,
+		},
 		{
 			name:      "basename without hyphen should error",
 			storePath: "/nix/store/badhash",
@@ -158,6 +169,8 @@ func TestParsePathInfoJSON(t *testing.T) {
 		{name: "empty input", input: "", wantErr: true},
 		{name: "whitespace only", input: "   \n\t  ", wantErr: true},
 		{name: "invalid JSON", input: "not json", wantErr: true},
+		{name: "truncated", input: nixJSON[:len(nixJSON)-3], wantErr: true},
+		{name: "invalid path", input: `{"/nix/store/8ha1dhmx807czjczmwy078s4r9s254il-hello-2.12.2": null}`, wantErr: true},
 	}
 
 	for _, tt := range tests {
@@ -360,3 +373,100 @@ func TestPathInfoCACompatibility(t *testing.T) {
 		})
 	}
 }
+
+// fakeNixPathInfo emits path-info JSON for every argument after "--" plus a
+// dependency shared by all of them, and logs each invocation.
+const fakeNixPathInfo = `#!/bin/sh
+echo call >> "$NIKS3_TEST_CALLS"
+while [ "$1" != "--" ]; do shift; done
+shift
+info='{"narHash":"sha256-FePFYIlMuycIXPZbWi7LGEiMmZSX9FMbaQenWBzm1Sc=","narSize":1,"references":[]}'
+printf '{"/nix/store/3n58xw4373jp0ljirf06d8077j15pc4j-glibc-2.37-8":%s' "$info"
+for p in "$@"; do printf ',"%s":%s' "$p" "$info"; done
+printf '}'
+`
+
+//nolint:paralleltest // modifies PATH via t.Setenv
+func TestGetPathInfoRecursiveChunked(t *testing.T) {
+	binDir := t.TempDir()
+	calls := filepath.Join(t.TempDir(), "calls")
+
+	if err := os.WriteFile(filepath.Join(binDir, "nix"), []byte(fakeNixPathInfo), 0o755); err != nil { //nolint:gosec // fake nix must be executable
+		t.Fatal(err)
+	}
+
+	t.Setenv("PATH", binDir+string(os.PathListSeparator)+os.Getenv("PATH"))
+	t.Setenv("NIKS3_TEST_CALLS", calls)
+
+	paths := make([]string, 5)
+	for i := range paths {
+		paths[i] = fmt.Sprintf("/nix/store/%032d-pkg%d", i, i)
+	}
+
+	infos, err := client.GetPathInfoRecursiveChunked(context.Background(), paths, nil, 2)
+	if err != nil {
+		t.Fatalf("GetPathInfoRecursiveChunked: %v", err)
+	}
+
+	// 5 inputs plus the shared dependency, deduplicated across chunks
+	if len(infos) != len(paths)+1 {
+		t.Errorf("expected %d path infos, got %d", len(paths)+1, len(infos))
+	}
+
+	for path, info := range infos {
+		if info.Path != path {
+			t.Errorf("info.Path = %q, want %q", info.Path, path)
+		}
+	}
+
+	log, err := os.ReadFile(calls)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if n := strings.Count(string(log), "call"); n != 3 {
+		t.Errorf("expected 3 nix invocations for 5 paths in chunks of 2, got %d", n)
+	}
+}
+
+// TestLoadPathInfoFile loads testdata/path-info.json, which documents the
+// schema --from-json expects: the output of `nix path-info --recursive
+// --json`, an object keyed by store path with narHash (SRI), narSize,
+// references and optional deriver, signatures and ca. Lix's array layout
+// with a "path" field is accepted as well.
+func TestLoadPathInfoFile(t *testing.T) {
+	t.Parallel()
+
+	const (
+		hello = "/nix/store/1b9p07z77phvv2hf6gm9f28syh6ym98a-hello-2.12.1"
+		glibc = "/nix/store/c10zhkbp6jmyh0xc5kd123ga8yy2p4hk-glibc-2.39-52"
+	)
+
+	all, err := client.LoadPathInfoFile(filepath.Join("testdata", "path-info.json"))
+	if err != nil {
+		t.Fatalf("LoadPathInfoFile: %v", err)
+	}
+
+	if len(all) != 3 {
+		t.Fatalf("expected 3 path infos, got %d", len(all))
+	}
+
+	for path, info := range all {
+		if info.Path != path {
+			t.Errorf("info.Path = %q, want %q", info.Path, path)
+		}
+	}
+
+	closure, err := client.ClosureFromPathInfos([]string{hello}, all)
+	if err != nil {
+		t.Fatalf("ClosureFromPathInfos: %v", err)
+	}
+
+	if len(closure) != 2 || closure[hello] == nil || closure[glibc] == nil {
+		t.Errorf("expected closure {hello, glibc}, got %v", closure)
+	}
+
+	if _, err := client.ClosureFromPathInfos([]string{"/nix/store/00000000000000000000000000000000-missing"}, all); err == nil {
+		t.Error("expected an error for a path missing from the file")
+	}
+}
@@ -5,7 +5,11 @@ import (
 	"errors"
 	"fmt"
 	"log/slog"
+	"os"
 	"strings"
+	"sync"
+	"sync/atomic"
+	"time"
 
 	"golang.org/x/sync/errgroup"
 )
@@ -15,13 +19,85 @@ type uploadTask struct {
 	obj PendingObject
 }
 
-// pendingObjectsByHash groups related objects by their store path hash.
-type pendingObjectsByHash map[string]struct {
+// pathUploadTasks holds the pending objects belonging to one store path.
+type pathUploadTasks struct {
 	narTask     *uploadTask
 	lsTask      *uploadTask
 	narinfoTask *uploadTask
 }
 
+// pendingObjectsByHash groups related objects by their store path hash.
+type pendingObjectsByHash map[string]pathUploadTasks
+
+// UploadStats summarizes the objects of a push.
+type UploadStats struct {
+	Total     int             // Distinct objects across the prepared closures
+	Uploaded  int             // Objects uploaded by this push
+	Skipped   int             // Objects the cache already had or a concurrent push uploaded
+	Succeeded []string        // Store paths whose narinfo this push uploaded
+	Failed    []UploadFailure // Paths that failed with ContinueOnError; a failure otherwise aborts the push
+	Manifest  []ManifestEntry // Every path of the pushed closures after a successful push, sorted by store path
+	Paths     int             // Store paths in the pushed closures, after exclusions
+	NarBytes  uint64          // Uncompressed size of the NARs this push uploaded
+	Elapsed   time.Duration   // Wall-clock time of the push
+
+	narDigests map[string]*FileDigest // Compressed NARs uploaded, by store path
+	narSizes   map[string]uint64      // Uncompressed size of the NARs in narDigests
+}
+
+// UploadFailure records a store path, or the key of a build log or
+// realisation, that could not be uploaded.
+type UploadFailure struct {
+	StorePath string
+	Err       error
+	keys      []string // Pending objects left unfinished by the failure
+}
+
+// ErrUploadIncomplete is returned with the stats of a ContinueOnError push
+// in which some paths failed. Closures containing them are not registered.
+var ErrUploadIncomplete = errors.New("some paths failed to upload")
+
+// uploadResults collects per-path outcomes from the upload workers.
+type uploadResults struct {
+	mu        sync.Mutex
+	succeeded []string
+	failed    []UploadFailure
+	digests   map[string]*FileDigest
+}
+
+// record notes the outcome of one path or object. With ContinueOnError a
+// failure is kept and nil returned so the other workers carry on; otherwise
+// err is returned to abort the errgroup. Cancellation always aborts.
+func (r *uploadResults) record(ctx context.Context, continueOnError bool, storePath string, keys []string, err error) error {
+	if err != nil && (!continueOnError || ctx.Err() != nil) {
+		return err
+	}
+
+	r.mu.Lock()
+	defer r.mu.Unlock()
+
+	if err != nil {
+		slog.Error("Failed to upload", "path", storePath, "error", err)
+		r.failed = append(r.failed, UploadFailure{StorePath: storePath, Err: err, keys: keys})
+	} else if storePath != "" {
+		r.succeeded = append(r.succeeded, storePath)
+	}
+
+	return nil
+}
+
+// recordDigest notes the compressed NAR uploaded for storePath.
+func (r *uploadResults) recordDigest(storePath string, digest *FileDigest) {
+	r.mu.Lock()
+	defer r.mu.Unlock()
+
+	if r.digests == nil {
+		r.digests = make(map[string]*FileDigest)
+	}
+
+	r.digests[storePath] = digest
+}
+
 // UploadContext contains all the context needed for uploading objects.
 type UploadContext struct {
 	PendingObjects    map[string]PendingObject
@@ -31,13 +107,18 @@ type UploadContext struct {
 	RealisationsByKey map[string]*RealisationInfo
 }
 
-// UploadPendingObjects uploads all pending objects (NARs, .ls files, build logs, and realisations).
-// Returns narinfo metadata for each closure to be signed and uploaded by the server.
+// UploadPendingObjects uploads all pending objects (NARs, .ls files, narinfos, build logs, and realisations).
 // Uses a unified worker pool where:
 // - Logs and realisations upload immediately (independent)
-// - NARs upload and queue their listings when complete
-// - Narinfo metadata is collected (not uploaded) for server-side signing.
-func (c *Client) UploadPendingObjects(ctx context.Context, uploadCtx *UploadContext) (map[string]NarinfoMetadata, error) {
+// - Each store path uploads its NAR and listing, then has its narinfo signed
+//   by the server and uploaded right away, so no path waits on the rest of
+//   the closure.
+// A path holds one worker slot from compression through its narinfo, so at
+// most MaxConcurrentNARUploads compressions and uploads run at once in total;
+// CompressionJobs further bounds the compressions among them.
+// Only Uploaded is filled in; Total and Skipped depend on the closures the
+// caller prepared.
+func (c *Client) UploadPendingObjects(ctx context.Context, uploadCtx *UploadContext) (*UploadStats, error) {
 	// Collect pending objects by type
 	pendingByHash := make(pendingObjectsByHash)
 
@@ -87,95 +168,210 @@ func (c *Client) UploadPendingObjects(ctx context.Context, uploadCtx *UploadCont
 		numWorkers = len(pendingByHash) + len(logTasks) + len(realisationTasks)
 	}
 
-	// Phase 1: Upload NARs (with listings), logs, and realisations in parallel
+	// Build logs above InMemoryLogLimit are staged on disk before upload;
+	// bail out now rather than running out of space halfway through.
+	stagedLogPaths := make([]string, 0, len(logTasks))
+
+	for _, task := range logTasks {
+		if logPath, ok := uploadCtx.LogPathsByKey[task.key]; ok {
+			stagedLogPaths = append(stagedLogPaths, logPath)
+		}
+	}
+
+	if err := checkBuildLogStaging(c.TempDir, c.TempDirBudget, numWorkers, c.InMemoryLogLimit, stagedLogPaths); err != nil {
+		return nil, err
+	}
+
+	// Stage logs in a directory of our own, so whatever a canceled upload
+	// leaves behind is removed with it.
+	stagingDir := c.TempDir
+
+	if len(stagedLogPaths) > 0 || c.KeepTemp {
+		dir, err := os.MkdirTemp(c.TempDir, "niks3-push-*")
+		if err != nil {
+			return nil, fmt.Errorf("creating staging directory: %w", err)
+		}
+
+		if c.KeepTemp {
+			c.keepDir = dir
+
+			defer func() {
+				c.keepDir = ""
+
+				slog.Info("Kept staged files", "dir", dir)
+			}()
+		} else {
+			defer removeStagingDir(dir)
+		}
+
+		stagingDir = dir
+	}
+
 	g, ctx := errgroup.WithContext(ctx)
 	g.SetLimit(numWorkers)
 
-	// Queue all log tasks
+	// Objects actually sent; missing logs and superseded NARs are not counted
+	var uploaded atomic.Int64
+
+	var results uploadResults
+
+	// Queue all log tasks. Each task captures only its own log path, not
+	// the maps of the whole closure.
 	for _, task := range logTasks {
+		logPath, ok := uploadCtx.LogPathsByKey[task.key]
+		if !ok {
+			// Log was requested by server but not found locally - this shouldn't happen
+			// but we'll log a warning and continue rather than failing the entire upload
+			slog.Warn("Build log not found", "key", task.key)
+
+			continue
+		}
+
 		g.Go(func() error {
-			return c.uploadLog(ctx, task, uploadCtx.LogPathsByKey)
+			ok, err := c.uploadLog(ctx, task, logPath, stagingDir)
+			if ok {
+				uploaded.Add(1)
+				c.checkpoint([]string{task.key})
+			}
+
+			if err == nil {
+				return nil
+			}
+
+			return results.record(ctx, c.ContinueOnError, task.key, []string{task.key}, err)
 		})
 	}
 
 	// Queue all realisation tasks
 	for _, task := range realisationTasks {
+		realisationInfo, ok := uploadCtx.RealisationsByKey[task.key]
+		if !ok {
+			// Realisation was requested by server but not found locally - this shouldn't happen
+			// but we'll log a warning and continue rather than failing the entire upload
+			slog.Warn("Realisation not found", "key", task.key)
+
+			continue
+		}
+
 		g.Go(func() error {
-			return c.uploadRealisation(ctx, task, uploadCtx.RealisationsByKey)
+			err := c.uploadRealisation(ctx, task, realisationInfo)
+			if err == nil {
+				uploaded.Add(1)
+				c.checkpoint([]string{task.key})
+
+				return nil
+			}
+
+			return results.record(ctx, c.ContinueOnError, task.key, []string{task.key}, err)
 		})
 	}
 
-	// Queue all NAR tasks and metadata-only tasks
+	// Queue one task per store path
 	for hash, entry := range pendingByHash {
+		if entry.narTask == nil && entry.narinfoTask == nil {
+			continue
+		}
+
 		pathInfo := uploadCtx.PathInfoByHash[hash]
 
-		if entry.narTask != nil {
-			g.Go(func() error {
-				return c.uploadNARWithListing(ctx, *entry.narTask, entry.lsTask, pathInfo)
-			})
-		} else if entry.narinfoTask != nil {
-			// Deduplicated NAR - queue metadata-only task
-			g.Go(func() error {
-				return c.uploadMetadataOnly(ctx, entry.lsTask, pathInfo)
-			})
+		storePath := hash
+		if pathInfo != nil {
+			storePath = pathInfo.Path
 		}
+
+		g.Go(func() error {
+			n, digest, err := c.uploadPath(ctx, entry, pathInfo)
+			uploaded.Add(int64(n))
+
+			if digest != nil {
+				results.recordDigest(storePath, digest)
+			}
+
+			if err == nil {
+				c.checkpoint(entry.keys())
+			}
+
+			if err == nil && entry.narinfoTask == nil {
+				return nil
+			}
+
+			return results.record(ctx, c.ContinueOnError, storePath, entry.keys(), err)
+		})
 	}
 
-	// Wait for phase 1 to complete
 	if err := g.Wait(); err != nil {
 		return nil, err //nolint:wrapcheck // errgroup returns the first task's already-wrapped error
 	}
 
-	// Phase 2: Collect narinfo metadata for successfully uploaded NARs
-	narinfoMetadata := make(map[string]NarinfoMetadata)
+	return &UploadStats{
+		Uploaded:   int(uploaded.Load()),
+		Succeeded:  results.succeeded,
+		Failed:     results.failed,
+		narDigests: results.digests,
+	}, nil
+}
 
-	for hash, entry := range pendingByHash {
-		if entry.narinfoTask == nil {
-			continue
-		}
+// keys returns the object keys of the path's pending objects.
+func (e pathUploadTasks) keys() []string {
+	var keys []string
 
-		pathInfo := uploadCtx.PathInfoByHash[hash]
-		if pathInfo == nil {
-			continue
+	for _, task := range []*uploadTask{e.narTask, e.lsTask, e.narinfoTask} {
+		if task != nil {
+			keys = append(keys, task.key)
 		}
+	}
 
-		// Convert NarHash to Nix32 format for the narinfo
-		narHash := pathInfo.NarHash.String()
-		if convertedHash, err := ConvertHashToNix32(pathInfo.NarHash.String()); err == nil {
-			narHash = convertedHash
-		}
+	return keys
+}
 
-		// Use NarHash-based key for URL (content-based deduplication)
-		narURL, err := getNARKey(pathInfo.NarHash.String())
-		if err != nil {
-			return nil, fmt.Errorf("getting NAR key for %s: %w", pathInfo.Path, err)
-		}
+// uploadPath uploads the NAR and listing of one store path, or only the
+// listing if the NAR is deduplicated, and then its narinfo. It returns the
+// number of objects uploaded and the digest of the NAR, if it uploaded one.
+func (c *Client) uploadPath(ctx context.Context, entry pathUploadTasks, pathInfo *PathInfo) (int, *FileDigest, error) {
+	var (
+		fileDigest *FileDigest
+		err        error
+	)
 
-		// Convert CA to string if present
-		var caStr *string
+	uploaded := 0
 
-		if pathInfo.CA != nil {
-			s := pathInfo.CA.String()
-			caStr = &s
-		}
+	if entry.narTask != nil {
+		fileDigest, err = c.uploadNARWithListing(ctx, *entry.narTask, entry.lsTask, pathInfo)
+		if err == nil && fileDigest != nil {
+			// A superseded NAR (nil digest) skipped its listing as well
+			uploaded++
 
-		// Create narinfo metadata
-		metadata := NarinfoMetadata{
-			StorePath:   pathInfo.Path,
-			URL:         narURL,
-			Compression: compressionZstd,
-			NarHash:     narHash,
-			NarSize:     pathInfo.NarSize,
-			References:  pathInfo.References,
-			Deriver:     pathInfo.Deriver,
-			Signatures:  pathInfo.Signatures,
-			CA:          caStr,
+			if entry.lsTask != nil {
+				uploaded++
+			}
 		}
+	} else {
+		err = c.uploadMetadataOnly(ctx, entry.lsTask, pathInfo)
+		if err == nil && entry.lsTask != nil {
+			uploaded++
+		}
+	}
 
-		narinfoMetadata[entry.narinfoTask.key] = metadata
+	if err != nil {
+		return uploaded, nil, err
 	}
 
-	return narinfoMetadata, nil
+	if entry.narinfoTask == nil {
+		return uploaded, fileDigest, nil
+	}
+
+	metadata, err := newNarinfoMetadata(pathInfo, pathInfo.narCompression(c.Compression), fileDigest)
+	if err != nil {
+		return uploaded, nil, err
+	}
+
+	if err := c.signAndUploadNarinfo(ctx, *entry.narinfoTask, metadata); err != nil {
+		return uploaded, nil, err
+	}
+
+	c.emit(NarinfoUploaded{StorePath: pathInfo.Path})
+
+	return uploaded + 1, fileDigest, nil
 }
 
 // uploadMetadataOnly handles metadata-only uploads for deduplicated NARs.
@@ -189,15 +385,23 @@ func (c *Client) uploadMetadataOnly(
 		return errors.New("missing PathInfo for metadata-only upload")
 	}
 
-	// Generate listing from store path (fast directory walk, no NAR serialization)
-	listing, err := GenerateListingOnly(pathInfo.Path)
+	if lsTask == nil {
+		// Listings are disabled or already present; there is nothing to generate.
+		return nil
+	}
+
+	// Generate listing from store path (a directory walk for the local store)
+	listing, err := c.generateListing(ctx, pathInfo.Path)
 	if err != nil {
 		return fmt.Errorf("generating listing for %s: %w", pathInfo.Path, err)
 	}
 
 	// Upload .ls file if needed
-	if lsTask != nil && listing != nil {
-		if err := c.UploadListingToPresignedURL(ctx, lsTask.obj.PresignedURL, listing); err != nil {
+	if listing != nil {
+		err := c.withPresignedURL(ctx, lsTask.key, lsTask.obj, func(presignedURL string) error {
+			return c.UploadListingToPresignedURL(ctx, presignedURL, listing)
+		})
+		if err != nil {
 			return fmt.Errorf("uploading listing %s: %w", lsTask.key, err)
 		}
 
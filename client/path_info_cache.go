@@ -0,0 +1,205 @@
+package client
+
+import (
+	"context"
+	"encoding/json"
+	"fmt"
+	"log/slog"
+	"maps"
+	"os"
+	"path/filepath"
+	"slices"
+	"time"
+)
+
+// DefaultPathInfoCacheTTL is how long `niks3 push --path-info-cache` trusts
+// a cache entry before asking nix again.
+const DefaultPathInfoCacheTTL = 24 * time.Hour
+
+// pathInfoCacheRecord is a cached PathInfo in the layout of
+// `nix path-info --json`, so parsePathInfoJSON reads it back.
+type pathInfoCacheRecord struct {
+	NarHash    string   `json:"narHash"` //nolint:tagliatelle // Nix's path-info layout
+	NarSize    uint64   `json:"narSize"` //nolint:tagliatelle // Nix's path-info layout
+	References []string `json:"references"`
+	Deriver    *string  `json:"deriver,omitempty"`
+	Signatures []string `json:"signatures,omitempty"`
+	CA         string   `json:"ca,omitempty"`
+}
+
+// getCachedPathInfo is getPathInfo through the PathInfoCache directory:
+// paths whose info is cached, fresh and still in the store are not asked
+// from nix, the others are queried together and cached. Path info of a
+// store path does not change while it is valid, so only deleted paths and
+// signatures added later can make an entry stale; PathInfoCacheTTL bounds
+// the latter.
+func (c *Client) getCachedPathInfo(ctx context.Context, storePaths []string) (map[string]*PathInfo, error) {
+	result := make(map[string]*PathInfo)
+	seen := make(map[string]bool)
+	queue := slices.Clone(storePaths)
+
+	var misses []string
+
+	for len(queue) > 0 {
+		storePath := queue[len(queue)-1]
+		queue = queue[:len(queue)-1]
+
+		if seen[storePath] {
+			continue
+		}
+
+		seen[storePath] = true
+
+		info := c.loadCachedPathInfo(storePath)
+		if info == nil {
+			misses = append(misses, storePath)
+
+			continue
+		}
+
+		result[storePath] = info
+
+		if !c.NoRecursive {
+			queue = append(queue, info.References...)
+		}
+	}
+
+	slog.Debug("Path info cache", "hits", len(result), "misses", len(misses))
+
+	if len(misses) == 0 {
+		return result, nil
+	}
+
+	getInfo := GetPathInfoRecursiveFromStore
+	if c.NoRecursive {
+		getInfo = GetPathInfoFromStore
+	}
+
+	fetched, err := getInfo(ctx, misses, c.NixEnv, "")
+	if err != nil {
+		return nil, err
+	}
+
+	for storePath, info := range fetched {
+		c.storeCachedPathInfo(storePath, info)
+	}
+
+	maps.Copy(result, fetched)
+
+	return result, nil
+}
+
+// pathInfoCacheFile returns the cache file of storePath, named by its hash.
+func (c *Client) pathInfoCacheFile(storePath string) (string, error) {
+	hash, err := GetStorePathHash(storePath)
+	if err != nil {
+		return "", err
+	}
+
+	return filepath.Join(c.PathInfoCache, hash+".json"), nil
+}
+
+// loadCachedPathInfo returns the cached info of storePath, or nil if there
+// is none, it expired or the path has left the store.
+func (c *Client) loadCachedPathInfo(storePath string) *PathInfo {
+	file, err := c.pathInfoCacheFile(storePath)
+	if err != nil {
+		return nil
+	}
+
+	stat, err := os.Stat(file)
+	if err != nil {
+		return nil
+	}
+
+	if _, err := os.Lstat(storePath); err != nil {
+		slog.Debug("Dropping cached path info of a path no longer in the store", "store_path", storePath)
+		_ = os.Remove(file)
+
+		return nil
+	}
+
+	if c.PathInfoCacheTTL > 0 && time.Since(stat.ModTime()) > c.PathInfoCacheTTL {
+		return nil
+	}
+
+	data, err := os.ReadFile(file)
+	if err != nil {
+		return nil
+	}
+
+	infos, err := parsePathInfoJSON(data)
+	if err != nil {
+		slog.Debug("Ignoring unreadable path info cache entry", "file", file, "error", err)
+
+		return nil
+	}
+
+	// Another store dir with the same hash, or a hash collision.
+	return infos[storePath]
+}
+
+// storeCachedPathInfo writes info to the cache. Failures only cost a nix
+// query next time, so they are logged and otherwise ignored.
+func (c *Client) storeCachedPathInfo(storePath string, info *PathInfo) {
+	if err := c.writeCachedPathInfo(storePath, info); err != nil {
+		slog.Warn("Failed to cache path info", "store_path", storePath, "error", err)
+	}
+}
+
+func (c *Client) writeCachedPathInfo(storePath string, info *PathInfo) error {
+	file, err := c.pathInfoCacheFile(storePath)
+	if err != nil {
+		return err
+	}
+
+	narHash, err := info.NarHash.nix32()
+	if err != nil {
+		return err
+	}
+
+	record := pathInfoCacheRecord{
+		NarHash:    narHash,
+		NarSize:    info.NarSize,
+		References: info.References,
+		Deriver:    info.Deriver,
+		Signatures: info.Signatures,
+	}
+
+	if info.CA != nil {
+		record.CA = info.CA.String()
+	}
+
+	data, err := json.Marshal(map[string]pathInfoCacheRecord{storePath: record})
+	if err != nil {
+		return fmt.Errorf("encoding path info: %w", err)
+	}
+
+	if err := os.MkdirAll(c.PathInfoCache, 0o750); err != nil {
+		return fmt.Errorf("creating path info cache: %w", err)
+	}
+
+	// Write and rename, so concurrent pushes sharing the cache never read a
+	// partial entry.
+	tmp, err := os.CreateTemp(c.PathInfoCache, ".tmp-*")
+	if err != nil {
+		return fmt.Errorf("creating path info cache entry: %w", err)
+	}
+
+	_, writeErr := tmp.Write(data)
+	if closeErr := tmp.Close(); writeErr == nil {
+		writeErr = closeErr
+	}
+
+	if writeErr == nil {
+		writeErr = os.Rename(tmp.Name(), file)
+	}
+
+	if writeErr != nil {
+		_ = os.Remove(tmp.Name())
+
+		return fmt.Errorf("writing path info cache entry: %w", writeErr)
+	}
+
+	return nil
+}
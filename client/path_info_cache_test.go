@@ -0,0 +1,106 @@
+package client_test
+
+import (
+	"context"
+	"fmt"
+	"os"
+	"path/filepath"
+	"strings"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+// fakeNixPathInfoOnly emits path-info JSON for exactly the arguments after
+// "--", and logs each path-info invocation.
+const fakeNixPathInfoOnly = `#!/bin/sh
+if [ "$1" = --version ]; then echo "nix (Nix) 2.24.0"; exit 0; fi
+echo call >> "$NIKS3_TEST_CALLS"
+while [ "$1" != "--" ]; do shift; done
+shift
+sep='{'
+for p in "$@"; do
+  printf '%s"%s":{"narHash":"sha256-FePFYIlMuycIXPZbWi7LGEiMmZSX9FMbaQenWBzm1Sc=","narSize":1,"references":[],"signatures":["test-1:c2ln"]}' "$sep" "$p"
+  sep=','
+done
+printf '}'
+`
+
+//nolint:paralleltest // modifies PATH via t.Setenv
+func TestPathInfoCache(t *testing.T) {
+	binDir := t.TempDir()
+	calls := filepath.Join(t.TempDir(), "calls")
+
+	if err := os.WriteFile(filepath.Join(binDir, "nix"), []byte(fakeNixPathInfoOnly), 0o755); err != nil { //nolint:gosec // fake nix must be executable
+		t.Fatal(err)
+	}
+
+	t.Setenv("PATH", binDir+string(os.PathListSeparator)+os.Getenv("PATH"))
+	t.Setenv("NIKS3_TEST_CALLS", calls)
+
+	nixCalls := func() int {
+		log, err := os.ReadFile(calls)
+		if err != nil && !os.IsNotExist(err) {
+			t.Fatal(err)
+		}
+
+		return strings.Count(string(log), "call")
+	}
+
+	// Cache entries are only trusted while their path exists, so the store
+	// paths are real files.
+	storeDir := t.TempDir()
+	paths := make([]string, 3)
+
+	for i := range paths {
+		paths[i] = filepath.Join(storeDir, fmt.Sprintf("%032d-pkg%d", i, i))
+		if err := os.WriteFile(paths[i], nil, 0o600); err != nil {
+			t.Fatal(err)
+		}
+	}
+
+	c := client.NewTestClientWithStoreDir(storeDir)
+	c.PathInfoCache = filepath.Join(t.TempDir(), "path-info")
+	c.NoRecursive = true
+
+	if _, err := c.GetPathInfo(context.Background(), paths); err != nil {
+		t.Fatal(err)
+	}
+
+	before := nixCalls()
+
+	infos, err := c.GetPathInfo(context.Background(), paths)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if n := nixCalls() - before; n != 0 {
+		t.Errorf("cached lookup ran nix %d times, want 0", n)
+	}
+
+	for _, p := range paths {
+		info := infos[p]
+		if info == nil {
+			t.Fatalf("no cached path info for %s", p)
+		}
+
+		if info.NarSize != 1 || len(info.Signatures) != 1 {
+			t.Errorf("cached path info of %s = %+v", p, info)
+		}
+	}
+
+	// A path that left the store is queried again.
+	if err := os.Remove(paths[0]); err != nil {
+		t.Fatal(err)
+	}
+
+	before = nixCalls()
+
+	if _, err := c.GetPathInfo(context.Background(), paths); err != nil {
+		t.Fatal(err)
+	}
+
+	if n := nixCalls() - before; n != 1 {
+		t.Errorf("lookup after deleting a path ran nix %d times, want 1", n)
+	}
+}
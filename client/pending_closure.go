@@ -4,16 +4,47 @@ import (
 	"bytes"
 	"context"
 	"encoding/json"
+	"errors"
 	"fmt"
 	"log/slog"
+	"maps"
 	"net/http"
+	"slices"
+	"strings"
+	"sync"
+	"time"
+
+	"github.com/Mic92/niks3/api"
+	"golang.org/x/sync/errgroup"
 )
 
+const (
+	// abortPendingClosuresTimeout bounds cleanup after a failed push, which may
+	// run after the caller's context was already cancelled.
+	abortPendingClosuresTimeout = 30 * time.Second
+
+	// completePendingClosuresBatchSize is how many ids one POST
+	// /api/pending_closures/complete request carries, below the server's
+	// limit of 10000.
+	completePendingClosuresBatchSize = 1000
+)
+
+// errCompletePendingClosuresUnsupported is returned by
+// CompletePendingClosures when the server predates batch completion.
+var errCompletePendingClosuresUnsupported = errors.New("server does not support completing pending closures in batches")
+
+// pendingClosuresCompleter is implemented by CacheAPIs that can complete
+// several pending closures with one request.
+type pendingClosuresCompleter interface {
+	CompletePendingClosures(ctx context.Context, closureIDs []string) ([]string, map[string]error, error)
+}
+
 // createPendingClosureRequest is the request to create a pending closure.
 type createPendingClosureRequest struct {
-	Closure  string           `json:"closure"`
-	Objects  []ObjectWithRefs `json:"objects"`
-	VerifyS3 bool             `json:"verify_s3,omitempty"`
+	Closure   string           `json:"closure"`
+	Objects   []ObjectWithRefs `json:"objects"`
+	VerifyS3  bool             `json:"verify_s3,omitempty"`
+	KeyPrefix string           `json:"key_prefix,omitempty"`
 }
 
 // PendingObject contains upload information for an object.
@@ -21,6 +52,8 @@ type PendingObject struct {
 	Type          string               `json:"type"`                     // Object type (narinfo, listing, build_log, nar)
 	PresignedURL  string               `json:"presigned_url,omitempty"`  // For small files
 	MultipartInfo *MultipartUploadInfo `json:"multipart_info,omitempty"` // For large files
+
+	closureID string // Pending closure the object is uploaded for (set by CreatePendingClosures)
 }
 
 // CreatePendingClosureResponse is the response from creating a pending closure.
@@ -31,13 +64,16 @@ type CreatePendingClosureResponse struct {
 }
 
 // CreatePendingClosure creates a pending closure and returns upload URLs.
+// With a KeyPrefix, keys are sent below it and the pending objects are
+// returned under their unprefixed keys.
 func (c *Client) CreatePendingClosure(ctx context.Context, closure string, objects []ObjectWithRefs, verifyS3 bool) (*CreatePendingClosureResponse, error) {
 	reqURL := c.baseURL.JoinPath("api/pending_closures")
 
 	reqBody := createPendingClosureRequest{
-		Closure:  closure,
-		Objects:  objects,
-		VerifyS3: verifyS3,
+		Closure:   c.wireKey(closure),
+		Objects:   c.wireObjects(objects),
+		VerifyS3:  verifyS3,
+		KeyPrefix: c.KeyPrefix,
 	}
 
 	jsonData, err := json.Marshal(reqBody)
@@ -60,7 +96,7 @@ func (c *Client) CreatePendingClosure(ctx context.Context, closure string, objec
 	defer deferCloseBody(resp)
 
 	if err := checkResponse(resp, http.StatusOK, http.StatusCreated); err != nil {
-		return nil, err
+		return nil, c.controlPlaneError(err)
 	}
 
 	var result CreatePendingClosureResponse
@@ -68,6 +104,15 @@ func (c *Client) CreatePendingClosure(ctx context.Context, closure string, objec
 		return nil, fmt.Errorf("decoding response: %w", err)
 	}
 
+	if c.KeyPrefix != "" {
+		pending := make(map[string]PendingObject, len(result.PendingObjects))
+		for key, obj := range result.PendingObjects {
+			pending[c.localKey(key)] = obj
+		}
+
+		result.PendingObjects = pending
+	}
+
 	slog.Debug("Created pending closure", "id", result.ID, "pending_objects", len(result.PendingObjects))
 
 	return &result, nil
@@ -76,12 +121,15 @@ func (c *Client) CreatePendingClosure(ctx context.Context, closure string, objec
 // NarinfoMetadata contains metadata for a narinfo file to be signed by the server.
 type NarinfoMetadata struct {
 	StorePath   string   `json:"store_path"`
-	URL         string   `json:"url"`         // e.g., "nar/xxxxx.nar.zst"
-	Compression string   `json:"compression"` // e.g., "zstd"
-	NarHash     string   `json:"nar_hash"`    // e.g., "sha256:xxxxx"
-	NarSize     uint64   `json:"nar_size"`    // Uncompressed NAR size
-	References  []string `json:"references"`  // Store paths (with /nix/store prefix)
+	URL         string   `json:"url"`                 // e.g., "nar/xxxxx.nar.zst"
+	Compression string   `json:"compression"`         // e.g., "zstd"
+	FileHash    string   `json:"file_hash,omitempty"` // Compressed NAR hash (nix32), unset for deduplicated NARs
+	FileSize    uint64   `json:"file_size,omitempty"` // Compressed NAR size
+	NarHash     string   `json:"nar_hash"`            // e.g., "sha256:xxxxx"
+	NarSize     uint64   `json:"nar_size"`            // Uncompressed NAR size
+	References  []string `json:"references"`          // Store paths (with /nix/store prefix)
 	Deriver     *string  `json:"deriver,omitempty"`
+	System      string   `json:"system,omitempty"`
 	Signatures  []string `json:"signatures,omitempty"`
 	CA          *string  `json:"ca,omitempty"`
 }
@@ -107,7 +155,7 @@ func (c *Client) CompletePendingClosure(ctx context.Context, closureID string) e
 	defer deferCloseBody(resp)
 
 	if err := checkResponse(resp, http.StatusOK, http.StatusNoContent); err != nil {
-		return err
+		return c.controlPlaneError(err)
 	}
 
 	slog.Debug("Completed pending closure", "id", closureID)
@@ -115,6 +163,188 @@ func (c *Client) CompletePendingClosure(ctx context.Context, closureID string) e
 	return nil
 }
 
+// CompletePendingClosures completes several pending closures with one
+// request. It returns the ids the server completed and, for those it could
+// not, the reason. Ids in neither were not tried and are still pending.
+func (c *Client) CompletePendingClosures(ctx context.Context, closureIDs []string) ([]string, map[string]error, error) {
+	reqBody, err := json.Marshal(api.CompletePendingClosuresRequest{IDs: closureIDs})
+	if err != nil {
+		return nil, nil, fmt.Errorf("marshaling request: %w", err)
+	}
+
+	reqURL := c.baseURL.JoinPath("api/pending_closures/complete")
+
+	req, err := http.NewRequestWithContext(ctx, http.MethodPost, reqURL.String(), bytes.NewReader(reqBody))
+	if err != nil {
+		return nil, nil, fmt.Errorf("creating request: %w", err)
+	}
+
+	req.Header.Set("Content-Type", "application/json")
+
+	// Not retried: the server may have committed some of the closures before
+	// the response was lost, and those would then be reported as not found.
+	resp, err := c.doServerRequestOnce(ctx, req)
+	if err != nil {
+		return nil, nil, fmt.Errorf("sending request: %w", err)
+	}
+
+	defer deferCloseBody(resp)
+
+	if resp.StatusCode == http.StatusNotFound || resp.StatusCode == http.StatusMethodNotAllowed {
+		return nil, nil, errCompletePendingClosuresUnsupported
+	}
+
+	if err := checkResponse(resp, http.StatusOK); err != nil {
+		return nil, nil, c.controlPlaneError(err)
+	}
+
+	var result api.CompletePendingClosuresResponse
+	if err := json.NewDecoder(resp.Body).Decode(&result); err != nil {
+		return nil, nil, fmt.Errorf("parsing response: %w", err)
+	}
+
+	failed := make(map[string]error, len(result.Failed))
+	for id, msg := range result.Failed {
+		failed[id] = errors.New(msg)
+	}
+
+	slog.Debug("Completed pending closures", "completed", len(result.Completed), "failed", len(failed))
+
+	return result.Completed, failed, nil
+}
+
+// completePendingClosures completes closureIDs in batches if the CacheAPI
+// supports it, else one request per closure, MaxConcurrentRequests at a
+// time. It tries every closure and returns those completed, with an error
+// naming the ids that could not be.
+func (c *Client) completePendingClosures(ctx context.Context, closureIDs []string) ([]string, error) {
+	completed := make([]string, 0, len(closureIDs))
+	failed := make(map[string]error)
+	remaining := closureIDs
+
+	if completer, ok := c.api().(pendingClosuresCompleter); ok {
+		for len(remaining) > 0 {
+			batch := remaining[:min(len(remaining), completePendingClosuresBatchSize)]
+
+			done, batchFailed, err := completer.CompletePendingClosures(ctx, batch)
+			if errors.Is(err, errCompletePendingClosuresUnsupported) {
+				slog.Debug("Server lacks batch completion, completing pending closures one by one")
+
+				break
+			}
+
+			if err != nil {
+				return completed, fmt.Errorf("completing pending closures: %w", err)
+			}
+
+			completed = append(completed, done...)
+			maps.Copy(failed, batchFailed)
+
+			// The server stops early when the request is cancelled; send the
+			// ids it did not get to again.
+			tried := make(map[string]bool, len(done)+len(batchFailed))
+			for _, id := range done {
+				tried[id] = true
+			}
+
+			for id := range batchFailed {
+				tried[id] = true
+			}
+
+			untried := slices.DeleteFunc(slices.Clone(batch), func(id string) bool { return tried[id] })
+			if len(untried) == len(batch) {
+				return completed, fmt.Errorf("completing pending closures: server completed none of %d", len(batch))
+			}
+
+			remaining = slices.Concat(untried, remaining[len(batch):])
+		}
+	}
+
+	if len(remaining) > 0 {
+		var (
+			mu sync.Mutex
+			g  errgroup.Group
+		)
+
+		g.SetLimit(max(c.MaxConcurrentRequests, 1))
+
+		for _, id := range remaining {
+			g.Go(func() error {
+				err := c.api().CompletePendingClosure(ctx, id)
+
+				mu.Lock()
+				defer mu.Unlock()
+
+				if err != nil {
+					failed[id] = err
+				} else {
+					completed = append(completed, id)
+				}
+
+				return nil
+			})
+		}
+
+		_ = g.Wait()
+	}
+
+	if len(failed) == 0 {
+		return completed, nil
+	}
+
+	ids := slices.Sorted(maps.Keys(failed))
+
+	errs := make([]error, 0, len(ids))
+	for _, id := range ids {
+		errs = append(errs, fmt.Errorf("pending closure %s: %w", id, failed[id]))
+	}
+
+	return completed, fmt.Errorf("failed to complete pending closures %s: %w", strings.Join(ids, ", "), errors.Join(errs...))
+}
+
+// AbortPendingClosure discards a pending closure that will not be completed,
+// so the server does not keep it around until its periodic cleanup.
+func (c *Client) AbortPendingClosure(ctx context.Context, closureID string) error {
+	reqURL := c.baseURL.JoinPath("api/pending_closures", closureID)
+
+	req, err := http.NewRequestWithContext(ctx, http.MethodDelete, reqURL.String(), http.NoBody)
+	if err != nil {
+		return fmt.Errorf("creating request: %w", err)
+	}
+
+	resp, err := c.DoServerRequest(ctx, req)
+	if err != nil {
+		return fmt.Errorf("sending request: %w", err)
+	}
+
+	defer deferCloseBody(resp)
+
+	if err := checkResponse(resp, http.StatusOK, http.StatusNoContent); err != nil {
+		return err
+	}
+
+	slog.Info("Aborted pending closure", "id", closureID)
+
+	return nil
+}
+
+// abortPendingClosures aborts every closure in closureIDs. It runs on error
+// paths, so it ignores cancellation of ctx and only logs failures.
+func (c *Client) abortPendingClosures(ctx context.Context, closureIDs []string) {
+	if len(closureIDs) == 0 {
+		return
+	}
+
+	ctx, cancel := context.WithTimeout(context.WithoutCancel(ctx), abortPendingClosuresTimeout)
+	defer cancel()
+
+	for _, id := range closureIDs {
+		if err := c.api().AbortPendingClosure(ctx, id); err != nil {
+			slog.Warn("Failed to abort pending closure", "id", id, "error", err)
+		}
+	}
+}
+
 type signNarinfosRequest struct {
 	Narinfos map[string]NarinfoMetadata `json:"narinfos"`
 }
@@ -128,7 +358,11 @@ func (c *Client) SignPendingClosure(ctx context.Context, closureID string, narin
 	reqURL := c.baseURL.JoinPath("api/pending_closures", closureID, "sign")
 
 	reqBody := signNarinfosRequest{
-		Narinfos: narinfos,
+		Narinfos: make(map[string]NarinfoMetadata, len(narinfos)),
+	}
+
+	for key, meta := range narinfos {
+		reqBody.Narinfos[c.wireKey(key)] = meta
 	}
 
 	jsonData, err := json.Marshal(reqBody)
@@ -161,5 +395,111 @@ func (c *Client) SignPendingClosure(ctx context.Context, closureID string, narin
 
 	slog.Debug("Signed narinfos", "id", closureID, "count", len(result.Signatures))
 
-	return result.Signatures, nil
+	signatures := make(map[string][]string, len(result.Signatures))
+	for key, sigs := range result.Signatures {
+		signatures[c.localKey(key)] = sigs
+	}
+
+	return signatures, nil
+}
+
+type presignObjectRequest struct {
+	ObjectKey string `json:"object_key"`
+}
+
+type presignObjectResponse struct {
+	PresignedURL string `json:"presigned_url"`
+}
+
+// RefreshPresignedURL asks the server for a new presigned PUT URL for
+// objectKey of the pending closure closureID, through
+// POST /api/pending_closures/{id}/presign. The server only signs keys the
+// pending closure was created with.
+func (c *Client) RefreshPresignedURL(ctx context.Context, closureID, objectKey string) (string, error) {
+	reqURL := c.baseURL.JoinPath("api/pending_closures", closureID, "presign")
+
+	jsonData, err := json.Marshal(presignObjectRequest{ObjectKey: c.wireKey(objectKey)})
+	if err != nil {
+		return "", fmt.Errorf("marshaling request: %w", err)
+	}
+
+	req, err := http.NewRequestWithContext(ctx, http.MethodPost, reqURL.String(), bytes.NewReader(jsonData))
+	if err != nil {
+		return "", fmt.Errorf("creating request: %w", err)
+	}
+
+	req.Header.Set("Content-Type", "application/json")
+
+	resp, err := c.DoServerRequest(ctx, req)
+	if err != nil {
+		return "", fmt.Errorf("sending request: %w", err)
+	}
+
+	defer deferCloseBody(resp)
+
+	if err := checkResponse(resp, http.StatusOK); err != nil {
+		return "", err
+	}
+
+	var result presignObjectResponse
+	if err := json.NewDecoder(resp.Body).Decode(&result); err != nil {
+		return "", fmt.Errorf("decoding response: %w", err)
+	}
+
+	if result.PresignedURL == "" {
+		return "", fmt.Errorf("server returned no presigned URL for %s", objectKey)
+	}
+
+	return result.PresignedURL, nil
+}
+
+// controlPlaneError tells apart the reasons a pending closure request can
+// fail for good. DoServerRequest already retried throttling (429/503) and
+// server errors, honouring Retry-After, but never retries 401/403: those
+// are auth failures no retry can fix.
+func (c *Client) controlPlaneError(err error) error {
+	var statusErr *HTTPStatusError
+	if !errors.As(err, &statusErr) {
+		return err
+	}
+
+	switch statusErr.StatusCode {
+	case http.StatusUnauthorized, http.StatusForbidden:
+		return fmt.Errorf("authentication failed, check the auth token: %w", err)
+	case http.StatusTooManyRequests, http.StatusServiceUnavailable:
+		return fmt.Errorf("server still overloaded after %d attempts: %w", c.Retry.MaxRetries+1, err)
+	default:
+		return err
+	}
+}
+
+// isPresignedURLExpired reports whether S3 rejected a presigned request
+// because its signature expired. S3 and MinIO answer 403 AccessDenied with
+// "Request has expired"; other 403s are not retried.
+func isPresignedURLExpired(err error) bool {
+	var statusErr *HTTPStatusError
+
+	return errors.As(err, &statusErr) &&
+		statusErr.StatusCode == http.StatusForbidden &&
+		strings.Contains(statusErr.Body, "Request has expired")
+}
+
+// withPresignedURL runs upload with obj's presigned URL. Slow compression
+// of a large closure can outlive the URL, so if S3 reports it expired the
+// URL is re-minted once and the upload repeated; upload must therefore be
+// safe to call twice.
+func (c *Client) withPresignedURL(ctx context.Context, key string, obj PendingObject, upload func(presignedURL string) error) error {
+	err := upload(obj.PresignedURL)
+	if err == nil || !isPresignedURLExpired(err) || obj.closureID == "" {
+		return err
+	}
+
+	slog.Warn("Presigned URL expired, requesting a new one", "key", key)
+
+	presignedURL, refreshErr := c.api().RefreshPresignedURL(ctx, obj.closureID, key)
+	if refreshErr != nil {
+		return fmt.Errorf("%w (refreshing presigned URL: %w)", err, refreshErr)
+	}
+
+	return upload(presignedURL)
 }
@@ -0,0 +1,425 @@
+package client_test
+
+import (
+	"context"
+	"encoding/json"
+	"errors"
+	"net/http"
+	"net/http/httptest"
+	"slices"
+	"strings"
+	"sync"
+	"sync/atomic"
+	"testing"
+	"time"
+
+	"github.com/Mic92/niks3/api"
+	"github.com/Mic92/niks3/client"
+)
+
+// TestCreatePendingClosures_MergesDeterministically creates closures that
+// share an object in parallel and checks that the shared object always comes
+// from the first closure, regardless of which response arrives first.
+func TestCreatePendingClosures_MergesDeterministically(t *testing.T) {
+	t.Parallel()
+
+	var inFlight, maxInFlight atomic.Int32
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if r.Method != http.MethodPost || r.URL.Path != "/api/pending_closures" {
+			http.Error(w, "unexpected request", http.StatusBadRequest)
+
+			return
+		}
+
+		n := inFlight.Add(1)
+		defer inFlight.Add(-1)
+
+		for {
+			m := maxInFlight.Load()
+			if n <= m || maxInFlight.CompareAndSwap(m, n) {
+				break
+			}
+		}
+
+		var req struct {
+			Closure string `json:"closure"`
+		}
+		if err := json.NewDecoder(r.Body).Decode(&req); err != nil {
+			http.Error(w, err.Error(), http.StatusBadRequest)
+
+			return
+		}
+
+		id := strings.TrimSuffix(req.Closure, ".narinfo")
+
+		_ = json.NewEncoder(w).Encode(client.CreatePendingClosureResponse{
+			ID: id,
+			PendingObjects: map[string]client.PendingObject{
+				req.Closure: {Type: "narinfo", PresignedURL: "http://s3/" + id},
+				"shared.narinfo": {Type: "narinfo", PresignedURL: "http://s3/shared-" + id},
+			},
+		})
+	}))
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	c.MaxConcurrentRequests = 2
+
+	var closures []client.ClosureInfo
+
+	for _, key := range []string{"a.narinfo", "b.narinfo", "c.narinfo", "d.narinfo"} {
+		closures = append(closures, client.ClosureInfo{NarinfoKey: key, Objects: []client.ObjectWithRefs{
+			{Key: key, Type: client.ObjectTypeNarinfo},
+			{Key: "shared.narinfo", Type: client.ObjectTypeNarinfo},
+		}})
+	}
+
+	for range 10 {
+		pending, ids, err := c.CreatePendingClosures(context.Background(), closures)
+		if err != nil {
+			t.Fatal(err)
+		}
+
+		if len(ids) != len(closures) || ids["c"] != "c.narinfo" {
+			t.Fatalf("unexpected closure ids: %v", ids)
+		}
+
+		if len(pending) != len(closures)+1 {
+			t.Fatalf("expected %d pending objects, got %d", len(closures)+1, len(pending))
+		}
+
+		if got := pending["shared.narinfo"].PresignedURL; got != "http://s3/shared-a" {
+			t.Fatalf("shared object should come from the first closure, got %s", got)
+		}
+	}
+
+	if got := maxInFlight.Load(); got > 2 {
+		t.Fatalf("expected at most 2 concurrent requests, got %d", got)
+	}
+}
+
+// TestExpiredPresignedURLIsRefreshed checks that an upload rejected with an
+// expired signature asks the server for a new URL and retries exactly once.
+func TestExpiredPresignedURLIsRefreshed(t *testing.T) {
+	t.Parallel()
+
+	var refreshes, freshPuts atomic.Int32
+
+	var srv *httptest.Server
+
+	srv = httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		switch {
+		case r.Method == http.MethodPut && r.URL.Path == "/s3/stale":
+			w.WriteHeader(http.StatusForbidden)
+			_, _ = w.Write([]byte(`<Error><Code>AccessDenied</Code><Message>Request has expired</Message></Error>`))
+		case r.Method == http.MethodPut && r.URL.Path == "/s3/denied":
+			w.WriteHeader(http.StatusForbidden)
+			_, _ = w.Write([]byte(`<Error><Code>AccessDenied</Code><Message>Access Denied</Message></Error>`))
+		case r.Method == http.MethodPut && r.URL.Path == "/s3/fresh":
+			freshPuts.Add(1)
+		case r.Method == http.MethodPost && r.URL.Path == "/api/pending_closures/42/presign":
+			refreshes.Add(1)
+
+			var req struct {
+				ObjectKey string `json:"object_key"`
+			}
+			if err := json.NewDecoder(r.Body).Decode(&req); err != nil || req.ObjectKey != "abc.ls" {
+				http.Error(w, "bad request", http.StatusBadRequest)
+
+				return
+			}
+
+			_ = json.NewEncoder(w).Encode(map[string]string{"presigned_url": srv.URL + "/s3/fresh"})
+		default:
+			http.Error(w, "unexpected request", http.StatusBadRequest)
+		}
+	}))
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if err := c.UploadBytesWithPresignedURL(context.Background(), "42", "abc.ls", srv.URL+"/s3/stale", []byte("data")); err != nil {
+		t.Fatalf("upload with expired URL: %v", err)
+	}
+
+	if refreshes.Load() != 1 || freshPuts.Load() != 1 {
+		t.Fatalf("expected one refresh and one retried PUT, got %d and %d", refreshes.Load(), freshPuts.Load())
+	}
+
+	// Other 403s are permission problems and must not trigger a refresh.
+	err = c.UploadBytesWithPresignedURL(context.Background(), "42", "abc.ls", srv.URL+"/s3/denied", []byte("data"))
+	if err == nil {
+		t.Fatal("expected upload with a denied URL to fail")
+	}
+
+	if refreshes.Load() != 1 {
+		t.Fatalf("unexpected refresh for a non-expiry error")
+	}
+}
+
+// TestCreatePendingClosureHonorsRetryAfter checks that a throttled pending
+// closure request is retried after the delay the server asked for.
+func TestCreatePendingClosureHonorsRetryAfter(t *testing.T) {
+	t.Parallel()
+
+	var requests atomic.Int32
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, _ *http.Request) {
+		if requests.Add(1) == 1 {
+			w.Header().Set("Retry-After", "1")
+			http.Error(w, "slow down", http.StatusTooManyRequests)
+
+			return
+		}
+
+		_ = json.NewEncoder(w).Encode(client.CreatePendingClosureResponse{ID: "1"})
+	}))
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	start := time.Now()
+
+	resp, err := c.CreatePendingClosure(context.Background(), "a.narinfo", nil, false)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if resp.ID != "1" || requests.Load() != 2 {
+		t.Errorf("got closure %q after %d requests, want closure 1 after 2", resp.ID, requests.Load())
+	}
+
+	if elapsed := time.Since(start); elapsed < time.Second {
+		t.Errorf("retried after %v, before Retry-After elapsed", elapsed)
+	}
+}
+
+// TestPendingClosureAuthErrorNotRetried checks that a rejected token fails
+// the control-plane calls at once, with an error that names the cause.
+func TestPendingClosureAuthErrorNotRetried(t *testing.T) {
+	t.Parallel()
+
+	var requests atomic.Int32
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, _ *http.Request) {
+		requests.Add(1)
+		http.Error(w, "invalid token", http.StatusUnauthorized)
+	}))
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	_, createErr := c.CreatePendingClosure(context.Background(), "a.narinfo", nil, false)
+	completeErr := c.CompletePendingClosure(context.Background(), "1")
+
+	for _, err := range []error{createErr, completeErr} {
+		var statusErr *client.HTTPStatusError
+		if !errors.As(err, &statusErr) || statusErr.StatusCode != http.StatusUnauthorized {
+			t.Fatalf("expected a 401 HTTPStatusError, got %v", err)
+		}
+
+		if !strings.Contains(err.Error(), "authentication failed") {
+			t.Errorf("error %q does not name the auth failure", err)
+		}
+	}
+
+	if requests.Load() != 2 {
+		t.Errorf("server got %d requests, want one per call", requests.Load())
+	}
+}
+
+// TestCompletePendingClosures completes closures in one batch request and,
+// against a server without the batch endpoint, one request per closure.
+// Either way the closures the server failed to complete are reported.
+func TestCompletePendingClosures(t *testing.T) {
+	t.Parallel()
+
+	batch := http.NewServeMux()
+	batch.HandleFunc("POST /api/pending_closures/complete", func(w http.ResponseWriter, r *http.Request) {
+		var req api.CompletePendingClosuresRequest
+		if err := json.NewDecoder(r.Body).Decode(&req); err != nil {
+			http.Error(w, err.Error(), http.StatusBadRequest)
+
+			return
+		}
+
+		resp := api.CompletePendingClosuresResponse{Failed: map[string]string{}}
+
+		for _, id := range req.IDs {
+			if id == "3" {
+				resp.Failed[id] = "pending closure not found"
+			} else {
+				resp.Completed = append(resp.Completed, id)
+			}
+		}
+
+		_ = json.NewEncoder(w).Encode(resp)
+	})
+
+	// An older server only has the per-closure routes, so the batch
+	// request gets 405 Method Not Allowed.
+	var perClosure atomic.Int32
+
+	legacy := http.NewServeMux()
+	legacy.HandleFunc("DELETE /api/pending_closures/{id}", func(w http.ResponseWriter, _ *http.Request) {
+		http.Error(w, "unexpected abort", http.StatusBadRequest)
+	})
+	legacy.HandleFunc("POST /api/pending_closures/{id}/complete", func(w http.ResponseWriter, r *http.Request) {
+		perClosure.Add(1)
+
+		if r.PathValue("id") == "3" {
+			http.Error(w, "pending closure not found", http.StatusNotFound)
+
+			return
+		}
+
+		w.WriteHeader(http.StatusNoContent)
+	})
+
+	for name, handler := range map[string]http.Handler{"batch": batch, "legacy": legacy} {
+		t.Run(name, func(t *testing.T) {
+			t.Parallel()
+
+			srv := httptest.NewServer(handler)
+			defer srv.Close()
+
+			c, err := client.NewTestClientForServer(srv.URL)
+			if err != nil {
+				t.Fatal(err)
+			}
+
+			c.Retry.MaxRetries = 0
+			c.MaxConcurrentRequests = 2
+
+			completed, err := c.CompleteAllPendingClosures(context.Background(), []string{"1", "2", "3"})
+			if err == nil || !strings.Contains(err.Error(), "pending closures 3:") {
+				t.Errorf("expected an error naming closure 3, got %v", err)
+			}
+
+			slices.Sort(completed)
+
+			if !slices.Equal(completed, []string{"1", "2"}) {
+				t.Errorf("completed = %v, want [1 2]", completed)
+			}
+
+			if name == "legacy" && perClosure.Load() != 3 {
+				t.Errorf("legacy server got %d completions, want 3", perClosure.Load())
+			}
+		})
+	}
+}
+
+// TestCompletePendingClosures_CancelledMidBatch has the first batch request
+// die after committing one closure. The batch request is not retried, which
+// would report the committed closure as not found; the ids the server did
+// not get to are sent again instead.
+func TestCompletePendingClosures_CancelledMidBatch(t *testing.T) {
+	t.Parallel()
+
+	var (
+		mu        sync.Mutex
+		requests  [][]string
+		committed = map[string]bool{}
+	)
+
+	mux := http.NewServeMux()
+	mux.HandleFunc("POST /api/pending_closures/complete", func(w http.ResponseWriter, r *http.Request) {
+		var req api.CompletePendingClosuresRequest
+		if err := json.NewDecoder(r.Body).Decode(&req); err != nil {
+			http.Error(w, err.Error(), http.StatusBadRequest)
+
+			return
+		}
+
+		mu.Lock()
+		defer mu.Unlock()
+
+		requests = append(requests, req.IDs)
+
+		resp := api.CompletePendingClosuresResponse{Failed: map[string]string{}}
+
+		for _, id := range req.IDs {
+			if committed[id] {
+				resp.Failed[id] = "pending closure not found"
+
+				continue
+			}
+
+			committed[id] = true
+
+			resp.Completed = append(resp.Completed, id)
+
+			// The first request is cancelled after its first commit.
+			if len(requests) == 1 {
+				break
+			}
+		}
+
+		_ = json.NewEncoder(w).Encode(resp)
+	})
+
+	srv := httptest.NewServer(mux)
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	completed, err := c.CompleteAllPendingClosures(context.Background(), []string{"1", "2", "3"})
+	if err != nil {
+		t.Fatalf("CompleteAllPendingClosures: %v", err)
+	}
+
+	if !slices.Equal(completed, []string{"1", "2", "3"}) {
+		t.Errorf("completed = %v, want [1 2 3]", completed)
+	}
+
+	mu.Lock()
+	defer mu.Unlock()
+
+	if len(requests) != 2 || !slices.Equal(requests[1], []string{"2", "3"}) {
+		t.Errorf("requests = %v, want [[1 2 3] [2 3]]", requests)
+	}
+}
+
+// TestCompletePendingClosures_NotRetried checks that a failed batch request
+// is reported instead of being sent again.
+func TestCompletePendingClosures_NotRetried(t *testing.T) {
+	t.Parallel()
+
+	var requests atomic.Int32
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, _ *http.Request) {
+		requests.Add(1)
+		http.Error(w, "request cancelled", http.StatusServiceUnavailable)
+	}))
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if _, err := c.CompleteAllPendingClosures(context.Background(), []string{"1"}); err == nil {
+		t.Error("expected an error")
+	}
+
+	if requests.Load() != 1 {
+		t.Errorf("server got %d requests, want 1", requests.Load())
+	}
+}
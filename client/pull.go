@@ -0,0 +1,313 @@
+package client
+
+import (
+	"bytes"
+	"compress/bzip2"
+	"context"
+	"errors"
+	"fmt"
+	"io"
+	"log/slog"
+	"net/http"
+	"os"
+	"os/exec"
+	"path"
+	"path/filepath"
+
+	"github.com/klauspost/compress/zstd"
+	"golang.org/x/sync/errgroup"
+)
+
+// ErrNotInCache is returned when the cache has no object for a requested
+// narinfo or NAR.
+var ErrNotInCache = errors.New("not found in cache")
+
+// FetchNarinfo downloads and parses <hash>.narinfo from the cache served by
+// the niks3 read proxy at the client's server URL.
+func (c *Client) FetchNarinfo(ctx context.Context, storePath string) (*NarinfoMetadata, error) {
+	hash, err := GetStorePathHash(storePath)
+	if err != nil {
+		return nil, err
+	}
+
+	body, err := c.fetchCacheObject(ctx, hash+".narinfo")
+	if err != nil {
+		return nil, err
+	}
+
+	defer closeResponseBody(body)
+
+	content, err := io.ReadAll(body)
+	if err != nil {
+		return nil, fmt.Errorf("reading narinfo for %s: %w", storePath, err)
+	}
+
+	meta, err := ParseNarinfo(string(content))
+	if err != nil {
+		return nil, fmt.Errorf("parsing narinfo for %s: %w", storePath, err)
+	}
+
+	if meta.StorePath != storePath {
+		return nil, fmt.Errorf("narinfo for %s describes %s", storePath, meta.StorePath)
+	}
+
+	return meta, nil
+}
+
+// fetchCacheObject GETs a cache object relative to the server URL. Bodies
+// still carrying Content-Encoding: zstd (narinfos fetched straight from S3)
+// are decompressed.
+func (c *Client) fetchCacheObject(ctx context.Context, key string) (io.ReadCloser, error) {
+	reqURL := c.baseURL.JoinPath(key)
+
+	req, err := http.NewRequestWithContext(ctx, http.MethodGet, reqURL.String(), nil)
+	if err != nil {
+		return nil, fmt.Errorf("creating request: %w", err)
+	}
+
+	// NARs can be large, so this is a transfer rather than a control call.
+	resp, err := c.doServerRequest(ctx, req, c.TransferTimeout)
+	if err != nil {
+		return nil, fmt.Errorf("fetching %s: %w", key, err)
+	}
+
+	switch resp.StatusCode {
+	case http.StatusOK:
+	case http.StatusNotFound:
+		closeResponseBody(resp.Body)
+
+		return nil, fmt.Errorf("%s: %w", key, ErrNotInCache)
+	default:
+		closeResponseBody(resp.Body)
+
+		return nil, fmt.Errorf("fetching %s: unexpected status %d", key, resp.StatusCode)
+	}
+
+	if resp.Header.Get("Content-Encoding") != compressionZstd {
+		return resp.Body, nil
+	}
+
+	decoder, err := zstd.NewReader(resp.Body)
+	if err != nil {
+		closeResponseBody(resp.Body)
+
+		return nil, fmt.Errorf("creating zstd decoder: %w", err)
+	}
+
+	return zstdBody{Decoder: decoder, body: resp.Body}, nil
+}
+
+// zstdBody decompresses a response body and releases both on Close.
+type zstdBody struct {
+	*zstd.Decoder
+
+	body io.ReadCloser
+}
+
+func (z zstdBody) Close() error {
+	z.Decoder.Close()
+
+	return z.body.Close() //nolint:wrapcheck // plain body close
+}
+
+// narDecompressor wraps r with a decoder for a narinfo Compression value.
+func narDecompressor(compression string, r io.Reader) (io.Reader, func(), error) {
+	switch compression {
+	case string(CompressionNone):
+		return r, func() {}, nil
+	case string(CompressionZstd):
+		// Accept windows up to what --zstd-long writes, as Nix does; the
+		// decoder then holds up to 2^MaxZstdWindowLog bytes of history.
+		decoder, err := zstd.NewReader(r, zstd.WithDecoderMaxWindow(1<<MaxZstdWindowLog))
+		if err != nil {
+			return nil, nil, fmt.Errorf("creating zstd decoder: %w", err)
+		}
+
+		return decoder, decoder.Close, nil
+	case string(CompressionXz):
+		return newXzDecompressor(r)
+	case string(CompressionBrotli):
+		return newBrotliDecompressor(r)
+	case "bzip2":
+		return bzip2.NewReader(r), func() {}, nil
+	default:
+		return nil, nil, fmt.Errorf("unsupported NAR compression %q", compression)
+	}
+}
+
+// isInLocalStore reports whether storePath is valid in the local Nix store.
+func isInLocalStore(ctx context.Context, storePath string, nixEnv []string) bool {
+	var cmd *exec.Cmd
+
+	if detectNixCLI().unified {
+		cmd = exec.CommandContext(ctx, "nix", "--extra-experimental-features", "nix-command", "path-info", "--", storePath)
+		if len(nixEnv) > 0 {
+			cmd.Env = nixEnv
+		}
+	} else {
+		cmd = nixStoreCommand(ctx, nixEnv, "", "--check-validity", storePath)
+	}
+
+	cmd.Stdout = io.Discard
+	cmd.Stderr = io.Discard
+
+	return cmd.Run() == nil
+}
+
+// PullPaths fetches the closures of storePaths from the cache, verifies each
+// NAR against its narinfo NarHash/NarSize and restores it at
+// destRoot/<store path>. Paths valid in the local Nix store or already
+// present under destRoot are skipped. Restored paths are not registered
+// with Nix. Returns the store paths that were restored.
+func (c *Client) PullPaths(ctx context.Context, storePaths []string, destRoot string) ([]string, error) {
+	// Resolve the closure by walking References breadth-first
+	narinfos := make(map[string]*NarinfoMetadata)
+	queue := append([]string(nil), storePaths...)
+
+	for len(queue) > 0 {
+		storePath := queue[0]
+		queue = queue[1:]
+
+		if _, seen := narinfos[storePath]; seen {
+			continue
+		}
+
+		meta, err := c.FetchNarinfo(ctx, storePath)
+		if err != nil {
+			return nil, err
+		}
+
+		narinfos[storePath] = meta
+
+		for _, ref := range meta.References {
+			if _, seen := narinfos[ref]; !seen {
+				queue = append(queue, ref)
+			}
+		}
+	}
+
+	slog.Info(fmt.Sprintf("Closure contains %d paths", len(narinfos)))
+
+	var toFetch []*NarinfoMetadata
+
+	for storePath, meta := range narinfos {
+		if _, err := os.Lstat(filepath.Join(destRoot, storePath)); err == nil {
+			slog.Debug("Skipping path already restored", "store_path", storePath)
+
+			continue
+		}
+
+		if isInLocalStore(ctx, storePath, c.NixEnv) {
+			slog.Debug("Skipping path present in local store", "store_path", storePath)
+
+			continue
+		}
+
+		toFetch = append(toFetch, meta)
+	}
+
+	slog.Info(fmt.Sprintf("Pulling %d paths (%d already present)", len(toFetch), len(narinfos)-len(toFetch)))
+
+	numWorkers := c.MaxConcurrentNARUploads
+	if numWorkers <= 0 {
+		numWorkers = 1
+	}
+
+	g, ctx := errgroup.WithContext(ctx)
+	g.SetLimit(numWorkers)
+
+	pulled := make([]string, len(toFetch))
+
+	for i, meta := range toFetch {
+		g.Go(func() error {
+			if err := c.pullNAR(ctx, meta, filepath.Join(destRoot, meta.StorePath)); err != nil {
+				return err
+			}
+
+			pulled[i] = meta.StorePath
+
+			return nil
+		})
+	}
+
+	if err := g.Wait(); err != nil {
+		return nil, err //nolint:wrapcheck // errgroup returns the first task's already-wrapped error
+	}
+
+	return pulled, nil
+}
+
+// pullNAR downloads one NAR, restores it at dest and checks it against the
+// narinfo. A NAR that fails verification is removed again.
+func (c *Client) pullNAR(ctx context.Context, meta *NarinfoMetadata, dest string) error {
+	algo, expected, err := DecodeNixHash(meta.NarHash)
+	if err != nil {
+		return fmt.Errorf("parsing NarHash of %s: %w", meta.StorePath, err)
+	}
+
+	h, err := algo.New()
+	if err != nil {
+		return fmt.Errorf("checking NarHash of %s: %w", meta.StorePath, err)
+	}
+
+	body, err := c.fetchCacheObject(ctx, meta.URL)
+	if err != nil {
+		return err
+	}
+
+	defer closeResponseBody(body)
+
+	nar, release, err := narDecompressor(meta.Compression, body)
+	if err != nil {
+		return fmt.Errorf("pulling %s: %w", meta.StorePath, err)
+	}
+	defer release()
+
+	if err := os.MkdirAll(filepath.Dir(dest), 0o755); err != nil {
+		return fmt.Errorf("creating %s: %w", filepath.Dir(dest), err)
+	}
+
+	counter := &countingWriter{}
+	tee := io.TeeReader(nar, io.MultiWriter(h, counter))
+
+	if err := c.NarOptions.RestorePath(tee, dest); err != nil {
+		removeRestored(dest)
+
+		return fmt.Errorf("restoring %s: %w", meta.StorePath, err)
+	}
+
+	// RestorePath may stop before EOF; hash whatever trails the archive
+	// so corrupt or oversized downloads are caught too.
+	if _, err := io.Copy(io.Discard, tee); err != nil {
+		removeRestored(dest)
+
+		return fmt.Errorf("reading NAR for %s: %w", meta.StorePath, err)
+	}
+
+	if counter.n != meta.NarSize || !bytes.Equal(h.Sum(nil), expected) {
+		removeRestored(dest)
+
+		return fmt.Errorf("NAR for %s does not match narinfo (size %d, expected %d)", meta.StorePath, counter.n, meta.NarSize)
+	}
+
+	slog.Info("Pulled " + path.Base(meta.StorePath))
+
+	return nil
+}
+
+// countingWriter counts bytes written to it.
+type countingWriter struct {
+	n uint64
+}
+
+func (w *countingWriter) Write(p []byte) (int, error) {
+	w.n += uint64(len(p))
+
+	return len(p), nil
+}
+
+func removeRestored(dest string) {
+	if err := os.RemoveAll(dest); err != nil {
+		slog.Error("Failed to remove partially restored path", "path", dest, "error", err)
+	}
+}
@@ -0,0 +1,176 @@
+package client_test
+
+import (
+	"bytes"
+	"context"
+	"crypto/sha256"
+	"errors"
+	"net/http"
+	"net/http/httptest"
+	"os"
+	"path/filepath"
+	"strings"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+	"github.com/klauspost/compress/zstd"
+)
+
+const (
+	pullPathA = "/nix/store/0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a-a"
+	pullPathB = "/nix/store/0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b-b"
+)
+
+// pullFixture serves a two-path closure (A references B) the way the read
+// proxy does: decompressed narinfos and zstd NARs.
+func pullFixture(t *testing.T, corruptB bool) *httptest.Server {
+	t.Helper()
+
+	objects := make(map[string][]byte)
+
+	addPath := func(storePath string, refs []string, corrupt bool) {
+		t.Helper()
+
+		src := filepath.Join(t.TempDir(), "src")
+		makeMixedTree(t, src)
+
+		var nar bytes.Buffer
+		if _, _, err := client.DumpPathWithDigest(&nar, src); err != nil {
+			t.Fatal(err)
+		}
+
+		sum := sha256.Sum256(nar.Bytes())
+		narHash := "sha256:" + client.EncodeNixBase32(sum[:])
+
+		if corrupt {
+			sum = sha256.Sum256([]byte("something else"))
+			narHash = "sha256:" + client.EncodeNixBase32(sum[:])
+		}
+
+		var compressed bytes.Buffer
+
+		enc, err := zstd.NewWriter(&compressed)
+		if err != nil {
+			t.Fatal(err)
+		}
+
+		if _, err := enc.Write(nar.Bytes()); err != nil {
+			t.Fatal(err)
+		}
+
+		if err := enc.Close(); err != nil {
+			t.Fatal(err)
+		}
+
+		hash := strings.SplitN(filepath.Base(storePath), "-", 2)[0]
+		narKey := "nar/" + hash + ".nar.zst"
+		objects[narKey] = compressed.Bytes()
+
+		meta := &client.NarinfoMetadata{
+			StorePath:   storePath,
+			URL:         narKey,
+			Compression: "zstd",
+			NarHash:     narHash,
+			NarSize:     uint64(nar.Len()),
+			References:  refs,
+		}
+		objects[hash+".narinfo"] = []byte(client.GenerateNarinfoContent(meta, nil))
+	}
+
+	addPath(pullPathA, []string{pullPathA, pullPathB}, false)
+	addPath(pullPathB, nil, corruptB)
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		data, ok := objects[strings.TrimPrefix(r.URL.Path, "/")]
+		if !ok {
+			http.NotFound(w, r)
+
+			return
+		}
+
+		if _, err := w.Write(data); err != nil {
+			t.Errorf("writing response: %v", err)
+		}
+	}))
+	t.Cleanup(srv.Close)
+
+	return srv
+}
+
+func TestPullPaths(t *testing.T) {
+	t.Parallel()
+
+	srv := pullFixture(t, false)
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	dest := t.TempDir()
+
+	pulled, err := c.PullPaths(context.Background(), []string{pullPathA}, dest)
+	if err != nil {
+		t.Fatalf("PullPaths: %v", err)
+	}
+
+	if len(pulled) != 2 {
+		t.Errorf("expected the whole closure to be pulled, got %v", pulled)
+	}
+
+	for _, storePath := range []string{pullPathA, pullPathB} {
+		var got, want bytes.Buffer
+
+		if _, err := client.DumpPathWithListing(&got, filepath.Join(dest, storePath)); err != nil {
+			t.Fatalf("dumping restored %s: %v", storePath, err)
+		}
+
+		src := filepath.Join(t.TempDir(), "src")
+		makeMixedTree(t, src)
+
+		if _, err := client.DumpPathWithListing(&want, src); err != nil {
+			t.Fatal(err)
+		}
+
+		if !bytes.Equal(got.Bytes(), want.Bytes()) {
+			t.Errorf("restored %s differs from the original tree", storePath)
+		}
+	}
+
+	// A second pull finds everything already restored
+	pulled, err = c.PullPaths(context.Background(), []string{pullPathA}, dest)
+	if err != nil {
+		t.Fatalf("second PullPaths: %v", err)
+	}
+
+	if len(pulled) != 0 {
+		t.Errorf("expected nothing to be pulled again, got %v", pulled)
+	}
+}
+
+func TestPullPathsHashMismatch(t *testing.T) {
+	t.Parallel()
+
+	srv := pullFixture(t, true)
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	dest := t.TempDir()
+
+	_, err = c.PullPaths(context.Background(), []string{pullPathB}, dest)
+	if err == nil {
+		t.Fatal("expected NarHash mismatch to fail the pull")
+	}
+
+	if _, statErr := os.Lstat(filepath.Join(dest, pullPathB)); !os.IsNotExist(statErr) {
+		t.Errorf("corrupt path should have been removed, stat error: %v", statErr)
+	}
+
+	_, err = c.PullPaths(context.Background(), []string{"/nix/store/0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c-missing"}, dest)
+	if !errors.Is(err, client.ErrNotInCache) {
+		t.Errorf("expected ErrNotInCache, got %v", err)
+	}
+}
@@ -0,0 +1,201 @@
+package client
+
+import (
+	"context"
+	"errors"
+	"fmt"
+	"io"
+	"log/slog"
+	"runtime"
+	"time"
+)
+
+// Bounds of the upload concurrency AutoConcurrency picks. Uploads mostly
+// wait on the network, so a few per CPU keep the link busy, but too many
+// only add S3 connections and memory for buffered parts.
+const (
+	autoUploadsPerCPU = 4
+	minAutoUploads    = 8
+	maxAutoUploads    = 64
+)
+
+// AutoConcurrency returns the concurrency used for MaxConcurrentUploads 0:
+// one compression job per CPU, as compressing is CPU-bound, and
+// autoUploadsPerCPU times as many uploads.
+func AutoConcurrency() (uploads, compressionJobs int) {
+	cpus := runtime.GOMAXPROCS(0)
+
+	return min(max(cpus*autoUploadsPerCPU, minAutoUploads), maxAutoUploads), cpus
+}
+
+// PushOptions configures Push. Start from DefaultPushOptions, since the zero
+// value disables retries and skipping of cached closures. Narinfos are signed
+// by the server, so there is nothing to configure for signing here.
+type PushOptions struct {
+	MaxConcurrentUploads  int             // Maximum number of concurrent uploads (0 = AutoConcurrency)
+	MaxConcurrentRequests int             // Maximum number of concurrent pending closure requests
+	VerifyS3Integrity     bool            // Enable S3 integrity checking when creating pending closures
+	SkipExisting          bool            // Skip closures whose narinfos are all already cached
+	WriteListings         bool            // Upload a .ls listing alongside every NAR
+	IncludeDerivations    bool            // Also push the derivations the paths were built from
+	ChecksumUploads       bool            // Send checksum headers so S3 rejects bodies corrupted in flight
+	MaxUploadRate         int64           // Bytes per second shared by all uploads (0 = unlimited)
+	Retry                 RetryConfig     // Retry configuration for HTTP requests
+	NarOptions            NarOptions      // NAR serialization options (case hack)
+	Compression           Compression     // NAR compression (zstd, xz, br or none)
+	CompressionLevel      int             // Compression level for NARs (0 = default)
+	ZstdWindowLog         int             // zstd window log for NARs (0 = the level's default)
+	CompressionWorkers    int             // zstd workers for large NARs (0 = GOMAXPROCS)
+	CompressionJobs       int             // NARs compressed at once across all uploads (0 = GOMAXPROCS)
+	MinCompressionRatio   float64         // Store NARs uncompressed below this sampled ratio (0 = always compress)
+	TempDir               string          // Directory for staging compressed build logs ("" = system default)
+	TempDirBudget         uint64          // Maximum bytes staged in TempDir at once (0 = unlimited)
+	KeepTemp              bool            // Keep staged files and copies of uploads in TempDir for debugging
+	InMemoryLogLimit      int64           // Build logs up to this size skip TempDir (0 = always stage)
+	StoreDir              string          // Nix store directory ("" = keep the detected one)
+	Store                 string          // Nix store URI to read paths from ("" = local store)
+	PathInfoFile          string          // Read path info from a `nix path-info --recursive --json` dump
+	PathInfoCache         string          // Directory caching nix path info between runs ("" = no cache)
+	PathInfoCacheTTL      time.Duration   // Age after which cached path info is queried again (0 = no expiry)
+	Pin                   string          // Pin the pushed closure under this name (requires exactly one path)
+	OnEvent               func(PushEvent) // Receives progress events; must be safe for concurrent use
+	DryRun                bool            // Only report what would be uploaded; the cache is only read
+	NarinfoOutput         io.Writer       // Print the narinfos that would be uploaded here and stop (nil = upload)
+	ContinueOnError       bool            // Keep uploading after a path fails; see ErrUploadIncomplete
+	Exclude               []string        // Store paths to leave out; other narinfos may still reference them
+	NoRecursive           bool            // Push only the given paths, not their closures
+	KeyPrefix             string          // Namespace every object key below this bucket prefix ("" = bucket root)
+	StateFile             string          // Checkpoint file to resume an interrupted push from ("" = none)
+	NARCache              *NARCache       // Share compressed NARs between pushes to several caches (nil = none)
+	API                   CacheAPI        // Push through this API instead of the server's, e.g. a DirectS3 (nil = server)
+}
+
+// DefaultPushOptions returns the options `niks3 push` uses without flags.
+func DefaultPushOptions() PushOptions {
+	return PushOptions{
+		MaxConcurrentUploads:  30,
+		MaxConcurrentRequests: 8,
+		SkipExisting:          true,
+		WriteListings:         true,
+		Retry:                 DefaultRetryConfig(),
+		NarOptions:            DefaultNarOptions(),
+		Compression:           CompressionZstd,
+		InMemoryLogLimit:      DefaultInMemoryLogLimit,
+	}
+}
+
+// apply copies opts onto the client's configuration.
+func (opts PushOptions) apply(c *Client) {
+	c.MaxConcurrentNARUploads = max(opts.MaxConcurrentUploads, 1)
+	c.CompressionJobs = opts.CompressionJobs
+
+	if opts.MaxConcurrentUploads == 0 {
+		uploads, jobs := AutoConcurrency()
+		c.MaxConcurrentNARUploads = uploads
+
+		if c.CompressionJobs == 0 {
+			c.CompressionJobs = jobs
+		}
+
+		slog.Info("Auto-detected concurrency", "max_concurrent_uploads", c.MaxConcurrentNARUploads, "compression_jobs", c.CompressionJobs)
+	}
+
+	c.MaxConcurrentRequests = max(opts.MaxConcurrentRequests, 1)
+	c.VerifyS3Integrity = opts.VerifyS3Integrity
+	c.SkipExisting = opts.SkipExisting
+	c.WriteListings = opts.WriteListings
+	c.IncludeDerivations = opts.IncludeDerivations
+	c.NoRecursive = opts.NoRecursive
+	c.KeyPrefix = opts.KeyPrefix
+	c.ChecksumUploads = opts.ChecksumUploads
+	c.MaxUploadRate = opts.MaxUploadRate
+	c.Retry = opts.Retry
+	c.NarOptions = opts.NarOptions
+	c.Compression = opts.Compression
+	c.CompressionLevel = opts.CompressionLevel
+	c.ZstdWindowLog = opts.ZstdWindowLog
+	c.CompressionWorkers = opts.CompressionWorkers
+	c.MinCompressionRatio = opts.MinCompressionRatio
+	c.TempDir = opts.TempDir
+	c.TempDirBudget = opts.TempDirBudget
+	c.KeepTemp = opts.KeepTemp
+	c.InMemoryLogLimit = opts.InMemoryLogLimit
+	c.Store = opts.Store
+	c.PathInfoFile = opts.PathInfoFile
+	c.PathInfoCache = opts.PathInfoCache
+	c.PathInfoCacheTTL = opts.PathInfoCacheTTL
+	c.OnEvent = opts.OnEvent
+	c.DryRun = opts.DryRun
+	c.NarinfoOutput = opts.NarinfoOutput
+	c.ContinueOnError = opts.ContinueOnError
+	c.Exclude = opts.Exclude
+	c.StateFile = opts.StateFile
+	c.NARCache = opts.NARCache
+
+	if opts.API != nil {
+		c.API = opts.API
+	}
+
+	if opts.StoreDir != "" {
+		c.SetStoreDir(opts.StoreDir)
+	}
+}
+
+// Push configures c with opts, uploads paths and their closures, and pins
+// the result if opts.Pin is set. It is what `niks3 push` runs, for programs
+// that want to push without spawning the binary. With ContinueOnError, a
+// push in which some paths failed returns its stats, listing the failures,
+// together with an error wrapping ErrUploadIncomplete.
+func Push(ctx context.Context, c *Client, paths []string, opts PushOptions) (*UploadStats, error) {
+	if len(paths) == 0 {
+		return nil, errors.New("at least one store path is required")
+	}
+
+	if opts.Pin != "" && len(paths) != 1 {
+		return nil, errors.New("pinning requires exactly one store path")
+	}
+
+	if err := ValidateKeyPrefix(opts.KeyPrefix); err != nil {
+		return nil, err
+	}
+
+	// Pins name narinfos at the bucket root.
+	if opts.Pin != "" && opts.KeyPrefix != "" {
+		return nil, errors.New("pinning is not supported with a key prefix")
+	}
+
+	opts.apply(c)
+
+	start := time.Now()
+
+	_, stats, err := c.pushPaths(ctx, paths)
+	if stats != nil {
+		stats.Elapsed = time.Since(start)
+	}
+	if errors.Is(err, ErrUploadIncomplete) {
+		return stats, err
+	}
+
+	if err != nil {
+		return nil, fmt.Errorf("pushing paths: %w", err)
+	}
+
+	if opts.Pin != "" && (opts.DryRun || opts.NarinfoOutput != nil) {
+		slog.Info("Would create pin", "name", opts.Pin, "path", paths[0])
+	} else if opts.Pin != "" {
+		// The server only accepts store paths, but users typically pass a
+		// ./result symlink as produced by nix-build.
+		storePath, err := c.ResolveStorePath(paths[0])
+		if err != nil {
+			return nil, fmt.Errorf("resolving store path for pin %q: %w", opts.Pin, err)
+		}
+
+		if err := c.CreatePin(ctx, opts.Pin, storePath); err != nil {
+			return nil, fmt.Errorf("creating pin %q: %w", opts.Pin, err)
+		}
+
+		slog.Info("Created pin", "name", opts.Pin, "store_path", storePath)
+	}
+
+	return stats, nil
+}
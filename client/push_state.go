@@ -0,0 +1,216 @@
+package client
+
+import (
+	"bufio"
+	"bytes"
+	"context"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"io/fs"
+	"log/slog"
+	"os"
+	"sync"
+
+	"golang.org/x/sync/errgroup"
+)
+
+// pushStateRecord is one line of a push state file. Each line records
+// either a pending closure created by the push or a group of object keys
+// whose upload finished; the file is only appended to.
+type pushStateRecord struct {
+	Closure   string   `json:"closure,omitempty"`   // Pending closure left open for a resumed push
+	Completed []string `json:"completed,omitempty"` // Objects uploaded together, e.g. a path's NAR, listing and narinfo
+}
+
+// pushState is the checkpoint behind --state-file. A push that fails keeps
+// its pending closures open instead of aborting them, since aborted objects
+// are handed to GC and would have to be deleted before they can be uploaded
+// again. The next push with the same file skips objects the previous one
+// finished (after confirming they are in S3), and aborts the old closures
+// once its own are registered.
+type pushState struct {
+	path string
+
+	mu        sync.Mutex
+	file      *os.File
+	closures  []string            // Pending closures of earlier runs
+	completed map[string][]string // Object key -> keys uploaded with it
+}
+
+// loadPushState reads the state file at path, if any, and opens it for
+// appending.
+func loadPushState(path string) (*pushState, error) {
+	state := &pushState{path: path, completed: make(map[string][]string)}
+
+	data, err := os.ReadFile(path)
+	if err != nil && !errors.Is(err, fs.ErrNotExist) {
+		return nil, fmt.Errorf("reading push state: %w", err)
+	}
+
+	scanner := bufio.NewScanner(bytes.NewReader(data))
+	scanner.Buffer(nil, 16<<20)
+
+	for scanner.Scan() {
+		var rec pushStateRecord
+		if err := json.Unmarshal(scanner.Bytes(), &rec); err != nil {
+			// A push killed mid-write leaves a truncated last line.
+			slog.Warn("Ignoring malformed push state line", "file", path, "error", err)
+
+			continue
+		}
+
+		if rec.Closure != "" {
+			state.closures = append(state.closures, rec.Closure)
+		}
+
+		for _, key := range rec.Completed {
+			state.completed[key] = rec.Completed
+		}
+	}
+
+	if err := scanner.Err(); err != nil {
+		return nil, fmt.Errorf("reading push state: %w", err)
+	}
+
+	state.file, err = os.OpenFile(path, os.O_WRONLY|os.O_CREATE|os.O_APPEND, 0o600)
+	if err != nil {
+		return nil, fmt.Errorf("opening push state: %w", err)
+	}
+
+	if len(state.closures) > 0 || len(state.completed) > 0 {
+		slog.Info("Resuming push", "state_file", path, "completed_objects", len(state.completed))
+	}
+
+	return state, nil
+}
+
+// append writes rec as one line.
+func (s *pushState) append(rec pushStateRecord) error {
+	line, err := json.Marshal(rec)
+	if err != nil {
+		return fmt.Errorf("encoding push state: %w", err)
+	}
+
+	s.mu.Lock()
+	defer s.mu.Unlock()
+
+	if _, err := s.file.Write(append(line, '\n')); err != nil {
+		return fmt.Errorf("writing push state: %w", err)
+	}
+
+	return nil
+}
+
+// recordClosures notes pending closures this push created.
+func (s *pushState) recordClosures(ids []string) error {
+	for _, id := range ids {
+		if err := s.append(pushStateRecord{Closure: id}); err != nil {
+			return err
+		}
+	}
+
+	return nil
+}
+
+// recordCompleted notes that keys were uploaded. A failure to write only
+// costs a re-upload on resume, so it is logged rather than returned.
+func (s *pushState) recordCompleted(keys []string) {
+	if len(keys) == 0 {
+		return
+	}
+
+	if err := s.append(pushStateRecord{Completed: keys}); err != nil {
+		slog.Warn("Failed to checkpoint upload", "keys", keys, "error", err)
+	}
+}
+
+// close closes the state file, leaving it for the next run.
+func (s *pushState) close() {
+	if err := s.file.Close(); err != nil {
+		slog.Warn("Failed to close push state", "file", s.path, "error", err)
+	}
+}
+
+// remove deletes the state file after a successful push.
+func (s *pushState) remove() error {
+	s.close()
+
+	if err := os.Remove(s.path); err != nil && !errors.Is(err, fs.ErrNotExist) {
+		return fmt.Errorf("removing push state: %w", err)
+	}
+
+	return nil
+}
+
+// checkpoint records finished uploads if the push has a state file.
+func (c *Client) checkpoint(keys []string) {
+	if c.pushState != nil {
+		c.pushState.recordCompleted(keys)
+	}
+}
+
+// skipCompletedObjects drops pending objects an earlier run of this push
+// already uploaded. Objects recorded together (a path's NAR, listing and
+// narinfo) are only dropped together, and only if the last of them, which
+// is uploaded last, is confirmed to be in S3. It returns the keys dropped.
+func (c *Client) skipCompletedObjects(ctx context.Context, pendingObjects map[string]PendingObject) ([]string, error) {
+	state := c.pushState
+
+	// Group the pending keys by the checkpoint record that completed them.
+	groups := make(map[string][]string)
+
+	for key := range pendingObjects {
+		if group, ok := state.completed[key]; ok {
+			last := group[len(group)-1]
+			groups[last] = append(groups[last], key)
+		}
+	}
+
+	if len(groups) == 0 {
+		return nil, nil
+	}
+
+	var (
+		mu      sync.Mutex
+		skipped []string
+	)
+
+	g, ctx := errgroup.WithContext(ctx)
+	g.SetLimit(max(c.MaxConcurrentRequests, 1))
+
+	for last, keys := range groups {
+		g.Go(func() error {
+			exists, err := c.ObjectExists(ctx, last)
+			if err != nil {
+				return fmt.Errorf("checking %s: %w", last, err)
+			}
+
+			if !exists {
+				slog.Debug("Checkpointed object missing from S3, uploading again", "key", last)
+
+				return nil
+			}
+
+			mu.Lock()
+			skipped = append(skipped, keys...)
+			mu.Unlock()
+
+			return nil
+		})
+	}
+
+	if err := g.Wait(); err != nil {
+		return nil, err //nolint:wrapcheck // errgroup returns the first task's already-wrapped error
+	}
+
+	for _, key := range skipped {
+		delete(pendingObjects, key)
+	}
+
+	if len(skipped) > 0 {
+		slog.Info(fmt.Sprintf("Skipping %d objects uploaded by an earlier run", len(skipped)))
+	}
+
+	return skipped, nil
+}
@@ -0,0 +1,74 @@
+package client_test
+
+import (
+	"context"
+	"net/http"
+	"net/http/httptest"
+	"os"
+	"path/filepath"
+	"slices"
+	"strings"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+func TestSkipCompletedObjects(t *testing.T) {
+	t.Parallel()
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		switch strings.TrimPrefix(r.URL.Path, "/api/objects/") {
+		case "a.narinfo", "log/a.drv":
+			w.WriteHeader(http.StatusNoContent)
+		default:
+			w.WriteHeader(http.StatusNotFound)
+		}
+	}))
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	c.Retry.MaxRetries = 0
+
+	// The last line was cut short by an interrupted push.
+	stateFile := filepath.Join(t.TempDir(), "push.state")
+	state := `{"closure":"7"}
+{"completed":["nar/a.nar.zst","a.ls","a.narinfo"]}
+{"completed":["log/a.drv"]}
+{"completed":["nar/b.nar.zst","b.narinfo"]}
+{"completed":["nar/c.n`
+
+	if err := os.WriteFile(stateFile, []byte(state), 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	pending := make(map[string]client.PendingObject)
+	for _, key := range []string{"nar/a.nar.zst", "a.ls", "a.narinfo", "log/a.drv", "nar/b.nar.zst", "b.narinfo", "nar/c.nar.zst"} {
+		pending[key] = client.PendingObject{}
+	}
+
+	skipped, err := c.SkipCompletedObjects(context.Background(), stateFile, pending)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	slices.Sort(skipped)
+
+	if want := []string{"a.ls", "a.narinfo", "log/a.drv", "nar/a.nar.zst"}; !slices.Equal(skipped, want) {
+		t.Errorf("skipped = %v, want %v", skipped, want)
+	}
+
+	// b's narinfo never reached S3, so all of b is uploaded again.
+	for _, key := range []string{"nar/b.nar.zst", "b.narinfo", "nar/c.nar.zst"} {
+		if _, ok := pending[key]; !ok {
+			t.Errorf("%s was dropped from pending objects", key)
+		}
+	}
+
+	if len(pending) != 3 {
+		t.Errorf("pending objects = %d, want 3", len(pending))
+	}
+}
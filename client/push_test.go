@@ -0,0 +1,195 @@
+package client_test
+
+import (
+	"bytes"
+	"context"
+	"encoding/json"
+	"net/http"
+	"net/http/httptest"
+	"os"
+	"path/filepath"
+	"runtime"
+	"strings"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+// TestPushValidatesArguments checks that Push rejects bad arguments before
+// reconfiguring the client or talking to the server.
+func TestPushValidatesArguments(t *testing.T) {
+	t.Parallel()
+
+	tests := []struct {
+		name    string
+		paths   []string
+		pin     string
+		wantErr string
+	}{
+		{name: "no paths", wantErr: "at least one store path"},
+		{name: "pin with several paths", paths: []string{"/nix/store/a", "/nix/store/b"}, pin: "release", wantErr: "exactly one store path"},
+	}
+
+	for _, tt := range tests {
+		t.Run(tt.name, func(t *testing.T) {
+			t.Parallel()
+
+			c := newTestClientWithRetries(http.DefaultClient, 0)
+
+			opts := client.DefaultPushOptions()
+			opts.Pin = tt.pin
+			opts.Compression = client.CompressionNone
+
+			_, err := client.Push(context.Background(), c, tt.paths, opts)
+			if err == nil || !strings.Contains(err.Error(), tt.wantErr) {
+				t.Fatalf("expected error containing %q, got %v", tt.wantErr, err)
+			}
+
+			if c.Compression == client.CompressionNone {
+				t.Error("client was reconfigured despite invalid arguments")
+			}
+		})
+	}
+}
+
+// TestPushPrintNarinfo checks that NarinfoOutput receives the narinfos of
+// the whole closure and that nothing is sent to the server.
+func TestPushPrintNarinfo(t *testing.T) {
+	t.Parallel()
+
+	const (
+		hello = "/nix/store/1b9p07z77phvv2hf6gm9f28syh6ym98a-hello-2.12.1"
+		glibc = "/nix/store/c10zhkbp6jmyh0xc5kd123ga8yy2p4hk-glibc-2.39-52"
+	)
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		t.Errorf("unexpected request %s %s", r.Method, r.URL.Path)
+		w.WriteHeader(http.StatusInternalServerError)
+	}))
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	var out bytes.Buffer
+
+	opts := client.DefaultPushOptions()
+	opts.SkipExisting = false
+	opts.PathInfoFile = filepath.Join("testdata", "path-info.json")
+	opts.StoreDir = "/nix/store"
+	opts.NarinfoOutput = &out
+
+	if _, err := client.Push(context.Background(), c, []string{hello}, opts); err != nil {
+		t.Fatal(err)
+	}
+
+	narinfos := strings.Split(out.String(), "\n\n")
+	if len(narinfos) != 2 {
+		t.Fatalf("expected 2 narinfos, got %d:\n%s", len(narinfos), out.String())
+	}
+
+	for i, storePath := range []string{hello, glibc} {
+		if !strings.HasPrefix(narinfos[i], "StorePath: "+storePath+"\n") {
+			t.Errorf("narinfo %d is not for %s:\n%s", i, storePath, narinfos[i])
+		}
+	}
+
+	if !strings.Contains(narinfos[0], "References: 1b9p07z77phvv2hf6gm9f28syh6ym98a-hello-2.12.1 c10zhkbp6jmyh0xc5kd123ga8yy2p4hk-glibc-2.39-52\n") {
+		t.Errorf("unexpected references:\n%s", narinfos[0])
+	}
+}
+
+func TestAutoConcurrency(t *testing.T) {
+	t.Parallel()
+
+	uploads, jobs := client.AutoConcurrency()
+
+	if jobs != runtime.GOMAXPROCS(0) {
+		t.Errorf("compression jobs = %d, want one per CPU (%d)", jobs, runtime.GOMAXPROCS(0))
+	}
+
+	if uploads < 8 || uploads > 64 {
+		t.Errorf("uploads = %d, want between 8 and 64", uploads)
+	}
+}
+
+// TestPushAllExcluded checks that excluding every given path is not an error
+// and talks to no server.
+func TestPushAllExcluded(t *testing.T) {
+	t.Parallel()
+
+	const hello = "/nix/store/1b9p07z77phvv2hf6gm9f28syh6ym98a-hello-2.12.1"
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		t.Errorf("unexpected request %s %s", r.Method, r.URL.Path)
+		w.WriteHeader(http.StatusInternalServerError)
+	}))
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	opts := client.DefaultPushOptions()
+	opts.PathInfoFile = filepath.Join("testdata", "path-info.json")
+	opts.StoreDir = "/nix/store"
+	opts.Exclude = []string{hello}
+
+	stats, err := client.Push(context.Background(), c, []string{hello}, opts)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if summary := stats.Summary(); summary.Paths != 0 || summary.UploadedPaths != 0 {
+		t.Errorf("unexpected summary %+v", summary)
+	}
+}
+
+// TestPushNoRecursive checks that NoRecursive uploads only the given path,
+// whose narinfo still references the dependency that was not uploaded.
+func TestPushNoRecursive(t *testing.T) {
+	t.Parallel()
+
+	m := newMockCache(t)
+	store := newTestStore(t, harnessStore)
+	dep, top := store.Paths[0], store.Paths[1]
+
+	// Make top depend on dep.
+	data, err := os.ReadFile(store.PathInfoFile)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	var infos map[string]map[string]any
+	if err := json.Unmarshal(data, &infos); err != nil {
+		t.Fatal(err)
+	}
+
+	infos[top]["references"] = []string{dep}
+
+	if data, err = json.Marshal(infos); err != nil {
+		t.Fatal(err)
+	}
+
+	if err := os.WriteFile(store.PathInfoFile, data, 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	opts := store.PushOptions()
+	opts.NoRecursive = true
+
+	if _, err := client.Push(context.Background(), m.Client(t), []string{top}, opts); err != nil {
+		t.Fatal(err)
+	}
+
+	if got := narinfoField(m.Narinfo(t, top), "References"); got != filepath.Base(dep) {
+		t.Errorf("narinfo References = %q, want %q", got, filepath.Base(dep))
+	}
+
+	if _, ok := m.Object(narinfoKey(t, dep)); ok {
+		t.Errorf("dependency %s was uploaded", dep)
+	}
+}
@@ -1,8 +1,11 @@
 package client_test
 
 import (
+	"io"
 	"net/http"
 	"net/http/httptest"
+	"sync"
+	"sync/atomic"
 	"testing"
 	"time"
 
@@ -133,3 +136,51 @@ func TestRateLimiterFeedback_400DoesNotCountAsSuccess(t *testing.T) {
 		t.Errorf("rate changed after %d 400s: before=%f after=%f", ratelimit.RateRecoveryAfter, rateBefore, rateAfter)
 	}
 }
+
+// TestMaxUploadRateSharedAcrossUploads checks that concurrent uploads share
+// one bandwidth budget rather than each getting MaxUploadRate.
+func TestMaxUploadRateSharedAcrossUploads(t *testing.T) {
+	t.Parallel()
+
+	const (
+		rate    = 128 << 10
+		payload = 64 << 10
+		uploads = 3
+	)
+
+	var received atomic.Int64
+
+	server := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		n, _ := io.Copy(io.Discard, r.Body)
+		received.Add(n)
+		w.WriteHeader(http.StatusOK)
+	}))
+	t.Cleanup(server.Close)
+
+	c := newTestClientWithRetries(server.Client(), 0)
+	c.MaxUploadRate = rate
+
+	start := time.Now()
+
+	var wg sync.WaitGroup
+
+	for range uploads {
+		wg.Go(func() {
+			if err := c.UploadBytesToPresignedURLWithHeaders(t.Context(), server.URL, make([]byte, payload), nil); err != nil {
+				t.Errorf("upload: %v", err)
+			}
+		})
+	}
+
+	wg.Wait()
+
+	if got := received.Load(); got != uploads*payload {
+		t.Fatalf("server received %d bytes, want %d", got, uploads*payload)
+	}
+
+	// Only the limiter's initial 32 KiB burst is free.
+	minimum := time.Duration(float64(uploads*payload-32<<10) / rate * float64(time.Second))
+	if elapsed := time.Since(start); elapsed < minimum*9/10 {
+		t.Errorf("uploading %d bytes at %d B/s took %v, want at least %v", uploads*payload, rate, elapsed, minimum)
+	}
+}
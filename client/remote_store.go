@@ -0,0 +1,269 @@
+package client
+
+import (
+	"bufio"
+	"bytes"
+	"context"
+	"fmt"
+	"io"
+	"log/slog"
+	"os/exec"
+	"strings"
+)
+
+// dumpNAR serializes storePath to w. Paths are read from the local
+// filesystem unless c.Store or NIX_REMOTE names a different Nix store, in
+// which case the NAR is streamed out of that store by nix.
+func (c *Client) dumpNAR(ctx context.Context, w io.Writer, storePath string) (*NarListing, *NarDigest, error) {
+	store := c.effectiveStore()
+	if store == "" {
+		return c.NarOptions.DumpPathWithDigest(w, storePath)
+	}
+
+	return dumpPathFromStore(ctx, w, storePath, store, c.NixEnv)
+}
+
+// generateListing builds the .ls listing of storePath without uploading the
+// NAR. For a non-local store the NAR has to be streamed and parsed anyway.
+func (c *Client) generateListing(ctx context.Context, storePath string) (*NarListing, error) {
+	store := c.effectiveStore()
+	if store == "" {
+		return c.NarOptions.GenerateListingOnly(storePath)
+	}
+
+	listing, _, err := dumpPathFromStore(ctx, io.Discard, storePath, store, c.NixEnv)
+
+	return listing, err
+}
+
+// dumpPathFromStore runs `nix store dump-path --store storeURI` (or
+// `nix-store --dump` for Nix before 2.4) and copies the NAR to w. The
+// listing and digest are computed from the stream, since the files do not
+// exist locally.
+func dumpPathFromStore(ctx context.Context, w io.Writer, storePath, storeURI string, nixEnv []string) (*NarListing, *NarDigest, error) {
+	var cmd *exec.Cmd
+
+	if detectNixCLI().unified {
+		cmd = exec.CommandContext(ctx, "nix", "--extra-experimental-features", "nix-command", "store", "dump-path", "--store", storeURI, "--", storePath)
+		if len(nixEnv) > 0 {
+			cmd.Env = nixEnv
+		}
+	} else {
+		cmd = nixStoreCommand(ctx, nixEnv, storeURI, "--dump", storePath)
+	}
+
+	var stderr bytes.Buffer
+
+	cmd.Stderr = &stderr
+
+	stdout, err := cmd.StdoutPipe()
+	if err != nil {
+		return nil, nil, fmt.Errorf("creating stdout pipe: %w", err)
+	}
+
+	if err := cmd.Start(); err != nil {
+		return nil, nil, fmt.Errorf("starting nix store dump-path: %w", err)
+	}
+
+	dw := newFileDigestWriter(w)
+	tee := io.TeeReader(stdout, dw)
+
+	listing, err := ListNAR(tee)
+	if err == nil {
+		// The parser stops at the closing ")"; anything after it would
+		// still be part of what nix considers the NAR.
+		_, err = io.Copy(io.Discard, tee)
+	}
+
+	if err != nil {
+		// Unblock nix if it is still writing to the pipe.
+		if killErr := cmd.Process.Kill(); killErr != nil {
+			slog.Debug("Failed to kill nix store dump-path", "error", killErr)
+		}
+
+		_ = cmd.Wait()
+
+		return nil, nil, fmt.Errorf("reading NAR of %s from %s: %w", storePath, storeURI, err)
+	}
+
+	if err := cmd.Wait(); err != nil {
+		return nil, nil, fmt.Errorf("command failed: %s\nstderr: %s\nerror: %w",
+			strings.Join(cmd.Args, " "), stderr.String(), err)
+	}
+
+	d := dw.Digest()
+
+	return listing, &NarDigest{NarHash: d.FileHash, NarSize: d.FileSize}, nil
+}
+
+// ListNAR parses a NAR stream and returns its listing, including the offset
+// of every regular file's contents. File contents are skipped, not stored.
+func ListNAR(r io.Reader) (*NarListing, error) {
+	nr := &narReader{r: bufio.NewReaderSize(r, 128*1024)}
+
+	if err := nr.expect(narVersionMagic); err != nil {
+		return nil, fmt.Errorf("not a NAR archive: %w", err)
+	}
+
+	if err := nr.expect("("); err != nil {
+		return nil, err
+	}
+
+	root, err := listNode(nr, "")
+	if err != nil {
+		return nil, err
+	}
+
+	return &NarListing{Version: 1, Root: root}, nil
+}
+
+// listNode parses one node whose opening "(" has already been consumed.
+// It consumes the node's closing ")". path is only used in errors.
+func listNode(nr *narReader, path string) (NarListingEntry, error) {
+	if err := nr.expect("type"); err != nil {
+		return NarListingEntry{}, err
+	}
+
+	kind, err := nr.readString()
+	if err != nil {
+		return NarListingEntry{}, err
+	}
+
+	switch kind {
+	case "regular":
+		return listRegularFile(nr, path)
+	case "directory":
+		return listDirectory(nr, path)
+	case "symlink":
+		return listSymlink(nr)
+	default:
+		return NarListingEntry{}, fmt.Errorf("unknown NAR node type %q at %q", kind, path)
+	}
+}
+
+func listRegularFile(nr *narReader, path string) (NarListingEntry, error) {
+	tag, err := nr.readString()
+	if err != nil {
+		return NarListingEntry{}, err
+	}
+
+	entry := NarListingEntry{Type: "regular"}
+
+	if tag == "executable" {
+		if err := nr.expect(""); err != nil {
+			return NarListingEntry{}, err
+		}
+
+		executable := true
+		entry.Executable = &executable
+
+		if tag, err = nr.readString(); err != nil {
+			return NarListingEntry{}, err
+		}
+	}
+
+	if tag != "contents" {
+		return NarListingEntry{}, fmt.Errorf("expected \"contents\" for %q, got %q", path, tag)
+	}
+
+	size, err := nr.readUint64()
+	if err != nil {
+		return NarListingEntry{}, err
+	}
+
+	offset := nr.offset
+	entry.Size = &size
+	entry.NarOffset = &offset
+
+	if _, err := io.CopyN(io.Discard, nr.r, int64(size)); err != nil { //nolint:gosec // size comes from the archive; CopyN fails on short input
+		return NarListingEntry{}, fmt.Errorf("skipping contents of %q: %w", path, err)
+	}
+
+	nr.offset += size
+
+	if err := nr.skipPadding(size); err != nil {
+		return NarListingEntry{}, err
+	}
+
+	return entry, nr.expect(")")
+}
+
+func listDirectory(nr *narReader, path string) (NarListingEntry, error) {
+	entries := make(map[string]NarListingEntry)
+
+	var prevName string
+
+	for {
+		tag, err := nr.readString()
+		if err != nil {
+			return NarListingEntry{}, err
+		}
+
+		if tag == ")" {
+			return NarListingEntry{Type: "directory", Entries: entries}, nil
+		}
+
+		if tag != "entry" {
+			return NarListingEntry{}, fmt.Errorf("expected \"entry\" in directory %q, got %q", path, tag)
+		}
+
+		if err := nr.expect("("); err != nil {
+			return NarListingEntry{}, err
+		}
+
+		if err := nr.expect("name"); err != nil {
+			return NarListingEntry{}, err
+		}
+
+		name, err := nr.readString()
+		if err != nil {
+			return NarListingEntry{}, err
+		}
+
+		if name == "" || name == "." || name == ".." || strings.ContainsAny(name, "/\x00") {
+			return NarListingEntry{}, fmt.Errorf("invalid entry name %q in directory %q", name, path)
+		}
+
+		if prevName != "" && name <= prevName {
+			return NarListingEntry{}, fmt.Errorf("entries in directory %q are not sorted: %q after %q", path, name, prevName)
+		}
+
+		prevName = name
+
+		if err := nr.expect("node"); err != nil {
+			return NarListingEntry{}, err
+		}
+
+		if err := nr.expect("("); err != nil {
+			return NarListingEntry{}, err
+		}
+
+		child, err := listNode(nr, path+"/"+name)
+		if err != nil {
+			return NarListingEntry{}, err
+		}
+
+		entries[name] = child
+
+		if err := nr.expect(")"); err != nil {
+			return NarListingEntry{}, err
+		}
+	}
+}
+
+func listSymlink(nr *narReader) (NarListingEntry, error) {
+	if err := nr.expect("target"); err != nil {
+		return NarListingEntry{}, err
+	}
+
+	target, err := nr.readString()
+	if err != nil {
+		return NarListingEntry{}, err
+	}
+
+	if err := nr.expect(")"); err != nil {
+		return NarListingEntry{}, err
+	}
+
+	return NarListingEntry{Type: "symlink", Target: &target}, nil
+}
@@ -0,0 +1,35 @@
+package client_test
+
+import (
+	"bytes"
+	"reflect"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+)
+
+// TestListNARMatchesDump checks that parsing a NAR stream, as done for
+// remote stores, yields the same listing (including file offsets) as
+// serializing the tree locally.
+func TestListNARMatchesDump(t *testing.T) {
+	t.Parallel()
+
+	src := t.TempDir()
+	makeMixedTree(t, src)
+
+	var nar bytes.Buffer
+
+	want, err := client.DumpPathWithListing(&nar, src)
+	if err != nil {
+		t.Fatalf("DumpPathWithListing: %v", err)
+	}
+
+	got, err := client.ListNAR(&nar)
+	if err != nil {
+		t.Fatalf("ListNAR: %v", err)
+	}
+
+	if !reflect.DeepEqual(got, want) {
+		t.Fatalf("listing mismatch:\ngot:  %+v\nwant: %+v", got, want)
+	}
+}
@@ -0,0 +1,133 @@
+package client
+
+import (
+	"bytes"
+	"context"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"io"
+	"log/slog"
+	"net/http"
+	"slices"
+	"strings"
+
+	"github.com/Mic92/niks3/api"
+	"github.com/Mic92/niks3/server/signing"
+)
+
+// ResignPath adds signatures by keys to the narinfo of storePath, a store
+// path or its hash, below KeyPrefix, e.g. to roll out a new signing key
+// without re-uploading NARs. With trustedKeys, the narinfo must already
+// carry a valid signature by one of them, so only paths the cache vouched
+// for are signed with the new key. Existing signatures are kept. Returns
+// the store path and how many signatures were added; keys that already
+// signed the narinfo add none.
+func (c *Client) ResignPath(ctx context.Context, storePath string, keys []*signing.Key, trustedKeys []*signing.PublicKey) (string, int, error) {
+	hash, err := GetStorePathHash(storePath)
+	if err != nil {
+		return "", 0, err
+	}
+
+	key := c.wireKey(hash + ".narinfo")
+
+	body, err := c.fetchCacheObject(ctx, key)
+	if err != nil {
+		return "", 0, err
+	}
+
+	defer closeResponseBody(body)
+
+	content, err := io.ReadAll(body)
+	if err != nil {
+		return "", 0, fmt.Errorf("reading narinfo for %s: %w", storePath, err)
+	}
+
+	meta, err := ParseNarinfo(string(content))
+	if err != nil {
+		return "", 0, fmt.Errorf("parsing narinfo for %s: %w", storePath, err)
+	}
+
+	if strings.Contains(storePath, "/") && meta.StorePath != storePath {
+		return "", 0, fmt.Errorf("narinfo for %s describes %s", storePath, meta.StorePath)
+	}
+
+	info := &signing.NarInfo{
+		StorePath:  meta.StorePath,
+		NarHash:    meta.NarHash,
+		NarSize:    meta.NarSize,
+		References: meta.References,
+	}
+
+	if len(trustedKeys) > 0 {
+		ok, err := signing.VerifyNarinfo(trustedKeys, info, meta.Signatures)
+		if err != nil {
+			return "", 0, fmt.Errorf("checking signatures of %s: %w", meta.StorePath, err)
+		}
+
+		if !ok {
+			return "", 0, fmt.Errorf("%s has no valid signature by a trusted key, not re-signing it", meta.StorePath)
+		}
+	}
+
+	sigs, err := signing.SignNarinfo(keys, info)
+	if err != nil {
+		return "", 0, fmt.Errorf("signing %s: %w", meta.StorePath, err)
+	}
+
+	// Ed25519 signatures are deterministic, so a key that already signed
+	// the narinfo produces an identical Sig value.
+	sigs = slices.DeleteFunc(sigs, func(sig string) bool { return slices.Contains(meta.Signatures, sig) })
+	if len(sigs) == 0 {
+		return meta.StorePath, 0, nil
+	}
+
+	added, err := c.addNarinfoSignatures(ctx, key, sigs)
+	if err != nil {
+		return "", 0, fmt.Errorf("adding signatures to %s: %w", meta.StorePath, err)
+	}
+
+	slog.Debug("Re-signed narinfo", "store_path", meta.StorePath, "added", added)
+
+	return meta.StorePath, added, nil
+}
+
+// addNarinfoSignatures asks the server to append sigs to the narinfo at
+// key, and returns how many it did not have yet.
+func (c *Client) addNarinfoSignatures(ctx context.Context, key string, sigs []string) (int, error) {
+	reqBody, err := json.Marshal(api.AddSignaturesRequest{Signatures: sigs})
+	if err != nil {
+		return 0, fmt.Errorf("marshaling request: %w", err)
+	}
+
+	reqURL := c.baseURL.JoinPath("api/signatures", key)
+
+	req, err := http.NewRequestWithContext(ctx, http.MethodPost, reqURL.String(), bytes.NewReader(reqBody))
+	if err != nil {
+		return 0, fmt.Errorf("creating request: %w", err)
+	}
+
+	req.Header.Set("Content-Type", "application/json")
+
+	resp, err := c.DoServerRequest(ctx, req)
+	if err != nil {
+		return 0, fmt.Errorf("sending request: %w", err)
+	}
+
+	defer deferCloseBody(resp)
+
+	if resp.StatusCode == http.StatusNotFound {
+		return 0, errors.New("narinfo is no longer in the cache")
+	}
+
+	if err := checkResponse(resp, http.StatusOK); err != nil {
+		return 0, err
+	}
+
+	var result api.AddSignaturesResponse
+	if err := json.NewDecoder(resp.Body).Decode(&result); err != nil {
+		return 0, fmt.Errorf("parsing response: %w", err)
+	}
+
+	return result.Added, nil
+}
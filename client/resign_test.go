@@ -0,0 +1,131 @@
+package client_test
+
+import (
+	"context"
+	"encoding/json"
+	"net/http"
+	"net/http/httptest"
+	"strings"
+	"sync"
+	"testing"
+
+	"github.com/Mic92/niks3/api"
+	"github.com/Mic92/niks3/client"
+	"github.com/Mic92/niks3/server/signing"
+)
+
+func TestResignPath(t *testing.T) {
+	t.Parallel()
+
+	const (
+		hash      = "0a1b2c3d4f5g6h7i8j9k0l1m2n3p4q5r"
+		storePath = "/nix/store/" + hash + "-foo"
+	)
+
+	newKey := func(name string) (*signing.Key, *signing.PublicKey) {
+		key, err := signing.GenerateKey(name, nil)
+		if err != nil {
+			t.Fatal(err)
+		}
+
+		pub, err := key.PublicKey()
+		if err != nil {
+			t.Fatal(err)
+		}
+
+		parsed, err := signing.ParsePublicKey(pub)
+		if err != nil {
+			t.Fatal(err)
+		}
+
+		return key, parsed
+	}
+
+	oldKey, oldPub := newKey("old-1")
+	rotated, rotatedPub := newKey("new-1")
+	_, otherPub := newKey("other-1")
+
+	info := &signing.NarInfo{
+		StorePath: storePath,
+		NarHash:   "sha256:" + strings.Repeat("0", 52),
+		NarSize:   120,
+	}
+
+	oldSigs, err := signing.SignNarinfo([]*signing.Key{oldKey}, info)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	var (
+		mu      sync.Mutex
+		narinfo = "StorePath: " + storePath + "\nURL: nar/x.nar.zst\nCompression: zstd\nNarHash: " + info.NarHash +
+			"\nNarSize: 120\nReferences: \nSig: " + oldSigs[0] + "\n"
+	)
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		mu.Lock()
+		defer mu.Unlock()
+
+		switch {
+		case r.Method == http.MethodGet && r.URL.Path == "/"+hash+".narinfo":
+			_, _ = w.Write([]byte(narinfo))
+		case r.Method == http.MethodPost && r.URL.Path == "/api/signatures/"+hash+".narinfo":
+			var req api.AddSignaturesRequest
+			if err := json.NewDecoder(r.Body).Decode(&req); err != nil {
+				http.Error(w, err.Error(), http.StatusBadRequest)
+
+				return
+			}
+
+			for _, sig := range req.Signatures {
+				narinfo += "Sig: " + sig + "\n"
+			}
+
+			_ = json.NewEncoder(w).Encode(api.AddSignaturesResponse{Added: len(req.Signatures)})
+		default:
+			http.NotFound(w, r)
+		}
+	}))
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	ctx := context.Background()
+	keys := []*signing.Key{rotated}
+
+	// Paths without a signature by a trusted key are left alone.
+	if _, _, err := c.ResignPath(ctx, storePath, keys, []*signing.PublicKey{otherPub}); err == nil {
+		t.Error("re-signed a narinfo without a trusted signature")
+	}
+
+	got, added, err := c.ResignPath(ctx, hash, keys, []*signing.PublicKey{oldPub})
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if got != storePath || added != 1 {
+		t.Errorf("ResignPath = %s, %d added; want %s, 1 added", got, added, storePath)
+	}
+
+	mu.Lock()
+	meta, err := client.ParseNarinfo(narinfo)
+	mu.Unlock()
+
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	for _, pub := range []*signing.PublicKey{oldPub, rotatedPub} {
+		if ok, err := signing.VerifyNarinfo([]*signing.PublicKey{pub}, info, meta.Signatures); err != nil || !ok {
+			t.Errorf("narinfo lacks a valid signature by %v: %v", pub, err)
+		}
+	}
+
+	// Signing again with the same key adds nothing.
+	if _, added, err := c.ResignPath(ctx, storePath, keys, nil); err != nil || added != 0 {
+		t.Errorf("second ResignPath added %d signatures, err %v", added, err)
+	}
+}
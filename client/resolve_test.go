@@ -3,6 +3,7 @@ package client_test
 import (
 	"os"
 	"path/filepath"
+	"strings"
 	"testing"
 
 	"github.com/Mic92/niks3/client"
@@ -13,7 +14,7 @@ func TestResolveStorePath(t *testing.T) {
 
 	tmp := t.TempDir()
 	storeDir := filepath.Join(tmp, "nix", "store")
-	storePath := filepath.Join(storeDir, "abc123-hello")
+	storePath := filepath.Join(storeDir, "0ha1dhmx807czjczmwy078s4r9s254il-hello")
 
 	if err := os.MkdirAll(storePath, 0o755); err != nil {
 		t.Fatal(err)
@@ -46,3 +47,156 @@ func TestResolveStorePath(t *testing.T) {
 		t.Errorf("expected %q, got %q", storePath, resolved)
 	}
 }
+
+func TestResolveStorePathSubdirectory(t *testing.T) {
+	t.Parallel()
+
+	tmp := t.TempDir()
+	storeDir := filepath.Join(tmp, "nix", "store")
+	storePath := filepath.Join(storeDir, "0ha1dhmx807czjczmwy078s4r9s254il-stream")
+	binDir := filepath.Join(storePath, "bin")
+
+	if err := os.MkdirAll(binDir, 0o755); err != nil {
+		t.Fatal(err)
+	}
+
+	if err := os.WriteFile(filepath.Join(binDir, "stream"), []byte("#!/bin/sh\n"), 0o755); err != nil { //nolint:gosec // test fixture must be executable
+		t.Fatal(err)
+	}
+
+	// A link pointing into a store path's subdirectory resolves to the
+	// top-level store path, not the file inside it.
+	link := filepath.Join(tmp, "stream")
+	if err := os.Symlink(filepath.Join(binDir, "stream"), link); err != nil {
+		t.Fatal(err)
+	}
+
+	c := client.NewTestClientWithStoreDir(storeDir)
+
+	resolved, err := c.ResolveStorePath(link)
+	if err != nil {
+		t.Fatalf("ResolveStorePath(%q): %v", link, err)
+	}
+
+	if resolved != storePath {
+		t.Errorf("expected %q, got %q", storePath, resolved)
+	}
+
+	// A link leaving the store entirely is rejected with both paths named.
+	outside := filepath.Join(tmp, "outside")
+	if err := os.WriteFile(outside, nil, 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	badLink := filepath.Join(tmp, "bad")
+	if err := os.Symlink(outside, badLink); err != nil {
+		t.Fatal(err)
+	}
+
+	_, err = c.ResolveStorePath(badLink)
+	if err == nil {
+		t.Fatal("expected error for path outside the store")
+	}
+
+	if !strings.Contains(err.Error(), badLink) || !strings.Contains(err.Error(), outside) {
+		t.Errorf("error should name input and target, got: %v", err)
+	}
+}
+
+func TestSetStoreDir(t *testing.T) {
+	t.Parallel()
+
+	tmp := t.TempDir()
+	storeDir := filepath.Join(tmp, "var", "nix", "store")
+	storePath := filepath.Join(storeDir, "0ha1dhmx807czjczmwy078s4r9s254il-hello")
+
+	if err := os.MkdirAll(filepath.Join(storePath, "bin"), 0o755); err != nil {
+		t.Fatal(err)
+	}
+
+	link := filepath.Join(tmp, "result")
+	if err := os.Symlink(filepath.Join(storePath, "bin"), link); err != nil {
+		t.Fatal(err)
+	}
+
+	c := client.NewTestClientWithStoreDir("/nix/store")
+	c.NixEnv = []string{"PATH=/bin", "NIX_STORE_DIR=/nix/store"}
+	c.SetStoreDir(storeDir + "/")
+
+	resolved, err := c.ResolveStorePath(link)
+	if err != nil {
+		t.Fatalf("ResolveStorePath(%q): %v", link, err)
+	}
+
+	if resolved != storePath {
+		t.Errorf("expected %q, got %q", storePath, resolved)
+	}
+
+	// nix commands must see the same store, and only once
+	want := []string{"PATH=/bin", "NIX_STORE_DIR=" + storeDir}
+	if strings.Join(c.NixEnv, "\n") != strings.Join(want, "\n") {
+		t.Errorf("expected NixEnv %q, got %q", want, c.NixEnv)
+	}
+}
+
+func TestResolveStorePathFromHash(t *testing.T) {
+	t.Parallel()
+
+	const hash = "8ha1dhmx807czjczmwy078s4r9s254il"
+
+	storeDir := filepath.Join(t.TempDir(), "nix", "store")
+	storePath := filepath.Join(storeDir, hash+"-hello-2.12.2")
+
+	if err := os.MkdirAll(storePath, 0o755); err != nil {
+		t.Fatal(err)
+	}
+
+	// Left behind by builds; must not make the hash ambiguous.
+	if err := os.WriteFile(storePath+".lock", nil, 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	c := client.NewTestClientWithStoreDir(storeDir)
+
+	for _, input := range []string{hash, hash + "-hello-2.12.2"} {
+		resolved, err := c.ResolveStorePath(input)
+		if err != nil {
+			t.Fatalf("ResolveStorePath(%q): %v", input, err)
+		}
+
+		if resolved != storePath {
+			t.Errorf("ResolveStorePath(%q) = %q, want %q", input, resolved, storePath)
+		}
+	}
+
+	if _, err := c.ResolveStorePath("0000000000000000000000000000000a"); err == nil {
+		t.Error("expected error for a hash not in the store")
+	}
+}
+
+func TestResolveStorePathRejectsInvalidStoreNames(t *testing.T) {
+	t.Parallel()
+
+	tmp := t.TempDir()
+	storeDir := filepath.Join(tmp, "nix", "store")
+	c := client.NewTestClientWithStoreDir(storeDir)
+
+	for _, name := range []string{".links", "hello", "abc123-hello", "0ha1dhmx807czjczmwy078s4r9s254il-", "0ha1dhmx807czjczmwy078s4r9s254il-.hidden", "0ha1dhmx807czjczmwy078s4r9s254il-a b"} {
+		storePath := filepath.Join(storeDir, name)
+		if err := os.MkdirAll(storePath, 0o750); err != nil {
+			t.Fatal(err)
+		}
+
+		link := filepath.Join(tmp, "result-"+strings.ReplaceAll(name, " ", "_"))
+		if err := os.Symlink(storePath, link); err != nil {
+			t.Fatal(err)
+		}
+
+		for _, input := range []string{storePath, link} {
+			_, err := c.ResolveStorePath(input)
+			if err == nil || !strings.Contains(err.Error(), "not a valid store path") {
+				t.Errorf("ResolveStorePath(%q) = %v, want a not a valid store path error", input, err)
+			}
+		}
+	}
+}
@@ -152,6 +152,12 @@ func retryAfterDuration(resp *http.Response) time.Duration {
 // client's TokenSource immediately before the request so short-lived tokens
 // (OIDC, vault) stay fresh across long uploads.
 func (c *Client) DoServerRequest(ctx context.Context, req *http.Request) (*http.Response, error) {
+	return c.doServerRequest(ctx, req, c.RequestTimeout)
+}
+
+// doServerRequest is DoServerRequest with an explicit per-attempt timeout,
+// e.g. TransferTimeout for NARs downloaded through the server.
+func (c *Client) doServerRequest(ctx context.Context, req *http.Request, timeout time.Duration) (*http.Response, error) {
 	tok, err := c.tokenSource(ctx)
 	if err != nil {
 		return nil, fmt.Errorf("resolving auth token: %w", err)
@@ -161,19 +167,81 @@ func (c *Client) DoServerRequest(ctx context.Context, req *http.Request) (*http.
 		req.Header.Set("Authorization", "Bearer "+tok)
 	}
 
-	return c.doWithRetry(ctx, req, c.ServerRateLimiter)
+	return c.doWithRetry(ctx, req, c.ServerRateLimiter, timeout)
+}
+
+// doServerRequestOnce is DoServerRequest without retries, for requests the
+// server may have acted on even when no response arrived.
+func (c *Client) doServerRequestOnce(ctx context.Context, req *http.Request) (*http.Response, error) {
+	tok, err := c.tokenSource(ctx)
+	if err != nil {
+		return nil, fmt.Errorf("resolving auth token: %w", err)
+	}
+
+	if tok != "" {
+		req.Header.Set("Authorization", "Bearer "+tok)
+	}
+
+	if c.UserAgent != "" && req.Header.Get("User-Agent") == "" {
+		req.Header.Set("User-Agent", c.UserAgent)
+	}
+
+	return doOnce(ctx, c.httpClientWithTimeout(c.RequestTimeout), req, c.ServerRateLimiter)
 }
 
 // DoS3Request executes an HTTP request to S3 (presigned URL) with rate limiting and retry.
+// Each attempt is bounded by TransferTimeout, as these carry NARs and logs.
+// Request bodies are throttled to MaxUploadRate.
 func (c *Client) DoS3Request(ctx context.Context, req *http.Request) (*http.Response, error) {
-	return c.doWithRetry(ctx, req, c.S3RateLimiter)
+	c.throttleUpload(ctx, req)
+
+	return c.doWithRetry(ctx, req, c.S3RateLimiter, c.TransferTimeout)
+}
+
+// throttleUpload wraps the body of req, and the bodies GetBody returns for
+// retries, in the bandwidth limiter shared by all uploads of the client.
+func (c *Client) throttleUpload(ctx context.Context, req *http.Request) {
+	c.uploadLimiterOnce.Do(func() {
+		c.uploadLimiter = ratelimit.NewBandwidthLimiter(c.MaxUploadRate)
+	})
+
+	limiter := c.uploadLimiter
+	if limiter == nil || req.Body == nil || req.Body == http.NoBody {
+		return
+	}
+
+	req.Body = limiter.ReadCloser(ctx, req.Body)
+
+	if getBody := req.GetBody; getBody != nil {
+		req.GetBody = func() (io.ReadCloser, error) {
+			body, err := getBody()
+			if err != nil {
+				return nil, err
+			}
+
+			return limiter.ReadCloser(ctx, body), nil
+		}
+	}
 }
 
 // DoWithRetry executes an HTTP request with exponential backoff retry logic.
 //
 // Deprecated: Use DoServerRequest or DoS3Request instead to get proper rate limiting.
 func (c *Client) DoWithRetry(ctx context.Context, req *http.Request) (*http.Response, error) {
-	return c.doWithRetry(ctx, req, c.ServerRateLimiter)
+	return c.doWithRetry(ctx, req, c.ServerRateLimiter, c.RequestTimeout)
+}
+
+// httpClientWithTimeout returns the HTTP client with timeout bounding each
+// request, including reading its response body. 0 means no timeout.
+func (c *Client) httpClientWithTimeout(timeout time.Duration) *http.Client {
+	if timeout <= 0 || timeout == c.httpClient.Timeout {
+		return c.httpClient
+	}
+
+	httpClient := *c.httpClient
+	httpClient.Timeout = timeout
+
+	return &httpClient
 }
 
 // recordLimiterFeedback updates the rate limiter based on the HTTP response status.
@@ -206,10 +274,17 @@ func waitForLimiter(ctx context.Context, limiter *ratelimit.AdaptiveRateLimiter)
 // doWithRetry executes an HTTP request with adaptive rate limiting and exponential backoff retry.
 // The request body will be read and stored for retries if necessary.
 // Auth headers must be set by the caller (e.g. DoServerRequest).
-func (c *Client) doWithRetry(ctx context.Context, req *http.Request, limiter *ratelimit.AdaptiveRateLimiter) (*http.Response, error) {
+// timeout bounds each attempt separately (0 = none).
+func (c *Client) doWithRetry(ctx context.Context, req *http.Request, limiter *ratelimit.AdaptiveRateLimiter, timeout time.Duration) (*http.Response, error) {
+	httpClient := c.httpClientWithTimeout(timeout)
+
+	if c.UserAgent != "" && req.Header.Get("User-Agent") == "" {
+		req.Header.Set("User-Agent", c.UserAgent)
+	}
+
 	// If retries are disabled, just do the request once
 	if c.Retry.MaxRetries <= 0 {
-		return c.doOnce(ctx, req, limiter)
+		return doOnce(ctx, httpClient, req, limiter)
 	}
 
 	// Require GetBody for retries so we can replay the body without
@@ -240,7 +315,7 @@ func (c *Client) doWithRetry(ctx context.Context, req *http.Request, limiter *ra
 		}
 
 		// Execute request
-		resp, err := c.httpClient.Do(req) //nolint:gosec // G704: req.URL is the configured server endpoint, not attacker input
+		resp, err := httpClient.Do(req) //nolint:gosec // G704: req.URL is the configured server endpoint, not attacker input
 
 		// Update rate limiter regardless of whether we retry
 		if err == nil {
@@ -304,14 +379,22 @@ func (c *Client) doWithRetry(ctx context.Context, req *http.Request, limiter *ra
 		}
 
 		// Log retry attempt
-		if err != nil {
+		switch {
+		case err != nil:
 			slog.Warn("Request failed, retrying",
 				"attempt", attempt+1,
 				"max_attempts", c.Retry.MaxRetries+1,
 				"backoff", backoff,
 				"error", err,
 				"url", req.URL.Redacted())
-		} else {
+		case isThrottle:
+			slog.Warn("Request was throttled, retrying",
+				"attempt", attempt+1,
+				"max_attempts", c.Retry.MaxRetries+1,
+				"backoff", backoff,
+				"status", resp.StatusCode,
+				"url", req.URL.Redacted())
+		default:
 			slog.Warn("Request returned retryable status, retrying",
 				"attempt", attempt+1,
 				"max_attempts", c.Retry.MaxRetries+1,
@@ -343,12 +426,12 @@ func (c *Client) doWithRetry(ctx context.Context, req *http.Request, limiter *ra
 }
 
 // doOnce executes a single HTTP request without retries, with rate limiting feedback.
-func (c *Client) doOnce(ctx context.Context, req *http.Request, limiter *ratelimit.AdaptiveRateLimiter) (*http.Response, error) {
+func doOnce(ctx context.Context, httpClient *http.Client, req *http.Request, limiter *ratelimit.AdaptiveRateLimiter) (*http.Response, error) {
 	if err := waitForLimiter(ctx, limiter); err != nil {
 		return nil, err
 	}
 
-	resp, err := c.httpClient.Do(req) //nolint:gosec // G704: req.URL is the configured server endpoint, not attacker input
+	resp, err := httpClient.Do(req) //nolint:gosec // G704: req.URL is the configured server endpoint, not attacker input
 	if err != nil {
 		return nil, fmt.Errorf("executing request: %w", err)
 	}
@@ -82,3 +82,87 @@ func TestDoWithRetry_BodyReplayedViaGetBody(t *testing.T) {
 		t.Fatalf("expected 3 attempts, got %d", got)
 	}
 }
+
+// TestDoWithRetry_ClientErrorNotRetried verifies that 4xx responses such as
+// an expired presigned URL (403) are returned immediately.
+func TestDoWithRetry_ClientErrorNotRetried(t *testing.T) {
+	t.Parallel()
+
+	var attempts atomic.Int32
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, _ *http.Request) {
+		attempts.Add(1)
+		w.WriteHeader(http.StatusForbidden)
+	}))
+	defer srv.Close()
+
+	c := client.NewTestClient(srv.Client(), client.RetryConfig{
+		MaxRetries:     5,
+		InitialBackoff: 1 * time.Millisecond,
+		MaxBackoff:     10 * time.Millisecond,
+		Multiplier:     1.0,
+	})
+
+	req, err := http.NewRequestWithContext(context.Background(), http.MethodPut, srv.URL, bytes.NewReader([]byte("x")))
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	resp, err := c.DoWithRetry(context.Background(), req)
+	if err != nil {
+		t.Fatalf("DoWithRetry failed: %v", err)
+	}
+
+	defer func() {
+		if err := resp.Body.Close(); err != nil {
+			t.Errorf("closing response body: %v", err)
+		}
+	}()
+
+	if resp.StatusCode != http.StatusForbidden {
+		t.Fatalf("expected 403, got %d", resp.StatusCode)
+	}
+
+	if got := int(attempts.Load()); got != 1 {
+		t.Fatalf("expected 1 attempt, got %d", got)
+	}
+}
+
+// TestRequestTimeoutOnlyBoundsServerCalls verifies that RequestTimeout
+// aborts slow server API calls while S3 transfers, bounded by the unset
+// TransferTimeout, may take longer.
+func TestRequestTimeoutOnlyBoundsServerCalls(t *testing.T) {
+	t.Parallel()
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, _ *http.Request) {
+		time.Sleep(200 * time.Millisecond)
+		w.WriteHeader(http.StatusOK)
+	}))
+	defer srv.Close()
+
+	c := client.NewTestClient(srv.Client(), client.RetryConfig{})
+	c.RequestTimeout = 50 * time.Millisecond
+
+	req, err := http.NewRequestWithContext(t.Context(), http.MethodGet, srv.URL, nil)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if resp, err := c.DoServerRequest(t.Context(), req); err == nil {
+		_ = resp.Body.Close()
+
+		t.Fatal("expected server request to time out")
+	}
+
+	req, err = http.NewRequestWithContext(t.Context(), http.MethodPut, srv.URL, bytes.NewReader([]byte("nar")))
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	resp, err := c.DoS3Request(t.Context(), req)
+	if err != nil {
+		t.Fatalf("DoS3Request failed: %v", err)
+	}
+
+	_ = resp.Body.Close()
+}
@@ -3,6 +3,8 @@ package client
 import (
 	"bytes"
 	"context"
+	"crypto/md5" //nolint:gosec // used for S3's Content-MD5 header only
+	"encoding/base64"
 	"fmt"
 	"log/slog"
 	"net/http"
@@ -13,6 +15,10 @@ import (
 
 // UploadBytesToPresignedURLWithHeaders uploads bytes to a presigned URL with optional custom headers.
 func (c *Client) UploadBytesToPresignedURLWithHeaders(ctx context.Context, presignedURL string, data []byte, headers map[string]string) error {
+	if len(data) > maxSimpleUploadSize {
+		return fmt.Errorf("object of %s exceeds the %s single-PUT limit", FormatBytes(uint64(len(data))), FormatBytes(maxSimpleUploadSize))
+	}
+
 	req, err := http.NewRequestWithContext(ctx, http.MethodPut, presignedURL, bytes.NewReader(data))
 	if err != nil {
 		return fmt.Errorf("creating request: %w", err)
@@ -20,6 +26,7 @@ func (c *Client) UploadBytesToPresignedURLWithHeaders(ctx context.Context, presi
 
 	req.ContentLength = int64(len(data))
 	req.Header.Set("Content-Type", "application/octet-stream")
+	c.setContentMD5(req, data)
 
 	// Add custom headers
 	for key, value := range headers {
@@ -36,6 +43,17 @@ func (c *Client) UploadBytesToPresignedURLWithHeaders(ctx context.Context, presi
 	return checkResponse(resp, http.StatusOK, http.StatusNoContent)
 }
 
+// setContentMD5 adds a Content-MD5 header for data if ChecksumUploads is
+// enabled, so S3 rejects a body that was corrupted in flight.
+func (c *Client) setContentMD5(req *http.Request, data []byte) {
+	if !c.ChecksumUploads {
+		return
+	}
+
+	sum := md5.Sum(data) //nolint:gosec // Content-MD5 is the integrity check defined by the S3 API
+	req.Header.Set("Content-MD5", base64.StdEncoding.EncodeToString(sum[:]))
+}
+
 // UploadListingToPresignedURL compresses a NAR listing with zstd and uploads it with Content-Encoding header.
 // The listing is stored as a .ls file, compatible with Nix's lazy NAR accessor format.
 func (c *Client) UploadListingToPresignedURL(ctx context.Context, presignedURL string, listing *NarListing) error {
@@ -55,8 +73,15 @@ func (c *Client) UploadListingToPresignedURL(ctx context.Context, presignedURL s
 
 // UploadBuildLogToPresignedURL uploads a compressed build log with Content-Encoding header.
 // This follows Nix's convention for compressed build logs stored at log/<drvPath>.
-// The compressedInfo must point to a temporary file created by CompressBuildLog.
+// The compressedInfo must come from CompressBuildLog.
 func (c *Client) UploadBuildLogToPresignedURL(ctx context.Context, presignedURL string, compressedInfo *CompressedBuildLogInfo) error {
+	if compressedInfo.TempFile == "" {
+		return c.UploadBytesToPresignedURLWithHeaders(ctx, presignedURL, compressedInfo.Data, map[string]string{
+			"Content-Type":     "text/plain; charset=utf-8",
+			"Content-Encoding": compressionZstd,
+		})
+	}
+
 	// Open file for mmap
 	file, err := os.Open(compressedInfo.TempFile)
 	if err != nil {
@@ -76,6 +101,9 @@ func (c *Client) UploadBuildLogToPresignedURL(ctx context.Context, presignedURL
 	}
 
 	fileSize := stat.Size()
+	if fileSize > maxSimpleUploadSize {
+		return fmt.Errorf("compressed log of %s exceeds the %s single-PUT limit", FormatBytes(uint64(fileSize)), FormatBytes(maxSimpleUploadSize)) //nolint:gosec // file sizes are never negative
+	}
 
 	var reader *bytes.Reader
 
@@ -114,6 +142,7 @@ func (c *Client) UploadBuildLogToPresignedURL(ctx context.Context, presignedURL
 	// Set headers
 	req.Header.Set("Content-Type", "text/plain; charset=utf-8")
 	req.Header.Set("Content-Encoding", compressionZstd)
+	c.setContentMD5(req, mmapData)
 
 	// Upload
 	resp, err := c.DoS3Request(ctx, req)
@@ -0,0 +1,226 @@
+package client
+
+import (
+	"bytes"
+	"context"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"log/slog"
+	"maps"
+	"net/http"
+	"slices"
+	"sync"
+
+	"github.com/Mic92/niks3/api"
+	"golang.org/x/sync/errgroup"
+)
+
+// narinfoExistsBatchSize is how many hashes one POST /api/narinfo_exists
+// request carries, below the server's limit of 10000.
+const narinfoExistsBatchSize = 1000
+
+// errNarinfoExistsUnsupported is returned by queryNarinfosExist when the
+// server predates POST /api/narinfo_exists.
+var errNarinfoExistsUnsupported = errors.New("server does not support bulk narinfo queries")
+
+// QueryCachedPaths returns the set of store paths in pathInfos whose
+// <hash>.narinfo the cache already has. It asks the server in bulk and
+// falls back to one HEAD request per path if the server does not support
+// that or the bulk query fails. Paths whose query fails are logged and
+// treated as missing, so a flaky check only costs a redundant upload.
+func (c *Client) QueryCachedPaths(ctx context.Context, pathInfos map[string]*PathInfo) (map[string]bool, error) {
+	// A direct S3 backend has no server to ask in bulk.
+	if _, ok := c.API.(objectChecker); !ok && len(pathInfos) > 0 {
+		cached, err := c.queryNarinfosExist(ctx, pathInfos)
+		if err == nil {
+			return cached, nil
+		}
+
+		if ctx.Err() != nil {
+			return nil, ctx.Err() //nolint:wrapcheck // cancellation is reported as-is
+		}
+
+		if errors.Is(err, errNarinfoExistsUnsupported) {
+			slog.Debug("Server lacks bulk narinfo queries, checking paths one by one")
+		} else {
+			slog.Warn("Bulk narinfo query failed, checking paths one by one", "error", err)
+		}
+	}
+
+	return c.headCachedPaths(ctx, pathInfos)
+}
+
+// queryNarinfosExist looks up the narinfos of pathInfos with POST
+// /api/narinfo_exists, in batches of narinfoExistsBatchSize hashes.
+func (c *Client) queryNarinfosExist(ctx context.Context, pathInfos map[string]*PathInfo) (map[string]bool, error) {
+	byHash := make(map[string]string, len(pathInfos))
+
+	for storePath := range pathInfos {
+		hash, err := GetStorePathHash(storePath)
+		if err != nil {
+			return nil, err
+		}
+
+		byHash[hash] = storePath
+	}
+
+	cached := make(map[string]bool)
+
+	for batch := range slices.Chunk(slices.Sorted(maps.Keys(byHash)), narinfoExistsBatchSize) {
+		present, err := c.narinfosExist(ctx, batch)
+		if err != nil {
+			return nil, err
+		}
+
+		for _, hash := range present {
+			if storePath, ok := byHash[hash]; ok {
+				cached[storePath] = true
+			}
+		}
+	}
+
+	return cached, nil
+}
+
+// narinfosExist returns which of hashes have a narinfo below KeyPrefix.
+func (c *Client) narinfosExist(ctx context.Context, hashes []string) ([]string, error) {
+	reqBody, err := json.Marshal(api.NarinfoExistsRequest{Hashes: hashes, KeyPrefix: c.KeyPrefix})
+	if err != nil {
+		return nil, fmt.Errorf("marshaling request: %w", err)
+	}
+
+	reqURL := c.baseURL.JoinPath("api/narinfo_exists")
+
+	req, err := http.NewRequestWithContext(ctx, http.MethodPost, reqURL.String(), bytes.NewReader(reqBody))
+	if err != nil {
+		return nil, fmt.Errorf("creating request: %w", err)
+	}
+
+	req.Header.Set("Content-Type", "application/json")
+
+	resp, err := c.DoServerRequest(ctx, req)
+	if err != nil {
+		return nil, fmt.Errorf("sending request: %w", err)
+	}
+
+	defer deferCloseBody(resp)
+
+	if resp.StatusCode == http.StatusNotFound || resp.StatusCode == http.StatusMethodNotAllowed {
+		return nil, errNarinfoExistsUnsupported
+	}
+
+	if err := checkResponse(resp, http.StatusOK); err != nil {
+		return nil, err
+	}
+
+	var result api.NarinfoExistsResponse
+	if err := json.NewDecoder(resp.Body).Decode(&result); err != nil {
+		return nil, fmt.Errorf("parsing response: %w", err)
+	}
+
+	return result.Present, nil
+}
+
+// headCachedPaths HEADs <hash>.narinfo for every store path in pathInfos,
+// for servers and backends without bulk narinfo queries.
+func (c *Client) headCachedPaths(ctx context.Context, pathInfos map[string]*PathInfo) (map[string]bool, error) {
+	cached := make(map[string]bool)
+
+	var mu sync.Mutex
+
+	numWorkers := c.MaxConcurrentNARUploads
+	if numWorkers <= 0 || numWorkers > len(pathInfos) {
+		numWorkers = max(len(pathInfos), 1)
+	}
+
+	g, ctx := errgroup.WithContext(ctx)
+	g.SetLimit(numWorkers)
+
+	for storePath := range pathInfos {
+		g.Go(func() error {
+			hash, err := GetStorePathHash(storePath)
+			if err != nil {
+				return err
+			}
+
+			exists, err := c.ObjectExists(ctx, hash+".narinfo")
+			if err != nil {
+				if ctx.Err() != nil {
+					return ctx.Err() //nolint:wrapcheck // cancellation is reported as-is
+				}
+
+				slog.Warn("Failed to query cache for narinfo", "store_path", storePath, "error", err)
+
+				return nil
+			}
+
+			if exists {
+				mu.Lock()
+				cached[storePath] = true
+				mu.Unlock()
+			}
+
+			return nil
+		})
+	}
+
+	if err := g.Wait(); err != nil {
+		return nil, err //nolint:wrapcheck // errgroup returns the first task's already-wrapped error
+	}
+
+	return cached, nil
+}
+
+// dropCachedClosures returns the top-level paths whose closure still has at
+// least one path missing from the cache, along with pathInfos pruned to
+// those closures.
+func dropCachedClosures(topLevelPaths []string, pathInfos map[string]*PathInfo, cached map[string]bool) ([]string, map[string]*PathInfo) {
+	remaining := make([]string, 0, len(topLevelPaths))
+	needed := make(map[string]*PathInfo)
+
+	for _, topLevelPath := range topLevelPaths {
+		closure := make(map[string]bool)
+		complete := true
+
+		var visit func(string)
+
+		visit = func(path string) {
+			if closure[path] {
+				return
+			}
+
+			closure[path] = true
+
+			if !cached[path] {
+				complete = false
+			}
+
+			pathInfo, ok := pathInfos[path]
+			if !ok {
+				return
+			}
+
+			for _, ref := range pathInfo.References {
+				visit(ref)
+			}
+		}
+		visit(topLevelPath)
+
+		if complete {
+			slog.Debug("Closure already cached", "store_path", topLevelPath)
+
+			continue
+		}
+
+		remaining = append(remaining, topLevelPath)
+
+		for path := range closure {
+			if pathInfo, ok := pathInfos[path]; ok {
+				needed[path] = pathInfo
+			}
+		}
+	}
+
+	return remaining, needed
+}
@@ -0,0 +1,140 @@
+package client_test
+
+import (
+	"context"
+	"encoding/json"
+	"net/http"
+	"net/http/httptest"
+	"slices"
+	"strings"
+	"testing"
+
+	"github.com/Mic92/niks3/api"
+	"github.com/Mic92/niks3/client"
+)
+
+const (
+	cachedLib  = "/nix/store/00000000000000000000000000000000-lib"
+	missingLib = "/nix/store/11111111111111111111111111111111-lib"
+	cachedApp  = "/nix/store/22222222222222222222222222222222-app"
+	missingApp = "/nix/store/33333333333333333333333333333333-app"
+)
+
+func skipExistingPathInfos() map[string]*client.PathInfo {
+	return map[string]*client.PathInfo{
+		cachedLib:  {References: []string{cachedLib}},
+		missingLib: {},
+		cachedApp:  {References: []string{cachedLib}},
+		missingApp: {References: []string{missingLib, cachedLib}},
+	}
+}
+
+func TestQueryCachedPaths(t *testing.T) {
+	t.Parallel()
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		// A server without POST /api/narinfo_exists, so the client falls
+		// back to HEAD requests.
+		if r.Method != http.MethodHead {
+			http.NotFound(w, r)
+
+			return
+		}
+
+		switch strings.TrimPrefix(r.URL.Path, "/api/objects/") {
+		case "00000000000000000000000000000000.narinfo", "22222222222222222222222222222222.narinfo":
+			w.WriteHeader(http.StatusNoContent)
+		case "33333333333333333333333333333333.narinfo":
+			// A failing check must not abort the push.
+			w.WriteHeader(http.StatusForbidden)
+		default:
+			w.WriteHeader(http.StatusNotFound)
+		}
+	}))
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	c.Retry.MaxRetries = 0
+
+	cached, err := c.QueryCachedPaths(context.Background(), skipExistingPathInfos())
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if len(cached) != 2 || !cached[cachedLib] || !cached[cachedApp] {
+		t.Fatalf("unexpected cached set: %v", cached)
+	}
+}
+
+func TestQueryCachedPathsBulk(t *testing.T) {
+	t.Parallel()
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if r.Method != http.MethodPost || r.URL.Path != "/api/narinfo_exists" {
+			http.Error(w, "unexpected request", http.StatusBadRequest)
+
+			return
+		}
+
+		var req api.NarinfoExistsRequest
+		if err := json.NewDecoder(r.Body).Decode(&req); err != nil {
+			http.Error(w, err.Error(), http.StatusBadRequest)
+
+			return
+		}
+
+		if len(req.Hashes) != 4 {
+			http.Error(w, "expected 4 hashes", http.StatusBadRequest)
+
+			return
+		}
+
+		_ = json.NewEncoder(w).Encode(api.NarinfoExistsResponse{
+			Present: []string{"00000000000000000000000000000000", "22222222222222222222222222222222"},
+		})
+	}))
+	defer srv.Close()
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	c.Retry.MaxRetries = 0
+
+	cached, err := c.QueryCachedPaths(context.Background(), skipExistingPathInfos())
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if len(cached) != 2 || !cached[cachedLib] || !cached[cachedApp] {
+		t.Fatalf("unexpected cached set: %v", cached)
+	}
+}
+
+func TestDropCachedClosures(t *testing.T) {
+	t.Parallel()
+
+	pathInfos := skipExistingPathInfos()
+	cached := map[string]bool{cachedLib: true, cachedApp: true}
+
+	remaining, needed := client.DropCachedClosures([]string{cachedApp, missingApp}, pathInfos, cached)
+
+	if !slices.Equal(remaining, []string{missingApp}) {
+		t.Fatalf("expected only %s to remain, got %v", missingApp, remaining)
+	}
+
+	// The cached lib stays because the remaining closure still references it.
+	if len(needed) != 3 || needed[cachedApp] != nil {
+		t.Fatalf("unexpected pruned path infos: %v", needed)
+	}
+
+	remaining, needed = client.DropCachedClosures([]string{cachedApp}, pathInfos, cached)
+	if len(remaining) != 0 || len(needed) != 0 {
+		t.Fatalf("fully cached closure should be dropped, got %v / %v", remaining, needed)
+	}
+}
@@ -0,0 +1,87 @@
+package client
+
+import (
+	"maps"
+	"math"
+	"slices"
+)
+
+// PushSummary is the machine-readable summary of a push printed by
+// `niks3 push --output json`. Scripts parse it, so fields may be added but
+// never renamed or removed.
+type PushSummary struct {
+	ServerURL        string               `json:"server_url,omitempty"` // Cache of a push to several; set by the caller
+	Paths            int                  `json:"paths"`                // Store paths in the pushed closures
+	UploadedPaths    int                  `json:"uploaded_paths"`       // Paths whose narinfo this push uploaded
+	SkippedPaths     int                  `json:"skipped_paths"`        // Paths the cache already had
+	FailedPaths      int                  `json:"failed_paths"`         // Entries of Failures
+	UploadedNARs     int                  `json:"uploaded_nars"`        // NARs uploaded; deduplicated NARs are not counted
+	NarBytes         uint64               `json:"nar_bytes"`            // Uncompressed size of the uploaded NARs
+	CompressedBytes  uint64               `json:"compressed_bytes"`     // Size of the uploaded NARs as stored
+	CompressionRatio float64              `json:"compression_ratio"`    // NarBytes / CompressedBytes, 0 if no NAR was uploaded
+	ElapsedSeconds   float64              `json:"elapsed_seconds"`
+	NARs             []PushSummaryNAR     `json:"nars"`     // Never null; sorted by store path
+	Failures         []PushSummaryFailure `json:"failures"` // Never null
+}
+
+// PushSummaryNAR is the compression result of one uploaded NAR.
+type PushSummaryNAR struct {
+	StorePath        string  `json:"store_path"`
+	NarSize          uint64  `json:"nar_size"`
+	FileSize         uint64  `json:"file_size"`
+	CompressionRatio float64 `json:"compression_ratio"`
+}
+
+// PushSummaryFailure is a path, or a build log or realisation key, that
+// failed to upload with ContinueOnError.
+type PushSummaryFailure struct {
+	StorePath string `json:"store_path"`
+	Error     string `json:"error"`
+}
+
+// Summary condenses the stats of a push into a PushSummary.
+func (s *UploadStats) Summary() PushSummary {
+	summary := PushSummary{
+		Paths:          s.Paths,
+		UploadedPaths:  len(s.Succeeded),
+		FailedPaths:    len(s.Failed),
+		UploadedNARs:   len(s.narDigests),
+		NarBytes:       s.NarBytes,
+		ElapsedSeconds: s.Elapsed.Seconds(),
+		NARs:           make([]PushSummaryNAR, 0, len(s.narDigests)),
+		Failures:       make([]PushSummaryFailure, 0, len(s.Failed)),
+	}
+
+	summary.SkippedPaths = max(summary.Paths-summary.UploadedPaths-summary.FailedPaths, 0)
+
+	for _, storePath := range slices.Sorted(maps.Keys(s.narDigests)) {
+		digest := s.narDigests[storePath]
+		summary.CompressedBytes += digest.FileSize
+
+		narSize := s.narSizes[storePath]
+		summary.NARs = append(summary.NARs, PushSummaryNAR{
+			StorePath:        storePath,
+			NarSize:          narSize,
+			FileSize:         digest.FileSize,
+			CompressionRatio: compressionRatio(narSize, digest.FileSize),
+		})
+	}
+
+	summary.CompressionRatio = compressionRatio(summary.NarBytes, summary.CompressedBytes)
+
+	for _, failure := range s.Failed {
+		summary.Failures = append(summary.Failures, PushSummaryFailure{StorePath: failure.StorePath, Error: failure.Err.Error()})
+	}
+
+	return summary
+}
+
+// compressionRatio returns how many times smaller compression made a NAR,
+// rounded to three decimals; 0 if either size is unknown.
+func compressionRatio(narSize, fileSize uint64) float64 {
+	if narSize == 0 || fileSize == 0 {
+		return 0
+	}
+
+	return math.Round(float64(narSize)/float64(fileSize)*1000) / 1000
+}
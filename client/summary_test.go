@@ -0,0 +1,81 @@
+package client_test
+
+import (
+	"encoding/json"
+	"errors"
+	"testing"
+	"time"
+
+	"github.com/Mic92/niks3/client"
+)
+
+func TestUploadStatsSummary(t *testing.T) {
+	t.Parallel()
+
+	stats := &client.UploadStats{
+		Succeeded: []string{"/nix/store/0123456789abcdfghijklmnpqrsvwxyz-hello"},
+		Failed: []client.UploadFailure{
+			{StorePath: "/nix/store/00000000000000000000000000000000-glibc", Err: errors.New("boom")},
+		},
+		Paths:    5,
+		NarBytes: 1024,
+		Elapsed:  1500 * time.Millisecond,
+	}
+	stats.SetNarDigests(map[string]*client.FileDigest{
+		"/nix/store/0123456789abcdfghijklmnpqrsvwxyz-hello": {FileSize: 512},
+	})
+	stats.SetNarSizes(map[string]uint64{
+		"/nix/store/0123456789abcdfghijklmnpqrsvwxyz-hello": 1024,
+	})
+
+	summary := stats.Summary()
+
+	if summary.Paths != 5 || summary.UploadedPaths != 1 || summary.SkippedPaths != 3 || summary.FailedPaths != 1 {
+		t.Errorf("unexpected path counts: %+v", summary)
+	}
+
+	if summary.UploadedNARs != 1 || summary.NarBytes != 1024 || summary.CompressedBytes != 512 {
+		t.Errorf("unexpected NAR counts: %+v", summary)
+	}
+
+	if summary.CompressionRatio != 2 {
+		t.Errorf("CompressionRatio = %v, want 2", summary.CompressionRatio)
+	}
+
+	want := client.PushSummaryNAR{StorePath: "/nix/store/0123456789abcdfghijklmnpqrsvwxyz-hello", NarSize: 1024, FileSize: 512, CompressionRatio: 2}
+	if len(summary.NARs) != 1 || summary.NARs[0] != want {
+		t.Errorf("NARs = %+v, want [%+v]", summary.NARs, want)
+	}
+
+	if summary.ElapsedSeconds != 1.5 {
+		t.Errorf("ElapsedSeconds = %v, want 1.5", summary.ElapsedSeconds)
+	}
+
+	if len(summary.Failures) != 1 || summary.Failures[0].Error != "boom" {
+		t.Errorf("unexpected failures: %+v", summary.Failures)
+	}
+}
+
+func TestUploadStatsSummaryEmptyFailures(t *testing.T) {
+	t.Parallel()
+
+	data, err := json.Marshal((&client.UploadStats{}).Summary())
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	var decoded map[string]any
+	if err := json.Unmarshal(data, &decoded); err != nil {
+		t.Fatal(err)
+	}
+
+	for _, field := range []string{"nars", "failures"} {
+		if values, ok := decoded[field].([]any); !ok || len(values) != 0 {
+			t.Errorf("%s = %v, want an empty array", field, decoded[field])
+		}
+	}
+
+	if decoded["compression_ratio"] != 0.0 {
+		t.Errorf("compression_ratio = %v, want 0 without NARs", decoded["compression_ratio"])
+	}
+}
@@ -16,6 +16,7 @@ import (
 	"net/http/httptest"
 	"os"
 	"path/filepath"
+	"sync/atomic"
 	"testing"
 	"time"
 
@@ -215,6 +216,11 @@ func TestSetClientTLSErrors(t *testing.T) {
 		t.Fatal(err)
 	}
 
+	garbled := filepath.Join(dir, "garbled.pem")
+	if err := os.WriteFile(garbled, pem.EncodeToMemory(&pem.Block{Type: "CERTIFICATE", Bytes: []byte("garbage")}), 0o600); err != nil {
+		t.Fatal(err)
+	}
+
 	tests := []struct {
 		name string
 		cert string
@@ -225,6 +231,8 @@ func TestSetClientTLSErrors(t *testing.T) {
 		{"missing key file", certPath, filepath.Join(dir, "nope.key"), ""},
 		{"missing ca file", certPath, keyPath, filepath.Join(dir, "nope.ca")},
 		{"invalid ca file", certPath, keyPath, bogus},
+		{"unparseable ca certificate", certPath, keyPath, garbled},
+		{"key instead of ca certificate", "", "", keyPath},
 	}
 
 	for _, tt := range tests {
@@ -238,3 +246,40 @@ func TestSetClientTLSErrors(t *testing.T) {
 		})
 	}
 }
+
+// TestSetProxy verifies requests are sent through the configured proxy.
+func TestSetProxy(t *testing.T) {
+	t.Parallel()
+
+	var proxied atomic.Bool
+
+	proxy := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		// A proxy receives the absolute URL of the target.
+		if r.URL.Host == "cache.invalid" {
+			proxied.Store(true)
+		}
+
+		_, _ = io.WriteString(w, "ok")
+	}))
+	t.Cleanup(proxy.Close)
+
+	c := client.NewTestClient(&http.Client{}, client.DefaultRetryConfig())
+	if err := c.SetProxy(proxy.URL); err != nil {
+		t.Fatalf("SetProxy: %v", err)
+	}
+
+	resp, err := doGet(t, c, "http://cache.invalid/nix-cache-info")
+	if err != nil {
+		t.Fatalf("request through proxy failed: %v", err)
+	}
+
+	_ = resp.Body.Close()
+
+	if !proxied.Load() {
+		t.Fatal("request did not go through the proxy")
+	}
+
+	if err := c.SetProxy("proxy:3128"); err == nil {
+		t.Fatal("expected error for proxy URL without scheme")
+	}
+}
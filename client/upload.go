@@ -9,6 +9,7 @@ import (
 	"net/http"
 	"os"
 	"path/filepath"
+	"slices"
 	"strings"
 	"time"
 
@@ -16,7 +17,8 @@ import (
 )
 
 // getNARKey generates the NAR object key based on content hash (NarHash) for deduplication.
-func getNARKey(narHash string) (string, error) {
+// The suffix reflects the compression the NAR is uploaded with.
+func getNARKey(narHash string, compression Compression) (string, error) {
 	// Convert NarHash to Nix32 format and strip "sha256:" prefix for filename
 	narHashNix32, err := ConvertHashToNix32(narHash)
 	if err != nil {
@@ -24,7 +26,7 @@ func getNARKey(narHash string) (string, error) {
 	}
 
 	narHashPart := strings.TrimPrefix(narHashNix32, "sha256:")
-	narFilename := narHashPart + ".nar.zst"
+	narFilename := narHashPart + compression.narExtension()
 
 	return "nar/" + narFilename, nil
 }
@@ -32,6 +34,8 @@ func getNARKey(narHash string) (string, error) {
 // resolveSymlinks resolves any symlinks in the given paths to their actual store paths.
 // Resolves symlinks iteratively until reaching a path in the Nix store, then stops.
 // This prevents resolving symlinks within the store to subdirectory paths which would break hash extraction.
+// Bare store path hashes and "<hash>-<name>" names are completed with storeDir first.
+// Paths that do not end up at a "<hash>-<name>" store object are rejected.
 func resolveSymlinks(paths []string, storeDir string) ([]string, error) {
 	resolved := make([]string, 0, len(paths))
 	storeDirPrefix := storeDir + "/"
@@ -39,7 +43,10 @@ func resolveSymlinks(paths []string, storeDir string) ([]string, error) {
 	const maxSymlinkDepth = 255 // Same limit as Go's filepath.EvalSymlinks (allows 255 resolutions, errors on 256th)
 
 	for _, path := range paths {
-		currentPath := path
+		currentPath, err := expandStoreName(path, storeDir)
+		if err != nil {
+			return nil, err
+		}
 
 		// Resolve symlinks iteratively until we reach a path in the store
 		for i := range maxSymlinkDepth {
@@ -71,17 +78,86 @@ func resolveSymlinks(paths []string, storeDir string) ([]string, error) {
 			currentPath = linkTarget
 		}
 
-		resolved = append(resolved, currentPath)
+		storePath, ok := topLevelStorePath(currentPath, storeDirPrefix)
+		if !ok {
+			return nil, fmt.Errorf("path %s resolves to %s, which is not in the Nix store %s", path, currentPath, storeDir)
+		}
+
+		if storePath != currentPath {
+			slog.Debug("Path resolves into a store path subdirectory", "path", path, "target", currentPath, "store_path", storePath)
+		}
+
+		if err := checkStoreObjectName(filepath.Base(storePath)); err != nil {
+			if storePath == path {
+				return nil, fmt.Errorf("%s is not a valid store path: %w", path, err)
+			}
+
+			return nil, fmt.Errorf("path %s resolves to %s, which is not a valid store path: %w", path, storePath, err)
+		}
+
+		resolved = append(resolved, storePath)
 	}
 
 	return resolved, nil
 }
 
+// expandStoreName turns a bare store path hash or a "<hash>-<name>" store
+// object name into a path in storeDir, as scripts often only have those at
+// hand. A bare hash is looked up in storeDir, so it only works for a local
+// store. Anything else, e.g. ./result or a full path, is returned as is.
+func expandStoreName(path, storeDir string) (string, error) {
+	if strings.Contains(path, "/") {
+		return path, nil
+	}
+
+	hash, _, hasName := strings.Cut(path, "-")
+	if !isStorePathHash(hash) {
+		return path, nil
+	}
+
+	if hasName {
+		return filepath.Join(storeDir, path), nil
+	}
+
+	matches, err := filepath.Glob(filepath.Join(storeDir, hash+"-*"))
+	if err != nil {
+		return "", fmt.Errorf("looking up store path hash %s: %w", hash, err)
+	}
+
+	// Nix keeps <path>.lock files next to paths while building them.
+	matches = slices.DeleteFunc(matches, func(m string) bool { return strings.HasSuffix(m, ".lock") })
+
+	switch len(matches) {
+	case 0:
+		return "", fmt.Errorf("no store path with hash %s in %s", hash, storeDir)
+	case 1:
+		return matches[0], nil
+	default:
+		return "", fmt.Errorf("store path hash %s is ambiguous in %s: %s", hash, storeDir, strings.Join(matches, ", "))
+	}
+}
+
+// topLevelStorePath strips anything below the store object from path, so
+// a link into e.g. /nix/store/<hash>-foo/bin/foo yields /nix/store/<hash>-foo.
+// It reports false if path is not inside the store directory.
+func topLevelStorePath(path, storeDirPrefix string) (string, bool) {
+	path = filepath.Clean(path)
+
+	rest, ok := strings.CutPrefix(path, storeDirPrefix)
+	if !ok || rest == "" {
+		return "", false
+	}
+
+	name, _, _ := strings.Cut(rest, "/")
+
+	return storeDirPrefix + name, true
+}
+
 // ResolveStorePath resolves symlinks (e.g. a nix-build ./result link) until
 // the path points into the Nix store. Needed wherever a raw user-supplied
 // path is sent to the server, which only accepts store paths.
 func (c *Client) ResolveStorePath(path string) (string, error) {
-	resolved, err := resolveSymlinks([]string{path}, c.storeDir)
+	resolved, err := resolveSymlinks([]string{path}, c.effectiveStoreDir())
 	if err != nil {
 		return "", err
 	}
@@ -89,6 +165,19 @@ func (c *Client) ResolveStorePath(path string) (string, error) {
 	return resolved[0], nil
 }
 
+// effectiveStoreDir returns the store directory paths must resolve into.
+// A NIX_STORE_DIR override in NixEnv (isolated test stores) takes precedence
+// over the directory detected when the client was created.
+func (c *Client) effectiveStoreDir() string {
+	for _, env := range c.NixEnv {
+		if dir, ok := strings.CutPrefix(env, "NIX_STORE_DIR="); ok {
+			return dir
+		}
+	}
+
+	return c.storeDir
+}
+
 // ClosureInfo represents a closure with its associated objects.
 type ClosureInfo struct {
 	NarinfoKey string
@@ -108,7 +197,9 @@ type PrepareClosuresResult struct {
 // Build logs are automatically discovered for output paths and included by default.
 // Realisations are queried for CA derivations and included automatically.
 // topLevelPaths specifies which paths are closure roots - one ClosureInfo is created per top-level path.
-func PrepareClosures(ctx context.Context, topLevelPaths []string, pathInfos map[string]*PathInfo, nixEnv []string) (*PrepareClosuresResult, error) {
+// compression determines the NAR object key suffix for paths that do not override it.
+// Without writeListings, no .ls objects are created.
+func PrepareClosures(ctx context.Context, topLevelPaths []string, pathInfos map[string]*PathInfo, nixEnv []string, compression Compression, writeListings bool) (*PrepareClosuresResult, error) {
 	pathInfoByHash := make(map[string]*PathInfo)
 	narKeyToHash := make(map[string]string)
 	logPathsByKey := make(map[string]string)
@@ -133,7 +224,8 @@ func PrepareClosures(ctx context.Context, topLevelPaths []string, pathInfos map[
 
 		pathInfoByHash[hash] = pathInfo
 
-		// Extract references as object keys (hash.narinfo)
+		// Extract references as object keys (hash.narinfo). A path's
+		// reference to itself gives GC nothing to follow, so it is left out.
 		var references []string
 
 		for _, ref := range pathInfo.References {
@@ -142,12 +234,19 @@ func PrepareClosures(ctx context.Context, topLevelPaths []string, pathInfos map[
 				return nil, fmt.Errorf("getting reference hash: %w", err)
 			}
 
+			if refHash == hash {
+				continue
+			}
+
 			// Store reference as object key (hash.narinfo) so GC can follow it
 			references = append(references, refHash+".narinfo")
 		}
 
+		slices.Sort(references)
+		references = slices.Compact(references)
+
 		// NAR file object - use NarHash for content-based deduplication
-		narKey, err := getNARKey(pathInfo.NarHash.String())
+		narKey, err := getNARKey(pathInfo.NarHash.String(), pathInfo.narCompression(compression))
 		if err != nil {
 			return nil, fmt.Errorf("getting NAR key: %w", err)
 		}
@@ -155,8 +254,12 @@ func PrepareClosures(ctx context.Context, topLevelPaths []string, pathInfos map[
 		// Map NAR key back to store path hash for later lookup
 		narKeyToHash[narKey] = hash
 
-		// .ls file (directory listing with brotli compression)
-		lsKey := hash + ".ls"
+		// .ls file (directory listing with brotli compression), as served
+		// to `nix store ls` and other tools that browse NARs lazily
+		var lsKey string
+		if writeListings {
+			lsKey = hash + ".ls"
+		}
 
 		// Check if this path has realisation objects
 		var realisationKeys []string
@@ -170,7 +273,12 @@ func PrepareClosures(ctx context.Context, topLevelPaths []string, pathInfos map[
 		// Narinfo references both dependencies, its own NAR file, .ls file, and any realisations
 		narinfoRefs := make([]string, 0, len(references)+2+len(realisationKeys))
 		narinfoRefs = append(narinfoRefs, references...)
-		narinfoRefs = append(narinfoRefs, narKey, lsKey)
+		narinfoRefs = append(narinfoRefs, narKey)
+
+		if lsKey != "" {
+			narinfoRefs = append(narinfoRefs, lsKey)
+		}
+
 		narinfoRefs = append(narinfoRefs, realisationKeys...)
 		narinfoKey := hash + ".narinfo"
 
@@ -187,16 +295,19 @@ func PrepareClosures(ctx context.Context, topLevelPaths []string, pathInfos map[
 				Refs:    []string{},
 				NarSize: &pathInfo.NarSize, // Include NarSize for multipart estimation
 			},
-			{
+		}
+
+		if lsKey != "" {
+			objects = append(objects, ObjectWithRefs{
 				Key:  lsKey,
 				Type: ObjectTypeListing,
 				Refs: []string{},
-			},
+			})
 		}
 
 		// Check if this path has a deriver (i.e., was built) and has a build log
-		if pathInfo.Deriver != nil && *pathInfo.Deriver != "" {
-			drvPath := *pathInfo.Deriver
+		if deriver := knownDeriver(pathInfo.Deriver); deriver != nil {
+			drvPath := *deriver
 
 			logPath, err := GetBuildLogPath(drvPath)
 			if err != nil {
@@ -299,133 +410,192 @@ func PrepareClosures(ctx context.Context, topLevelPaths []string, pathInfos map[
 	}, nil
 }
 
+// countClosureObjects returns the number of distinct objects across closures;
+// dependencies shared by several closures count once.
+func countClosureObjects(closures []ClosureInfo) int {
+	keys := make(map[string]struct{})
+
+	for _, closure := range closures {
+		for _, obj := range closure.Objects {
+			keys[obj.Key] = struct{}{}
+		}
+	}
+
+	return len(keys)
+}
+
 // CreatePendingClosures creates pending closures and returns all pending objects and closure ID to narinfo key mapping.
+// Up to MaxConcurrentRequests closures are created in parallel. Objects shared by several
+// closures are taken from the first closure (in input order) that reports them pending.
 func (c *Client) CreatePendingClosures(ctx context.Context, closures []ClosureInfo) (map[string]PendingObject, map[string]string, error) {
-	pendingObjects := make(map[string]PendingObject)
-	closureIDToNarinfoKey := make(map[string]string) // Maps closure ID -> narinfo key
+	responses := make([]*CreatePendingClosureResponse, len(closures))
 
-	for _, closure := range closures {
-		resp, err := c.CreatePendingClosure(ctx, closure.NarinfoKey, closure.Objects, c.VerifyS3Integrity)
-		if err != nil {
-			return nil, nil, fmt.Errorf("creating pending closure: %w", err)
+	numWorkers := c.MaxConcurrentRequests
+	if numWorkers <= 0 || numWorkers > len(closures) {
+		numWorkers = max(len(closures), 1)
+	}
+
+	g, gctx := errgroup.WithContext(ctx)
+	g.SetLimit(numWorkers)
+
+	for i, closure := range closures {
+		g.Go(func() error {
+			resp, err := c.api().CreatePendingClosure(gctx, closure.NarinfoKey, closure.Objects, c.VerifyS3Integrity)
+			if err != nil {
+				return fmt.Errorf("creating pending closure: %w", err)
+			}
+
+			responses[i] = resp
+
+			if err := checkPendingObjects(closure.Objects, resp.PendingObjects); err != nil {
+				return fmt.Errorf("pending closure %s for %s: %w", resp.ID, closure.NarinfoKey, err)
+			}
+
+			return nil
+		})
+	}
+
+	if err := g.Wait(); err != nil {
+		var createdIDs []string
+
+		for _, resp := range responses {
+			if resp != nil {
+				createdIDs = append(createdIDs, resp.ID)
+			}
 		}
 
-		closureIDToNarinfoKey[resp.ID] = closure.NarinfoKey
+		c.abortPendingClosures(ctx, createdIDs)
 
-		// Collect pending objects
-		maps.Copy(pendingObjects, resp.PendingObjects)
+		return nil, nil, err //nolint:wrapcheck // errgroup returns the first task's already-wrapped error
 	}
 
-	return pendingObjects, closureIDToNarinfoKey, nil
-}
+	pendingObjects := make(map[string]PendingObject)
+	closureIDToNarinfoKey := make(map[string]string) // Maps closure ID -> narinfo key
 
-type narinfoTask struct {
-	closureID string
-	key       string
-	meta      NarinfoMetadata
-}
+	for i, resp := range responses {
+		closureIDToNarinfoKey[resp.ID] = closures[i].NarinfoKey
 
-// SignAndUploadNarinfos signs narinfos on the server and uploads them to S3 in parallel.
-func (c *Client) SignAndUploadNarinfos(ctx context.Context, narinfosByClosureID map[string]map[string]NarinfoMetadata, pendingObjects map[string]PendingObject) error {
-	// Collect all narinfo metadata and closure IDs
-	var narinfosToSign []narinfoTask
-
-	for closureID, narinfos := range narinfosByClosureID {
-		for key, meta := range narinfos {
-			narinfosToSign = append(narinfosToSign, narinfoTask{
-				closureID: closureID,
-				key:       key,
-				meta:      meta,
-			})
+		// Collect pending objects, remembering which closure each came from
+		for key, obj := range resp.PendingObjects {
+			if _, ok := pendingObjects[key]; !ok {
+				obj.closureID = resp.ID
+				pendingObjects[key] = obj
+			}
 		}
 	}
 
-	if len(narinfosToSign) == 0 {
-		return nil
+	return pendingObjects, closureIDToNarinfoKey, nil
+}
+
+// checkPendingObjects checks the pending objects returned for a closure
+// against the objects requested for it. Keys that were not requested are
+// dropped with a warning. An object returned with another type than it was
+// requested as, or without a presigned URL or multipart upload, is an error.
+// Requested keys may be missing: the server leaves out what it already has.
+func checkPendingObjects(requested []ObjectWithRefs, pending map[string]PendingObject) error {
+	types := make(map[string]ObjectType, len(requested))
+	for _, obj := range requested {
+		types[obj.Key] = obj.Type
 	}
 
-	// Sign narinfos for each closure
-	signaturesByKey := make(map[string][]string)
+	for key, obj := range pending {
+		requestedType, ok := types[key]
+		if !ok {
+			slog.Warn("Ignoring pending object that was not requested", "key", key, "type", obj.Type)
+			delete(pending, key)
 
-	for closureID, narinfos := range narinfosByClosureID {
-		signatures, err := c.SignPendingClosure(ctx, closureID, narinfos)
-		if err != nil {
-			return fmt.Errorf("signing narinfos for closure %s: %w", closureID, err)
+			continue
+		}
+
+		if obj.Type != string(requestedType) {
+			return fmt.Errorf("server returned %s as a %s object, but it was requested as %s", key, obj.Type, requestedType)
 		}
 
-		maps.Copy(signaturesByKey, signatures)
+		if obj.PresignedURL == "" && obj.MultipartInfo == nil {
+			return fmt.Errorf("server returned %s without a presigned URL or multipart upload", key)
+		}
 	}
 
-	// Generate, compress, and upload narinfos in parallel
-	return c.uploadNarinfosInParallel(ctx, narinfosToSign, signaturesByKey, pendingObjects)
+	return nil
 }
 
-// uploadNarinfosInParallel generates, compresses, and uploads narinfos in parallel.
-func (c *Client) uploadNarinfosInParallel(ctx context.Context, narinfos []narinfoTask, signaturesByKey map[string][]string, pendingObjects map[string]PendingObject) error {
-	if len(narinfos) == 0 {
-		return nil
+// signAndUploadNarinfo has the server sign a narinfo for the pending
+// closure that owns it, then compresses and uploads it.
+func (c *Client) signAndUploadNarinfo(ctx context.Context, task uploadTask, meta *NarinfoMetadata) error {
+	if task.obj.PresignedURL == "" {
+		return fmt.Errorf("no presigned URL for narinfo %s", task.key)
+	}
+
+	signaturesByKey, err := c.api().SignPendingClosure(ctx, task.obj.closureID, map[string]NarinfoMetadata{task.key: *meta})
+	if err != nil {
+		return fmt.Errorf("signing narinfo %s: %w", task.key, err)
 	}
 
-	slog.Info(fmt.Sprintf("Uploading %d narinfos", len(narinfos)))
+	// Generate narinfo content with signatures
+	content := generateNarinfoContent(meta, signaturesByKey[task.key])
+	c.keepBytes(task.key, []byte(content))
 
-	// Determine concurrency limit
-	numWorkers := c.MaxConcurrentNARUploads
-	if numWorkers <= 0 || numWorkers > len(narinfos) {
-		numWorkers = len(narinfos)
+	// Compress narinfo
+	compressed, err := CompressNarinfo(content)
+	if err != nil {
+		return fmt.Errorf("compressing narinfo %s: %w", task.key, err)
 	}
 
-	g, ctx := errgroup.WithContext(ctx)
-	g.SetLimit(numWorkers)
+	// Upload to S3
+	err = c.withPresignedURL(ctx, task.key, task.obj, func(presignedURL string) error {
+		req, err := http.NewRequestWithContext(ctx, http.MethodPut, presignedURL, bytes.NewReader(compressed))
+		if err != nil {
+			return fmt.Errorf("creating upload request: %w", err)
+		}
 
-	for _, task := range narinfos {
-		g.Go(func() error {
-			// Get signatures for this narinfo
-			signatures := signaturesByKey[task.key]
+		req.Header.Set("Content-Type", "text/x-nix-narinfo")
+		req.Header.Set("Content-Encoding", "zstd")
+		c.setContentMD5(req, compressed)
 
-			// Generate narinfo content with signatures
-			content := generateNarinfoContent(&task.meta, signatures)
+		resp, err := c.DoS3Request(ctx, req)
+		if err != nil {
+			return err //nolint:wrapcheck // wrapped with the narinfo key below
+		}
 
-			// Compress narinfo
-			compressed, err := CompressNarinfo(content)
-			if err != nil {
-				return fmt.Errorf("compressing narinfo %s: %w", task.key, err)
-			}
+		defer deferCloseBody(resp)
 
-			// Get presigned URL from pending objects
-			pendingObj, ok := pendingObjects[task.key]
-			if !ok || pendingObj.PresignedURL == "" {
-				return fmt.Errorf("no presigned URL for narinfo %s", task.key)
-			}
+		return checkResponse(resp, http.StatusOK, http.StatusCreated, http.StatusNoContent)
+	})
+	if err != nil {
+		return fmt.Errorf("uploading narinfo %s: %w", task.key, err)
+	}
 
-			// Upload to S3
-			req, err := http.NewRequestWithContext(ctx, http.MethodPut, pendingObj.PresignedURL, bytes.NewReader(compressed))
-			if err != nil {
-				return fmt.Errorf("creating upload request for %s: %w", task.key, err)
-			}
+	slog.Debug("Uploaded narinfo", "key", task.key, "size", len(compressed))
 
-			req.Header.Set("Content-Type", "text/x-nix-narinfo")
-			req.Header.Set("Content-Encoding", "zstd")
+	return nil
+}
 
-			resp, err := c.DoS3Request(ctx, req)
-			if err != nil {
-				return fmt.Errorf("uploading narinfo %s: %w", task.key, err)
-			}
+// getPathInfo returns the closures of storePaths, or with c.NoRecursive
+// only storePaths themselves, either from c.PathInfoFile or by asking nix,
+// through c.PathInfoCache for the local store.
+func (c *Client) getPathInfo(ctx context.Context, storePaths []string) (map[string]*PathInfo, error) {
+	if c.PathInfoFile == "" {
+		if c.PathInfoCache != "" && c.effectiveStore() == "" {
+			return c.getCachedPathInfo(ctx, storePaths)
+		}
 
-			if err := resp.Body.Close(); err != nil {
-				slog.Warn("Failed to close response body", "error", err)
-			}
+		if c.NoRecursive {
+			return GetPathInfoFromStore(ctx, storePaths, c.NixEnv, c.Store)
+		}
 
-			if resp.StatusCode < 200 || resp.StatusCode >= 300 {
-				return fmt.Errorf("uploading narinfo %s: unexpected status %d", task.key, resp.StatusCode)
-			}
+		return GetPathInfoRecursiveFromStore(ctx, storePaths, c.NixEnv, c.Store)
+	}
 
-			slog.Debug("Uploaded narinfo", "key", task.key, "size", len(compressed))
+	all, err := LoadPathInfoFile(c.PathInfoFile)
+	if err != nil {
+		return nil, err
+	}
 
-			return nil
-		})
+	if c.NoRecursive {
+		return pathsFromPathInfos(storePaths, all)
 	}
 
-	return g.Wait() //nolint:wrapcheck // errgroup returns the first task's already-wrapped error
+	return closureFromPathInfos(storePaths, all)
 }
 
 // PushPaths uploads store paths and their closures to the server.
@@ -433,12 +603,19 @@ func (c *Client) uploadNarinfosInParallel(ctx context.Context, narinfos []narinf
 // closures (including transitive dependencies), which callers can use to
 // prune queues of dependency paths that no longer need separate uploads.
 func (c *Client) PushPaths(ctx context.Context, paths []string) ([]string, error) {
+	closurePaths, _, err := c.pushPaths(ctx, paths)
+
+	return closurePaths, err
+}
+
+// pushPaths is PushPaths that also reports upload statistics.
+func (c *Client) pushPaths(ctx context.Context, paths []string) ([]string, *UploadStats, error) {
 	startTime := time.Now()
 
 	// Resolve symlinks to actual store paths
-	resolvedPaths, err := resolveSymlinks(paths, c.storeDir)
+	resolvedPaths, err := resolveSymlinks(paths, c.effectiveStoreDir())
 	if err != nil {
-		return nil, fmt.Errorf("resolving symlinks: %w", err)
+		return nil, nil, fmt.Errorf("resolving symlinks: %w", err)
 	}
 
 	slog.Debug("Resolved paths", "original", paths, "resolved", resolvedPaths)
@@ -446,23 +623,85 @@ func (c *Client) PushPaths(ctx context.Context, paths []string) ([]string, error
 	// Get path info for all paths and their closures
 	slog.Debug("Getting path info", "count", len(resolvedPaths))
 
-	pathInfos, err := GetPathInfoRecursive(ctx, resolvedPaths, c.NixEnv)
+	pathInfos, err := c.getPathInfo(ctx, resolvedPaths)
 	if err != nil {
-		return nil, fmt.Errorf("getting path info: %w", err)
+		return nil, nil, fmt.Errorf("getting path info: %w", err)
+	}
+
+	if len(pathInfos) == 0 {
+		return nil, nil, fmt.Errorf("getting path info: nix reported nothing for %s", strings.Join(resolvedPaths, " "))
 	}
 
 	slog.Debug("Found paths in closure", "count", len(pathInfos))
 
+	if c.IncludeDerivations {
+		resolvedPaths, pathInfos, err = c.addDerivations(ctx, resolvedPaths, pathInfos)
+		if err != nil {
+			return nil, nil, err
+		}
+	}
+
+	if len(c.Exclude) > 0 {
+		resolvedPaths, pathInfos, err = c.excludePaths(resolvedPaths, pathInfos)
+		if err != nil {
+			return nil, nil, err
+		}
+
+		// Likely a mistake in --exclude rather than a cache hit, so warn.
+		if len(resolvedPaths) == 0 {
+			slog.Warn("Nothing to upload, every given path is excluded", "paths", len(paths))
+
+			return nil, &UploadStats{}, nil
+		}
+	}
+
+	// Every path of the pushed closures, for the manifest.
+	allInfos := pathInfos
+
 	// Collect all closure paths to return to the caller.
 	closurePaths := make([]string, 0, len(pathInfos))
 	for storePath := range pathInfos {
 		closurePaths = append(closurePaths, storePath)
 	}
 
+	// Skip closures whose narinfos are all in the cache before doing any
+	// compression or creating pending closures for them.
+	var cached map[string]bool
+
+	if c.SkipExisting {
+		cached, err = c.QueryCachedPaths(ctx, pathInfos)
+		if err != nil {
+			return nil, nil, fmt.Errorf("querying cache: %w", err)
+		}
+
+		remainingPaths, remainingInfos := dropCachedClosures(resolvedPaths, pathInfos, cached)
+		if skipped := len(pathInfos) - len(remainingInfos); skipped > 0 {
+			slog.Info(fmt.Sprintf("Skipped %d paths already in the cache (%d of %d paths found cached)", skipped, len(cached), len(pathInfos)))
+		}
+
+		if len(remainingPaths) == 0 {
+			slog.Info(fmt.Sprintf("Nothing to upload, all %d paths are already in the cache. (%s)", len(allInfos), time.Since(startTime).Round(time.Millisecond)))
+
+			return closurePaths, &UploadStats{Manifest: buildManifest(allInfos, nil, nil, nil), Paths: len(allInfos)}, nil
+		}
+
+		resolvedPaths, pathInfos = remainingPaths, remainingInfos
+	}
+
+	if c.OnEvent != nil {
+		for _, storePath := range slices.Sorted(maps.Keys(pathInfos)) {
+			c.emit(PathDiscovered{StorePath: storePath, NarSize: pathInfos[storePath].NarSize})
+		}
+	}
+
+	if c.MinCompressionRatio > 0 && c.Compression != CompressionNone {
+		c.skipIncompressibleNARs(ctx, pathInfos)
+	}
+
 	// Prepare closures - one per top-level path
-	result, err := PrepareClosures(ctx, resolvedPaths, pathInfos, c.NixEnv)
+	result, err := PrepareClosures(ctx, resolvedPaths, pathInfos, c.NixEnv, c.Compression, c.WriteListings)
 	if err != nil {
-		return nil, fmt.Errorf("preparing closures: %w", err)
+		return nil, nil, fmt.Errorf("preparing closures: %w", err)
 	}
 
 	if len(result.LogPathsByKey) > 0 {
@@ -473,10 +712,69 @@ func (c *Client) PushPaths(ctx context.Context, paths []string) ([]string, error
 		slog.Debug("Found realisations for CA derivations", "count", len(result.RealisationsByKey))
 	}
 
+	if c.NarinfoOutput != nil {
+		if err := c.printNarinfos(c.NarinfoOutput, pathInfos); err != nil {
+			return nil, nil, err
+		}
+
+		return closurePaths, &UploadStats{Total: countClosureObjects(result.Closures)}, nil
+	}
+
+	if c.DryRun {
+		reportDryRun(pathInfos, cached, result)
+
+		return closurePaths, &UploadStats{Total: countClosureObjects(result.Closures)}, nil
+	}
+
+	if c.StateFile != "" {
+		if c.pushState, err = loadPushState(c.StateFile); err != nil {
+			return nil, nil, err
+		}
+
+		defer func() {
+			if c.pushState != nil {
+				c.pushState.close()
+				c.pushState = nil
+			}
+		}()
+	}
+
 	// Create pending closures and collect what needs uploading
 	pendingObjects, closureIDToNarinfoKey, err := c.CreatePendingClosures(ctx, result.Closures)
 	if err != nil {
-		return nil, fmt.Errorf("creating pending closures: %w", err)
+		return nil, nil, fmt.Errorf("creating pending closures: %w", err)
+	}
+
+	// Abort whatever is left pending if we bail out before completing it
+	unfinishedIDs := make(map[string]bool, len(closureIDToNarinfoKey))
+	for id := range closureIDToNarinfoKey {
+		unfinishedIDs[id] = true
+	}
+
+	defer func() {
+		if c.pushState != nil && len(unfinishedIDs) > 0 {
+			// Aborting would hand the uploaded objects to GC; keep the
+			// closures pending so the next run can pick up from here.
+			slog.Info("Keeping pending closures for a resumed push", "count", len(unfinishedIDs), "state_file", c.StateFile)
+
+			return
+		}
+
+		if ctx.Err() != nil && len(unfinishedIDs) > 0 {
+			slog.Warn("Push canceled, aborting pending closures", "count", len(unfinishedIDs))
+		}
+
+		c.abortPendingClosures(ctx, slices.Collect(maps.Keys(unfinishedIDs)))
+	}()
+
+	if c.pushState != nil {
+		if err := c.pushState.recordClosures(slices.Collect(maps.Keys(closureIDToNarinfoKey))); err != nil {
+			return nil, nil, err
+		}
+
+		if _, err := c.skipCompletedObjects(ctx, pendingObjects); err != nil {
+			return nil, nil, fmt.Errorf("checking objects of an earlier run: %w", err)
+		}
 	}
 
 	// Calculate how many paths are already cached vs need uploading
@@ -489,13 +787,14 @@ func (c *Client) PushPaths(ctx context.Context, paths []string) ([]string, error
 		}
 	}
 
-	cachedPaths := len(pathInfos) - newPaths
+	// Count against the whole closure, including paths --skip-existing pruned
+	cachedPaths := len(closurePaths) - newPaths
 
 	slog.Info(fmt.Sprintf("Uploading %d paths to %s (%d already cached)", newPaths, c.baseURL.Hostname(), cachedPaths))
 	slog.Debug("Need to upload objects", "pending", len(pendingObjects), "closures", len(closureIDToNarinfoKey))
 
-	// Upload all pending objects and collect narinfo metadata
-	narinfoMetadata, err := c.UploadPendingObjects(ctx, &UploadContext{
+	// Upload all pending objects; each narinfo follows right after its NAR
+	stats, err := c.UploadPendingObjects(ctx, &UploadContext{
 		PendingObjects:    pendingObjects,
 		PathInfoByHash:    result.PathInfoByHash,
 		NARKeyToHash:      result.NARKeyToHash,
@@ -503,51 +802,108 @@ func (c *Client) PushPaths(ctx context.Context, paths []string) ([]string, error
 		RealisationsByKey: result.RealisationsByKey,
 	})
 	if err != nil {
-		return nil, fmt.Errorf("uploading objects: %w", err)
+		return nil, nil, fmt.Errorf("uploading objects: %w", err)
 	}
 
-	slog.Debug("Uploaded all objects", "narinfos", len(narinfoMetadata))
+	stats.Total = countClosureObjects(result.Closures)
+	stats.Skipped = stats.Total - stats.Uploaded
+	stats.Paths = len(allInfos)
+
+	stats.narSizes = make(map[string]uint64, len(stats.narDigests))
 
-	// Build a quick lookup map: narinfo key -> closure
-	closureByNarinfoKey := make(map[string]ClosureInfo)
-	for _, closure := range result.Closures {
-		closureByNarinfoKey[closure.NarinfoKey] = closure
+	var compressedBytes uint64
+
+	for storePath, digest := range stats.narDigests {
+		if pathInfo := allInfos[storePath]; pathInfo != nil {
+			stats.narSizes[storePath] = pathInfo.NarSize
+			stats.NarBytes += pathInfo.NarSize
+			compressedBytes += digest.FileSize
+		}
 	}
 
-	// Build per-closure narinfo maps for signing
-	// Only include narinfos for objects that belong to each specific closure
-	narinfosByClosureID := make(map[string]map[string]NarinfoMetadata)
+	slog.Info(fmt.Sprintf("Uploaded %d objects out of %d total (%d skipped)", stats.Uploaded, stats.Total, stats.Skipped))
 
-	for id, topLevelNarinfoKey := range closureIDToNarinfoKey {
-		closure := closureByNarinfoKey[topLevelNarinfoKey]
-		closureNarinfos := make(map[string]NarinfoMetadata)
+	if compressedBytes > 0 {
+		slog.Info(fmt.Sprintf("Compressed %s of NARs to %s (ratio %.2f)",
+			FormatBytes(stats.NarBytes), FormatBytes(compressedBytes), compressionRatio(stats.NarBytes, compressedBytes)))
+	}
 
-		// Add only narinfos for objects in this closure
-		for _, obj := range closure.Objects {
-			if obj.Type == ObjectTypeNarinfo {
-				if meta, ok := narinfoMetadata[obj.Key]; ok {
-					closureNarinfos[obj.Key] = meta
-				}
-			}
+	// With ContinueOnError, closures containing a failed object stay
+	// pending and are aborted; registering them would break the cache.
+	incomplete := incompleteClosures(result.Closures, stats.Failed)
+
+	// Complete all pending closures (all objects including narinfos are now uploaded)
+	completeIDs := make([]string, 0, len(closureIDToNarinfoKey))
+
+	for id, narinfoKey := range closureIDToNarinfoKey {
+		if !incomplete[narinfoKey] {
+			completeIDs = append(completeIDs, id)
 		}
+	}
+
+	completedIDs, err := c.completePendingClosures(ctx, completeIDs)
+	for _, id := range completedIDs {
+		delete(unfinishedIDs, id)
+		c.emit(ClosureCompleted{NarinfoKey: closureIDToNarinfoKey[id]})
+	}
 
-		narinfosByClosureID[id] = closureNarinfos
+	if err != nil {
+		return nil, nil, err
 	}
 
-	// Sign narinfos for each closure and upload them
-	if err := c.SignAndUploadNarinfos(ctx, narinfosByClosureID, pendingObjects); err != nil {
-		return nil, fmt.Errorf("signing and uploading narinfos: %w", err)
+	duration := time.Since(startTime)
+
+	if len(stats.Failed) > 0 {
+		slog.Error(fmt.Sprintf("Upload incomplete: %d paths uploaded, %d failed, %d closures not registered. (%s)",
+			len(stats.Succeeded), len(stats.Failed), len(incomplete), duration.Round(time.Millisecond)))
+
+		return closurePaths, stats, fmt.Errorf("%w: %d failed", ErrUploadIncomplete, len(stats.Failed))
 	}
 
-	// Complete all pending closures (all objects including narinfos are now uploaded)
-	for id := range closureIDToNarinfoKey {
-		if err := c.CompletePendingClosure(ctx, id); err != nil {
-			return nil, fmt.Errorf("completing pending closure %s: %w", id, err)
+	if c.pushState != nil {
+		// The objects are registered now, so the closures an interrupted
+		// run left pending can go.
+		c.abortPendingClosures(ctx, c.pushState.closures)
+
+		if err := c.pushState.remove(); err != nil {
+			slog.Warn("Failed to remove push state", "error", err)
 		}
+
+		c.pushState = nil
 	}
 
-	duration := time.Since(startTime)
 	slog.Info(fmt.Sprintf("Upload complete. (%s)", duration.Round(time.Millisecond)))
 
-	return closurePaths, nil
+	stats.Manifest = buildManifest(allInfos, result.NARKeyToHash, stats.Succeeded, stats.narDigests)
+
+	return closurePaths, stats, nil
+}
+
+// incompleteClosures returns the narinfo keys of the closures that contain
+// an object of a failed upload.
+func incompleteClosures(closures []ClosureInfo, failed []UploadFailure) map[string]bool {
+	incomplete := make(map[string]bool)
+	if len(failed) == 0 {
+		return incomplete
+	}
+
+	failedKeys := make(map[string]bool)
+
+	for _, f := range failed {
+		for _, key := range f.keys {
+			failedKeys[key] = true
+		}
+	}
+
+	for _, closure := range closures {
+		for _, obj := range closure.Objects {
+			if failedKeys[obj.Key] {
+				incomplete[closure.NarinfoKey] = true
+
+				break
+			}
+		}
+	}
+
+	return incomplete
 }
@@ -0,0 +1,282 @@
+package client_test
+
+import (
+	"context"
+	"crypto/md5" //nolint:gosec // checking S3's Content-MD5 header
+	"encoding/base64"
+	"encoding/json"
+	"fmt"
+	"io"
+	"net/http"
+	"net/http/httptest"
+	"path/filepath"
+	"slices"
+	"strings"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/Mic92/niks3/client"
+)
+
+func TestCountClosureObjects(t *testing.T) {
+	t.Parallel()
+
+	shared := []client.ObjectWithRefs{
+		{Key: "lib.narinfo", Type: client.ObjectTypeNarinfo},
+		{Key: "nar/lib.nar.zst", Type: client.ObjectTypeNAR},
+		{Key: "lib.ls", Type: client.ObjectTypeListing},
+	}
+
+	closures := []client.ClosureInfo{
+		{
+			NarinfoKey: "app.narinfo",
+			Objects: append([]client.ObjectWithRefs{
+				{Key: "app.narinfo", Type: client.ObjectTypeNarinfo},
+				{Key: "nar/app.nar.zst", Type: client.ObjectTypeNAR},
+				{Key: "app.ls", Type: client.ObjectTypeListing},
+				{Key: "log/app.drv", Type: client.ObjectTypeBuildLog},
+			}, shared...),
+		},
+		{NarinfoKey: "lib.narinfo", Objects: shared},
+	}
+
+	// Not len(paths) * 2: listings and logs count, shared objects count once.
+	if got := client.CountClosureObjects(closures); got != 7 {
+		t.Fatalf("expected 7 distinct objects, got %d", got)
+	}
+}
+
+func TestPrepareClosuresWithoutListings(t *testing.T) {
+	t.Parallel()
+
+	const storePath = "/nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1"
+
+	pathInfos, err := client.ParsePathInfoJSON([]byte(`{
+		"` + storePath + `": {
+			"narHash": "sha256-FePFYIlMuycIXPZbWi7LGEiMmZSX9FMbaQenWBzm1Sc=",
+			"narSize": 226560,
+			"references": []
+		}
+	}`))
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	for _, writeListings := range []bool{true, false} {
+		result, err := client.PrepareClosures(context.Background(), []string{storePath}, pathInfos, nil, client.CompressionZstd, writeListings)
+		if err != nil {
+			t.Fatal(err)
+		}
+
+		hasListing := false
+
+		for _, obj := range result.Closures[0].Objects {
+			if obj.Type == client.ObjectTypeListing {
+				hasListing = true
+			}
+
+			if obj.Type == client.ObjectTypeNarinfo && slices.Contains(obj.Refs, "26xbg1ndr7hbcncrlf9nhx5is2b25d13.ls") != writeListings {
+				t.Errorf("writeListings=%v: narinfo refs = %v", writeListings, obj.Refs)
+			}
+		}
+
+		if hasListing != writeListings {
+			t.Errorf("writeListings=%v: listing object present = %v", writeListings, hasListing)
+		}
+	}
+}
+
+// TestUploadPendingObjectsConcurrencyLimit checks that compression and
+// upload of a path share one worker slot, so no more than
+// MaxConcurrentNARUploads paths are ever in flight at once.
+func TestUploadPendingObjectsConcurrencyLimit(t *testing.T) {
+	t.Parallel()
+
+	const (
+		limit    = 2
+		numPaths = 6
+	)
+
+	var (
+		mu          sync.Mutex
+		inFlight    int
+		maxInFlight int
+	)
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		mu.Lock()
+		inFlight++
+		maxInFlight = max(maxInFlight, inFlight)
+		mu.Unlock()
+
+		time.Sleep(20 * time.Millisecond)
+
+		mu.Lock()
+		inFlight--
+		mu.Unlock()
+
+		w.WriteHeader(http.StatusOK)
+	}))
+	defer srv.Close()
+
+	uploadCtx := &client.UploadContext{
+		PendingObjects: make(map[string]client.PendingObject),
+		PathInfoByHash: make(map[string]*client.PathInfo),
+		NARKeyToHash:   make(map[string]string),
+	}
+
+	for i := range numPaths {
+		src := filepath.Join(t.TempDir(), "src")
+		makeMixedTree(t, src)
+
+		_, digest, err := client.DumpPathWithDigest(io.Discard, src)
+		if err != nil {
+			t.Fatal(err)
+		}
+
+		pathInfo := &client.PathInfo{}
+		if err := json.Unmarshal(fmt.Appendf(nil, `{"narHash":%q,"narSize":%d}`, digest.NarHash, digest.NarSize), pathInfo); err != nil {
+			t.Fatal(err)
+		}
+
+		pathInfo.Path = src
+
+		hash := fmt.Sprintf("%032d", i)
+		narKey := "nar/" + hash + ".nar.zst"
+		uploadCtx.PendingObjects[narKey] = client.PendingObject{Type: "nar", PresignedURL: srv.URL + "/" + narKey}
+		uploadCtx.PathInfoByHash[hash] = pathInfo
+		uploadCtx.NARKeyToHash[narKey] = hash
+	}
+
+	c := newTestClientWithRetries(srv.Client(), 0)
+	c.MaxConcurrentNARUploads = limit
+
+	stats, err := c.UploadPendingObjects(context.Background(), uploadCtx)
+	if err != nil {
+		t.Fatalf("UploadPendingObjects: %v", err)
+	}
+
+	if stats.Uploaded != numPaths {
+		t.Errorf("expected %d uploads, got %d", numPaths, stats.Uploaded)
+	}
+
+	if maxInFlight > limit {
+		t.Errorf("%d uploads in flight at once, limit is %d", maxInFlight, limit)
+	}
+}
+
+func TestUploadPendingObjectsContinueOnError(t *testing.T) {
+	t.Parallel()
+
+	const failingHash = "00000000000000000000000000000001"
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if strings.Contains(r.URL.Path, failingHash) {
+			http.Error(w, "broken", http.StatusBadRequest)
+
+			return
+		}
+
+		w.WriteHeader(http.StatusOK)
+	}))
+	defer srv.Close()
+
+	newUploadCtx := func() (*client.UploadContext, string) {
+		uploadCtx := &client.UploadContext{
+			PendingObjects: make(map[string]client.PendingObject),
+			PathInfoByHash: make(map[string]*client.PathInfo),
+			NARKeyToHash:   make(map[string]string),
+		}
+
+		var failingPath string
+
+		for i := range 3 {
+			src := filepath.Join(t.TempDir(), "src")
+			makeMixedTree(t, src)
+
+			_, digest, err := client.DumpPathWithDigest(io.Discard, src)
+			if err != nil {
+				t.Fatal(err)
+			}
+
+			pathInfo := &client.PathInfo{}
+			if err := json.Unmarshal(fmt.Appendf(nil, `{"narHash":%q,"narSize":%d}`, digest.NarHash, digest.NarSize), pathInfo); err != nil {
+				t.Fatal(err)
+			}
+
+			pathInfo.Path = src
+
+			hash := fmt.Sprintf("%032d", i)
+			if hash == failingHash {
+				failingPath = src
+			}
+
+			narKey := "nar/" + hash + ".nar.zst"
+			uploadCtx.PendingObjects[narKey] = client.PendingObject{Type: "nar", PresignedURL: srv.URL + "/" + narKey}
+			uploadCtx.PathInfoByHash[hash] = pathInfo
+			uploadCtx.NARKeyToHash[narKey] = hash
+		}
+
+		return uploadCtx, failingPath
+	}
+
+	c := newTestClientWithRetries(srv.Client(), 0)
+
+	uploadCtx, _ := newUploadCtx()
+	if _, err := c.UploadPendingObjects(context.Background(), uploadCtx); err == nil {
+		t.Fatal("expected the failing upload to abort by default")
+	}
+
+	c.ContinueOnError = true
+
+	uploadCtx, failingPath := newUploadCtx()
+
+	stats, err := c.UploadPendingObjects(context.Background(), uploadCtx)
+	if err != nil {
+		t.Fatalf("UploadPendingObjects with ContinueOnError: %v", err)
+	}
+
+	if stats.Uploaded != 2 {
+		t.Errorf("expected the 2 healthy NARs to upload, got %d", stats.Uploaded)
+	}
+
+	if len(stats.Failed) != 1 || stats.Failed[0].StorePath != failingPath {
+		t.Fatalf("expected %s to be reported as failed, got %+v", failingPath, stats.Failed)
+	}
+}
+
+func TestChecksumUploadsSendsContentMD5(t *testing.T) {
+	t.Parallel()
+
+	data := []byte("listing contents")
+	sum := md5.Sum(data) //nolint:gosec // checking S3's Content-MD5 header
+	want := base64.StdEncoding.EncodeToString(sum[:])
+
+	for _, enabled := range []bool{false, true} {
+		var got string
+
+		srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+			got = r.Header.Get("Content-MD5")
+
+			w.WriteHeader(http.StatusOK)
+		}))
+
+		c := newTestClientWithRetries(http.DefaultClient, 0)
+		c.ChecksumUploads = enabled
+
+		if err := c.UploadBytesToPresignedURLWithHeaders(context.Background(), srv.URL, data, nil); err != nil {
+			t.Fatalf("upload (checksums %v): %v", enabled, err)
+		}
+
+		srv.Close()
+
+		if enabled && got != want {
+			t.Errorf("Content-MD5 = %q, want %q", got, want)
+		}
+
+		if !enabled && got != "" {
+			t.Errorf("Content-MD5 sent without ChecksumUploads: %q", got)
+		}
+	}
+}
@@ -0,0 +1,40 @@
+package client
+
+import (
+	"runtime/debug"
+	"strings"
+)
+
+// Version is the niks3 version reported in the User-Agent header. Packagers
+// set it at build time via ldflags:
+//
+//	-ldflags "-X github.com/Mic92/niks3/client.Version=1.7.0"
+//
+// If unset, the module version recorded by `go install` is used.
+var Version = "" //nolint:gochecknoglobals // ldflags override
+
+// DefaultUserAgent returns "niks3/<version>", with "devel" standing in for
+// the version of builds that carry none.
+func DefaultUserAgent() string {
+	version := Version
+
+	if version == "" {
+		if info, ok := debug.ReadBuildInfo(); ok && info.Main.Version != "" && info.Main.Version != "(devel)" {
+			version = strings.TrimPrefix(info.Main.Version, "v")
+		}
+	}
+
+	if version == "" {
+		version = "devel"
+	}
+
+	return "niks3/" + version
+}
+
+// SetUserAgentSuffix appends suffix to the User-Agent header, e.g. so CI
+// systems can tag their pushes as "niks3/1.7.0 github-actions".
+func (c *Client) SetUserAgentSuffix(suffix string) {
+	if suffix = strings.TrimSpace(suffix); suffix != "" {
+		c.UserAgent = DefaultUserAgent() + " " + suffix
+	}
+}
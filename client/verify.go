@@ -0,0 +1,217 @@
+package client
+
+import (
+	"bytes"
+	"context"
+	"errors"
+	"fmt"
+	"io"
+	"maps"
+	"slices"
+	"strings"
+
+	"github.com/Mic92/niks3/server/signing"
+	"golang.org/x/sync/errgroup"
+)
+
+// VerifyStatus is the outcome of verifying one cached store path.
+type VerifyStatus string
+
+const (
+	VerifyOK      VerifyStatus = "ok"
+	VerifyCorrupt VerifyStatus = "corrupt" // NAR or narinfo does not check out
+	VerifyMissing VerifyStatus = "missing" // narinfo or NAR is not in the cache
+)
+
+// VerifyResult reports the verification of one store path. Err explains
+// why a path is not VerifyOK.
+type VerifyResult struct {
+	StorePath string
+	Status    VerifyStatus
+	Err       error
+}
+
+// VerifyPaths checks the closures of storePaths in the cache: every NAR is
+// downloaded, decompressed, parsed and hashed, and compared with the
+// FileHash/FileSize and NarHash/NarSize of its narinfo. With trustedKeys,
+// each narinfo must also carry a valid signature by one of them. Results
+// are sorted by store path; the error is only set if checking itself failed.
+func (c *Client) VerifyPaths(ctx context.Context, storePaths []string, trustedKeys []*signing.PublicKey) ([]VerifyResult, error) {
+	var results []VerifyResult
+
+	narinfos := make(map[string]*NarinfoMetadata)
+	seen := make(map[string]bool)
+	queue := slices.Clone(storePaths)
+
+	for len(queue) > 0 {
+		storePath := queue[0]
+		queue = queue[1:]
+
+		if seen[storePath] {
+			continue
+		}
+
+		seen[storePath] = true
+
+		meta, err := c.FetchNarinfo(ctx, storePath)
+		if errors.Is(err, ErrNotInCache) {
+			results = append(results, VerifyResult{StorePath: storePath, Status: VerifyMissing, Err: err})
+
+			continue
+		}
+
+		if err != nil {
+			return nil, err
+		}
+
+		narinfos[storePath] = meta
+
+		for _, ref := range meta.References {
+			if !seen[ref] {
+				queue = append(queue, ref)
+			}
+		}
+	}
+
+	numWorkers := c.MaxConcurrentNARUploads
+	if numWorkers <= 0 {
+		numWorkers = 1
+	}
+
+	g, ctx := errgroup.WithContext(ctx)
+	g.SetLimit(numWorkers)
+
+	metas := slices.Collect(maps.Values(narinfos))
+	verified := make([]VerifyResult, len(metas))
+
+	for i, meta := range metas {
+		g.Go(func() error {
+			result, err := c.verifyPath(ctx, meta, trustedKeys)
+			if err != nil {
+				return err
+			}
+
+			verified[i] = result
+
+			return nil
+		})
+	}
+
+	if err := g.Wait(); err != nil {
+		return nil, err //nolint:wrapcheck // errgroup returns the first task's already-wrapped error
+	}
+
+	results = append(results, verified...)
+	slices.SortFunc(results, func(a, b VerifyResult) int {
+		return strings.Compare(a.StorePath, b.StorePath)
+	})
+
+	return results, nil
+}
+
+// verifyPath checks one path's signatures and NAR against its narinfo.
+func (c *Client) verifyPath(ctx context.Context, meta *NarinfoMetadata, trustedKeys []*signing.PublicKey) (VerifyResult, error) {
+	corrupt := func(format string, args ...any) (VerifyResult, error) {
+		return VerifyResult{StorePath: meta.StorePath, Status: VerifyCorrupt, Err: fmt.Errorf(format, args...)}, nil
+	}
+
+	if len(trustedKeys) > 0 {
+		ok, err := signing.VerifyNarinfo(trustedKeys, &signing.NarInfo{
+			StorePath:  meta.StorePath,
+			NarHash:    meta.NarHash,
+			NarSize:    meta.NarSize,
+			References: meta.References,
+		}, meta.Signatures)
+		if err != nil {
+			return corrupt("checking signatures: %w", err)
+		}
+
+		if !ok {
+			return corrupt("no valid signature by a trusted key")
+		}
+	}
+
+	// A content-addressed path's name is derived from its content address,
+	// so an altered CA field or reference list no longer matches it.
+	if meta.CA != nil && !strings.HasPrefix(*meta.CA, "fixed:git:") {
+		if err := CheckContentAddressedStorePath(meta.StorePath, *meta.CA, meta.References); err != nil {
+			return corrupt("%w", err)
+		}
+
+		// The NAR is checked against NarHash below, which a recursive sha256
+		// content address must equal.
+		if strings.HasPrefix(*meta.CA, "fixed:r:sha256:") && *meta.CA != "fixed:r:"+meta.NarHash {
+			return corrupt("NarHash %s does not match content address %s", meta.NarHash, *meta.CA)
+		}
+	}
+
+	algo, expected, err := DecodeNixHash(meta.NarHash)
+	if err != nil {
+		return corrupt("parsing NarHash: %w", err)
+	}
+
+	h, err := algo.New()
+	if err != nil {
+		return corrupt("checking NarHash: %w", err)
+	}
+
+	// Check the compressed NAR with whatever algorithm its FileHash uses.
+	fileAlgo := HashSHA256
+
+	if meta.FileHash != "" {
+		if fileAlgo, _, err = DecodeNixHash(meta.FileHash); err != nil {
+			return corrupt("parsing FileHash: %w", err)
+		}
+	}
+
+	file, err := newFileDigestWriterFor(io.Discard, fileAlgo)
+	if err != nil {
+		return corrupt("checking FileHash: %w", err)
+	}
+
+	body, err := c.fetchCacheObject(ctx, meta.URL)
+	if errors.Is(err, ErrNotInCache) {
+		return VerifyResult{StorePath: meta.StorePath, Status: VerifyMissing, Err: err}, nil
+	}
+
+	if err != nil {
+		return VerifyResult{}, err
+	}
+
+	defer closeResponseBody(body)
+
+	nar, release, err := narDecompressor(meta.Compression, io.TeeReader(body, file))
+	if err != nil {
+		return corrupt("%w", err)
+	}
+	defer release()
+
+	counter := &countingWriter{}
+	tee := io.TeeReader(nar, io.MultiWriter(h, counter))
+
+	if _, err := ListNAR(tee); err != nil {
+		return corrupt("parsing NAR: %w", err)
+	}
+
+	// Hash whatever trails the archive, as pull does.
+	if _, err := io.Copy(io.Discard, tee); err != nil {
+		return corrupt("reading NAR: %w", err)
+	}
+
+	if counter.n != meta.NarSize || !bytes.Equal(h.Sum(nil), expected) {
+		return corrupt("NAR does not match narinfo (size %d, expected %d)", counter.n, meta.NarSize)
+	}
+
+	// The decompressor may not have consumed the compressed stream to EOF.
+	if _, err := io.Copy(file, body); err != nil {
+		return corrupt("reading compressed NAR: %w", err)
+	}
+
+	if meta.FileHash != "" {
+		if digest := file.Digest(); digest.FileHash != meta.FileHash || digest.FileSize != meta.FileSize {
+			return corrupt("compressed NAR does not match FileHash/FileSize of narinfo")
+		}
+	}
+
+	return VerifyResult{StorePath: meta.StorePath, Status: VerifyOK}, nil
+}
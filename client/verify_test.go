@@ -0,0 +1,151 @@
+package client_test
+
+import (
+	"bytes"
+	"context"
+	"crypto/sha1" //nolint:gosec // old caches declare sha1 FileHashes
+	"crypto/sha512"
+	"net/http"
+	"net/http/httptest"
+	"path/filepath"
+	"strings"
+	"testing"
+
+	"github.com/Mic92/niks3/client"
+	"github.com/Mic92/niks3/server/signing"
+	"github.com/klauspost/compress/zstd"
+)
+
+func TestVerifyPaths(t *testing.T) {
+	t.Parallel()
+
+	const missingPath = "/nix/store/0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c-c"
+
+	srv := pullFixture(t, true)
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	results, err := c.VerifyPaths(context.Background(), []string{pullPathA, missingPath}, nil)
+	if err != nil {
+		t.Fatalf("VerifyPaths: %v", err)
+	}
+
+	want := map[string]client.VerifyStatus{
+		pullPathA:   client.VerifyOK,
+		pullPathB:   client.VerifyCorrupt,
+		missingPath: client.VerifyMissing,
+	}
+
+	if len(results) != len(want) {
+		t.Fatalf("expected %d results, got %+v", len(want), results)
+	}
+
+	for _, r := range results {
+		if r.Status != want[r.StorePath] {
+			t.Errorf("%s: status %s (%v), want %s", r.StorePath, r.Status, r.Err, want[r.StorePath])
+		}
+	}
+}
+
+func TestVerifyPathsRequiresTrustedSignature(t *testing.T) {
+	t.Parallel()
+
+	srv := pullFixture(t, false)
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	key, err := signing.ParsePublicKey("test-1:" + "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=")
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	results, err := c.VerifyPaths(context.Background(), []string{pullPathB}, []*signing.PublicKey{key})
+	if err != nil {
+		t.Fatalf("VerifyPaths: %v", err)
+	}
+
+	if len(results) != 1 || results[0].Status != client.VerifyCorrupt {
+		t.Fatalf("expected the unsigned path to fail verification, got %+v", results)
+	}
+}
+
+// TestVerifyPathsOtherHashAlgorithms checks narinfos of older caches, whose
+// NarHash and FileHash may use sha512 or sha1 instead of sha256.
+func TestVerifyPathsOtherHashAlgorithms(t *testing.T) {
+	t.Parallel()
+
+	const storePath = "/nix/store/0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b-old"
+
+	src := filepath.Join(t.TempDir(), "src")
+	makeMixedTree(t, src)
+
+	var nar bytes.Buffer
+	if _, _, err := client.DumpPathWithDigest(&nar, src); err != nil {
+		t.Fatal(err)
+	}
+
+	var compressed bytes.Buffer
+
+	enc, err := zstd.NewWriter(&compressed)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	if _, err := enc.Write(nar.Bytes()); err != nil {
+		t.Fatal(err)
+	}
+
+	if err := enc.Close(); err != nil {
+		t.Fatal(err)
+	}
+
+	narSum := sha512.Sum512(nar.Bytes())
+	fileSum := sha1.Sum(compressed.Bytes()) //nolint:gosec // the algorithm under test
+
+	meta := &client.NarinfoMetadata{
+		StorePath:   storePath,
+		URL:         "nar/old.nar.zst",
+		Compression: "zstd",
+		NarHash:     "sha512:" + client.EncodeNixBase32(narSum[:]),
+		NarSize:     uint64(nar.Len()),
+		FileHash:    "sha1:" + client.EncodeNixBase32(fileSum[:]),
+		FileSize:    uint64(compressed.Len()),
+	}
+
+	objects := map[string][]byte{
+		meta.URL: compressed.Bytes(),
+	}
+	objects["0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b.narinfo"] = []byte(client.GenerateNarinfoContent(meta, nil))
+
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		data, ok := objects[strings.TrimPrefix(r.URL.Path, "/")]
+		if !ok {
+			http.NotFound(w, r)
+
+			return
+		}
+
+		_, _ = w.Write(data)
+	}))
+	t.Cleanup(srv.Close)
+
+	c, err := client.NewTestClientForServer(srv.URL)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	results, err := c.VerifyPaths(context.Background(), []string{storePath}, nil)
+	if err != nil {
+		t.Fatalf("VerifyPaths: %v", err)
+	}
+
+	if len(results) != 1 || results[0].Status != client.VerifyOK {
+		t.Fatalf("expected the path to verify, got %+v", results)
+	}
+}
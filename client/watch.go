@@ -0,0 +1,229 @@
+package client
+
+import (
+	"context"
+	"fmt"
+	"log/slog"
+	"maps"
+	"os"
+	"path/filepath"
+	"slices"
+	"strings"
+	"time"
+)
+
+const (
+	// DefaultWatchInterval is how often Watch polls for changes.
+	DefaultWatchInterval = 2 * time.Second
+	// DefaultWatchDebounce is how long Watch waits for a burst of new
+	// paths, e.g. the outputs of one build, to end before pushing them.
+	DefaultWatchDebounce = 10 * time.Second
+)
+
+// WatchOptions configures Watch.
+type WatchOptions struct {
+	StoreDir string        // Store directory whose new entries are pushed
+	GCRoots  []string      // Push the targets of these symlinks when they change instead of watching StoreDir
+	Interval time.Duration // Polling interval (0 = DefaultWatchInterval)
+	Debounce time.Duration // Push once nothing changed for this long (0 = DefaultWatchDebounce)
+	NixEnv   []string      // Environment for the nix commands checking path validity
+}
+
+// Watch calls push with the store paths that appear until ctx is done.
+// Without GCRoots, new entries of StoreDir are pushed once nix has
+// registered them; paths that existed when Watch started are not. With
+// GCRoots, the target of each root is pushed at start and whenever the root
+// is repointed, e.g. by nix-env or a NixOS switch. Changes are collected
+// until Debounce passes without another, and paths deleted in the meantime
+// are dropped. A failed push is logged and retried after the next debounce
+// period. Watch returns nil when ctx is cancelled.
+func Watch(ctx context.Context, opts WatchOptions, push func(context.Context, []string) error) error {
+	w := &watcher{
+		opts:        opts,
+		known:       make(map[string]struct{}),
+		rootTargets: make(map[string]string),
+		pending:     make(map[string]string),
+	}
+
+	if w.opts.Interval <= 0 {
+		w.opts.Interval = DefaultWatchInterval
+	}
+
+	if w.opts.Debounce <= 0 {
+		w.opts.Debounce = DefaultWatchDebounce
+	}
+
+	// The store's current contents are the baseline; roots are pushed once.
+	if err := w.poll(len(opts.GCRoots) == 0); err != nil {
+		return err
+	}
+
+	ticker := time.NewTicker(w.opts.Interval)
+	defer ticker.Stop()
+
+	var lastChange time.Time
+
+	for {
+		select {
+		case <-ctx.Done():
+			return nil
+		case <-ticker.C:
+		}
+
+		before := len(w.pending)
+
+		if err := w.poll(false); err != nil {
+			return err
+		}
+
+		if len(w.pending) != before {
+			lastChange = time.Now()
+		}
+
+		if len(w.pending) == 0 || time.Since(lastChange) < w.opts.Debounce {
+			continue
+		}
+
+		batch := w.ready(ctx)
+
+		// Unregistered paths left pending are checked again a debounce
+		// period later, not on every tick.
+		lastChange = time.Now()
+
+		if len(batch) == 0 {
+			continue
+		}
+
+		slog.Info("Pushing new store paths", "count", len(batch))
+
+		if err := push(ctx, batch); err != nil {
+			if ctx.Err() != nil {
+				return nil
+			}
+
+			slog.Error("Push of new store paths failed, retrying later", "error", err)
+
+			for _, storePath := range batch {
+				w.pending[storePath] = storePath
+			}
+		}
+	}
+}
+
+// watcher is the polling state of Watch.
+type watcher struct {
+	opts        WatchOptions
+	storeMod    time.Time           // StoreDir's mtime at the last scan
+	known       map[string]struct{} // StoreDir entries at the last scan
+	rootTargets map[string]string   // GC root -> store path it pointed to at the last poll
+	pending     map[string]string   // New store path or changed root -> store path to push
+}
+
+// poll records new store entries or repointed roots in w.pending. With
+// baseline, new store entries are only remembered, not queued.
+func (w *watcher) poll(baseline bool) error {
+	if len(w.opts.GCRoots) > 0 {
+		w.pollGCRoots()
+
+		return nil
+	}
+
+	return w.pollStore(baseline)
+}
+
+func (w *watcher) pollStore(baseline bool) error {
+	stat, err := os.Stat(w.opts.StoreDir)
+	if err != nil {
+		return fmt.Errorf("watching store: %w", err)
+	}
+
+	// Adding or removing an entry updates the directory's mtime, so an
+	// unchanged one spares listing a store of possibly millions of entries.
+	if stat.ModTime().Equal(w.storeMod) {
+		return nil
+	}
+
+	w.storeMod = stat.ModTime()
+
+	entries, err := os.ReadDir(w.opts.StoreDir)
+	if err != nil {
+		return fmt.Errorf("watching store: %w", err)
+	}
+
+	current := make(map[string]struct{}, len(entries))
+
+	for _, entry := range entries {
+		name := entry.Name()
+
+		// Build locks and temporary roots are not store paths, and new
+		// derivations appear on every evaluation.
+		if strings.HasPrefix(name, ".") || strings.HasSuffix(name, ".lock") || strings.HasSuffix(name, ".drv") {
+			continue
+		}
+
+		current[name] = struct{}{}
+
+		if _, ok := w.known[name]; ok || baseline {
+			continue
+		}
+
+		storePath := filepath.Join(w.opts.StoreDir, name)
+		w.pending[storePath] = storePath
+	}
+
+	// Forgetting deleted entries lets a path that is added again be pushed.
+	w.known = current
+
+	return nil
+}
+
+func (w *watcher) pollGCRoots() {
+	for _, root := range w.opts.GCRoots {
+		// Profiles are chains of links, e.g. profile -> profile-42-link ->
+		// /nix/store/...; the store path is at the end.
+		target, err := filepath.EvalSymlinks(root)
+		if err != nil {
+			slog.Debug("GC root does not resolve", "root", root, "error", err)
+
+			continue
+		}
+
+		if w.rootTargets[root] == target {
+			continue
+		}
+
+		w.rootTargets[root] = target
+		// A root repointed again before the push only pushes its latest target.
+		w.pending[root] = target
+	}
+}
+
+// ready removes the pushable paths from w.pending and returns them. Paths
+// that were deleted are dropped; new store entries that nix has not
+// registered yet, e.g. outputs still being built, stay pending.
+func (w *watcher) ready(ctx context.Context) []string {
+	var batch []string
+
+	for _, key := range slices.Sorted(maps.Keys(w.pending)) {
+		storePath := w.pending[key]
+
+		if _, err := os.Lstat(storePath); err != nil {
+			slog.Debug("New store path was removed before it was pushed", "store_path", storePath)
+			delete(w.pending, key)
+
+			continue
+		}
+
+		if len(w.opts.GCRoots) == 0 && !isInLocalStore(ctx, storePath, w.opts.NixEnv) {
+			continue
+		}
+
+		delete(w.pending, key)
+
+		if !slices.Contains(batch, storePath) {
+			batch = append(batch, storePath)
+		}
+	}
+
+	return batch
+}
@@ -0,0 +1,105 @@
+package client_test
+
+import (
+	"context"
+	"os"
+	"path/filepath"
+	"slices"
+	"testing"
+	"time"
+
+	"github.com/Mic92/niks3/client"
+)
+
+func TestWatchGCRoots(t *testing.T) {
+	t.Parallel()
+
+	storeDir := t.TempDir()
+	rootDir := t.TempDir()
+
+	storePath := func(name string) string {
+		p := filepath.Join(storeDir, name)
+		if err := os.Mkdir(p, 0o750); err != nil {
+			t.Fatal(err)
+		}
+
+		return p
+	}
+
+	root := filepath.Join(rootDir, "result")
+
+	// Repoint the root atomically, as nix does.
+	pointRoot := func(target string) {
+		tmp := root + ".tmp"
+		if err := os.Symlink(target, tmp); err != nil {
+			t.Fatal(err)
+		}
+
+		if err := os.Rename(tmp, root); err != nil {
+			t.Fatal(err)
+		}
+	}
+
+	first, second, third := storePath("first"), storePath("second"), storePath("third")
+	pointRoot(first)
+
+	ctx, cancel := context.WithCancel(context.Background())
+	batches := make(chan []string, 10)
+	done := make(chan error, 1)
+
+	const debounce = 200 * time.Millisecond
+
+	go func() {
+		done <- client.Watch(ctx, client.WatchOptions{
+			GCRoots:  []string{root},
+			Interval: 10 * time.Millisecond,
+			Debounce: debounce,
+		}, func(_ context.Context, paths []string) error {
+			batches <- paths
+
+			return nil
+		})
+	}()
+
+	expect := func(want []string) {
+		t.Helper()
+
+		select {
+		case got := <-batches:
+			if !slices.Equal(got, want) {
+				t.Errorf("pushed %v, want %v", got, want)
+			}
+		case <-time.After(5 * time.Second):
+			t.Fatalf("nothing pushed, want %v", want)
+		}
+	}
+
+	// The root's target is pushed at start.
+	expect([]string{first})
+
+	// Repointing twice within the debounce period pushes the last target.
+	pointRoot(second)
+	time.Sleep(debounce / 4)
+	pointRoot(third)
+	expect([]string{third})
+
+	// A target deleted before the debounce period ends is not pushed.
+	pointRoot(storePath("fourth"))
+	time.Sleep(debounce / 4)
+
+	if err := os.Remove(filepath.Join(storeDir, "fourth")); err != nil {
+		t.Fatal(err)
+	}
+
+	select {
+	case got := <-batches:
+		t.Errorf("pushed deleted path: %v", got)
+	case <-time.After(3 * debounce):
+	}
+
+	cancel()
+
+	if err := <-done; err != nil {
+		t.Fatal(err)
+	}
+}
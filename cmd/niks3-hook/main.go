@@ -57,6 +57,7 @@ func printServeHelp() {
 	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenHelp)
 	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenPathHelp)
 	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenScriptHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.ConfigHelp)
 	fmt.Fprintf(os.Stderr, "  --socket string\n        Unix socket path (default: %s)\n", hook.DefaultSocketPath)
 	fmt.Fprintln(os.Stderr, "  --db-path string")
 	fmt.Fprintln(os.Stderr, "        SQLite database path (default: /var/lib/niks3-hook/upload-queue.db)")
@@ -64,13 +65,15 @@ func printServeHelp() {
 	fmt.Fprintln(os.Stderr, "        Paths per upload batch (default: 50)")
 	fmt.Fprintln(os.Stderr, "  --idle-exit-timeout string")
 	fmt.Fprintln(os.Stderr, `        Exit after no activity; "0" to disable (default: "60s")`)
-	fmt.Fprintln(os.Stderr, "  --max-concurrent-uploads int")
-	fmt.Fprintln(os.Stderr, "        Concurrent upload limit (default: 30)")
+	fmt.Fprintln(os.Stderr, "  --max-concurrent-uploads int|auto")
+	fmt.Fprintln(os.Stderr, "        Concurrent upload limit (default: 30). 0 or auto picks 4 per CPU, between")
+	fmt.Fprintln(os.Stderr, "        8 and 64, and one compression job per CPU")
 	fmt.Fprintln(os.Stderr, "  --verify-s3-integrity")
 	fmt.Fprintln(os.Stderr, "        Verify S3 objects before skipping")
 	fmt.Fprintln(os.Stderr, cmdutil.TLSHelp)
 	fmt.Fprintln(os.Stderr, "  --debug")
 	fmt.Fprintln(os.Stderr, "        Enable debug logging")
+	fmt.Fprintln(os.Stderr, cmdutil.LogHelp)
 	fmt.Fprintln(os.Stderr, "  -h, --help")
 	fmt.Fprintln(os.Stderr, "        Show this help message")
 }
@@ -142,11 +145,11 @@ func runServe() error {
 	dbPath := fs.String("db-path", "/var/lib/niks3-hook/upload-queue.db", "SQLite database path")
 	batchSize := fs.Int("batch-size", 50, "Paths per upload batch")
 	idleExitTimeout := fs.String("idle-exit-timeout", "60s", "Exit after no activity; \"0\" to disable")
-	maxConcurrent := fs.Int("max-concurrent-uploads", 30, "Concurrent upload limit")
+	maxConcurrent := cmdutil.ConcurrencyFlag(fs, "max-concurrent-uploads", 30, "Concurrent upload limit (0 or auto = per CPU)")
 	verifyS3 := fs.Bool("verify-s3-integrity", false, "Verify S3 integrity")
 	tf := cmdutil.AddTLSFlags(fs)
 
-	if err := fs.Parse(os.Args[2:]); err != nil {
+	if err := cmdutil.ParseFlags(fs, os.Args[2:]); err != nil {
 		if errors.Is(err, flag.ErrHelp) {
 			printServeHelp()
 			os.Exit(0)
@@ -160,7 +163,9 @@ func runServe() error {
 		os.Exit(0)
 	}
 
-	cmdutil.SetupLogger(*cf.Debug)
+	if err := cf.SetupLogger(); err != nil {
+		return err //nolint:wrapcheck // cmdutil errors are already user-facing
+	}
 
 	if err := cmdutil.RequireServerURL(*cf.ServerURL); err != nil {
 		return err //nolint:wrapcheck // cmdutil errors are already user-facing
@@ -179,10 +184,6 @@ func runServe() error {
 		}
 	}
 
-	if *maxConcurrent < 1 {
-		*maxConcurrent = 1
-	}
-
 	// Open the SQLite queue.
 	queue, err := hook.OpenQueue(*dbPath)
 	if err != nil {
@@ -212,6 +213,11 @@ func runServe() error {
 
 	c.MaxConcurrentNARUploads = *maxConcurrent
 
+	if *maxConcurrent == 0 {
+		c.MaxConcurrentNARUploads, c.CompressionJobs = client.AutoConcurrency()
+		slog.Info("Auto-detected concurrency", "max_concurrent_uploads", c.MaxConcurrentNARUploads, "compression_jobs", c.CompressionJobs)
+	}
+
 	c.VerifyS3Integrity = *verifyS3
 	if *cf.Debug {
 		c.SetDebugHTTP(true)
@@ -0,0 +1,160 @@
+package main
+
+import (
+	"fmt"
+	"os"
+)
+
+// The completion scripts read subcommands and flags from `niks3 [command]
+// --help` when completing, so they follow the binary they complete: new
+// commands and flags only need to appear in their help text. Commands are
+// listed under "Commands:" or "Subcommands:", flags as "  --name" lines.
+
+const bashCompletion = `# bash completion for niks3
+# Load with: source <(niks3 completions bash)
+_niks3_subcommands() {
+    niks3 "$@" --help 2>&1 | awk '/^(Sub)?[Cc]ommands:$/ {f=1; next} /^$/ {f=0} f && /^  [a-z]/ {print $1}'
+}
+
+_niks3_flags() {
+    niks3 "$@" --help 2>&1 | grep -oE '^  (-h, )?--[a-z0-9-]+' | grep -oE -- '--[a-z0-9-]+'
+}
+
+_niks3() {
+    local cur=${COMP_WORDS[COMP_CWORD]}
+    local cmd=${COMP_WORDS[1]}
+
+    if [[ $COMP_CWORD -eq 1 ]]; then
+        COMPREPLY=($(compgen -W "$(_niks3_subcommands)" -- "$cur"))
+    elif [[ $cmd == completions ]]; then
+        COMPREPLY=($(compgen -W "bash zsh fish powershell" -- "$cur"))
+    elif [[ $cur == -* ]]; then
+        COMPREPLY=($(compgen -W "$(_niks3_flags "$cmd")" -- "$cur"))
+    elif [[ $COMP_CWORD -eq 2 ]]; then
+        COMPREPLY=($(compgen -W "$(_niks3_subcommands "$cmd")" -- "$cur"))
+    fi
+}
+
+complete -o default -F _niks3 niks3
+`
+
+const zshCompletion = `#compdef niks3
+# zsh completion for niks3
+# Load with: source <(niks3 completions zsh)
+_niks3() {
+    local -a candidates
+
+    if (( CURRENT == 2 )); then
+        candidates=(${(f)"$(niks3 --help 2>&1 | awk '/^(Sub)?[Cc]ommands:$/ {f=1; next} /^$/ {f=0} f && /^  [a-z]/ {print $1}')"})
+    elif [[ $words[2] == completions ]]; then
+        candidates=(bash zsh fish powershell)
+    elif [[ $PREFIX == -* ]]; then
+        candidates=(${(f)"$(niks3 $words[2] --help 2>&1 | grep -oE '^  (-h, )?--[a-z0-9-]+' | grep -oE -- '--[a-z0-9-]+')"})
+    elif (( CURRENT == 3 )); then
+        candidates=(${(f)"$(niks3 $words[2] --help 2>&1 | awk '/^(Sub)?[Cc]ommands:$/ {f=1; next} /^$/ {f=0} f && /^  [a-z]/ {print $1}')"})
+    fi
+
+    if (( ${#candidates} )); then
+        compadd -- $candidates
+    else
+        _files
+    fi
+}
+
+compdef _niks3 niks3
+`
+
+const fishCompletion = `# fish completion for niks3
+# Load with: niks3 completions fish | source
+function __niks3_subcommands
+    niks3 $argv --help 2>&1 | awk '/^(Sub)?[Cc]ommands:$/ {f=1; next} /^$/ {f=0} f && /^  [a-z]/ {print $1}'
+end
+
+function __niks3_flags
+    set -l words (commandline -opc)
+    niks3 $words[2] --help 2>&1 | string match -r -g '^  (?:-h, )?(--[a-z0-9-]+)'
+end
+
+function __niks3_subcommand_of_command
+    set -l words (commandline -opc)
+    test (count $words) -eq 2; and __niks3_subcommands $words[2]
+end
+
+complete -c niks3 -f -n __fish_use_subcommand -a '(__niks3_subcommands)'
+complete -c niks3 -f -n '__fish_seen_subcommand_from completions' -a 'bash zsh fish powershell'
+complete -c niks3 -n 'not __fish_use_subcommand' -a '(__niks3_subcommand_of_command)'
+complete -c niks3 -n 'not __fish_use_subcommand; and string match -q -- "-*" (commandline -ct)' -a '(__niks3_flags)'
+`
+
+const powershellCompletion = `# PowerShell completion for niks3
+# Load with: niks3 completions powershell | Out-String | Invoke-Expression
+function __niks3_help([string[]]$helpArgs) {
+    & niks3 @helpArgs --help 2>&1 | ForEach-Object { "$_" }
+}
+
+Register-ArgumentCompleter -Native -CommandName niks3 -ScriptBlock {
+    param($wordToComplete, $commandAst, $cursorPosition)
+
+    $words = @($commandAst.CommandElements | ForEach-Object { $_.ToString() })
+    if ($wordToComplete -ne '') {
+        $words = @($words | Select-Object -SkipLast 1)
+    }
+
+    $subcommands = {
+        param([string[]]$helpArgs)
+        $inSection = $false
+        foreach ($line in (__niks3_help $helpArgs)) {
+            if ($line -match '^(Sub)?[Cc]ommands:$') { $inSection = $true; continue }
+            if ($line -eq '') { $inSection = $false }
+            if ($inSection -and $line -match '^  ([a-z][a-z-]*)') { $Matches[1] }
+        }
+    }
+
+    if ($words.Count -eq 1) {
+        $candidates = & $subcommands @()
+    } elseif ($words[1] -eq 'completions') {
+        $candidates = 'bash', 'zsh', 'fish', 'powershell'
+    } elseif ($wordToComplete -like '-*') {
+        $candidates = foreach ($line in (__niks3_help @($words[1]))) {
+            if ($line -match '^  (?:-h, )?(--[a-z0-9-]+)') { $Matches[1] }
+        }
+    } elseif ($words.Count -eq 2) {
+        $candidates = & $subcommands @($words[1])
+    } else {
+        return
+    }
+
+    $candidates | Where-Object { $_ -like "$wordToComplete*" } | ForEach-Object {
+        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+    }
+}
+`
+
+func printCompletionsHelp() {
+	fmt.Fprintln(os.Stderr, "Usage: niks3 completions <bash|zsh|fish|powershell>")
+	fmt.Fprintln(os.Stderr, "\nPrint a shell completion script for commands and flags. Load it with:")
+	fmt.Fprintln(os.Stderr, "  bash        source <(niks3 completions bash)")
+	fmt.Fprintln(os.Stderr, "  zsh         source <(niks3 completions zsh)")
+	fmt.Fprintln(os.Stderr, "  fish        niks3 completions fish | source")
+	fmt.Fprintln(os.Stderr, "  powershell  niks3 completions powershell | Out-String | Invoke-Expression")
+	fmt.Fprintln(os.Stderr, "\nThe scripts read commands and flags from the installed niks3's --help output,")
+	fmt.Fprintln(os.Stderr, "so they do not need regenerating after an upgrade.")
+}
+
+func completionsCommand(shell string) error {
+	scripts := map[string]string{
+		"bash":       bashCompletion,
+		"zsh":        zshCompletion,
+		"fish":       fishCompletion,
+		"powershell": powershellCompletion,
+	}
+
+	script, ok := scripts[shell]
+	if !ok {
+		return fmt.Errorf("unsupported shell %q (expected bash, zsh, fish or powershell)", shell)
+	}
+
+	_, _ = fmt.Fprint(os.Stdout, script)
+
+	return nil
+}
@@ -1,6 +1,7 @@
 package main
 
 import (
+	"cmp"
 	"context"
 	"encoding/json"
 	"errors"
@@ -9,11 +10,15 @@ import (
 	"log/slog"
 	"os"
 	"os/signal"
+	"strconv"
+	"strings"
 	"syscall"
 	"text/tabwriter"
 
+	"github.com/Mic92/niks3/api"
 	"github.com/Mic92/niks3/client"
 	"github.com/Mic92/niks3/cmdutil"
+	"github.com/Mic92/niks3/server/signing"
 )
 
 func main() {
@@ -27,29 +32,314 @@ func printUsage() {
 	fmt.Fprintln(os.Stderr, "Usage: niks3 <command> [flags]")
 	fmt.Fprintln(os.Stderr, "\nCommands:")
 	fmt.Fprintln(os.Stderr, "  push    Upload paths to S3-compatible binary cache")
+	fmt.Fprintln(os.Stderr, "  watch   Push new store paths as they appear")
+	fmt.Fprintln(os.Stderr, "  pull    Download paths from the binary cache")
+	fmt.Fprintln(os.Stderr, "  verify  Check cached paths against their narinfos")
+	fmt.Fprintln(os.Stderr, "  info    Show what a push would upload for a path")
 	fmt.Fprintln(os.Stderr, "  gc      Run garbage collection on old closures")
+	fmt.Fprintln(os.Stderr, "  delete  Remove paths from the cache")
+	fmt.Fprintln(os.Stderr, "  resign  Add signatures to narinfos already in the cache")
 	fmt.Fprintln(os.Stderr, "  pins    Manage pins (list, delete)")
+	fmt.Fprintln(os.Stderr, "  init-cache    Write the cache's nix-cache-info")
+	fmt.Fprintln(os.Stderr, "  generate-key  Create a narinfo signing keypair")
+	fmt.Fprintln(os.Stderr, "  doctor        Check nix, server connectivity, auth and the store")
+	fmt.Fprintln(os.Stderr, "  completions   Print a shell completion script")
 	fmt.Fprintln(os.Stderr, "\nGlobal flags:")
 	fmt.Fprintln(os.Stderr, "  -h, --help    Show help")
 	fmt.Fprintln(os.Stderr, "\nUse 'niks3 <command> --help' for more information about a command.")
 }
 
 func printPushHelp() {
-	fmt.Fprintln(os.Stderr, "Usage: niks3 push [flags] <store-paths...>")
+	fmt.Fprintln(os.Stderr, "Usage: niks3 push [flags] <store-paths...|@file...|->")
 	fmt.Fprintln(os.Stderr, "\nUpload Nix store paths to S3-compatible binary cache.")
+	fmt.Fprintln(os.Stderr, "Paths may also be given as <hash>-<name> or as a bare <hash>, which are looked")
+	fmt.Fprintln(os.Stderr, "up in --store-dir. An @file argument is replaced by the paths listed in file,")
+	fmt.Fprintln(os.Stderr, "one per line ('#' starts a comment), which avoids argument length limits.")
+	fmt.Fprintln(os.Stderr, "Every NAR is hashed while it is serialized and is not uploaded unless its")
+	fmt.Fprintln(os.Stderr, "sha256 and size match the NarHash and NarSize nix registered for the path.")
 	fmt.Fprintln(os.Stderr, "\nFlags:")
 	fmt.Fprintln(os.Stderr, "  --server-url string")
-	fmt.Fprintln(os.Stderr, "        Server URL (can also use NIKS3_SERVER_URL env var)")
+	fmt.Fprintln(os.Stderr, "        Server URL (can also use NIKS3_SERVER_URL env var). Repeat to push to")
+	fmt.Fprintln(os.Stderr, "        several caches, e.g. a primary and a mirror, one after another with the")
+	fmt.Fprintln(os.Stderr, "        same auth token. Each NAR is compressed once into --temp-dir, which needs")
+	fmt.Fprintln(os.Stderr, "        room for the compressed closure, and uploaded to every cache from there.")
+	fmt.Fprintln(os.Stderr, "        A failing cache does not stop the others; the push fails if any did")
 	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenHelp)
 	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenPathHelp)
 	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenScriptHelp)
-	fmt.Fprintln(os.Stderr, "  --max-concurrent-uploads int")
-	fmt.Fprintln(os.Stderr, "        Maximum concurrent uploads (default: 30)")
+	fmt.Fprintln(os.Stderr, cmdutil.ConfigHelp)
+	fmt.Fprintln(os.Stderr, "  --max-concurrent-uploads int|auto")
+	fmt.Fprintln(os.Stderr, "        Maximum concurrent uploads (default: 30). 0 or auto picks 4 per CPU, between")
+	fmt.Fprintln(os.Stderr, "        8 and 64, and one compression job per CPU unless --compression-jobs is set")
+	fmt.Fprintln(os.Stderr, "  --max-concurrent-requests int")
+	fmt.Fprintln(os.Stderr, "        Maximum concurrent pending closure requests to the server (default: 8)")
 	fmt.Fprintln(os.Stderr, "  --verify-s3-integrity")
 	fmt.Fprintln(os.Stderr, "        Verify that objects in database actually exist in S3 before skipping upload")
+	fmt.Fprintln(os.Stderr, "  --skip-existing")
+	fmt.Fprintln(os.Stderr, "        Check the cache for each narinfo first and skip closures that are fully")
+	fmt.Fprintln(os.Stderr, "        present (default: true). Skipped closures are not re-registered, so use")
+	fmt.Fprintln(os.Stderr, "        --skip-existing=false to refresh their garbage collection age")
+	fmt.Fprintln(os.Stderr, "  --continue-on-error")
+	fmt.Fprintln(os.Stderr, "        Keep uploading when a path fails, then list the failed paths and exit")
+	fmt.Fprintln(os.Stderr, "        non-zero. Closures containing a failed path are not registered")
+	fmt.Fprintln(os.Stderr, "        (default: false, stop at the first failure)")
+	fmt.Fprintln(os.Stderr, "  --exclude path")
+	fmt.Fprintln(os.Stderr, "        Leave this store path out of the push (repeatable). Pushed narinfos may")
+	fmt.Fprintln(os.Stderr, "        still reference it; paths only reachable through it are left out too")
+	fmt.Fprintln(os.Stderr, "  --exclude-from file")
+	fmt.Fprintln(os.Stderr, "        Read store paths to exclude from a file, one per line ('#' starts a comment)")
+	fmt.Fprintln(os.Stderr, "  --key-prefix string")
+	fmt.Fprintln(os.Stderr, "        Upload every object below this bucket prefix, e.g. 'team-a', to keep several")
+	fmt.Fprintln(os.Stderr, "        caches in one bucket. Substituters use <cache URL>/<prefix> as the cache;")
+	fmt.Fprintln(os.Stderr, "        narinfo URL lines stay relative to it. Cannot be combined with --pin")
+	fmt.Fprintln(os.Stderr, "  --direct-s3")
+	fmt.Fprintln(os.Stderr, "        Upload straight to an S3 bucket with SigV4-presigned requests instead of")
+	fmt.Fprintln(os.Stderr, "        through a niks3 server; --server-url and the auth token are not used.")
+	fmt.Fprintln(os.Stderr, "        Objects already in the bucket are skipped. Nothing is registered, so pins")
+	fmt.Fprintln(os.Stderr, "        and garbage collection are unavailable. A default nix-cache-info is")
+	fmt.Fprintln(os.Stderr, "        written if the bucket has none")
+	fmt.Fprintln(os.Stderr, "  --s3-bucket string")
+	fmt.Fprintln(os.Stderr, "        Bucket to upload to with --direct-s3 (required)")
+	fmt.Fprintln(os.Stderr, "  --s3-endpoint string")
+	fmt.Fprintln(os.Stderr, "        S3 endpoint, host[:port] or http(s)://host[:port] (default:")
+	fmt.Fprintln(os.Stderr, "        $AWS_ENDPOINT_URL_S3 or $AWS_ENDPOINT_URL, else s3.amazonaws.com)")
+	fmt.Fprintln(os.Stderr, "  --s3-region string")
+	fmt.Fprintln(os.Stderr, "        Bucket region (default: $AWS_REGION or $AWS_DEFAULT_REGION, else asked")
+	fmt.Fprintln(os.Stderr, "        from S3)")
+	fmt.Fprintln(os.Stderr, "  --s3-access-key string")
+	fmt.Fprintln(os.Stderr, "        Access key ID (default: $AWS_ACCESS_KEY_ID with $AWS_SESSION_TOKEN, else")
+	fmt.Fprintln(os.Stderr, "        IAM instance credentials)")
+	fmt.Fprintln(os.Stderr, "  --s3-secret-key string")
+	fmt.Fprintln(os.Stderr, "        Secret access key (default: $AWS_SECRET_ACCESS_KEY)")
+	fmt.Fprintln(os.Stderr, "  --sign-key-path string")
+	fmt.Fprintln(os.Stderr, "        Sign narinfos with this secret key file in --direct-s3 mode (repeatable;")
+	fmt.Fprintln(os.Stderr, "        see generate-key). Without one, narinfos are unsigned")
+	fmt.Fprintln(os.Stderr, "  --no-recursive")
+	fmt.Fprintln(os.Stderr, "        Push only the given paths, not their closures. Their narinfos still list")
+	fmt.Fprintln(os.Stderr, "        all references, which must already be in the cache or be pushed separately")
+	fmt.Fprintln(os.Stderr, "        (default: false; --recursive=false is the same)")
+	fmt.Fprintln(os.Stderr, "  --state-file string")
+	fmt.Fprintln(os.Stderr, "        Checkpoint completed uploads to this file. A failed push keeps its pending")
+	fmt.Fprintln(os.Stderr, "        closures, and re-running with the same file skips objects that were already")
+	fmt.Fprintln(os.Stderr, "        uploaded. The file is removed once the push succeeds")
+	fmt.Fprintln(os.Stderr, "  --manifest string")
+	fmt.Fprintln(os.Stderr, "        After a successful push, write a JSON array describing every path of the")
+	fmt.Fprintln(os.Stderr, "        pushed closures: store_path, narinfo_key, nar_key, nar_size, file_size,")
+	fmt.Fprintln(os.Stderr, "        file_hash and skipped (true if the cache already had the path)")
+	fmt.Fprintln(os.Stderr, "  --write-listings")
+	fmt.Fprintln(os.Stderr, "        Upload a <hash>.ls JSON listing of each NAR's file tree (types, sizes,")
+	fmt.Fprintln(os.Stderr, "        executable bits, symlink targets) for lazy browsing, e.g. by")
+	fmt.Fprintln(os.Stderr, "        `nix store ls` (default: true)")
+	fmt.Fprintln(os.Stderr, "  --include-derivations")
+	fmt.Fprintln(os.Stderr, "        Also push the .drv files the paths were built from, and their closures, so")
+	fmt.Fprintln(os.Stderr, "        clients can resolve the build graph from the cache. Derivations that were")
+	fmt.Fprintln(os.Stderr, "        garbage collected are skipped with a warning")
+	fmt.Fprintln(os.Stderr, "  --checksum-uploads")
+	fmt.Fprintln(os.Stderr, "        Send Content-MD5 on every PUT and x-amz-checksum-sha256 on single-PUT NARs so")
+	fmt.Fprintln(os.Stderr, "        the S3 backend rejects bodies corrupted in flight. Off by default because")
+	fmt.Fprintln(os.Stderr, "        some backends reject unsigned checksum headers on presigned URLs")
+	fmt.Fprintln(os.Stderr, "  --max-upload-rate int")
+	fmt.Fprintln(os.Stderr, "        Cap the combined upload rate of NARs, listings and logs, in bytes per")
+	fmt.Fprintln(os.Stderr, "        second, across all concurrent uploads (default: 0, unlimited)")
+	fmt.Fprintln(os.Stderr, "  --retries int")
+	fmt.Fprintln(os.Stderr, "        Retry attempts for failed requests, 0 disables retries (default: 5)")
+	fmt.Fprintln(os.Stderr, "  --retry-base-delay duration")
+	fmt.Fprintln(os.Stderr, "        Initial backoff between retries, doubled per attempt (default: 100ms)")
+	fmt.Fprintln(os.Stderr, "  --stdin")
+	fmt.Fprintln(os.Stderr, "        Read whitespace-separated store paths from stdin ('#' starts a comment);")
+	fmt.Fprintln(os.Stderr, "        passing '-' as the only path does the same. Empty input, e.g. an empty")
+	fmt.Fprintln(os.Stderr, "        $OUT_PATHS in a post-build hook, pushes nothing and succeeds")
+	fmt.Fprintln(os.Stderr, "  --dry-run")
+	fmt.Fprintln(os.Stderr, "        Resolve closures and report what would be uploaded (paths, NAR bytes, paths")
+	fmt.Fprintln(os.Stderr, "        already cached) without creating pending closures or uploading anything")
+	fmt.Fprintln(os.Stderr, "  --print-narinfo")
+	fmt.Fprintln(os.Stderr, "        Print the narinfo of every path that would be uploaded to stdout and exit")
+	fmt.Fprintln(os.Stderr, "        before creating pending closures. FileHash, FileSize and the server's")
+	fmt.Fprintln(os.Stderr, "        signatures are left out, as they are only known during an upload")
+	fmt.Fprintln(os.Stderr, "  --output string")
+	fmt.Fprintln(os.Stderr, "        'json' prints a summary to stdout when the push ends: paths, uploaded_paths,")
+	fmt.Fprintln(os.Stderr, "        skipped_paths, failed_paths, uploaded_nars, nar_bytes, compressed_bytes,")
+	fmt.Fprintln(os.Stderr, "        compression_ratio (nar_bytes / compressed_bytes), elapsed_seconds, nars")
+	fmt.Fprintln(os.Stderr, "        (per-NAR sizes and ratio) and failures (default: text, logs only)")
+	fmt.Fprintln(os.Stderr, "  --progress")
+	fmt.Fprintln(os.Stderr, "        Print a line to stderr as each path is uploaded: '[done/total] <NAR bytes")
+	fmt.Fprintln(os.Stderr, "        done>/<total> (<compressed bytes> compressed), <throughput>/s, ETA <time>:")
+	fmt.Fprintln(os.Stderr, "        <store path>'. Throughput and ETA count uncompressed NAR bytes")
+	fmt.Fprintln(os.Stderr, "  --pin string")
+	fmt.Fprintln(os.Stderr, "        Create a named pin for the pushed closure (requires exactly one store path)")
+	fmt.Fprintln(os.Stderr, "  --compression string")
+	fmt.Fprintln(os.Stderr, "        NAR compression: zstd, xz, br or none (default: zstd; xz and br need xz or brotli in PATH)")
+	fmt.Fprintln(os.Stderr, "  --compression-level int")
+	fmt.Fprintln(os.Stderr, "        NAR compression level: zstd 1-22, xz 1-9, br 1-11 (default: 0, the encoder's default)")
+	fmt.Fprintln(os.Stderr, "  --zstd-long[=windowLog]")
+	fmt.Fprintln(os.Stderr, "        Compress NARs with a 2^windowLog byte window, 10-27 (27 if given without")
+	fmt.Fprintln(os.Stderr, "        a value), so large NARs find matches far apart. Memory grows with the")
+	fmt.Fprintln(os.Stderr, "        window: each NAR compressed at once holds up to 2^windowLog bytes per")
+	fmt.Fprintln(os.Stderr, "        --compression-workers worker (128 MiB each at 27), and decompressing needs")
+	fmt.Fprintln(os.Stderr, "        up to 2^windowLog as well. 27 is the most Nix accepts when substituting")
+	fmt.Fprintln(os.Stderr, "  --compression-workers int")
+	fmt.Fprintln(os.Stderr, "        zstd worker goroutines for NARs over 64 MiB (default: 0, one per CPU)")
+	fmt.Fprintln(os.Stderr, "  --compression-jobs int")
+	fmt.Fprintln(os.Stderr, "        NARs compressed at once (default: 0, one per CPU). Compression is CPU-bound")
+	fmt.Fprintln(os.Stderr, "        while --max-concurrent-uploads bounds paths in flight on the network; a path")
+	fmt.Fprintln(os.Stderr, "        needs an upload slot before it can take a compression slot, so at most")
+	fmt.Fprintln(os.Stderr, "        min(jobs, uploads) NARs compress at once. Small NARs give their compression")
+	fmt.Fprintln(os.Stderr, "        slot back before uploading; multipart NARs stream and hold it throughout")
+	fmt.Fprintln(os.Stderr, "  --min-compression-ratio float")
+	fmt.Fprintln(os.Stderr, "        Store a NAR uncompressed if its first MiB compresses by less than this")
+	fmt.Fprintln(os.Stderr, "        ratio, e.g. 1.1 for already-compressed media (default: 0, always compress)")
+	fmt.Fprintln(os.Stderr, "  --store-dir string")
+	fmt.Fprintln(os.Stderr, "        Nix store directory (default: $NIX_STORE_DIR, else asked from nix, else /nix/store)")
+	fmt.Fprintln(os.Stderr, "  --store string")
+	fmt.Fprintln(os.Stderr, "        Nix store URI to push from, e.g. ssh-ng://builder (default: the local store);")
+	fmt.Fprintln(os.Stderr, "        NARs are then streamed with 'nix store dump-path' instead of read from disk")
+	fmt.Fprintln(os.Stderr, "  --from-json string")
+	fmt.Fprintln(os.Stderr, "        Take path metadata from a 'nix path-info --recursive --json' dump instead of")
+	fmt.Fprintln(os.Stderr, "        running nix; NARs are still read from the store")
+	fmt.Fprintln(os.Stderr, "  --path-info-cache string")
+	fmt.Fprintln(os.Stderr, "        Directory caching path metadata between runs, so only paths not seen")
+	fmt.Fprintln(os.Stderr, "        before are queried from nix; entries of deleted paths are dropped")
+	fmt.Fprintln(os.Stderr, "  --path-info-cache-ttl duration")
+	fmt.Fprintln(os.Stderr, "        Query cached path metadata again once it is this old, e.g. to pick up")
+	fmt.Fprintln(os.Stderr, "        new signatures (default: 24h, 0 = never)")
+	fmt.Fprintln(os.Stderr, "  --temp-dir string")
+	fmt.Fprintln(os.Stderr, "        Directory to stage compressed build logs in (default: $TMPDIR or /tmp)")
+	fmt.Fprintln(os.Stderr, "  --temp-dir-budget uint")
+	fmt.Fprintln(os.Stderr, "        Maximum bytes staged in --temp-dir at once, checked before uploading")
+	fmt.Fprintln(os.Stderr, "        along with the free space (default: 0, no limit)")
+	fmt.Fprintln(os.Stderr, "  --keep-temp")
+	fmt.Fprintln(os.Stderr, "        Keep the staged build logs and a copy of every uploaded NAR and narinfo")
+	fmt.Fprintln(os.Stderr, "        in a niks3-push-* directory below --temp-dir, and log their paths, to")
+	fmt.Fprintln(os.Stderr, "        debug what was sent")
+	fmt.Fprintln(os.Stderr, "  --in-memory-log-limit int")
+	fmt.Fprintln(os.Stderr, "        Compress build logs up to this many bytes in memory instead of staging")
+	fmt.Fprintln(os.Stderr, "        them in --temp-dir (default: 4194304, 4 MiB; 0 stages every log)")
+	fmt.Fprintln(os.Stderr, "  --case-hack string")
+	fmt.Fprintln(os.Stderr, "        Strip the ~nix~case~hack~ suffix when serializing NARs: auto, on, or off")
+	fmt.Fprintln(os.Stderr, "        (default: auto, which enables it on macOS only)")
+	fmt.Fprintln(os.Stderr, "  --on-special-file string")
+	fmt.Fprintln(os.Stderr, "        What to do with fifos, sockets and device nodes, which NARs cannot hold:")
+	fmt.Fprintln(os.Stderr, "        fail names the file and stops the push, skip leaves it out of the NAR")
+	fmt.Fprintln(os.Stderr, "        with a warning, as if it did not exist (default: fail)")
+	fmt.Fprintln(os.Stderr, cmdutil.TLSHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.TimeoutHelp)
+	fmt.Fprintln(os.Stderr, "  --debug")
+	fmt.Fprintln(os.Stderr, "        Enable debug logging (includes HTTP requests/responses)")
+	fmt.Fprintln(os.Stderr, cmdutil.LogHelp)
+	fmt.Fprintln(os.Stderr, "  -h, --help")
+	fmt.Fprintln(os.Stderr, "        Show this help message")
+}
+
+func printWatchHelp() {
+	fmt.Fprintln(os.Stderr, "Usage: niks3 watch [flags]")
+	fmt.Fprintln(os.Stderr, "\nRun until interrupted, pushing store paths as they appear. By default new")
+	fmt.Fprintln(os.Stderr, "entries of --store-dir are pushed once nix has registered them; with --gc-root,")
+	fmt.Fprintln(os.Stderr, "the closure a root points to is pushed at start and whenever it changes.")
+	fmt.Fprintln(os.Stderr, "Changes are pushed together once none arrived for --debounce, leaving out")
+	fmt.Fprintln(os.Stderr, "paths deleted in the meantime. Closures already in the cache are skipped, and")
+	fmt.Fprintln(os.Stderr, "a failed push is logged and retried instead of stopping the watch.")
+	fmt.Fprintln(os.Stderr, "\nFlags:")
+	fmt.Fprintln(os.Stderr, "  --server-url string")
+	fmt.Fprintln(os.Stderr, "        Server URL (can also use NIKS3_SERVER_URL env var)")
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenPathHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenScriptHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.ConfigHelp)
+	fmt.Fprintln(os.Stderr, "  --gc-root path")
+	fmt.Fprintln(os.Stderr, "        Watch this symlink, e.g. /run/current-system or a profile, instead of the")
+	fmt.Fprintln(os.Stderr, "        whole store (repeatable)")
+	fmt.Fprintln(os.Stderr, "  --store-dir string")
+	fmt.Fprintln(os.Stderr, "        Nix store directory (default: $NIX_STORE_DIR, else asked from nix, else /nix/store)")
+	fmt.Fprintln(os.Stderr, "  --interval duration")
+	fmt.Fprintln(os.Stderr, "        How often to check for changes (default: 2s)")
+	fmt.Fprintln(os.Stderr, "  --debounce duration")
+	fmt.Fprintln(os.Stderr, "        Push once nothing changed for this long, so the outputs of a build are")
+	fmt.Fprintln(os.Stderr, "        pushed together (default: 10s)")
+	fmt.Fprintln(os.Stderr, "  --max-concurrent-uploads int|auto")
+	fmt.Fprintln(os.Stderr, "        Maximum concurrent uploads (default: 30). 0 or auto picks 4 per CPU")
+	fmt.Fprintln(os.Stderr, "  --key-prefix string")
+	fmt.Fprintln(os.Stderr, "        Upload every object below this bucket prefix, as with push")
+	fmt.Fprintln(os.Stderr, cmdutil.TLSHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.TimeoutHelp)
+	fmt.Fprintln(os.Stderr, "  --debug")
+	fmt.Fprintln(os.Stderr, "        Enable debug logging (includes HTTP requests/responses)")
+	fmt.Fprintln(os.Stderr, cmdutil.LogHelp)
+	fmt.Fprintln(os.Stderr, "  -h, --help")
+	fmt.Fprintln(os.Stderr, "        Show this help message")
+}
+
+func printPullHelp() {
+	fmt.Fprintln(os.Stderr, "Usage: niks3 pull [flags] <store-path>...")
+	fmt.Fprintln(os.Stderr, "\nDownload store paths and their closures through the server's read proxy.")
+	fmt.Fprintln(os.Stderr, "Each NAR is verified against its narinfo and restored below --dest;")
+	fmt.Fprintln(os.Stderr, "paths already in the local Nix store are skipped. Restored paths are not")
+	fmt.Fprintln(os.Stderr, "registered with Nix.")
+	fmt.Fprintln(os.Stderr, "\nFlags:")
+	fmt.Fprintln(os.Stderr, "  --server-url string")
+	fmt.Fprintln(os.Stderr, "        Server URL (can also use NIKS3_SERVER_URL env var)")
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenPathHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenScriptHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.ConfigHelp)
+	fmt.Fprintln(os.Stderr, "  --dest string")
+	fmt.Fprintln(os.Stderr, "        Root directory to restore paths under, e.g. <dest>/nix/store/... (required)")
+	fmt.Fprintln(os.Stderr, "  --max-concurrent-downloads int")
+	fmt.Fprintln(os.Stderr, "        Maximum concurrent downloads (default: 8)")
+	fmt.Fprintln(os.Stderr, "  --store-dir string")
+	fmt.Fprintln(os.Stderr, "        Nix store directory (default: $NIX_STORE_DIR, else asked from nix, else /nix/store)")
+	fmt.Fprintln(os.Stderr, cmdutil.TLSHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.TimeoutHelp)
+	fmt.Fprintln(os.Stderr, "  --debug")
+	fmt.Fprintln(os.Stderr, "        Enable debug logging (includes HTTP requests/responses)")
+	fmt.Fprintln(os.Stderr, cmdutil.LogHelp)
+	fmt.Fprintln(os.Stderr, "  -h, --help")
+	fmt.Fprintln(os.Stderr, "        Show this help message")
+}
+
+func printVerifyHelp() {
+	fmt.Fprintln(os.Stderr, "Usage: niks3 verify [flags] <store-path>...")
+	fmt.Fprintln(os.Stderr, "\nDownload the closures of the given store paths through the server's read")
+	fmt.Fprintln(os.Stderr, "proxy and check every NAR against its narinfo (FileHash, NarHash and sizes).")
+	fmt.Fprintln(os.Stderr, "Prints ok, corrupt or missing per path and fails if any path is not ok.")
+	fmt.Fprintln(os.Stderr, "\nFlags:")
+	fmt.Fprintln(os.Stderr, "  --server-url string")
+	fmt.Fprintln(os.Stderr, "        Server URL (can also use NIKS3_SERVER_URL env var)")
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenPathHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenScriptHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.ConfigHelp)
+	fmt.Fprintln(os.Stderr, "  --trusted-public-key string")
+	fmt.Fprintln(os.Stderr, "        Require a valid signature by this key (name:base64-key); may be repeated")
+	fmt.Fprintln(os.Stderr, "  --max-concurrent-downloads int")
+	fmt.Fprintln(os.Stderr, "        Maximum concurrent downloads (default: 8)")
 	fmt.Fprintln(os.Stderr, cmdutil.TLSHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.TimeoutHelp)
 	fmt.Fprintln(os.Stderr, "  --debug")
 	fmt.Fprintln(os.Stderr, "        Enable debug logging (includes HTTP requests/responses)")
+	fmt.Fprintln(os.Stderr, cmdutil.LogHelp)
+	fmt.Fprintln(os.Stderr, "  -h, --help")
+	fmt.Fprintln(os.Stderr, "        Show this help message")
+}
+
+func printInfoHelp() {
+	fmt.Fprintln(os.Stderr, "Usage: niks3 info [flags] <store-path>")
+	fmt.Fprintln(os.Stderr, "\nShow the store path hash, NAR hash and size, references and deriver of a path,")
+	fmt.Fprintln(os.Stderr, "and the narinfo a push would upload for it. Nothing is uploaded and the server")
+	fmt.Fprintln(os.Stderr, "is not contacted. The narinfo lacks FileHash/FileSize, which are only known")
+	fmt.Fprintln(os.Stderr, "after compression, and the server's signature.")
+	fmt.Fprintln(os.Stderr, "\nFlags:")
+	fmt.Fprintln(os.Stderr, "  --json")
+	fmt.Fprintln(os.Stderr, "        Print the report as JSON")
+	fmt.Fprintln(os.Stderr, "  --compression string")
+	fmt.Fprintln(os.Stderr, "        NAR compression the push would use: zstd, xz, br or none (default: zstd)")
+	fmt.Fprintln(os.Stderr, "  --store-dir string")
+	fmt.Fprintln(os.Stderr, "        Nix store directory (default: $NIX_STORE_DIR, else asked from nix, else /nix/store)")
+	fmt.Fprintln(os.Stderr, "  --store string")
+	fmt.Fprintln(os.Stderr, "        Nix store URI to read the path from (default: local store)")
+	fmt.Fprintln(os.Stderr, "  --from-json string")
+	fmt.Fprintln(os.Stderr, "        Read path info from a 'nix path-info --recursive --json' dump")
+	fmt.Fprintln(os.Stderr, "  --debug")
+	fmt.Fprintln(os.Stderr, "        Enable debug logging")
 	fmt.Fprintln(os.Stderr, "  -h, --help")
 	fmt.Fprintln(os.Stderr, "        Show this help message")
 }
@@ -63,6 +353,7 @@ func printGcHelp() {
 	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenHelp)
 	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenPathHelp)
 	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenScriptHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.ConfigHelp)
 	fmt.Fprintln(os.Stderr, "  --older-than string")
 	fmt.Fprintln(os.Stderr, "        Delete closures older than this duration (default: '720h' for 30 days)")
 	fmt.Fprintln(os.Stderr, "  --failed-uploads-older-than string")
@@ -73,6 +364,123 @@ func printGcHelp() {
 	fmt.Fprintln(os.Stderr, cmdutil.TLSHelp)
 	fmt.Fprintln(os.Stderr, "  --debug")
 	fmt.Fprintln(os.Stderr, "        Enable debug logging (includes HTTP requests/responses)")
+	fmt.Fprintln(os.Stderr, cmdutil.LogHelp)
+	fmt.Fprintln(os.Stderr, "  -h, --help")
+	fmt.Fprintln(os.Stderr, "        Show this help message")
+}
+
+func printDeleteHelp() {
+	fmt.Fprintln(os.Stderr, "Usage: niks3 delete [flags] <store-path-or-hash>...")
+	fmt.Fprintln(os.Stderr, "\nRemove paths from the cache. The narinfo is deleted right away; the NAR and")
+	fmt.Fprintln(os.Stderr, "listing follow with the next gc after its grace period, unless another path")
+	fmt.Fprintln(os.Stderr, "shares them. Dependencies are left to gc. The server refuses paths that are")
+	fmt.Fprintln(os.Stderr, "pinned or still referenced by other paths in the cache.")
+	fmt.Fprintln(os.Stderr, "\nFlags:")
+	fmt.Fprintln(os.Stderr, "  --server-url string")
+	fmt.Fprintln(os.Stderr, "        Server URL (can also use NIKS3_SERVER_URL env var)")
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenPathHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenScriptHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.ConfigHelp)
+	fmt.Fprintln(os.Stderr, "  --dry-run")
+	fmt.Fprintln(os.Stderr, "        Report what would be deleted without deleting anything")
+	fmt.Fprintln(os.Stderr, "  --key-prefix string")
+	fmt.Fprintln(os.Stderr, "        Delete from the cache below this key prefix in the bucket")
+	fmt.Fprintln(os.Stderr, cmdutil.TLSHelp)
+	fmt.Fprintln(os.Stderr, "  --debug")
+	fmt.Fprintln(os.Stderr, "        Enable debug logging (includes HTTP requests/responses)")
+	fmt.Fprintln(os.Stderr, cmdutil.LogHelp)
+	fmt.Fprintln(os.Stderr, "  -h, --help")
+	fmt.Fprintln(os.Stderr, "        Show this help message")
+}
+
+func printResignHelp() {
+	fmt.Fprintln(os.Stderr, "Usage: niks3 resign [flags] <store-path-or-hash|@file>...")
+	fmt.Fprintln(os.Stderr, "\nAdd a signature by each --sign-key-path key to the narinfos of the given paths,")
+	fmt.Fprintln(os.Stderr, "e.g. when rotating signing keys. Only the narinfos are rewritten, by the server;")
+	fmt.Fprintln(os.Stderr, "NARs are not re-uploaded and existing signatures are kept. Paths a key already")
+	fmt.Fprintln(os.Stderr, "signed are left unchanged.")
+	fmt.Fprintln(os.Stderr, "\nFlags:")
+	fmt.Fprintln(os.Stderr, "  --server-url string")
+	fmt.Fprintln(os.Stderr, "        Server URL (can also use NIKS3_SERVER_URL env var)")
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenPathHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenScriptHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.ConfigHelp)
+	fmt.Fprintln(os.Stderr, "  --sign-key-path string")
+	fmt.Fprintln(os.Stderr, "        Secret key file to sign with (required, repeatable; see generate-key)")
+	fmt.Fprintln(os.Stderr, "  --trusted-public-key string")
+	fmt.Fprintln(os.Stderr, "        Only re-sign narinfos that carry a valid signature by this key, e.g. the")
+	fmt.Fprintln(os.Stderr, "        one being rotated out (repeatable, format: name:base64)")
+	fmt.Fprintln(os.Stderr, "  --key-prefix string")
+	fmt.Fprintln(os.Stderr, "        Re-sign in the cache below this key prefix in the bucket")
+	fmt.Fprintln(os.Stderr, cmdutil.TLSHelp)
+	fmt.Fprintln(os.Stderr, "  --debug")
+	fmt.Fprintln(os.Stderr, "        Enable debug logging (includes HTTP requests/responses)")
+	fmt.Fprintln(os.Stderr, cmdutil.LogHelp)
+	fmt.Fprintln(os.Stderr, "  -h, --help")
+	fmt.Fprintln(os.Stderr, "        Show this help message")
+}
+
+func printInitCacheHelp() {
+	fmt.Fprintln(os.Stderr, "Usage: niks3 init-cache [flags]")
+	fmt.Fprintln(os.Stderr, "\nWrite the cache's nix-cache-info, which nix reads before using a binary cache.")
+	fmt.Fprintln(os.Stderr, "The server creates a default one on startup; this replaces it.")
+	fmt.Fprintln(os.Stderr, "\nFlags:")
+	fmt.Fprintln(os.Stderr, "  --server-url string")
+	fmt.Fprintln(os.Stderr, "        Server URL (can also use NIKS3_SERVER_URL env var)")
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenPathHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenScriptHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.ConfigHelp)
+	fmt.Fprintln(os.Stderr, "  --store-dir string")
+	fmt.Fprintln(os.Stderr, "        Nix store directory (default: $NIX_STORE_DIR, else asked from nix, else /nix/store)")
+	fmt.Fprintln(os.Stderr, "  --priority int")
+	fmt.Fprintln(os.Stderr, "        Substituter priority; lower is tried first (default: 30, before cache.nixos.org's 40)")
+	fmt.Fprintln(os.Stderr, "  --want-mass-query")
+	fmt.Fprintln(os.Stderr, "        Let nix query many paths at once (default: true)")
+	fmt.Fprintln(os.Stderr, cmdutil.TLSHelp)
+	fmt.Fprintln(os.Stderr, "  --debug")
+	fmt.Fprintln(os.Stderr, "        Enable debug logging (includes HTTP requests/responses)")
+	fmt.Fprintln(os.Stderr, cmdutil.LogHelp)
+	fmt.Fprintln(os.Stderr, "  -h, --help")
+	fmt.Fprintln(os.Stderr, "        Show this help message")
+}
+
+func printDoctorHelp() {
+	fmt.Fprintln(os.Stderr, "Usage: niks3 doctor [flags]")
+	fmt.Fprintln(os.Stderr, "\nCheck that nix is usable, the server is reachable and accepts the auth token,")
+	fmt.Fprintln(os.Stderr, "and the Nix store can be read. Prints one line per check with a hint for each")
+	fmt.Fprintln(os.Stderr, "failure, and exits non-zero if any check failed.")
+	fmt.Fprintln(os.Stderr, "\nFlags:")
+	fmt.Fprintln(os.Stderr, "  --server-url string")
+	fmt.Fprintln(os.Stderr, "        Server URL (can also use NIKS3_SERVER_URL env var)")
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenPathHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.AuthTokenScriptHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.ConfigHelp)
+	fmt.Fprintln(os.Stderr, "  --store-dir string")
+	fmt.Fprintln(os.Stderr, "        Nix store directory (default: $NIX_STORE_DIR, else asked from nix, else /nix/store)")
+	fmt.Fprintln(os.Stderr, cmdutil.TLSHelp)
+	fmt.Fprintln(os.Stderr, cmdutil.TimeoutHelp)
+	fmt.Fprintln(os.Stderr, "  --debug")
+	fmt.Fprintln(os.Stderr, "        Enable debug logging (includes HTTP requests/responses)")
+	fmt.Fprintln(os.Stderr, cmdutil.LogHelp)
+	fmt.Fprintln(os.Stderr, "  -h, --help")
+	fmt.Fprintln(os.Stderr, "        Show this help message")
+}
+
+func printGenerateKeyHelp() {
+	fmt.Fprintln(os.Stderr, "Usage: niks3 generate-key <key-name> --secret-out <file> --public-out <file>")
+	fmt.Fprintln(os.Stderr, "\nCreate an Ed25519 keypair for signing narinfos. The files are identical in")
+	fmt.Fprintln(os.Stderr, "format to the output of 'nix key generate-secret' and 'nix key")
+	fmt.Fprintln(os.Stderr, "convert-secret-to-public'. Pass the secret to the server's --sign-key-path and")
+	fmt.Fprintln(os.Stderr, "add the public key to trusted-public-keys.")
+	fmt.Fprintln(os.Stderr, "\nFlags:")
+	fmt.Fprintln(os.Stderr, "  --secret-out string")
+	fmt.Fprintln(os.Stderr, "        File to write the secret key to, mode 0600 (required, must not exist)")
+	fmt.Fprintln(os.Stderr, "  --public-out string")
+	fmt.Fprintln(os.Stderr, "        File to write the public key to (required, must not exist)")
 	fmt.Fprintln(os.Stderr, "  -h, --help")
 	fmt.Fprintln(os.Stderr, "        Show this help message")
 }
@@ -94,12 +502,102 @@ func run() error {
 	case "push":
 		pushCmd := flag.NewFlagSet("push", flag.ContinueOnError)
 		cf := cmdutil.AddCommonFlags(pushCmd)
-		maxConcurrent := pushCmd.Int("max-concurrent-uploads", 30, "Maximum concurrent uploads")
+		maxConcurrent := cmdutil.ConcurrencyFlag(pushCmd, "max-concurrent-uploads", 30, "Maximum concurrent uploads (0 or auto = per CPU)")
+		maxConcurrentRequests := pushCmd.Int("max-concurrent-requests", 8, "Maximum concurrent pending closure requests")
 		verifyS3Integrity := pushCmd.Bool("verify-s3-integrity", false, "Verify S3 integrity")
+		skipExisting := pushCmd.Bool("skip-existing", true, "Skip closures already in the cache")
+		continueOnError := pushCmd.Bool("continue-on-error", false, "Upload every path that can be uploaded and report failures at the end")
+		var exclude []string
+
+		pushCmd.Func("exclude", "Store path to leave out of the push (repeatable)", func(s string) error {
+			exclude = append(exclude, s)
+
+			return nil
+		})
+		excludeFrom := pushCmd.String("exclude-from", "", "Read store paths to exclude from a file")
+		keyPrefix := pushCmd.String("key-prefix", "", "Upload every object below this bucket prefix")
+		recursive := pushCmd.Bool("recursive", true, "Push the closures of the given paths")
+		directS3 := pushCmd.Bool("direct-s3", false, "Upload straight to an S3 bucket without a niks3 server")
+		s3Endpoint := pushCmd.String("s3-endpoint", "", "S3 endpoint for --direct-s3")
+		s3Bucket := pushCmd.String("s3-bucket", "", "S3 bucket for --direct-s3")
+		s3Region := pushCmd.String("s3-region", "", "S3 region for --direct-s3")
+		s3AccessKey := pushCmd.String("s3-access-key", "", "S3 access key ID for --direct-s3")
+		s3SecretKey := pushCmd.String("s3-secret-key", "", "S3 secret access key for --direct-s3")
+
+		var signKeyPaths []string
+
+		pushCmd.Func("sign-key-path", "Sign narinfos with this key file in --direct-s3 mode (repeatable)", func(s string) error {
+			signKeyPaths = append(signKeyPaths, s)
+
+			return nil
+		})
+
+		pushCmd.BoolFunc("no-recursive", "Push only the given paths, not their closures", func(s string) error {
+			v, err := strconv.ParseBool(s)
+			*recursive = !v
+
+			return err //nolint:wrapcheck // flag reports the invalid value
+		})
+		stateFile := pushCmd.String("state-file", "", "Checkpoint file for resuming an interrupted push")
+		manifest := pushCmd.String("manifest", "", "Write a JSON manifest of the pushed paths to this file")
+		writeListings := pushCmd.Bool("write-listings", true, "Upload a .ls listing alongside each NAR")
+		includeDerivations := pushCmd.Bool("include-derivations", false, "Also push the derivations of the paths")
+		checksumUploads := pushCmd.Bool("checksum-uploads", false, "Send checksum headers so S3 rejects corrupted uploads")
+		maxUploadRate := pushCmd.Int64("max-upload-rate", 0, "Maximum combined upload rate in bytes per second (0 = unlimited)")
 		pinName := pushCmd.String("pin", "", "Create a named pin for the pushed closure")
+		fromStdin := pushCmd.Bool("stdin", false, "Read store paths from stdin")
+		progress := pushCmd.Bool("progress", false, "Print a line per uploaded path with throughput and ETA")
+		output := pushCmd.String("output", "text", "Summary format: text or json")
+		dryRun := pushCmd.Bool("dry-run", false, "Report what would be uploaded without uploading")
+		printNarinfo := pushCmd.Bool("print-narinfo", false, "Print the narinfos that would be uploaded and exit")
+		retries := pushCmd.Int("retries", client.DefaultRetryConfig().MaxRetries, "Retry attempts for failed requests")
+		retryBaseDelay := pushCmd.Duration("retry-base-delay", client.DefaultRetryConfig().InitialBackoff, "Initial backoff between retries")
+		caseHack := pushCmd.String("case-hack", "auto", "Strip the case hack suffix (auto, on, off)")
+		onSpecialFile := pushCmd.String("on-special-file", "fail", "What to do with fifos, sockets and devices (fail, skip)")
+		compression := pushCmd.String("compression", "zstd", "NAR compression (zstd, xz, br, none)")
+		compressionLevel := pushCmd.Int("compression-level", 0, "NAR compression level (zstd 1-22, xz 1-9, br 1-11, 0 = default)")
+
+		var zstdWindowLog int
+
+		// --zstd-long alone selects the largest window; --zstd-long=N sets it.
+		pushCmd.BoolFunc("zstd-long", "zstd window log for long-distance matches", func(s string) error {
+			switch s {
+			case "true":
+				zstdWindowLog = client.DefaultZstdWindowLog
+			case "false":
+				zstdWindowLog = 0
+			default:
+				windowLog, err := strconv.Atoi(s)
+				if err != nil {
+					return fmt.Errorf("expected a window log: %w", err)
+				}
+
+				if err := client.ValidateZstdWindowLog(windowLog); err != nil {
+					return err //nolint:wrapcheck // the flag package prefixes the flag name
+				}
+
+				zstdWindowLog = windowLog
+			}
+
+			return nil
+		})
+
+		compressionWorkers := pushCmd.Int("compression-workers", 0, "zstd workers for large NARs (0 = one per CPU)")
+		compressionJobs := pushCmd.Int("compression-jobs", 0, "NARs compressed at once (0 = one per CPU)")
+		minCompressionRatio := pushCmd.Float64("min-compression-ratio", 0, "Store NARs uncompressed below this sampled ratio (0 = always compress)")
+		tempDir := pushCmd.String("temp-dir", "", "Directory to stage compressed build logs in")
+		storeDir := pushCmd.String("store-dir", "", "Nix store directory")
+		store := pushCmd.String("store", "", "Nix store URI to push from (default: local store)")
+		fromJSON := pushCmd.String("from-json", "", "Read path info from a 'nix path-info --recursive --json' dump")
+		pathInfoCache := pushCmd.String("path-info-cache", "", "Directory caching path info between runs")
+		pathInfoCacheTTL := pushCmd.Duration("path-info-cache-ttl", client.DefaultPathInfoCacheTTL, "Age after which cached path info is queried again (0 = never)")
+		tempDirBudget := pushCmd.Uint64("temp-dir-budget", 0, "Maximum bytes staged in --temp-dir at once (0 = no limit)")
+		keepTemp := pushCmd.Bool("keep-temp", false, "Keep staged files and copies of uploads for debugging")
+		inMemoryLogLimit := pushCmd.Int64("in-memory-log-limit", client.DefaultInMemoryLogLimit, "Compress build logs up to this size in memory")
 		tf := cmdutil.AddTLSFlags(pushCmd)
+		tof := cmdutil.AddTimeoutFlags(pushCmd)
 
-		if err := pushCmd.Parse(os.Args[2:]); err != nil {
+		if err := cmdutil.ParseFlags(pushCmd, os.Args[2:]); err != nil {
 			if errors.Is(err, flag.ErrHelp) {
 				printPushHelp()
 				os.Exit(0)
@@ -113,39 +611,237 @@ func run() error {
 			os.Exit(0)
 		}
 
-		cmdutil.SetupLogger(*cf.Debug)
-
-		if err := cmdutil.RequireServerURL(*cf.ServerURL); err != nil {
+		if err := cf.SetupLogger(); err != nil {
 			return err //nolint:wrapcheck // cmdutil errors are already user-facing
 		}
 
-		ts, err := cf.TokenSource(pushCmd, tf)
-		if err != nil {
-			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		var (
+			ts     client.TokenSource
+			direct *client.DirectS3
+			err    error
+		)
+
+		if *directS3 {
+			if *pinName != "" {
+				return errors.New("--pin needs a niks3 server and cannot be combined with --direct-s3")
+			}
+
+			if *s3Bucket == "" {
+				return errors.New("--direct-s3 requires --s3-bucket")
+			}
+
+			cfg := directS3Config(*s3Endpoint, *s3Bucket, *s3Region, *s3AccessKey, *s3SecretKey, strings.Trim(*keyPrefix, "/"))
+
+			if cfg.StoreDir = *storeDir; cfg.StoreDir == "" {
+				if cfg.StoreDir, err = client.GetStoreDir(context.Background(), nil); err != nil {
+					return fmt.Errorf("getting store directory: %w", err)
+				}
+			}
+
+			for _, path := range signKeyPaths {
+				key, err := signing.LoadKeyFromFile(path)
+				if err != nil {
+					return fmt.Errorf("loading signing key: %w", err)
+				}
+
+				cfg.SigningKeys = append(cfg.SigningKeys, key)
+			}
+
+			if direct, err = client.NewDirectS3(cfg); err != nil {
+				return err //nolint:wrapcheck // client errors are already descriptive
+			}
+
+			ts = client.StaticToken("")
+		} else {
+			if len(signKeyPaths) > 0 {
+				return errors.New("--sign-key-path is only used with --direct-s3; the server signs narinfos")
+			}
+
+			if err := cmdutil.RequireServerURL(*cf.ServerURL); err != nil {
+				return err //nolint:wrapcheck // cmdutil errors are already user-facing
+			}
+
+			if ts, err = cf.TokenSource(pushCmd, tf); err != nil {
+				return err //nolint:wrapcheck // cmdutil errors are already user-facing
+			}
 		}
 
 		paths := pushCmd.Args()
+
+		// "-" as the only argument is shorthand for --stdin
+		if len(paths) == 1 && paths[0] == "-" {
+			*fromStdin = true
+			paths = nil
+		}
+
+		if paths, err = cmdutil.ExpandPathFiles(paths); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		if *fromStdin {
+			if len(paths) > 0 {
+				return errors.New("--stdin cannot be combined with store path arguments")
+			}
+
+			if paths, err = cmdutil.ReadStorePaths(os.Stdin); err != nil {
+				return err //nolint:wrapcheck // cmdutil errors are already user-facing
+			}
+
+			// A post-build hook piping an empty $OUT_PATHS has nothing to do.
+			if len(paths) == 0 {
+				slog.Info("No store paths on stdin, nothing to push")
+
+				return nil
+			}
+		}
+
 		if len(paths) == 0 {
-			return errors.New("at least one store path is required")
+			return errors.New("no store paths given: pass them as arguments, in an @file, or on stdin with --stdin")
 		}
 
 		if *pinName != "" && len(paths) > 1 {
 			return errors.New("--pin requires exactly one store path")
 		}
 
-		return pushCommand(*cf.ServerURL, ts, paths, *maxConcurrent, *verifyS3Integrity, *pinName, *cf.Debug, tf)
+		if *excludeFrom != "" {
+			var excludePaths []string
+			if excludePaths, err = readExcludeFile(*excludeFrom); err != nil {
+				return err
+			}
 
-	case "gc":
-		gcCmd := flag.NewFlagSet("gc", flag.ContinueOnError)
-		cf := cmdutil.AddCommonFlags(gcCmd)
-		olderThan := gcCmd.String("older-than", "720h", "Delete closures older than this duration")
-		pendingOlderThan := gcCmd.String("failed-uploads-older-than", "6h", "Delete failed uploads older than this duration")
-		force := gcCmd.Bool("force", false, "Force immediate deletion without grace period")
-		tf := cmdutil.AddTLSFlags(gcCmd)
+			exclude = append(exclude, excludePaths...)
+		}
+
+		useCaseHack, err := client.ParseCaseHackMode(*caseHack)
+		if err != nil {
+			return fmt.Errorf("parsing --case-hack: %w", err)
+		}
+
+		skipSpecialFiles, err := client.ParseSpecialFileMode(*onSpecialFile)
+		if err != nil {
+			return fmt.Errorf("parsing --on-special-file: %w", err)
+		}
+
+		narCompression, err := client.ParseCompression(*compression)
+		if err != nil {
+			return fmt.Errorf("parsing --compression: %w", err)
+		}
+
+		if err := client.ValidateCompressionLevel(narCompression, *compressionLevel); err != nil {
+			return fmt.Errorf("parsing --compression-level: %w", err)
+		}
+
+		if *retries < 0 {
+			return errors.New("--retries must not be negative")
+		}
+
+		if *retryBaseDelay <= 0 {
+			return errors.New("--retry-base-delay must be positive")
+		}
+
+		if *compressionWorkers < 0 {
+			return errors.New("--compression-workers must not be negative")
+		}
+
+		if *maxUploadRate < 0 {
+			return errors.New("--max-upload-rate must not be negative")
+		}
+
+		if *compressionJobs < 0 {
+			return errors.New("--compression-jobs must not be negative")
+		}
+
+		if *minCompressionRatio < 0 {
+			return errors.New("--min-compression-ratio must not be negative")
+		}
+
+		opts := client.DefaultPushOptions()
+		opts.MaxConcurrentUploads = *maxConcurrent
+		opts.MaxConcurrentRequests = *maxConcurrentRequests
+		opts.VerifyS3Integrity = *verifyS3Integrity
+		opts.SkipExisting = *skipExisting
+		opts.WriteListings = *writeListings
+		opts.IncludeDerivations = *includeDerivations
+		opts.ChecksumUploads = *checksumUploads
+		opts.MaxUploadRate = *maxUploadRate
+		opts.ContinueOnError = *continueOnError
+		opts.Exclude = exclude
+		opts.NoRecursive = !*recursive
+		opts.KeyPrefix = strings.Trim(*keyPrefix, "/")
+		opts.StateFile = *stateFile
+		opts.Pin = *pinName
+		opts.Retry.MaxRetries = *retries
+		opts.Retry.InitialBackoff = *retryBaseDelay
+		opts.NarOptions.CaseHack = useCaseHack
+		opts.NarOptions.SkipSpecialFiles = skipSpecialFiles
+		opts.Compression = narCompression
+		opts.CompressionLevel = *compressionLevel
+		opts.ZstdWindowLog = zstdWindowLog
+		opts.CompressionWorkers = *compressionWorkers
+		opts.CompressionJobs = *compressionJobs
+		opts.MinCompressionRatio = *minCompressionRatio
+		opts.TempDir = *tempDir
+		opts.TempDirBudget = *tempDirBudget
+		opts.KeepTemp = *keepTemp
+		opts.InMemoryLogLimit = *inMemoryLogLimit
+		opts.StoreDir = *storeDir
+		opts.Store = *store
+		opts.PathInfoFile = *fromJSON
+		opts.PathInfoCache = *pathInfoCache
+		opts.PathInfoCacheTTL = *pathInfoCacheTTL
+		opts.DryRun = *dryRun
+
+		if *printNarinfo {
+			opts.NarinfoOutput = os.Stdout
+		}
+
+		if *progress {
+			opts.OnEvent = (&progressReporter{w: os.Stderr}).handle
+		}
+
+		if *output != "text" && *output != "json" {
+			return fmt.Errorf("unknown --output %q, expected text or json", *output)
+		}
+
+		if *manifest != "" && (*dryRun || *printNarinfo) {
+			return errors.New("--manifest cannot be used with --dry-run or --print-narinfo, which push nothing")
+		}
+
+		serverURLs := cf.ServerURLs()
+		if direct != nil {
+			opts.API = direct
+			serverURLs = []string{direct.URL()}
+		}
+
+		if len(serverURLs) > 1 && (*manifest != "" || *stateFile != "" || *printNarinfo) {
+			return errors.New("--manifest, --state-file and --print-narinfo take a single --server-url")
+		}
+
+		return pushCommand(serverURLs, ts, paths, opts, *manifest, *output == "json", *cf.Debug, tf, tof)
+
+	case "watch":
+		watchCmd := flag.NewFlagSet("watch", flag.ContinueOnError)
+		cf := cmdutil.AddCommonFlags(watchCmd)
+
+		var gcRoots []string
+
+		watchCmd.Func("gc-root", "Push the target of this symlink when it changes (repeatable)", func(s string) error {
+			gcRoots = append(gcRoots, s)
 
-		if err := gcCmd.Parse(os.Args[2:]); err != nil {
+			return nil
+		})
+
+		storeDir := watchCmd.String("store-dir", "", "Nix store directory")
+		interval := watchCmd.Duration("interval", client.DefaultWatchInterval, "How often to check for changes")
+		debounce := watchCmd.Duration("debounce", client.DefaultWatchDebounce, "Push once nothing changed for this long")
+		maxConcurrent := cmdutil.ConcurrencyFlag(watchCmd, "max-concurrent-uploads", 30, "Maximum concurrent uploads (0 or auto = per CPU)")
+		keyPrefix := watchCmd.String("key-prefix", "", "Upload every object below this bucket prefix")
+		tf := cmdutil.AddTLSFlags(watchCmd)
+		tof := cmdutil.AddTimeoutFlags(watchCmd)
+
+		if err := cmdutil.ParseFlags(watchCmd, os.Args[2:]); err != nil {
 			if errors.Is(err, flag.ErrHelp) {
-				printGcHelp()
+				printWatchHelp()
 				os.Exit(0)
 			}
 
@@ -153,93 +849,748 @@ func run() error {
 		}
 
 		if *cf.Help {
-			printGcHelp()
+			printWatchHelp()
 			os.Exit(0)
 		}
 
-		cmdutil.SetupLogger(*cf.Debug)
+		if watchCmd.NArg() > 0 {
+			return errors.New("watch takes no store paths; use --gc-root to watch specific ones")
+		}
+
+		if err := cf.SetupLogger(); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
 
 		if err := cmdutil.RequireServerURL(*cf.ServerURL); err != nil {
 			return err //nolint:wrapcheck // cmdutil errors are already user-facing
 		}
 
-		ts, err := cf.TokenSource(gcCmd, tf)
+		ts, err := cf.TokenSource(watchCmd, tf)
 		if err != nil {
 			return err //nolint:wrapcheck // cmdutil errors are already user-facing
 		}
 
-		return gcCommand(*cf.ServerURL, ts, *olderThan, *pendingOlderThan, *force, *cf.Debug, tf)
+		watchOpts := client.WatchOptions{
+			StoreDir: *storeDir,
+			GCRoots:  gcRoots,
+			Interval: *interval,
+			Debounce: *debounce,
+		}
 
-	case "pins":
+		opts := client.DefaultPushOptions()
+		opts.MaxConcurrentUploads = *maxConcurrent
+		opts.KeyPrefix = strings.Trim(*keyPrefix, "/")
+		opts.StoreDir = *storeDir
 
-		if len(os.Args) < 3 {
-			printPinsHelp()
+		return watchCommand(*cf.ServerURL, ts, watchOpts, opts, *cf.Debug, tf, tof)
 
-			return errors.New("missing subcommand (create, list, or delete)")
+	case "pull":
+		pullCmd := flag.NewFlagSet("pull", flag.ContinueOnError)
+		cf := cmdutil.AddCommonFlags(pullCmd)
+		dest := pullCmd.String("dest", "", "Root directory to restore paths under")
+		maxConcurrent := pullCmd.Int("max-concurrent-downloads", 8, "Maximum concurrent downloads")
+		storeDir := pullCmd.String("store-dir", "", "Nix store directory")
+		tf := cmdutil.AddTLSFlags(pullCmd)
+		tof := cmdutil.AddTimeoutFlags(pullCmd)
+
+		if err := cmdutil.ParseFlags(pullCmd, os.Args[2:]); err != nil {
+			if errors.Is(err, flag.ErrHelp) {
+				printPullHelp()
+				os.Exit(0)
+			}
+
+			return fmt.Errorf("parsing flags: %w", err)
 		}
 
-		pinsCmd := flag.NewFlagSet("pins", flag.ContinueOnError)
-		cf := cmdutil.AddCommonFlags(pinsCmd)
-		namesOnly := pinsCmd.Bool("names-only", false, "Output only pin names (list only)")
-		jsonOutput := pinsCmd.Bool("json", false, "Output as JSON (list only)")
-		tf := cmdutil.AddTLSFlags(pinsCmd)
+		if *cf.Help {
+			printPullHelp()
+			os.Exit(0)
+		}
 
-		subcommand := os.Args[2]
+		if err := cf.SetupLogger(); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
 
-		if err := pinsCmd.Parse(os.Args[3:]); err != nil {
+		if err := cmdutil.RequireServerURL(*cf.ServerURL); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		if *dest == "" {
+			return errors.New("--dest is required")
+		}
+
+		paths := pullCmd.Args()
+		if len(paths) == 0 {
+			return errors.New("at least one store path is required")
+		}
+
+		ts, err := cf.TokenSource(pullCmd, tf)
+		if err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		return pullCommand(*cf.ServerURL, ts, paths, *dest, *storeDir, *maxConcurrent, *cf.Debug, tf, tof)
+
+	case "verify":
+		verifyCmd := flag.NewFlagSet("verify", flag.ContinueOnError)
+		cf := cmdutil.AddCommonFlags(verifyCmd)
+		maxConcurrent := verifyCmd.Int("max-concurrent-downloads", 8, "Maximum concurrent downloads")
+		tf := cmdutil.AddTLSFlags(verifyCmd)
+		tof := cmdutil.AddTimeoutFlags(verifyCmd)
+
+		var trustedKeys []*signing.PublicKey
+
+		verifyCmd.Func("trusted-public-key", "Require a valid signature by this key", func(s string) error {
+			key, err := signing.ParsePublicKey(s)
+			if err != nil {
+				return err //nolint:wrapcheck // flag package adds the flag name
+			}
+
+			trustedKeys = append(trustedKeys, key)
+
+			return nil
+		})
+
+		if err := cmdutil.ParseFlags(verifyCmd, os.Args[2:]); err != nil {
 			if errors.Is(err, flag.ErrHelp) {
-				printPinsHelp()
+				printVerifyHelp()
 				os.Exit(0)
 			}
 
 			return fmt.Errorf("parsing flags: %w", err)
 		}
 
-		cmdutil.SetupLogger(*cf.Debug)
+		if *cf.Help {
+			printVerifyHelp()
+			os.Exit(0)
+		}
+
+		if err := cf.SetupLogger(); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		if err := cmdutil.RequireServerURL(*cf.ServerURL); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		paths := verifyCmd.Args()
+		if len(paths) == 0 {
+			return errors.New("at least one store path is required")
+		}
+
+		ts, err := cf.TokenSource(verifyCmd, tf)
+		if err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		return verifyCommand(*cf.ServerURL, ts, paths, trustedKeys, *maxConcurrent, *cf.Debug, tf, tof)
+
+	case "gc":
+		gcCmd := flag.NewFlagSet("gc", flag.ContinueOnError)
+		cf := cmdutil.AddCommonFlags(gcCmd)
+		olderThan := gcCmd.String("older-than", "720h", "Delete closures older than this duration")
+		pendingOlderThan := gcCmd.String("failed-uploads-older-than", "6h", "Delete failed uploads older than this duration")
+		force := gcCmd.Bool("force", false, "Force immediate deletion without grace period")
+		tf := cmdutil.AddTLSFlags(gcCmd)
+
+		if err := cmdutil.ParseFlags(gcCmd, os.Args[2:]); err != nil {
+			if errors.Is(err, flag.ErrHelp) {
+				printGcHelp()
+				os.Exit(0)
+			}
+
+			return fmt.Errorf("parsing flags: %w", err)
+		}
+
+		if *cf.Help {
+			printGcHelp()
+			os.Exit(0)
+		}
+
+		if err := cf.SetupLogger(); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		if err := cmdutil.RequireServerURL(*cf.ServerURL); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		ts, err := cf.TokenSource(gcCmd, tf)
+		if err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		return gcCommand(*cf.ServerURL, ts, *olderThan, *pendingOlderThan, *force, *cf.Debug, tf)
+
+	case "delete":
+		deleteCmd := flag.NewFlagSet("delete", flag.ContinueOnError)
+		cf := cmdutil.AddCommonFlags(deleteCmd)
+		dryRun := deleteCmd.Bool("dry-run", false, "Report what would be deleted without deleting anything")
+		keyPrefix := deleteCmd.String("key-prefix", "", "Delete from the cache below this bucket prefix")
+		tf := cmdutil.AddTLSFlags(deleteCmd)
+
+		if err := cmdutil.ParseFlags(deleteCmd, os.Args[2:]); err != nil {
+			if errors.Is(err, flag.ErrHelp) {
+				printDeleteHelp()
+				os.Exit(0)
+			}
+
+			return fmt.Errorf("parsing flags: %w", err)
+		}
+
+		if *cf.Help {
+			printDeleteHelp()
+			os.Exit(0)
+		}
+
+		if deleteCmd.NArg() == 0 {
+			printDeleteHelp()
+
+			return errors.New("no store paths specified")
+		}
+
+		if err := cf.SetupLogger(); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		if err := cmdutil.RequireServerURL(*cf.ServerURL); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		ts, err := cf.TokenSource(deleteCmd, tf)
+		if err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		return deleteCommand(*cf.ServerURL, ts, deleteCmd.Args(), strings.Trim(*keyPrefix, "/"), *dryRun, *cf.Debug, tf)
+
+	case "resign":
+		resignCmd := flag.NewFlagSet("resign", flag.ContinueOnError)
+		cf := cmdutil.AddCommonFlags(resignCmd)
+		keyPrefix := resignCmd.String("key-prefix", "", "Re-sign in the cache below this bucket prefix")
+		tf := cmdutil.AddTLSFlags(resignCmd)
+
+		var (
+			signKeys    []*signing.Key
+			trustedKeys []*signing.PublicKey
+		)
+
+		resignCmd.Func("sign-key-path", "Sign with this secret key file (repeatable)", func(s string) error {
+			key, err := signing.LoadKeyFromFile(s)
+			if err != nil {
+				return err //nolint:wrapcheck // flag package adds the flag name
+			}
+
+			signKeys = append(signKeys, key)
+
+			return nil
+		})
+
+		resignCmd.Func("trusted-public-key", "Only re-sign narinfos with a valid signature by this key", func(s string) error {
+			key, err := signing.ParsePublicKey(s)
+			if err != nil {
+				return err //nolint:wrapcheck // flag package adds the flag name
+			}
+
+			trustedKeys = append(trustedKeys, key)
+
+			return nil
+		})
+
+		if err := cmdutil.ParseFlags(resignCmd, os.Args[2:]); err != nil {
+			if errors.Is(err, flag.ErrHelp) {
+				printResignHelp()
+				os.Exit(0)
+			}
+
+			return fmt.Errorf("parsing flags: %w", err)
+		}
+
+		if *cf.Help {
+			printResignHelp()
+			os.Exit(0)
+		}
+
+		paths, err := cmdutil.ExpandPathFiles(resignCmd.Args())
+		if err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		if len(paths) == 0 {
+			printResignHelp()
+
+			return errors.New("no store paths specified")
+		}
+
+		if len(signKeys) == 0 {
+			return errors.New("--sign-key-path is required")
+		}
+
+		if err := cf.SetupLogger(); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		if err := cmdutil.RequireServerURL(*cf.ServerURL); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		ts, err := cf.TokenSource(resignCmd, tf)
+		if err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		return resignCommand(*cf.ServerURL, ts, paths, signKeys, trustedKeys, strings.Trim(*keyPrefix, "/"), *cf.Debug, tf)
+
+	case "pins":
+
+		if len(os.Args) < 3 {
+			printPinsHelp()
+
+			return errors.New("missing subcommand (create, list, or delete)")
+		}
+
+		pinsCmd := flag.NewFlagSet("pins", flag.ContinueOnError)
+		cf := cmdutil.AddCommonFlags(pinsCmd)
+		namesOnly := pinsCmd.Bool("names-only", false, "Output only pin names (list only)")
+		jsonOutput := pinsCmd.Bool("json", false, "Output as JSON (list only)")
+		tf := cmdutil.AddTLSFlags(pinsCmd)
+
+		subcommand := os.Args[2]
+
+		if subcommand == "--help" || subcommand == "-h" {
+			printPinsHelp()
+			os.Exit(0)
+		}
+
+		if err := cmdutil.ParseFlags(pinsCmd, os.Args[3:]); err != nil {
+			if errors.Is(err, flag.ErrHelp) {
+				printPinsHelp()
+				os.Exit(0)
+			}
+
+			return fmt.Errorf("parsing flags: %w", err)
+		}
+
+		if *cf.Help {
+			printPinsHelp()
+			os.Exit(0)
+		}
+
+		if err := cf.SetupLogger(); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		if err := cmdutil.RequireServerURL(*cf.ServerURL); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		ts, err := cf.TokenSource(pinsCmd, tf)
+		if err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		switch subcommand {
+		case "create":
+			args := pinsCmd.Args()
+			if len(args) < 2 {
+				return errors.New("create requires a pin name and store path")
+			}
+
+			return pinsCreateCommandNew(*cf.ServerURL, ts, args[0], args[1], *cf.Debug, tf)
+		case "list":
+			return pinsListCommandNew(*cf.ServerURL, ts, namesOnly, jsonOutput, *cf.Debug, tf)
+		case "delete":
+			args := pinsCmd.Args()
+			if len(args) < 1 {
+				return errors.New("delete requires a pin name")
+			}
+
+			return pinsDeleteCommandNew(*cf.ServerURL, ts, args[0], *cf.Debug, tf)
+		default:
+			return fmt.Errorf("unknown pins subcommand: %s", subcommand)
+		}
+
+	case "info":
+		infoCmd := flag.NewFlagSet("info", flag.ContinueOnError)
+		jsonOutput := infoCmd.Bool("json", false, "Print the report as JSON")
+		compression := infoCmd.String("compression", "zstd", "NAR compression (zstd, xz, br, none)")
+		storeDir := infoCmd.String("store-dir", "", "Nix store directory")
+		store := infoCmd.String("store", "", "Nix store URI to read the path from (default: local store)")
+		fromJSON := infoCmd.String("from-json", "", "Read path info from a 'nix path-info --recursive --json' dump")
+		debug := infoCmd.Bool("debug", false, "Enable debug logging")
+
+		args, err := parseInterspersed(infoCmd, os.Args[2:])
+		if err != nil {
+			if errors.Is(err, flag.ErrHelp) {
+				printInfoHelp()
+				os.Exit(0)
+			}
+
+			return fmt.Errorf("parsing flags: %w", err)
+		}
+
+		if len(args) != 1 {
+			return errors.New("exactly one store path is required")
+		}
+
+		narCompression, err := client.ParseCompression(*compression)
+		if err != nil {
+			return fmt.Errorf("parsing --compression: %w", err)
+		}
+
+		cmdutil.SetupLogger(*debug)
+
+		c, err := client.NewClient(context.Background(), "", "")
+		if err != nil {
+			return fmt.Errorf("creating client: %w", err)
+		}
+
+		c.Compression = narCompression
+		c.Store = *store
+		c.PathInfoFile = *fromJSON
+
+		if *storeDir != "" {
+			c.SetStoreDir(*storeDir)
+		}
+
+		return infoCommand(c, args[0], *jsonOutput)
+
+	case "init-cache":
+		initCmd := flag.NewFlagSet("init-cache", flag.ContinueOnError)
+		cf := cmdutil.AddCommonFlags(initCmd)
+		storeDir := initCmd.String("store-dir", "", "Nix store directory")
+		priority := initCmd.Int("priority", 30, "Substituter priority (lower is tried first)")
+		wantMassQuery := initCmd.Bool("want-mass-query", true, "Let nix query many paths at once")
+		tf := cmdutil.AddTLSFlags(initCmd)
+
+		if err := cmdutil.ParseFlags(initCmd, os.Args[2:]); err != nil {
+			if errors.Is(err, flag.ErrHelp) {
+				printInitCacheHelp()
+				os.Exit(0)
+			}
+
+			return fmt.Errorf("parsing flags: %w", err)
+		}
+
+		if *cf.Help {
+			printInitCacheHelp()
+			os.Exit(0)
+		}
+
+		if err := cf.SetupLogger(); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		if err := cmdutil.RequireServerURL(*cf.ServerURL); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		if *priority < 0 {
+			return errors.New("--priority must not be negative")
+		}
+
+		ts, err := cf.TokenSource(initCmd, tf)
+		if err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		info := api.CacheInfo{StoreDir: *storeDir, WantMassQuery: *wantMassQuery, Priority: *priority}
+
+		return initCacheCommand(*cf.ServerURL, ts, info, *cf.Debug, tf)
+
+	case "doctor":
+		doctorCmd := flag.NewFlagSet("doctor", flag.ContinueOnError)
+		cf := cmdutil.AddCommonFlags(doctorCmd)
+		storeDir := doctorCmd.String("store-dir", "", "Nix store directory")
+		tf := cmdutil.AddTLSFlags(doctorCmd)
+		tof := cmdutil.AddTimeoutFlags(doctorCmd)
+
+		if err := cmdutil.ParseFlags(doctorCmd, os.Args[2:]); err != nil {
+			if errors.Is(err, flag.ErrHelp) {
+				printDoctorHelp()
+				os.Exit(0)
+			}
+
+			return fmt.Errorf("parsing flags: %w", err)
+		}
+
+		if *cf.Help {
+			printDoctorHelp()
+			os.Exit(0)
+		}
+
+		if err := cf.SetupLogger(); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		if err := cmdutil.RequireServerURL(*cf.ServerURL); err != nil {
+			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+		}
+
+		// A missing token is one of the things doctor reports, so it
+		// fails the auth check instead of the command.
+		ts, tsErr := cf.TokenSource(doctorCmd, tf)
+		if tsErr != nil {
+			ts = func(context.Context) (string, error) { return "", tsErr }
+		}
+
+		return doctorCommand(*cf.ServerURL, ts, *storeDir, *cf.Debug, tf, tof)
+
+	case "completions":
+		if len(os.Args) != 3 || os.Args[2] == "--help" || os.Args[2] == "-h" {
+			printCompletionsHelp()
+
+			if len(os.Args) == 3 {
+				os.Exit(0)
+			}
+
+			return errors.New("exactly one shell is required")
+		}
+
+		return completionsCommand(os.Args[2])
+
+	case "generate-key":
+		genCmd := flag.NewFlagSet("generate-key", flag.ContinueOnError)
+		secretOut := genCmd.String("secret-out", "", "File to write the secret key to")
+		publicOut := genCmd.String("public-out", "", "File to write the public key to")
+
+		args, err := parseInterspersed(genCmd, os.Args[2:])
+		if err != nil {
+			if errors.Is(err, flag.ErrHelp) {
+				printGenerateKeyHelp()
+				os.Exit(0)
+			}
+
+			return fmt.Errorf("parsing flags: %w", err)
+		}
+
+		if len(args) != 1 {
+			return errors.New("exactly one key name is required, e.g. cache.example.com-1")
+		}
+
+		if *secretOut == "" || *publicOut == "" {
+			return errors.New("--secret-out and --public-out are required")
+		}
+
+		cmdutil.SetupLogger(false)
+
+		return generateKeyCommand(args[0], *secretOut, *publicOut)
+
+	default:
+		return fmt.Errorf("unknown command: %s", os.Args[1])
+	}
+}
+
+// parseInterspersed parses flags that may also follow positional arguments,
+// as in `niks3 generate-key name --secret-out file`, and returns the
+// positional arguments.
+func parseInterspersed(fs *flag.FlagSet, args []string) ([]string, error) {
+	var positional []string
+
+	for {
+		if err := fs.Parse(args); err != nil {
+			return nil, err //nolint:wrapcheck // caller wraps
+		}
+
+		args = fs.Args()
+		if len(args) == 0 {
+			return positional, nil
+		}
+
+		positional = append(positional, args[0])
+		args = args[1:]
+	}
+}
+
+// pushCommand pushes paths to each of serverURLs in turn. A cache that fails
+// does not stop the push to the others; the error lists the failed ones.
+func pushCommand(serverURLs []string, ts client.TokenSource, paths []string, opts client.PushOptions, manifestPath string, jsonSummary bool, debug bool, tf cmdutil.TLSFlags, tof cmdutil.TimeoutFlags) error {
+	ctx, stop := signal.NotifyContext(context.Background(), os.Interrupt, syscall.SIGTERM)
+	defer stop()
+
+	// The first signal cancels the push and lets it abort its pending
+	// closures and remove staged files; restore the default handler so a
+	// second one exits immediately.
+	stopCleanupNotice := context.AfterFunc(ctx, func() {
+		stop()
+		slog.Warn("Interrupted, cleaning up (interrupt again to exit immediately)")
+	})
+	defer stopCleanupNotice()
+
+	slog.Info("NAR compression", "compression", opts.Compression, "level", opts.CompressionLevel, "window_log", opts.ZstdWindowLog, "workers", opts.CompressionWorkers)
+
+	if len(serverURLs) == 1 {
+		return pushToCache(ctx, serverURLs[0], "", ts, paths, opts, manifestPath, jsonSummary, debug, tf, tof)
+	}
+
+	// Compress each NAR once and upload it to every cache from disk.
+	narCache, err := client.NewNARCache(opts.TempDir)
+	if err != nil {
+		return err //nolint:wrapcheck // client errors are already descriptive
+	}
+
+	defer func() {
+		if err := narCache.Close(); err != nil {
+			slog.Warn("Failed to remove compressed NARs", "error", err)
+		}
+	}()
+
+	opts.NARCache = narCache
+
+	var failed []string
+
+	for _, serverURL := range serverURLs {
+		slog.Info("Pushing to cache", "server_url", serverURL)
+
+		if err := pushToCache(ctx, serverURL, serverURL, ts, paths, opts, manifestPath, jsonSummary, debug, tf, tof); err != nil {
+			if ctx.Err() != nil {
+				return err
+			}
+
+			slog.Error("Push to cache failed", "server_url", serverURL, "error", err)
+			failed = append(failed, serverURL)
+
+			continue
+		}
+
+		slog.Info("Push to cache succeeded", "server_url", serverURL)
+	}
+
+	if len(failed) > 0 {
+		return fmt.Errorf("push failed for %d of %d caches: %s", len(failed), len(serverURLs), strings.Join(failed, ", "))
+	}
+
+	return nil
+}
+
+// pushToCache runs one push to serverURL. summaryURL is recorded in the JSON
+// summary to tell the caches of a multi-cache push apart.
+func pushToCache(ctx context.Context, serverURL, summaryURL string, ts client.TokenSource, paths []string, opts client.PushOptions, manifestPath string, jsonSummary bool, debug bool, tf cmdutil.TLSFlags, tof cmdutil.TimeoutFlags) error {
+	c, err := client.NewClientWithTokenSource(ctx, serverURL, ts)
+	if err != nil {
+		return fmt.Errorf("creating client: %w", err)
+	}
+
+	if err := tf.Configure(c); err != nil {
+		return err //nolint:wrapcheck // cmdutil errors are already user-facing
+	}
+
+	tof.Configure(c)
+
+	if debug {
+		c.SetDebugHTTP(true)
+	}
+
+	stats, err := client.Push(ctx, c, paths, opts)
+	if errors.Is(err, client.ErrUploadIncomplete) {
+		for _, failure := range stats.Failed {
+			_, _ = fmt.Fprintf(os.Stderr, "failed: %s: %v\n", failure.StorePath, failure.Err)
+		}
+	}
+
+	if jsonSummary && stats != nil {
+		enc := json.NewEncoder(os.Stdout)
+		enc.SetIndent("", "  ")
+
+		summary := stats.Summary()
+		summary.ServerURL = summaryURL
+
+		if encErr := enc.Encode(summary); encErr != nil {
+			return fmt.Errorf("writing summary: %w", encErr)
+		}
+	}
+
+	if err != nil {
+		return err //nolint:wrapcheck // client.Push wraps its errors
+	}
+
+	if manifestPath != "" {
+		return writeManifest(manifestPath, stats.Manifest)
+	}
+
+	return nil
+}
 
-		if err := cmdutil.RequireServerURL(*cf.ServerURL); err != nil {
-			return err //nolint:wrapcheck // cmdutil errors are already user-facing
-		}
+// watchCommand pushes the store paths client.Watch reports until
+// interrupted. Every batch is an ordinary push, so closures already in the
+// cache are skipped.
+func watchCommand(serverURL string, ts client.TokenSource, watchOpts client.WatchOptions, opts client.PushOptions, debug bool, tf cmdutil.TLSFlags, tof cmdutil.TimeoutFlags) error {
+	ctx, stop := signal.NotifyContext(context.Background(), os.Interrupt, syscall.SIGTERM)
+	defer stop()
 
-		ts, err := cf.TokenSource(pinsCmd, tf)
+	if watchOpts.StoreDir == "" && len(watchOpts.GCRoots) == 0 {
+		storeDir, err := client.GetStoreDir(ctx, nil)
 		if err != nil {
-			return err //nolint:wrapcheck // cmdutil errors are already user-facing
+			return fmt.Errorf("getting store directory: %w", err)
 		}
 
-		switch subcommand {
-		case "create":
-			args := pinsCmd.Args()
-			if len(args) < 2 {
-				return errors.New("create requires a pin name and store path")
-			}
+		watchOpts.StoreDir = storeDir
+	}
 
-			return pinsCreateCommandNew(*cf.ServerURL, ts, args[0], args[1], *cf.Debug, tf)
-		case "list":
-			return pinsListCommandNew(*cf.ServerURL, ts, namesOnly, jsonOutput, *cf.Debug, tf)
-		case "delete":
-			args := pinsCmd.Args()
-			if len(args) < 1 {
-				return errors.New("delete requires a pin name")
-			}
+	slog.Info("Watching for new store paths", "store_dir", watchOpts.StoreDir, "gc_roots", watchOpts.GCRoots)
 
-			return pinsDeleteCommandNew(*cf.ServerURL, ts, args[0], *cf.Debug, tf)
-		default:
-			return fmt.Errorf("unknown pins subcommand: %s", subcommand)
-		}
+	return client.Watch(ctx, watchOpts, func(ctx context.Context, paths []string) error { //nolint:wrapcheck // client errors are already descriptive
+		return pushToCache(ctx, serverURL, "", ts, paths, opts, "", false, debug, tf, tof)
+	})
+}
 
-	default:
-		return fmt.Errorf("unknown command: %s", os.Args[1])
+// writeManifest writes the manifest of a push as a JSON array.
+func writeManifest(path string, manifest []client.ManifestEntry) error {
+	if manifest == nil {
+		manifest = []client.ManifestEntry{} // Everything was excluded; still write an array
 	}
+
+	data, err := json.MarshalIndent(manifest, "", "  ")
+	if err != nil {
+		return fmt.Errorf("encoding manifest: %w", err)
+	}
+
+	if err := os.WriteFile(path, append(data, '\n'), 0o644); err != nil { //nolint:gosec // G306: the manifest lists public cache contents
+		return fmt.Errorf("writing manifest: %w", err)
+	}
+
+	return nil
 }
 
-func pushCommand(serverURL string, ts client.TokenSource, paths []string, maxConcurrent int, verifyS3Integrity bool, pinName string, debug bool, tf cmdutil.TLSFlags) error {
+func pullCommand(serverURL string, ts client.TokenSource, paths []string, dest, storeDir string, maxConcurrent int, debug bool, tf cmdutil.TLSFlags, tof cmdutil.TimeoutFlags) error {
 	ctx, stop := signal.NotifyContext(context.Background(), os.Interrupt, syscall.SIGTERM)
 	defer stop()
 
-	if maxConcurrent < 1 {
-		maxConcurrent = 1
+	c, err := client.NewClientWithTokenSource(ctx, serverURL, ts)
+	if err != nil {
+		return fmt.Errorf("creating client: %w", err)
+	}
+
+	if err := tf.Configure(c); err != nil {
+		return err //nolint:wrapcheck // cmdutil errors are already user-facing
+	}
+
+	tof.Configure(c)
+
+	c.MaxConcurrentNARUploads = max(maxConcurrent, 1)
+
+	if storeDir != "" {
+		c.SetStoreDir(storeDir)
+	}
+
+	if debug {
+		c.SetDebugHTTP(true)
+	}
+
+	pulled, err := c.PullPaths(ctx, paths, dest)
+	if err != nil {
+		return fmt.Errorf("pulling paths: %w", err)
 	}
 
+	slog.Info(fmt.Sprintf("Pulled %d paths into %s", len(pulled), dest))
+
+	return nil
+}
+
+func verifyCommand(serverURL string, ts client.TokenSource, paths []string, trustedKeys []*signing.PublicKey, maxConcurrent int, debug bool, tf cmdutil.TLSFlags, tof cmdutil.TimeoutFlags) error {
+	ctx, stop := signal.NotifyContext(context.Background(), os.Interrupt, syscall.SIGTERM)
+	defer stop()
+
 	c, err := client.NewClientWithTokenSource(ctx, serverURL, ts)
 	if err != nil {
 		return fmt.Errorf("creating client: %w", err)
@@ -249,30 +1600,35 @@ func pushCommand(serverURL string, ts client.TokenSource, paths []string, maxCon
 		return err //nolint:wrapcheck // cmdutil errors are already user-facing
 	}
 
-	c.MaxConcurrentNARUploads = maxConcurrent
-	c.VerifyS3Integrity = verifyS3Integrity
+	tof.Configure(c)
+
+	c.MaxConcurrentNARUploads = max(maxConcurrent, 1)
 
 	if debug {
 		c.SetDebugHTTP(true)
 	}
 
-	if _, err := c.PushPaths(ctx, paths); err != nil {
-		return fmt.Errorf("pushing paths: %w", err)
+	results, err := c.VerifyPaths(ctx, paths, trustedKeys)
+	if err != nil {
+		return fmt.Errorf("verifying paths: %w", err)
 	}
 
-	if pinName != "" {
-		// The server only accepts store paths, but users typically pass a
-		// ./result symlink as produced by nix-build.
-		storePath, err := c.ResolveStorePath(paths[0])
-		if err != nil {
-			return fmt.Errorf("resolving store path for pin %q: %w", pinName, err)
-		}
+	failed := 0
 
-		if err := c.CreatePin(ctx, pinName, storePath); err != nil {
-			return fmt.Errorf("creating pin %q: %w", pinName, err)
+	for _, r := range results {
+		if r.Status == client.VerifyOK {
+			_, _ = fmt.Fprintf(os.Stdout, "%-8s%s\n", r.Status, r.StorePath)
+
+			continue
 		}
 
-		slog.Info("Created pin", "name", pinName, "store_path", storePath)
+		failed++
+
+		_, _ = fmt.Fprintf(os.Stdout, "%-8s%s: %v\n", r.Status, r.StorePath, r.Err)
+	}
+
+	if failed > 0 {
+		return fmt.Errorf("%d of %d paths failed verification", failed, len(results))
 	}
 
 	return nil
@@ -319,6 +1675,114 @@ func gcCommand(serverURL string, ts client.TokenSource, olderThan, pendingOlderT
 	return nil
 }
 
+// deleteCommand deletes each path from the cache. A refused path does not
+// stop the others; the error counts the failures.
+func deleteCommand(serverURL string, ts client.TokenSource, paths []string, keyPrefix string, dryRun bool, debug bool, tf cmdutil.TLSFlags) error {
+	ctx, stop := signal.NotifyContext(context.Background(), os.Interrupt, syscall.SIGTERM)
+	defer stop()
+
+	if err := client.ValidateKeyPrefix(keyPrefix); err != nil {
+		return err //nolint:wrapcheck // already names the prefix
+	}
+
+	c, err := client.NewClientWithTokenSource(ctx, serverURL, ts)
+	if err != nil {
+		return fmt.Errorf("creating client: %w", err)
+	}
+
+	if err := tf.Configure(c); err != nil {
+		return err //nolint:wrapcheck // cmdutil errors are already user-facing
+	}
+
+	if debug {
+		c.SetDebugHTTP(true)
+	}
+
+	c.KeyPrefix = keyPrefix
+
+	failed := 0
+
+	for _, path := range paths {
+		result, err := c.DeletePath(ctx, path, dryRun)
+		if err != nil {
+			if ctx.Err() != nil {
+				return fmt.Errorf("deleting %s: %w", path, err)
+			}
+
+			slog.Error("Failed to delete path", "path", path, "error", err)
+
+			failed++
+
+			continue
+		}
+
+		if dryRun {
+			slog.Info("Would delete", "path", path, "objects", result.Deleted)
+		} else {
+			slog.Info("Deleted", "path", path, "objects", result.Deleted)
+		}
+	}
+
+	if failed > 0 {
+		return fmt.Errorf("failed to delete %d of %d paths", failed, len(paths))
+	}
+
+	return nil
+}
+
+func resignCommand(serverURL string, ts client.TokenSource, paths []string, keys []*signing.Key, trustedKeys []*signing.PublicKey, keyPrefix string, debug bool, tf cmdutil.TLSFlags) error {
+	ctx, stop := signal.NotifyContext(context.Background(), os.Interrupt, syscall.SIGTERM)
+	defer stop()
+
+	if err := client.ValidateKeyPrefix(keyPrefix); err != nil {
+		return err //nolint:wrapcheck // already names the prefix
+	}
+
+	c, err := client.NewClientWithTokenSource(ctx, serverURL, ts)
+	if err != nil {
+		return fmt.Errorf("creating client: %w", err)
+	}
+
+	if err := tf.Configure(c); err != nil {
+		return err //nolint:wrapcheck // cmdutil errors are already user-facing
+	}
+
+	if debug {
+		c.SetDebugHTTP(true)
+	}
+
+	c.KeyPrefix = keyPrefix
+
+	failed := 0
+
+	for _, path := range paths {
+		storePath, added, err := c.ResignPath(ctx, path, keys, trustedKeys)
+		if err != nil {
+			if ctx.Err() != nil {
+				return fmt.Errorf("re-signing %s: %w", path, err)
+			}
+
+			slog.Error("Failed to re-sign path", "path", path, "error", err)
+
+			failed++
+
+			continue
+		}
+
+		if added == 0 {
+			slog.Info("Already signed", "path", storePath)
+		} else {
+			slog.Info("Re-signed", "path", storePath, "added", added)
+		}
+	}
+
+	if failed > 0 {
+		return fmt.Errorf("failed to re-sign %d of %d paths", failed, len(paths))
+	}
+
+	return nil
+}
+
 func printPinsHelp() {
 	fmt.Fprintln(os.Stderr, "Usage: niks3 pins <subcommand> [flags]")
 	fmt.Fprintln(os.Stderr, "\nManage pins that protect closures from garbage collection.")
@@ -452,3 +1916,197 @@ func pinsDeleteCommandNew(serverURL string, ts client.TokenSource, name string,
 
 	return nil
 }
+
+func infoCommand(c *client.Client, path string, jsonOutput bool) error {
+	ctx, stop := signal.NotifyContext(context.Background(), os.Interrupt, syscall.SIGTERM)
+	defer stop()
+
+	report, err := c.InspectPath(ctx, path)
+	if err != nil {
+		return fmt.Errorf("inspecting %s: %w", path, err)
+	}
+
+	if jsonOutput {
+		enc := json.NewEncoder(os.Stdout)
+		enc.SetIndent("", "  ")
+
+		return enc.Encode(report) //nolint:wrapcheck // direct output
+	}
+
+	_, _ = fmt.Fprintf(os.Stdout, "Store path:  %s\n", report.StorePath)
+	_, _ = fmt.Fprintf(os.Stdout, "Hash:        %s\n", report.Hash)
+	_, _ = fmt.Fprintf(os.Stdout, "NAR hash:    %s\n", report.NarHash)
+	_, _ = fmt.Fprintf(os.Stdout, "NAR size:    %d\n", report.NarSize)
+	_, _ = fmt.Fprintf(os.Stdout, "NAR key:     %s\n", report.NarKey)
+
+	if report.Deriver != nil {
+		_, _ = fmt.Fprintf(os.Stdout, "Deriver:     %s\n", *report.Deriver)
+	}
+
+	_, _ = fmt.Fprintln(os.Stdout, "References:")
+
+	for _, ref := range report.References {
+		_, _ = fmt.Fprintf(os.Stdout, "  %s\n", ref)
+	}
+
+	_, _ = fmt.Fprintf(os.Stdout, "\nNarinfo (%s.narinfo):\n%s", report.Hash, report.Narinfo)
+
+	return nil
+}
+
+func initCacheCommand(serverURL string, ts client.TokenSource, info api.CacheInfo, debug bool, tf cmdutil.TLSFlags) error {
+	ctx, stop := signal.NotifyContext(context.Background(), os.Interrupt, syscall.SIGTERM)
+	defer stop()
+
+	c, err := client.NewClientWithTokenSource(ctx, serverURL, ts)
+	if err != nil {
+		return fmt.Errorf("creating client: %w", err)
+	}
+
+	if err := tf.Configure(c); err != nil {
+		return err //nolint:wrapcheck // cmdutil errors are already user-facing
+	}
+
+	if debug {
+		c.SetDebugHTTP(true)
+	}
+
+	if err := c.PutCacheInfo(ctx, info); err != nil {
+		return fmt.Errorf("writing nix-cache-info: %w", err)
+	}
+
+	slog.Info("Wrote nix-cache-info", "priority", info.Priority, "want_mass_query", info.WantMassQuery)
+
+	return nil
+}
+
+func doctorCommand(serverURL string, ts client.TokenSource, storeDir string, debug bool, tf cmdutil.TLSFlags, tof cmdutil.TimeoutFlags) error {
+	ctx, stop := signal.NotifyContext(context.Background(), os.Interrupt, syscall.SIGTERM)
+	defer stop()
+
+	c, err := client.NewClientWithTokenSource(ctx, serverURL, ts)
+	if err != nil {
+		return fmt.Errorf("creating client: %w", err)
+	}
+
+	if err := tf.Configure(c); err != nil {
+		return err //nolint:wrapcheck // cmdutil errors are already user-facing
+	}
+
+	tof.Configure(c)
+
+	if debug {
+		c.SetDebugHTTP(true)
+	}
+
+	if storeDir != "" {
+		c.SetStoreDir(storeDir)
+	}
+
+	// Report an unreachable server right away instead of after backoffs.
+	c.Retry.MaxRetries = 0
+
+	failures := 0
+
+	for _, check := range c.Doctor(ctx) {
+		if check.Err == nil {
+			_, _ = fmt.Fprintf(os.Stdout, "[ok]   %s: %s\n", check.Name, check.Detail)
+
+			continue
+		}
+
+		failures++
+
+		_, _ = fmt.Fprintf(os.Stdout, "[FAIL] %s: %v\n       hint: %s\n", check.Name, check.Err, check.Hint)
+	}
+
+	if failures > 0 {
+		return fmt.Errorf("%d checks failed", failures)
+	}
+
+	return nil
+}
+
+func generateKeyCommand(name, secretOut, publicOut string) error {
+	key, err := signing.GenerateKey(name, nil)
+	if err != nil {
+		return fmt.Errorf("generating key: %w", err)
+	}
+
+	public, err := key.PublicKey()
+	if err != nil {
+		return fmt.Errorf("deriving public key: %w", err)
+	}
+
+	// Like nix, write neither key with a trailing newline.
+	if err := writeNewFile(secretOut, key.SecretKey(), 0o600); err != nil {
+		return err
+	}
+
+	if err := writeNewFile(publicOut, public, 0o644); err != nil {
+		return err
+	}
+
+	slog.Info("Generated signing key", "public_key", public, "secret_out", secretOut)
+
+	return nil
+}
+
+// writeNewFile writes data to path, refusing to overwrite an existing file.
+func writeNewFile(path, data string, perm os.FileMode) error {
+	f, err := os.OpenFile(path, os.O_WRONLY|os.O_CREATE|os.O_EXCL, perm)
+	if err != nil {
+		return fmt.Errorf("creating %s: %w", path, err)
+	}
+
+	if _, err := f.WriteString(data); err != nil {
+		_ = f.Close()
+
+		return fmt.Errorf("writing %s: %w", path, err)
+	}
+
+	if err := f.Close(); err != nil {
+		return fmt.Errorf("closing %s: %w", path, err)
+	}
+
+	return nil
+}
+
+// readExcludeFile reads the store paths listed in an --exclude-from file.
+func readExcludeFile(path string) ([]string, error) {
+	f, err := os.Open(path)
+	if err != nil {
+		return nil, fmt.Errorf("opening --exclude-from file: %w", err)
+	}
+	defer func() { _ = f.Close() }()
+
+	paths, err := cmdutil.ReadStorePaths(f)
+	if err != nil {
+		return nil, fmt.Errorf("reading %s: %w", path, err)
+	}
+
+	return paths, nil
+}
+
+// directS3Config builds the --direct-s3 configuration from the flags,
+// falling back to the standard AWS environment variables for those left
+// empty.
+func directS3Config(endpoint, bucket, region, accessKey, secretKey, keyPrefix string) client.DirectS3Config {
+	cfg := client.DirectS3Config{
+		Endpoint:     cmp.Or(endpoint, os.Getenv("AWS_ENDPOINT_URL_S3"), os.Getenv("AWS_ENDPOINT_URL")),
+		Bucket:       bucket,
+		Region:       cmp.Or(region, os.Getenv("AWS_REGION"), os.Getenv("AWS_DEFAULT_REGION")),
+		AccessKey:    cmp.Or(accessKey, os.Getenv("AWS_ACCESS_KEY_ID")),
+		SecretKey:    cmp.Or(secretKey, os.Getenv("AWS_SECRET_ACCESS_KEY")),
+		SessionToken: os.Getenv("AWS_SESSION_TOKEN"),
+		KeyPrefix:    keyPrefix,
+	}
+
+	// A session token belongs to the environment's keys, not to keys given
+	// on the command line.
+	if accessKey != "" {
+		cfg.SessionToken = ""
+	}
+
+	return cfg
+}
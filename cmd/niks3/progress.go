@@ -0,0 +1,72 @@
+package main
+
+import (
+	"fmt"
+	"io"
+	"strings"
+	"sync"
+	"time"
+
+	"github.com/Mic92/niks3/client"
+)
+
+// progressReporter prints a line per uploaded path for --progress, with the
+// NAR bytes pushed so far, the throughput and an estimate of the time left.
+// Events arrive concurrently from the upload goroutines.
+type progressReporter struct {
+	mu    sync.Mutex
+	w     io.Writer
+	start time.Time
+	total int
+	done  int
+
+	narSizes        map[string]uint64 // Store path to NAR size, from PathDiscovered
+	totalBytes      uint64            // Sum of the NAR sizes of all paths
+	doneBytes       uint64            // Sum of the NAR sizes of uploaded paths
+	compressedBytes uint64            // Sum of the compressed sizes of uploaded NARs
+}
+
+func (p *progressReporter) handle(ev client.PushEvent) {
+	p.mu.Lock()
+	defer p.mu.Unlock()
+
+	if p.start.IsZero() {
+		p.start = time.Now()
+		p.narSizes = make(map[string]uint64)
+	}
+
+	switch ev := ev.(type) {
+	case client.PathDiscovered:
+		p.total++
+		p.narSizes[ev.StorePath] = ev.NarSize
+		p.totalBytes += ev.NarSize
+	case client.NarUploaded:
+		p.compressedBytes += ev.CompressedSize
+	case client.NarinfoUploaded:
+		p.done++
+		p.doneBytes += p.narSizes[ev.StorePath]
+		fmt.Fprintf(p.w, "[%d/%d] %s: %s\n", p.done, p.total, p.status(time.Since(p.start)), ev.StorePath)
+	}
+}
+
+// status formats the byte counts, the NAR throughput and, while paths are
+// left, the time they will take at that throughput.
+func (p *progressReporter) status(elapsed time.Duration) string {
+	var b strings.Builder
+
+	fmt.Fprintf(&b, "%s/%s (%s compressed)", client.FormatBytes(p.doneBytes), client.FormatBytes(p.totalBytes), client.FormatBytes(p.compressedBytes))
+
+	if elapsed <= 0 || p.doneBytes == 0 {
+		return b.String()
+	}
+
+	rate := float64(p.doneBytes) / elapsed.Seconds()
+	fmt.Fprintf(&b, ", %s/s", client.FormatBytes(uint64(rate)))
+
+	if p.doneBytes < p.totalBytes {
+		eta := time.Duration(float64(p.totalBytes-p.doneBytes) / rate * float64(time.Second))
+		fmt.Fprintf(&b, ", ETA %s", eta.Round(time.Second))
+	}
+
+	return b.String()
+}
@@ -3,12 +3,17 @@
 package cmdutil
 
 import (
+	"bufio"
 	"errors"
 	"flag"
 	"fmt"
+	"io"
 	"log/slog"
 	"os"
 	"path/filepath"
+	"strconv"
+	"strings"
+	"time"
 
 	"github.com/Mic92/niks3/client"
 )
@@ -24,19 +29,30 @@ func SetupLogger(debug bool) {
 	slog.SetDefault(slog.New(handler))
 }
 
-// DefaultAuthTokenPath returns the default XDG-compliant path for the auth token.
-func DefaultAuthTokenPath() string {
-	configDir := os.Getenv("XDG_CONFIG_HOME")
-	if configDir == "" {
+// configDir returns $XDG_CONFIG_HOME/niks3, or ~/.config/niks3, or "" if
+// the home directory is unknown.
+func configDir() string {
+	dir := os.Getenv("XDG_CONFIG_HOME")
+	if dir == "" {
 		home, err := os.UserHomeDir()
 		if err != nil {
 			return ""
 		}
 
-		configDir = filepath.Join(home, ".config")
+		dir = filepath.Join(home, ".config")
 	}
 
-	return filepath.Join(configDir, "niks3", "auth-token")
+	return filepath.Join(dir, "niks3")
+}
+
+// DefaultAuthTokenPath returns the default XDG-compliant path for the auth token.
+func DefaultAuthTokenPath() string {
+	dir := configDir()
+	if dir == "" {
+		return ""
+	}
+
+	return filepath.Join(dir, "auth-token")
 }
 
 // envAuthTokenPath returns the token file from NIKS3_AUTH_TOKEN_FILE or the
@@ -59,13 +75,18 @@ func envAuthTokenPath() string {
 }
 
 // ResolveTokenSource picks a client.TokenSource from the auth flags, in
-// priority order: script > file > literal token > env/XDG file. All file
+// priority order: script > file > literal token > env/XDG file. The
+// deprecated literal token may not be combined with a script or file. All file
 // sources (--auth-token-path, NIKS3_AUTH_TOKEN_FILE, the XDG default) get
 // the same FileToken behavior with periodic re-reads, so an external
 // refresher rotating the file works regardless of how the path was supplied.
 // hasMTLS reports whether a client certificate is configured — in which
 // case the transport carries the credential and no bearer token is needed.
 func ResolveTokenSource(flagToken, flagTokenPath, flagTokenScript string, hasMTLS bool) (client.TokenSource, error) {
+	if flagToken != "" && (flagTokenPath != "" || flagTokenScript != "") {
+		return nil, errors.New("--auth-token cannot be combined with --auth-token-path or --auth-token-script")
+	}
+
 	switch {
 	case flagTokenScript != "":
 		return client.ScriptToken(flagTokenScript), nil
@@ -94,21 +115,75 @@ type CommonFlags struct {
 	AuthTokenScript *string
 	Debug           *bool
 	Help            *bool
+	Config          *string
+	Log             LogFlags
+
+	serverURLs *serverURLFlag
+}
+
+// serverURLFlag is --server-url. Commands talking to one server use the
+// last value, stored in CommonFlags.ServerURL; push uploads to all of them.
+type serverURLFlag struct {
+	last     *string
+	urls     []string
+	defaults bool // urls came from the config file; the command line replaces them
+}
+
+func (f *serverURLFlag) String() string {
+	if f == nil || f.last == nil {
+		return ""
+	}
+
+	return *f.last
+}
+
+func (f *serverURLFlag) Set(s string) error {
+	if f.defaults {
+		f.urls, f.defaults = nil, false
+	}
+
+	*f.last = s
+	f.urls = append(f.urls, s)
+
+	return nil
+}
+
+func (f *serverURLFlag) configApplied() { f.defaults = true }
+
+// ServerURLs returns every --server-url value, or the NIKS3_SERVER_URL
+// default if none was given.
+func (cf CommonFlags) ServerURLs() []string {
+	if len(cf.serverURLs.urls) > 0 {
+		return cf.serverURLs.urls
+	}
+
+	if *cf.ServerURL == "" {
+		return nil
+	}
+
+	return []string{*cf.ServerURL}
 }
 
-// AddCommonFlags registers --server-url, the auth flags, --debug, and
-// -h/--help on the given FlagSet and returns pointers to them.
+// AddCommonFlags registers --server-url, the auth flags, --debug, the
+// logging flags, --config, and -h/--help on the given FlagSet and returns
+// pointers to them.
 func AddCommonFlags(fs *flag.FlagSet) CommonFlags {
 	fs.Usage = func() {} // Suppress default usage; each command prints its own.
+	serverURL := os.Getenv("NIKS3_SERVER_URL")
 	cf := CommonFlags{
-		ServerURL:       fs.String("server-url", os.Getenv("NIKS3_SERVER_URL"), "Server URL"),
+		ServerURL:       &serverURL,
 		AuthToken:       fs.String("auth-token", "", "Auth token (deprecated)"),
 		AuthTokenPath:   fs.String("auth-token-path", "", "Path to auth token file"),
 		AuthTokenScript: fs.String("auth-token-script", "", "Command that emits a token JSON document"),
 		Debug:           fs.Bool("debug", false, "Enable debug logging"),
 		Help:            fs.Bool("help", false, "Show help"),
+		Config:          fs.String("config", "", "Config file with flag defaults"),
+		Log:             addLogFlags(fs),
+		serverURLs:      &serverURLFlag{last: &serverURL},
 	}
+	fs.Var(cf.serverURLs, "server-url", "Server URL (push accepts several)")
 	fs.BoolVar(cf.Help, "h", false, "Show help")
+	fs.StringVar(cf.AuthTokenPath, "auth-token-file", "", "Alias for --auth-token-path")
 
 	return cf
 }
@@ -130,6 +205,139 @@ func (cf CommonFlags) TokenSource(fs *flag.FlagSet, tf TLSFlags) (client.TokenSo
 	return ResolveTokenSource(*cf.AuthToken, *cf.AuthTokenPath, *cf.AuthTokenScript, hasMTLS)
 }
 
+// ReadStorePaths reads whitespace-separated store paths, e.g. a post-build
+// hook's $OUT_PATHS piped to stdin. Blank lines and everything after a '#'
+// are ignored.
+func ReadStorePaths(r io.Reader) ([]string, error) {
+	var paths []string
+
+	scanner := bufio.NewScanner(r)
+	for scanner.Scan() {
+		line, _, _ := strings.Cut(scanner.Text(), "#")
+		paths = append(paths, strings.Fields(line)...)
+	}
+
+	if err := scanner.Err(); err != nil {
+		return nil, fmt.Errorf("reading store paths: %w", err)
+	}
+
+	return paths, nil
+}
+
+// ExpandPathFiles replaces each "@file" argument with the store paths listed
+// in file, one per line, keeping the other arguments as they are. As with
+// ReadStorePaths, blank lines and everything after a '#' are ignored; a line
+// holding more than one path is an error naming the file and line.
+func ExpandPathFiles(args []string) ([]string, error) {
+	paths := make([]string, 0, len(args))
+
+	for _, arg := range args {
+		file, ok := strings.CutPrefix(arg, "@")
+		if !ok {
+			paths = append(paths, arg)
+
+			continue
+		}
+
+		listed, err := readPathFile(file)
+		if err != nil {
+			return nil, err
+		}
+
+		paths = append(paths, listed...)
+	}
+
+	return paths, nil
+}
+
+// readPathFile reads the store paths of an "@file" argument.
+func readPathFile(file string) ([]string, error) {
+	if file == "" {
+		return nil, errors.New("@ must be followed by a file listing store paths")
+	}
+
+	f, err := os.Open(file)
+	if err != nil {
+		return nil, fmt.Errorf("reading store paths from @%s: %w", file, err)
+	}
+	defer func() { _ = f.Close() }()
+
+	var (
+		paths   []string
+		lineErr []error
+		lineNo  int
+	)
+
+	scanner := bufio.NewScanner(f)
+	for scanner.Scan() {
+		lineNo++
+
+		line, _, _ := strings.Cut(scanner.Text(), "#")
+
+		switch fields := strings.Fields(line); len(fields) {
+		case 0:
+		case 1:
+			paths = append(paths, fields[0])
+		default:
+			lineErr = append(lineErr, fmt.Errorf("%s:%d: expected one store path per line, got %d", file, lineNo, len(fields)))
+		}
+	}
+
+	if err := scanner.Err(); err != nil {
+		return nil, fmt.Errorf("reading store paths from @%s: %w", file, err)
+	}
+
+	if len(lineErr) > 0 {
+		return nil, errors.Join(lineErr...)
+	}
+
+	return paths, nil
+}
+
+// concurrencyFlag is an int flag that also accepts "auto", stored as 0.
+type concurrencyFlag struct{ n *int }
+
+func (f concurrencyFlag) String() string {
+	switch {
+	case f.n == nil: // the zero value flag.PrintDefaults compares against
+		return ""
+	case *f.n == 0:
+		return "auto"
+	default:
+		return strconv.Itoa(*f.n)
+	}
+}
+
+func (f concurrencyFlag) Set(s string) error {
+	if s == "auto" {
+		*f.n = 0
+
+		return nil
+	}
+
+	n, err := strconv.Atoi(s)
+	if err != nil {
+		return fmt.Errorf("expected a number or auto: %w", err)
+	}
+
+	if n < 0 {
+		return errors.New("must not be negative")
+	}
+
+	*f.n = n
+
+	return nil
+}
+
+// ConcurrencyFlag registers an int flag for which "auto" and 0 both select
+// client.AutoConcurrency.
+func ConcurrencyFlag(fs *flag.FlagSet, name string, value int, usage string) *int {
+	n := &value
+	fs.Var(concurrencyFlag{n: n}, name, usage)
+
+	return n
+}
+
 // RequireServerURL returns an error if the URL is empty.
 func RequireServerURL(url string) error {
 	if url == "" {
@@ -147,8 +355,9 @@ const AuthTokenHelp = `  --auth-token string
         When unset, falls back to NIKS3_AUTH_TOKEN_FILE or $XDG_CONFIG_HOME/niks3/auth-token`
 
 //nolint:gosec // G101: help text, not credentials
-const AuthTokenPathHelp = `  --auth-token-path string
-        Path to file containing the auth token (preferred over --auth-token).
+const AuthTokenPathHelp = `  --auth-token-path, --auth-token-file string
+        Path to file containing the auth token, surrounding whitespace is
+        trimmed (preferred over --auth-token, and mutually exclusive with it).
         Re-read periodically so an external refresher can rotate it`
 
 //nolint:gosec // G101: help text, not credentials
@@ -161,43 +370,110 @@ const TLSHelp = `  --client-cert string
   --client-key string
         Client private key file for mTLS authentication
   --ca-cert string
-        CA certificate file for server verification (optional)`
-
-// TLSFlags holds pointers to the mTLS-related flags shared across subcommands.
+        PEM file of CA certificates to trust in addition to the system ones,
+        e.g. the internal CA of a proxy (alias: --cacert)
+  --proxy string
+        Proxy URL for requests to the server and S3, e.g. http://proxy:3128
+        (default: $HTTPS_PROXY / $HTTP_PROXY, excluding $NO_PROXY hosts)
+  --insecure
+        Skip verification of TLS certificates. For testing only
+  --user-agent-suffix string
+        Appended to the "niks3/<version>" User-Agent, e.g. to tag CI pushes
+        (can also use NIKS3_USER_AGENT_SUFFIX env var)`
+
+// TLSFlags holds pointers to the TLS and proxy flags shared across subcommands.
 type TLSFlags struct {
 	ClientCert *string
 	ClientKey  *string
 	CACert     *string
+	Proxy      *string
+	Insecure   *bool
+	UserAgent  *string
 }
 
-// AddTLSFlags registers --client-cert, --client-key, and --ca-cert on the
-// given FlagSet and returns pointers to them.
+// AddTLSFlags registers --client-cert, --client-key, --ca-cert (alias
+// --cacert), --proxy, --insecure, and --user-agent-suffix on the given
+// FlagSet and returns pointers to them.
 func AddTLSFlags(fs *flag.FlagSet) TLSFlags {
-	return TLSFlags{
+	tf := TLSFlags{
 		ClientCert: fs.String("client-cert", "", "Client certificate file for mTLS"),
 		ClientKey:  fs.String("client-key", "", "Client private key file for mTLS"),
-		CACert:     fs.String("ca-cert", "", "CA certificate file for server verification (optional)"),
+		CACert:     fs.String("ca-cert", "", "CA certificates to trust in addition to the system ones"),
+		Proxy:      fs.String("proxy", "", "Proxy URL (default: $HTTPS_PROXY / $HTTP_PROXY)"),
+		Insecure:   fs.Bool("insecure", false, "Skip TLS certificate verification (testing only)"),
+		UserAgent:  fs.String("user-agent-suffix", os.Getenv("NIKS3_USER_AGENT_SUFFIX"), "Appended to the User-Agent header"),
 	}
+	fs.StringVar(tf.CACert, "cacert", "", "Alias for --ca-cert")
+
+	return tf
 }
 
-// Configure sets up mTLS on the client when a certificate/key pair is
-// supplied. It is a no-op when neither is set and errors when only one is set.
+// Configure sets up mTLS, extra CAs, the proxy, certificate verification,
+// and the User-Agent suffix on the client. Unset flags leave the defaults alone; it errors when only
+// one of --client-cert and --client-key is set or a file cannot be parsed.
 func (tf TLSFlags) Configure(c *client.Client) error {
 	certFile, keyFile, caFile := *tf.ClientCert, *tf.ClientKey, *tf.CACert
 
-	if certFile == "" && keyFile == "" && caFile == "" {
-		return nil
-	}
-
 	if (certFile == "") != (keyFile == "") {
 		return errors.New("both --client-cert and --client-key must be provided for mTLS")
 	}
 
-	slog.Info("Configuring client TLS", "cert", certFile, "key", keyFile, "ca", caFile)
+	if certFile != "" || caFile != "" {
+		slog.Info("Configuring client TLS", "cert", certFile, "key", keyFile, "ca", caFile)
 
-	if err := c.SetClientTLS(certFile, keyFile, caFile); err != nil {
-		return fmt.Errorf("setting up client TLS: %w", err)
+		if err := c.SetClientTLS(certFile, keyFile, caFile); err != nil {
+			return fmt.Errorf("setting up client TLS: %w", err)
+		}
 	}
 
+	if *tf.Proxy != "" {
+		if err := c.SetProxy(*tf.Proxy); err != nil {
+			return fmt.Errorf("setting up proxy: %w", err)
+		}
+	}
+
+	if *tf.Insecure {
+		slog.Warn("TLS certificate verification is disabled (--insecure)")
+		c.SetInsecureSkipVerify()
+	}
+
+	c.SetUserAgentSuffix(*tf.UserAgent)
+
 	return nil
 }
+
+const TimeoutHelp = `  --timeout duration
+        Timeout for each server API call, including reading the response
+        (default 5m, 0 = none)
+  --transfer-timeout duration
+        Timeout for each NAR, log or listing upload or download. Multi-GB NARs
+        on slow links can take hours (default 0 = none)
+  --connect-timeout duration
+        Timeout for connecting to the server or S3, including the TLS
+        handshake (default 30s)`
+
+// TimeoutFlags holds pointers to the HTTP timeout flags shared across
+// subcommands.
+type TimeoutFlags struct {
+	Timeout         *time.Duration
+	TransferTimeout *time.Duration
+	ConnectTimeout  *time.Duration
+}
+
+// AddTimeoutFlags registers --timeout, --transfer-timeout, and
+// --connect-timeout on the given FlagSet and returns pointers to them.
+func AddTimeoutFlags(fs *flag.FlagSet) TimeoutFlags {
+	return TimeoutFlags{
+		Timeout:         fs.Duration("timeout", 5*time.Minute, "Timeout for each server API call (0 = none)"),
+		TransferTimeout: fs.Duration("transfer-timeout", 0, "Timeout for each NAR, log or listing transfer (0 = none)"),
+		ConnectTimeout:  fs.Duration("connect-timeout", 30*time.Second, "Timeout for connecting, including the TLS handshake"),
+	}
+}
+
+// Configure applies the timeouts to the client. Timeouts apply to each
+// attempt separately, so a timed-out request is still retried.
+func (tf TimeoutFlags) Configure(c *client.Client) {
+	c.RequestTimeout = *tf.Timeout
+	c.TransferTimeout = *tf.TransferTimeout
+	c.SetConnectTimeout(*tf.ConnectTimeout)
+}
@@ -0,0 +1,47 @@
+package cmdutil_test
+
+import (
+	"os"
+	"path/filepath"
+	"slices"
+	"strings"
+	"testing"
+
+	"github.com/Mic92/niks3/cmdutil"
+)
+
+func TestExpandPathFiles(t *testing.T) {
+	t.Parallel()
+
+	dir := t.TempDir()
+
+	list := filepath.Join(dir, "paths.txt")
+	content := "# built by CI\n/nix/store/aaaa-foo\n\n  /nix/store/bbbb-bar  # runtime dep\n"
+
+	if err := os.WriteFile(list, []byte(content), 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	got, err := cmdutil.ExpandPathFiles([]string{"/nix/store/cccc-baz", "@" + list, "./result"})
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	want := []string{"/nix/store/cccc-baz", "/nix/store/aaaa-foo", "/nix/store/bbbb-bar", "./result"}
+	if !slices.Equal(got, want) {
+		t.Errorf("got %v, want %v", got, want)
+	}
+
+	bad := filepath.Join(dir, "bad.txt")
+	if err := os.WriteFile(bad, []byte("/nix/store/aaaa-foo\n/nix/store/bbbb-bar /nix/store/cccc-baz\n"), 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	if _, err := cmdutil.ExpandPathFiles([]string{"@" + bad}); err == nil || !strings.Contains(err.Error(), bad+":2:") {
+		t.Errorf("expected an error for line 2, got %v", err)
+	}
+
+	if _, err := cmdutil.ExpandPathFiles([]string{"@" + filepath.Join(dir, "missing.txt")}); err == nil {
+		t.Error("expected an error for a missing file")
+	}
+}
@@ -0,0 +1,319 @@
+package cmdutil
+
+import (
+	"bufio"
+	"errors"
+	"flag"
+	"fmt"
+	"io"
+	"os"
+	"path/filepath"
+	"strconv"
+	"strings"
+)
+
+// ConfigHelp documents --config for command help texts.
+const ConfigHelp = `  --config string
+        TOML file with defaults for any flag, keyed by flag name: top-level keys
+        apply to every command that has the flag, keys under [push], [pull], ...
+        to that command only. Command-line flags override environment variables,
+        which override the file (default: $XDG_CONFIG_HOME/niks3/config.toml)`
+
+// configEnv maps flags whose defaults come from the environment to their
+// variable. A set variable takes precedence over the config file.
+//
+//nolint:gochecknoglobals // constant lookup table
+var configEnv = map[string]string{
+	"server-url":        "NIKS3_SERVER_URL",
+	"auth-token-path":   "NIKS3_AUTH_TOKEN_FILE",
+	"auth-token-file":   "NIKS3_AUTH_TOKEN_FILE",
+	"user-agent-suffix": "NIKS3_USER_AGENT_SUFFIX",
+}
+
+// configEntry is one `key = value` line of a config file. Arrays hold one
+// value per element, each passed to the flag in turn.
+type configEntry struct {
+	section string
+	key     string
+	values  []string
+	line    int
+}
+
+// DefaultConfigPath returns $XDG_CONFIG_HOME/niks3/config.toml, or "" if
+// the home directory is unknown.
+func DefaultConfigPath() string {
+	dir := configDir()
+	if dir == "" {
+		return ""
+	}
+
+	return filepath.Join(dir, "config.toml")
+}
+
+// ParseFlags parses args into fs after filling in defaults from the config
+// file named by --config in args, or DefaultConfigPath if that exists. The
+// resulting precedence is: command line, environment, config file, built-in
+// default.
+func ParseFlags(fs *flag.FlagSet, args []string) error {
+	path, explicit := configPathFromArgs(args)
+	if !explicit {
+		path = DefaultConfigPath()
+	}
+
+	if path != "" {
+		entries, err := readConfig(path)
+		if errors.Is(err, os.ErrNotExist) && !explicit {
+			entries, err = nil, nil
+		}
+
+		if err != nil {
+			return err
+		}
+
+		if err := applyConfig(fs, path, entries); err != nil {
+			return err
+		}
+	}
+
+	// Repeatable flags start over on the command line instead of adding to
+	// the config file's values.
+	fs.VisitAll(func(f *flag.Flag) {
+		if v, ok := f.Value.(interface{ configApplied() }); ok {
+			v.configApplied()
+		}
+	})
+
+	return fs.Parse(args) //nolint:wrapcheck // callers check for flag.ErrHelp and wrap
+}
+
+// configPathFromArgs finds --config in args before flags are parsed, as the
+// file has to be applied first so that flags override it.
+func configPathFromArgs(args []string) (string, bool) {
+	for i, arg := range args {
+		if arg == "--" {
+			break
+		}
+
+		name, value, hasValue := strings.Cut(strings.TrimLeft(arg, "-"), "=")
+		if !strings.HasPrefix(arg, "-") || name != "config" {
+			continue
+		}
+
+		if hasValue {
+			return value, true
+		}
+
+		if i+1 < len(args) {
+			return args[i+1], true
+		}
+	}
+
+	return "", false
+}
+
+func readConfig(path string) ([]configEntry, error) {
+	f, err := os.Open(path)
+	if err != nil {
+		return nil, fmt.Errorf("opening config: %w", err)
+	}
+
+	defer func() { _ = f.Close() }()
+
+	return parseConfig(f, path)
+}
+
+// parseConfig reads the subset of TOML that flag defaults need: bare keys,
+// [section] headers, and strings, numbers, booleans and single-line arrays
+// of them as values.
+func parseConfig(r io.Reader, path string) ([]configEntry, error) {
+	var (
+		entries []configEntry
+		section string
+		lineNo  int
+	)
+
+	scanner := bufio.NewScanner(r)
+	for scanner.Scan() {
+		lineNo++
+
+		line := strings.TrimSpace(stripConfigComment(scanner.Text()))
+		if line == "" {
+			continue
+		}
+
+		if strings.HasPrefix(line, "[") {
+			name, ok := strings.CutSuffix(strings.TrimPrefix(line, "["), "]")
+			if !ok || !validConfigKey(name) {
+				return nil, fmt.Errorf("%s:%d: invalid section header %q", path, lineNo, line)
+			}
+
+			section = name
+
+			continue
+		}
+
+		key, raw, ok := strings.Cut(line, "=")
+		key = strings.TrimSpace(key)
+
+		if !ok || !validConfigKey(key) {
+			return nil, fmt.Errorf("%s:%d: expected key = value", path, lineNo)
+		}
+
+		values, err := parseConfigValue(strings.TrimSpace(raw))
+		if err != nil {
+			return nil, fmt.Errorf("%s:%d: %s: %w", path, lineNo, key, err)
+		}
+
+		entries = append(entries, configEntry{section: section, key: key, values: values, line: lineNo})
+	}
+
+	if err := scanner.Err(); err != nil {
+		return nil, fmt.Errorf("reading config: %w", err)
+	}
+
+	return entries, nil
+}
+
+func validConfigKey(key string) bool {
+	return key != "" && strings.Trim(key, "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_-") == ""
+}
+
+// stripConfigComment cuts a line at the first '#' outside a string.
+func stripConfigComment(line string) string {
+	var quote byte
+
+	for i := 0; i < len(line); i++ {
+		switch c := line[i]; {
+		case quote == '"' && c == '\\':
+			i++
+		case quote != 0 && c == quote:
+			quote = 0
+		case quote == 0 && (c == '"' || c == '\''):
+			quote = c
+		case quote == 0 && c == '#':
+			return line[:i]
+		}
+	}
+
+	return line
+}
+
+func parseConfigValue(raw string) ([]string, error) {
+	inner, isArray := strings.CutPrefix(raw, "[")
+	if !isArray {
+		value, err := parseConfigScalar(raw)
+		if err != nil {
+			return nil, err
+		}
+
+		return []string{value}, nil
+	}
+
+	inner, ok := strings.CutSuffix(inner, "]")
+	if !ok {
+		return nil, errors.New("arrays must be closed on the same line")
+	}
+
+	var values []string
+
+	for _, elem := range splitConfigArray(inner) {
+		if elem = strings.TrimSpace(elem); elem == "" {
+			continue
+		}
+
+		value, err := parseConfigScalar(elem)
+		if err != nil {
+			return nil, err
+		}
+
+		values = append(values, value)
+	}
+
+	return values, nil
+}
+
+// splitConfigArray splits array elements at commas outside strings.
+func splitConfigArray(s string) []string {
+	var (
+		elems []string
+		quote byte
+		start int
+	)
+
+	for i := 0; i < len(s); i++ {
+		switch c := s[i]; {
+		case quote == '"' && c == '\\':
+			i++
+		case quote != 0 && c == quote:
+			quote = 0
+		case quote == 0 && (c == '"' || c == '\''):
+			quote = c
+		case quote == 0 && c == ',':
+			elems = append(elems, s[start:i])
+			start = i + 1
+		}
+	}
+
+	return append(elems, s[start:])
+}
+
+func parseConfigScalar(s string) (string, error) {
+	switch {
+	case len(s) >= 2 && s[0] == '"' && s[len(s)-1] == '"':
+		value, err := strconv.Unquote(s)
+		if err != nil {
+			return "", fmt.Errorf("invalid string %s: %w", s, err)
+		}
+
+		return value, nil
+	case len(s) >= 2 && s[0] == '\'' && s[len(s)-1] == '\'':
+		return s[1 : len(s)-1], nil
+	case s == "true" || s == "false":
+		return s, nil
+	}
+
+	number := strings.ReplaceAll(s, "_", "")
+	if _, err := strconv.ParseFloat(number, 64); err == nil && s != "" {
+		return number, nil
+	}
+
+	return "", fmt.Errorf("unsupported value %q, quote strings", s)
+}
+
+// applyConfig sets the flags of fs named in entries. Top-level keys are
+// shared by all commands, so ones fs does not have are skipped; keys in the
+// section named after fs must be flags of it.
+func applyConfig(fs *flag.FlagSet, path string, entries []configEntry) error {
+	for _, entry := range entries {
+		if entry.section != "" && entry.section != fs.Name() {
+			continue
+		}
+
+		name := strings.ReplaceAll(entry.key, "_", "-")
+
+		switch name {
+		case "config", "help", "h":
+			return fmt.Errorf("%s:%d: %q cannot be set in a config file", path, entry.line, entry.key)
+		}
+
+		if fs.Lookup(name) == nil {
+			if entry.section == "" {
+				continue
+			}
+
+			return fmt.Errorf("%s:%d: unknown %s option %q", path, entry.line, entry.section, entry.key)
+		}
+
+		if env := configEnv[name]; env != "" && os.Getenv(env) != "" {
+			continue
+		}
+
+		for _, value := range entry.values {
+			if err := fs.Set(name, value); err != nil {
+				return fmt.Errorf("%s:%d: %s: %w", path, entry.line, entry.key, err)
+			}
+		}
+	}
+
+	return nil
+}
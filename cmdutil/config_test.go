@@ -0,0 +1,158 @@
+package cmdutil_test
+
+import (
+	"flag"
+	"os"
+	"path/filepath"
+	"slices"
+	"strings"
+	"testing"
+
+	"github.com/Mic92/niks3/cmdutil"
+)
+
+func writeConfig(t *testing.T, content string) string {
+	t.Helper()
+
+	path := filepath.Join(t.TempDir(), "config.toml")
+	if err := os.WriteFile(path, []byte(content), 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	return path
+}
+
+func TestParseFlagsConfigPrecedence(t *testing.T) {
+	t.Setenv("NIKS3_SERVER_URL", "")
+	t.Setenv("NIKS3_USER_AGENT_SUFFIX", "from-env")
+
+	path := writeConfig(t, `
+# defaults for every command
+server-url = "https://cache.example.com" # trailing comment
+user-agent-suffix = "from-file"
+max_concurrent_uploads = 1_6
+
+[push]
+compression = 'none'
+exclude = ["/nix/store/a", "/nix/store/b",]
+
+[pull]
+max-concurrent-downloads = 4
+`)
+
+	fs := flag.NewFlagSet("push", flag.ContinueOnError)
+	cf := cmdutil.AddCommonFlags(fs)
+	tf := cmdutil.AddTLSFlags(fs)
+	uploads := fs.Int("max-concurrent-uploads", 30, "")
+	compression := fs.String("compression", "zstd", "")
+
+	var exclude []string
+
+	fs.Func("exclude", "", func(s string) error {
+		exclude = append(exclude, s)
+
+		return nil
+	})
+
+	if err := cmdutil.ParseFlags(fs, []string{"--config", path, "--compression", "zstd", "/nix/store/c"}); err != nil {
+		t.Fatal(err)
+	}
+
+	if *cf.ServerURL != "https://cache.example.com" {
+		t.Errorf("server URL = %q, want the file's", *cf.ServerURL)
+	}
+
+	if *tf.UserAgent != "from-env" {
+		t.Errorf("user agent suffix = %q, want the environment's", *tf.UserAgent)
+	}
+
+	if *uploads != 16 {
+		t.Errorf("max concurrent uploads = %d, want 16", *uploads)
+	}
+
+	if *compression != "zstd" {
+		t.Errorf("compression = %q, want the command line's", *compression)
+	}
+
+	if !slices.Equal(exclude, []string{"/nix/store/a", "/nix/store/b"}) {
+		t.Errorf("exclude = %v", exclude)
+	}
+
+	if !slices.Equal(fs.Args(), []string{"/nix/store/c"}) {
+		t.Errorf("args = %v", fs.Args())
+	}
+}
+
+func TestParseFlagsConfigErrors(t *testing.T) {
+	t.Parallel()
+
+	tests := []struct {
+		name    string
+		content string
+		want    string
+	}{
+		{"unknown section key", "[push]\nno-such-flag = true\n", `unknown push option "no-such-flag"`},
+		{"unquoted string", "compression = zstd\n", "quote strings"},
+		{"multi-line array", "exclude = [\n", "same line"},
+		{"bad value", "max-concurrent-uploads = 1.5\n", "max-concurrent-uploads"},
+	}
+
+	for _, tt := range tests {
+		t.Run(tt.name, func(t *testing.T) {
+			t.Parallel()
+
+			fs := flag.NewFlagSet("push", flag.ContinueOnError)
+			cmdutil.AddCommonFlags(fs)
+			fs.Int("max-concurrent-uploads", 30, "")
+			fs.String("compression", "zstd", "")
+
+			err := cmdutil.ParseFlags(fs, []string{"--config=" + writeConfig(t, tt.content)})
+			if err == nil || !strings.Contains(err.Error(), tt.want) {
+				t.Errorf("got error %v, want one containing %q", err, tt.want)
+			}
+		})
+	}
+}
+
+func TestParseFlagsMissingExplicitConfig(t *testing.T) {
+	t.Parallel()
+
+	fs := flag.NewFlagSet("push", flag.ContinueOnError)
+	cmdutil.AddCommonFlags(fs)
+
+	if err := cmdutil.ParseFlags(fs, []string{"--config", filepath.Join(t.TempDir(), "missing.toml")}); err == nil {
+		t.Error("expected an error for a missing --config file")
+	}
+}
+
+func TestServerURLsReplaceConfigFile(t *testing.T) {
+	t.Setenv("NIKS3_SERVER_URL", "")
+
+	path := writeConfig(t, `server-url = ["https://primary.example.com", "https://mirror.example.com"]`+"\n")
+
+	fs := flag.NewFlagSet("push", flag.ContinueOnError)
+	cf := cmdutil.AddCommonFlags(fs)
+
+	if err := cmdutil.ParseFlags(fs, []string{"--config", path}); err != nil {
+		t.Fatal(err)
+	}
+
+	if got := cf.ServerURLs(); !slices.Equal(got, []string{"https://primary.example.com", "https://mirror.example.com"}) {
+		t.Errorf("server URLs from the config file = %v", got)
+	}
+
+	fs = flag.NewFlagSet("push", flag.ContinueOnError)
+	cf = cmdutil.AddCommonFlags(fs)
+
+	if err := cmdutil.ParseFlags(fs, []string{"--config", path, "--server-url", "https://a.example.com", "--server-url", "https://b.example.com"}); err != nil {
+		t.Fatal(err)
+	}
+
+	if got := cf.ServerURLs(); !slices.Equal(got, []string{"https://a.example.com", "https://b.example.com"}) {
+		t.Errorf("server URLs from the command line = %v", got)
+	}
+
+	if *cf.ServerURL != "https://b.example.com" {
+		t.Errorf("ServerURL = %q, want the last --server-url", *cf.ServerURL)
+	}
+}
@@ -0,0 +1,299 @@
+package cmdutil
+
+import (
+	"context"
+	"errors"
+	"flag"
+	"fmt"
+	"io"
+	"log/slog"
+	"os"
+	"slices"
+	"strconv"
+	"strings"
+	"sync"
+)
+
+// Log formats accepted by --log-format.
+const (
+	LogFormatPretty  = "pretty"  // Aligned, optionally colored lines for humans
+	LogFormatCompact = "compact" // slog's key=value text format
+	LogFormatJSON    = "json"    // One JSON object per line, for log pipelines
+)
+
+// Color modes accepted by --color.
+const (
+	ColorAuto   = "auto"
+	ColorAlways = "always"
+	ColorNever  = "never"
+)
+
+// LogFlags holds the logging flags registered by AddCommonFlags.
+type LogFlags struct {
+	Format    *string
+	Color     *string
+	Verbosity *int  // Number of -v flags; -vv counts twice
+	Quiet     *bool // -q/--quiet
+}
+
+// LogHelp documents the logging flags for command help texts.
+const LogHelp = `  -v, -vv
+        More log output: -v enables debug logging, -vv also logs HTTP
+        requests/responses like --debug
+  --quiet, -q
+        Only log warnings and errors
+  --log-format string
+        Log format: pretty, compact (key=value) or json (default: compact)
+  --color string
+        Color pretty logs: auto, always or never (default: auto, colors when
+        stderr is a terminal and $NO_COLOR is unset)`
+
+// verbosityFlag is a boolean flag that adds step to count each time it is
+// given, so `-v -v` and `-vv` both count as two.
+type verbosityFlag struct {
+	count *int
+	step  int
+}
+
+func (v verbosityFlag) String() string {
+	if v.count == nil {
+		return "0"
+	}
+
+	return strconv.Itoa(*v.count)
+}
+
+func (v verbosityFlag) Set(s string) error {
+	on, err := strconv.ParseBool(s)
+	if err != nil {
+		return fmt.Errorf("parsing verbosity: %w", err)
+	}
+
+	if on {
+		*v.count += v.step
+	}
+
+	return nil
+}
+
+func (v verbosityFlag) IsBoolFlag() bool { return true }
+
+// addLogFlags registers -v/-vv, -q/--quiet, --log-format and --color.
+func addLogFlags(fs *flag.FlagSet) LogFlags {
+	lf := LogFlags{
+		Format:    fs.String("log-format", LogFormatCompact, "Log format: pretty, compact or json"),
+		Color:     fs.String("color", ColorAuto, "Color pretty logs: auto, always or never"),
+		Verbosity: new(int),
+		Quiet:     fs.Bool("quiet", false, "Only log warnings and errors"),
+	}
+	fs.Var(verbosityFlag{count: lf.Verbosity, step: 1}, "v", "Enable debug logging")
+	fs.Var(verbosityFlag{count: lf.Verbosity, step: 2}, "vv", "Enable debug logging including HTTP requests/responses")
+	fs.BoolVar(lf.Quiet, "q", false, "Only log warnings and errors")
+
+	return lf
+}
+
+// SetupLogger configures the global slog logger from the logging flags.
+// -vv implies --debug, so it must run before *cf.Debug is read.
+func (cf CommonFlags) SetupLogger() error {
+	verbosity := *cf.Log.Verbosity
+	if *cf.Debug {
+		verbosity = max(verbosity, 2)
+	}
+
+	if *cf.Log.Quiet && verbosity > 0 {
+		return errors.New("-q cannot be combined with -v, -vv or --debug")
+	}
+
+	level := slog.LevelInfo
+
+	switch {
+	case *cf.Log.Quiet:
+		level = slog.LevelWarn
+	case verbosity >= 2:
+		level = slog.LevelDebug
+		*cf.Debug = true
+	case verbosity == 1:
+		level = slog.LevelDebug
+	}
+
+	handler, err := NewLogHandler(os.Stderr, *cf.Log.Format, *cf.Log.Color, level)
+	if err != nil {
+		return err
+	}
+
+	slog.SetDefault(slog.New(handler))
+
+	return nil
+}
+
+// NewLogHandler returns the slog handler for a --log-format and --color
+// combination writing to w.
+func NewLogHandler(w io.Writer, format, color string, level slog.Level) (slog.Handler, error) {
+	if !slices.Contains([]string{ColorAuto, ColorAlways, ColorNever}, color) {
+		return nil, fmt.Errorf("unknown --color %q, expected auto, always or never", color)
+	}
+
+	opts := &slog.HandlerOptions{Level: level}
+
+	switch format {
+	case LogFormatPretty:
+		return &prettyHandler{w: w, mu: &sync.Mutex{}, level: level, color: useColor(w, color)}, nil
+	case LogFormatCompact:
+		return slog.NewTextHandler(w, opts), nil
+	case LogFormatJSON:
+		return slog.NewJSONHandler(w, opts), nil
+	default:
+		return nil, fmt.Errorf("unknown --log-format %q, expected pretty, compact or json", format)
+	}
+}
+
+// useColor resolves --color auto: color when w is a terminal, $NO_COLOR is
+// unset and $TERM is not "dumb".
+func useColor(w io.Writer, color string) bool {
+	switch color {
+	case ColorAlways:
+		return true
+	case ColorNever:
+		return false
+	}
+
+	if os.Getenv("NO_COLOR") != "" || os.Getenv("TERM") == "dumb" {
+		return false
+	}
+
+	f, ok := w.(*os.File)
+	if !ok {
+		return false
+	}
+
+	fi, err := f.Stat()
+
+	return err == nil && fi.Mode()&os.ModeCharDevice != 0
+}
+
+const (
+	ansiReset = "\x1b[0m"
+	ansiFaint = "\x1b[2m"
+)
+
+// prettyHandler writes one line per record: time, level, message and the
+// attributes as key=value, with the level and keys colored on terminals.
+type prettyHandler struct {
+	w     io.Writer
+	mu    *sync.Mutex // Shared by handlers derived via WithAttrs/WithGroup
+	level slog.Level
+	color bool
+	attrs []byte // Attributes from WithAttrs, already formatted
+	group string // Key prefix from WithGroup, e.g. "upload."
+}
+
+func (h *prettyHandler) Enabled(_ context.Context, level slog.Level) bool {
+	return level >= h.level
+}
+
+func (h *prettyHandler) Handle(_ context.Context, r slog.Record) error {
+	buf := make([]byte, 0, 256)
+
+	if !r.Time.IsZero() {
+		buf = h.appendColored(buf, ansiFaint, r.Time.Format("15:04:05"))
+		buf = append(buf, ' ')
+	}
+
+	buf = h.appendColored(buf, levelColor(r.Level), fmt.Sprintf("%-5s", r.Level.String()))
+	buf = append(buf, ' ')
+	buf = append(buf, r.Message...)
+	buf = append(buf, h.attrs...)
+
+	r.Attrs(func(a slog.Attr) bool {
+		buf = h.appendAttr(buf, h.group, a)
+
+		return true
+	})
+
+	buf = append(buf, '\n')
+
+	h.mu.Lock()
+	defer h.mu.Unlock()
+
+	if _, err := h.w.Write(buf); err != nil {
+		return fmt.Errorf("writing log record: %w", err)
+	}
+
+	return nil
+}
+
+func (h *prettyHandler) WithAttrs(attrs []slog.Attr) slog.Handler {
+	clone := *h
+	clone.attrs = slices.Clone(h.attrs)
+
+	for _, a := range attrs {
+		clone.attrs = h.appendAttr(clone.attrs, h.group, a)
+	}
+
+	return &clone
+}
+
+func (h *prettyHandler) WithGroup(name string) slog.Handler {
+	if name == "" {
+		return h
+	}
+
+	clone := *h
+	clone.group = h.group + name + "."
+
+	return &clone
+}
+
+func (h *prettyHandler) appendAttr(buf []byte, prefix string, a slog.Attr) []byte {
+	a.Value = a.Value.Resolve()
+	if a.Equal(slog.Attr{}) {
+		return buf
+	}
+
+	if a.Value.Kind() == slog.KindGroup {
+		if a.Key != "" {
+			prefix += a.Key + "."
+		}
+
+		for _, ga := range a.Value.Group() {
+			buf = h.appendAttr(buf, prefix, ga)
+		}
+
+		return buf
+	}
+
+	buf = append(buf, ' ')
+	buf = h.appendColored(buf, ansiFaint, prefix+a.Key+"=")
+
+	value := a.Value.String()
+	if value == "" || strings.ContainsAny(value, " =\"\t\n") {
+		value = strconv.Quote(value)
+	}
+
+	return append(buf, value...)
+}
+
+func (h *prettyHandler) appendColored(buf []byte, color, s string) []byte {
+	if !h.color {
+		return append(buf, s...)
+	}
+
+	buf = append(buf, color...)
+	buf = append(buf, s...)
+
+	return append(buf, ansiReset...)
+}
+
+func levelColor(level slog.Level) string {
+	switch {
+	case level >= slog.LevelError:
+		return "\x1b[31m" // red
+	case level >= slog.LevelWarn:
+		return "\x1b[33m" // yellow
+	case level >= slog.LevelInfo:
+		return "\x1b[32m" // green
+	default:
+		return "\x1b[34m" // blue
+	}
+}
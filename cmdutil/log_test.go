@@ -0,0 +1,51 @@
+package cmdutil_test
+
+import (
+	"bytes"
+	"log/slog"
+	"strings"
+	"testing"
+
+	"github.com/Mic92/niks3/cmdutil"
+)
+
+func TestPrettyLogHandler(t *testing.T) {
+	t.Parallel()
+
+	var buf bytes.Buffer
+
+	handler, err := cmdutil.NewLogHandler(&buf, cmdutil.LogFormatPretty, cmdutil.ColorNever, slog.LevelInfo)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	logger := slog.New(handler).With("closure", "abc").WithGroup("upload")
+	logger.Debug("hidden")
+	logger.Info("Uploaded path", "path", "/nix/store/x", "error", "two words")
+
+	line := buf.String()
+	if strings.Contains(line, "hidden") {
+		t.Errorf("debug record logged at info level: %q", line)
+	}
+
+	want := `INFO  Uploaded path closure=abc upload.path=/nix/store/x upload.error="two words"` + "\n"
+	if !strings.HasSuffix(line, want) {
+		t.Errorf("got %q, want suffix %q", line, want)
+	}
+
+	if strings.Contains(line, "\x1b[") {
+		t.Errorf("colors written with --color never: %q", line)
+	}
+}
+
+func TestNewLogHandlerRejectsUnknownFormat(t *testing.T) {
+	t.Parallel()
+
+	if _, err := cmdutil.NewLogHandler(&bytes.Buffer{}, "xml", cmdutil.ColorAuto, slog.LevelInfo); err == nil {
+		t.Error("expected an error for --log-format xml")
+	}
+
+	if _, err := cmdutil.NewLogHandler(&bytes.Buffer{}, cmdutil.LogFormatJSON, "sometimes", slog.LevelInfo); err == nil {
+		t.Error("expected an error for --color sometimes")
+	}
+}
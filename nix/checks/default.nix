@@ -33,6 +33,8 @@ packages
           selfPackages.rustfs
           pkgs.postgresql
           pkgs.nix
+          pkgs.xz
+          pkgs.brotli
         ];
         __darwinAllowLocalNetworking = true;
       }
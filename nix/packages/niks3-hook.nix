@@ -8,7 +8,7 @@
 let
   common = import ./niks3-src.nix { inherit lib; };
 in
-pkgs.buildGoModule {
+pkgs.buildGoModule rec {
   pname = "niks3-hook";
   version = "1.4.0";
   vendorHash = common.vendorHashHook;
@@ -29,6 +29,8 @@ pkgs.buildGoModule {
   ldflags = [
     "-X"
     "main.socketPath=${postBuildHookSocketPath}"
+    "-X"
+    "github.com/Mic92/niks3/client.Version=${version}"
   ];
 
   subPackages = [ "cmd/niks3-hook" ];
@@ -7,7 +7,7 @@
 let
   common = import ./niks3-src.nix { inherit lib; };
 in
-pkgs.buildGoModule {
+pkgs.buildGoModule rec {
   pname = "niks3";
   version = "1.7.0";
   inherit (common) vendorHash;
@@ -26,5 +26,22 @@ pkgs.buildGoModule {
 
   subPackages = [ "cmd/niks3" ];
 
+  ldflags = [
+    "-X"
+    "github.com/Mic92/niks3/client.Version=${version}"
+  ];
+
+  nativeBuildInputs = [ pkgs.makeWrapper ];
+
+  # --compression xz/br pipe NARs through xz/brotli; keep the user's PATH first.
+  postInstall = ''
+    wrapProgram $out/bin/niks3 --suffix PATH : ${
+      lib.makeBinPath [
+        pkgs.xz
+        pkgs.brotli
+      ]
+    }
+  '';
+
   doCheck = false;
 }
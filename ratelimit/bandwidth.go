@@ -0,0 +1,77 @@
+package ratelimit
+
+import (
+	"context"
+	"fmt"
+	"io"
+
+	"golang.org/x/time/rate"
+)
+
+// maxBandwidthBurst caps how many bytes a single read may take at once, so
+// concurrent streams interleave instead of one draining the bucket.
+const maxBandwidthBurst = 32 << 10
+
+// BandwidthLimiter caps the combined throughput of every stream it wraps
+// with a token bucket counting bytes.
+type BandwidthLimiter struct {
+	limiter *rate.Limiter
+}
+
+// NewBandwidthLimiter returns a limiter allowing bytesPerSecond across all
+// readers it wraps. It returns nil if bytesPerSecond is 0 or less, which
+// Reader treats as unlimited.
+func NewBandwidthLimiter(bytesPerSecond int64) *BandwidthLimiter {
+	if bytesPerSecond <= 0 {
+		return nil
+	}
+
+	burst := int(min(bytesPerSecond, maxBandwidthBurst))
+
+	return &BandwidthLimiter{limiter: rate.NewLimiter(rate.Limit(bytesPerSecond), burst)}
+}
+
+// Reader returns r throttled to the limiter's rate. Waiting stops with an
+// error once ctx is canceled. A nil limiter returns r unchanged.
+func (b *BandwidthLimiter) Reader(ctx context.Context, r io.Reader) io.Reader {
+	if b == nil {
+		return r
+	}
+
+	return &throttledReader{ctx: ctx, r: r, limiter: b.limiter}
+}
+
+// ReadCloser is Reader for an io.ReadCloser, closing rc when closed.
+func (b *BandwidthLimiter) ReadCloser(ctx context.Context, rc io.ReadCloser) io.ReadCloser {
+	if b == nil {
+		return rc
+	}
+
+	return struct {
+		io.Reader
+		io.Closer
+	}{b.Reader(ctx, rc), rc}
+}
+
+type throttledReader struct {
+	ctx     context.Context //nolint:containedctx // bounds waits of a request body, which has no context of its own
+	r       io.Reader
+	limiter *rate.Limiter
+}
+
+func (t *throttledReader) Read(p []byte) (int, error) {
+	if len(p) > t.limiter.Burst() {
+		p = p[:t.limiter.Burst()]
+	}
+
+	n, err := t.r.Read(p)
+	if n > 0 {
+		// Pay for the bytes after reading them, so a short read
+		// near EOF does not wait for bytes that never come.
+		if waitErr := t.limiter.WaitN(t.ctx, n); waitErr != nil {
+			return n, fmt.Errorf("waiting for upload bandwidth: %w", waitErr)
+		}
+	}
+
+	return n, err //nolint:wrapcheck // io.EOF must reach the caller unwrapped
+}
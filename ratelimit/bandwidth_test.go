@@ -0,0 +1,71 @@
+package ratelimit_test
+
+import (
+	"bytes"
+	"context"
+	"errors"
+	"io"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/Mic92/niks3/ratelimit"
+)
+
+func TestBandwidthLimiterSharedAcrossReaders(t *testing.T) {
+	t.Parallel()
+
+	const (
+		rate    = 128 << 10
+		payload = 48 << 10
+		readers = 4
+	)
+
+	limiter := ratelimit.NewBandwidthLimiter(rate)
+
+	start := time.Now()
+
+	var wg sync.WaitGroup
+
+	for range readers {
+		wg.Go(func() {
+			r := limiter.Reader(t.Context(), bytes.NewReader(make([]byte, payload)))
+
+			n, err := io.Copy(io.Discard, r)
+			if err != nil || n != payload {
+				t.Errorf("copied %d bytes, err %v", n, err)
+			}
+		})
+	}
+
+	wg.Wait()
+
+	// The initial burst is free; everything else is paid at the shared rate.
+	minimum := time.Duration(float64(readers*payload-32<<10) / rate * float64(time.Second))
+	if elapsed := time.Since(start); elapsed < minimum*9/10 {
+		t.Errorf("%d bytes at %d B/s took %v, want at least %v", readers*payload, rate, elapsed, minimum)
+	}
+}
+
+func TestBandwidthLimiterCanceled(t *testing.T) {
+	t.Parallel()
+
+	limiter := ratelimit.NewBandwidthLimiter(1024)
+
+	ctx, cancel := context.WithCancel(t.Context())
+	cancel()
+
+	_, err := io.Copy(io.Discard, limiter.Reader(ctx, bytes.NewReader(make([]byte, 4096))))
+	if !errors.Is(err, context.Canceled) {
+		t.Fatalf("got %v, want context.Canceled", err)
+	}
+}
+
+func TestBandwidthLimiterUnlimited(t *testing.T) {
+	t.Parallel()
+
+	r := bytes.NewReader(nil)
+	if got := ratelimit.NewBandwidthLimiter(0).Reader(t.Context(), r); got != r {
+		t.Fatal("unlimited limiter should return the reader unchanged")
+	}
+}
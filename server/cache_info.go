@@ -0,0 +1,99 @@
+package server
+
+import (
+	"bytes"
+	"errors"
+	"fmt"
+	"log/slog"
+	"net/http"
+	"os"
+	"path"
+	"strings"
+
+	"github.com/Mic92/niks3/api"
+	minio "github.com/minio/minio-go/v7"
+)
+
+// defaultCacheInfo is written by InitializeBucket when the bucket has no
+// nix-cache-info yet. Priority 30 is higher than the default nixos.org cache
+// (priority 40).
+func defaultCacheInfo() api.CacheInfo {
+	storeDir := os.Getenv("NIX_STORE_DIR")
+	if storeDir == "" {
+		storeDir = "/nix/store"
+	}
+
+	return api.CacheInfo{StoreDir: storeDir, WantMassQuery: true, Priority: 30}
+}
+
+// RenderCacheInfo validates info and formats it as a nix-cache-info file.
+func RenderCacheInfo(info api.CacheInfo) (string, error) {
+	if !path.IsAbs(info.StoreDir) || path.Clean(info.StoreDir) != info.StoreDir {
+		return "", fmt.Errorf("store dir %q must be a clean absolute path", info.StoreDir)
+	}
+
+	if strings.ContainsAny(info.StoreDir, " \t\r\n") {
+		return "", fmt.Errorf("store dir %q must not contain whitespace", info.StoreDir)
+	}
+
+	if info.Priority < 0 {
+		return "", errors.New("priority must not be negative")
+	}
+
+	wantMassQuery := 0
+	if info.WantMassQuery {
+		wantMassQuery = 1
+	}
+
+	return fmt.Sprintf("StoreDir: %s\nWantMassQuery: %d\nPriority: %d\n",
+		info.StoreDir, wantMassQuery, info.Priority), nil
+}
+
+// PutCacheInfoHandler handles PUT /api/cache-info.
+// It replaces the bucket's nix-cache-info. The object is server-owned, so it
+// is rendered here from validated fields instead of being uploaded through a
+// presigned URL (see IsValidUploadKey).
+func (s *Service) PutCacheInfoHandler(w http.ResponseWriter, r *http.Request) {
+	defer func() {
+		if err := r.Body.Close(); err != nil {
+			slog.Error("Failed to close request body", "error", err)
+		}
+	}()
+
+	var info api.CacheInfo
+	if !decodeJSONBody(w, r, maxAPIRequestBody, &info) {
+		return
+	}
+
+	cacheInfo, err := RenderCacheInfo(info)
+	if err != nil {
+		http.Error(w, "invalid cache info: "+err.Error(), http.StatusBadRequest)
+
+		return
+	}
+
+	if err := s.S3RateLimiter.Wait(r.Context()); err != nil {
+		http.Error(w, "request cancelled", http.StatusServiceUnavailable)
+
+		return
+	}
+
+	_, err = s.MinioClient.PutObject(r.Context(), s.Bucket, "nix-cache-info",
+		bytes.NewReader([]byte(cacheInfo)), int64(len(cacheInfo)),
+		minio.PutObjectOptions{ContentType: "text/plain"})
+	if err != nil {
+		if isRateLimitError(err) {
+			s.S3RateLimiter.RecordThrottle()
+		}
+
+		slog.Error("Failed to write nix-cache-info", "error", err)
+		http.Error(w, "failed to write nix-cache-info: "+err.Error(), http.StatusInternalServerError)
+
+		return
+	}
+
+	s.S3RateLimiter.RecordSuccess()
+	slog.Info("Updated nix-cache-info", "store_dir", info.StoreDir, "priority", info.Priority, "want_mass_query", info.WantMassQuery)
+
+	w.WriteHeader(http.StatusNoContent)
+}
@@ -0,0 +1,60 @@
+package server_test
+
+import (
+	"testing"
+
+	"github.com/Mic92/niks3/api"
+	"github.com/Mic92/niks3/server"
+)
+
+func TestRenderCacheInfo(t *testing.T) {
+	t.Parallel()
+
+	tests := []struct {
+		name    string
+		info    api.CacheInfo
+		want    string
+		wantErr bool
+	}{
+		{
+			name: "defaults",
+			info: api.CacheInfo{StoreDir: "/nix/store", WantMassQuery: true, Priority: 30},
+			want: "StoreDir: /nix/store\nWantMassQuery: 1\nPriority: 30\n",
+		},
+		{
+			name: "custom store without mass query",
+			info: api.CacheInfo{StoreDir: "/var/nix/store", Priority: 50},
+			want: "StoreDir: /var/nix/store\nWantMassQuery: 0\nPriority: 50\n",
+		},
+		{
+			name:    "relative store dir",
+			info:    api.CacheInfo{StoreDir: "nix/store"},
+			wantErr: true,
+		},
+		{
+			name:    "injected field",
+			info:    api.CacheInfo{StoreDir: "/nix/store\nPriority: 0"},
+			wantErr: true,
+		},
+		{
+			name:    "negative priority",
+			info:    api.CacheInfo{StoreDir: "/nix/store", Priority: -1},
+			wantErr: true,
+		},
+	}
+
+	for _, tt := range tests {
+		t.Run(tt.name, func(t *testing.T) {
+			t.Parallel()
+
+			got, err := server.RenderCacheInfo(tt.info)
+			if (err != nil) != tt.wantErr {
+				t.Fatalf("RenderCacheInfo() error = %v, wantErr %v", err, tt.wantErr)
+			}
+
+			if got != tt.want {
+				t.Errorf("RenderCacheInfo() = %q, want %q", got, tt.want)
+			}
+		})
+	}
+}
@@ -0,0 +1,185 @@
+package server
+
+import (
+	"context"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"log/slog"
+	"net/http"
+	"strings"
+
+	"github.com/Mic92/niks3/api"
+	"github.com/Mic92/niks3/server/pg"
+	"github.com/jackc/pgx/v5"
+	minio "github.com/minio/minio-go/v7"
+)
+
+// DeleteObjectHandler handles DELETE /api/objects/{key...} for narinfo keys.
+// It removes the narinfo right away and tombstones it together with the
+// NAR, listing and realisations no other object references, which the next
+// GC deletes after its grace period. A pinned narinfo, or one still
+// referenced by another narinfo or a push in progress, is refused with 409
+// Conflict. With ?dry-run=true nothing is changed. The path's dependencies
+// stay until GC finds them unreachable.
+func (s *Service) DeleteObjectHandler(w http.ResponseWriter, r *http.Request) {
+	slog.Info("Received delete object request", "method", r.Method, "path", r.URL.Path)
+
+	key := r.PathValue("key")
+	if !IsValidUploadKey(key, "narinfo") {
+		http.Error(w, "invalid key: only narinfos can be deleted, their NARs follow", http.StatusBadRequest)
+
+		return
+	}
+
+	dryRun := r.URL.Query().Get("dry-run") == "true"
+
+	tx, err := s.Pool.Begin(r.Context())
+	if err != nil {
+		http.Error(w, "failed to begin transaction: "+err.Error(), http.StatusInternalServerError)
+
+		return
+	}
+
+	committed := false
+
+	defer rollbackOnError(r.Context(), &tx, &err, &committed)
+
+	queries := pg.New(tx)
+
+	refs, err := queries.GetObjectRefsForUpdate(r.Context(), key)
+	if err != nil {
+		if errors.Is(err, pgx.ErrNoRows) {
+			http.Error(w, "object not found", http.StatusNotFound)
+
+			return
+		}
+
+		http.Error(w, "failed to get object: "+err.Error(), http.StatusInternalServerError)
+
+		return
+	}
+
+	conflict, err := deleteConflict(r.Context(), queries, key)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+
+		return
+	}
+
+	if conflict != nil {
+		// Not an error for rollbackOnError, so release the lock here.
+		_ = tx.Rollback(r.Context())
+
+		w.Header().Set("Content-Type", "application/json")
+		w.WriteHeader(http.StatusConflict)
+		_ = json.NewEncoder(w).Encode(conflict)
+
+		return
+	}
+
+	deleted, err := ownedObjects(r.Context(), queries, key, refs)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+
+		return
+	}
+
+	if dryRun {
+		_ = tx.Rollback(r.Context())
+
+		writeDeleteResult(w, api.DeleteResult{Deleted: deleted, DryRun: true})
+
+		return
+	}
+
+	if _, err = queries.DeleteClosure(r.Context(), key); err != nil {
+		http.Error(w, "failed to delete closure: "+err.Error(), http.StatusInternalServerError)
+
+		return
+	}
+
+	if err = queries.MarkObjectsForDeletion(r.Context(), deleted); err != nil {
+		http.Error(w, "failed to mark objects for deletion: "+err.Error(), http.StatusInternalServerError)
+
+		return
+	}
+
+	if err = tx.Commit(r.Context()); err != nil {
+		http.Error(w, "failed to commit transaction: "+err.Error(), http.StatusInternalServerError)
+
+		return
+	}
+
+	committed = true
+
+	// Substituters stop seeing the path once the narinfo is gone; the rest
+	// waits for GC, so pushes still using the NAR have time to resurrect it.
+	if err := s.MinioClient.RemoveObject(r.Context(), s.Bucket, key, minio.RemoveObjectOptions{}); err != nil {
+		// GC deletes it with the other tombstoned objects.
+		slog.Warn("Failed to delete narinfo from S3", "key", key, "error", err)
+	}
+
+	slog.Info("Deleted narinfo", "key", key, "objects", deleted)
+
+	writeDeleteResult(w, api.DeleteResult{Deleted: deleted})
+}
+
+// deleteConflict returns why key cannot be deleted, or nil if it can.
+func deleteConflict(ctx context.Context, queries *pg.Queries, key string) (*api.DeleteConflictResponse, error) {
+	pins, err := queries.ListPinNamesForClosure(ctx, key)
+	if err != nil {
+		return nil, fmt.Errorf("failed to list pins: %w", err)
+	}
+
+	referrers, err := queries.GetObjectReferrers(ctx, key)
+	if err != nil {
+		return nil, fmt.Errorf("failed to get referrers: %w", err)
+	}
+
+	switch {
+	case len(pins) > 0:
+		return &api.DeleteConflictResponse{
+			Error: fmt.Sprintf("%s is pinned by %s", key, strings.Join(pins, ", ")),
+			Pins:  pins,
+		}, nil
+	case len(referrers) > 0:
+		return &api.DeleteConflictResponse{
+			Error:     fmt.Sprintf("%s is still referenced by %d other objects", key, len(referrers)),
+			Referrers: referrers,
+		}, nil
+	}
+
+	return nil, nil //nolint:nilnil // no conflict
+}
+
+// ownedObjects returns key and those of its references that are not
+// narinfos and that no other object references.
+func ownedObjects(ctx context.Context, queries *pg.Queries, key string, refs []string) ([]string, error) {
+	owned := []string{key}
+
+	for _, ref := range refs {
+		if ref == key || strings.HasSuffix(ref, ".narinfo") {
+			continue
+		}
+
+		referrers, err := queries.GetObjectReferrers(ctx, ref)
+		if err != nil {
+			return nil, fmt.Errorf("failed to get referrers of %s: %w", ref, err)
+		}
+
+		if len(referrers) == 1 && referrers[0] == key {
+			owned = append(owned, ref)
+		}
+	}
+
+	return owned, nil
+}
+
+func writeDeleteResult(w http.ResponseWriter, result api.DeleteResult) {
+	w.Header().Set("Content-Type", "application/json")
+
+	if err := json.NewEncoder(w).Encode(result); err != nil {
+		slog.Error("Failed to encode response", "error", err)
+	}
+}
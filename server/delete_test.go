@@ -0,0 +1,146 @@
+package server_test
+
+import (
+	"encoding/json"
+	"errors"
+	"net/http"
+	"slices"
+	"testing"
+
+	"github.com/Mic92/niks3/api"
+	"github.com/Mic92/niks3/server/pg"
+	"github.com/jackc/pgx/v5"
+	"github.com/minio/minio-go/v7"
+)
+
+func TestService_deleteObjectHandler(t *testing.T) {
+	t.Parallel()
+
+	service := createTestService(t)
+	defer service.Close()
+
+	ctx := t.Context()
+	queries := pg.New(service.Pool)
+
+	hashA := "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+	hashB := "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb"
+	keyA := hashA + ".narinfo"
+	keyB := hashB + ".narinfo"
+
+	createTestClosure(t, service, queries, hashA)
+	createTestClosure(t, service, queries, hashB)
+
+	// B depends on A.
+	_, err := service.Pool.Exec(ctx, "UPDATE objects SET refs = array_append(refs, $1) WHERE key = $2", keyA, keyB)
+	ok(t, err)
+
+	deleteRequest := func(key, query string, status int) *api.DeleteResult {
+		t.Helper()
+
+		check := checkStatusCode(status)
+		rr := testRequest(t, &TestRequest{
+			method:        "DELETE",
+			path:          "/api/objects/" + key + query,
+			handler:       service.DeleteObjectHandler,
+			pathValues:    map[string]string{"key": key},
+			checkResponse: &check,
+		})
+
+		if status != http.StatusOK {
+			return nil
+		}
+
+		var result api.DeleteResult
+		ok(t, json.Unmarshal(rr.Body.Bytes(), &result))
+
+		return &result
+	}
+
+	// A is still needed by B.
+	check := checkStatusCode(http.StatusConflict)
+	rr := testRequest(t, &TestRequest{
+		method:        "DELETE",
+		path:          "/api/objects/" + keyA,
+		handler:       service.DeleteObjectHandler,
+		pathValues:    map[string]string{"key": keyA},
+		checkResponse: &check,
+	})
+
+	var conflict api.DeleteConflictResponse
+	ok(t, json.Unmarshal(rr.Body.Bytes(), &conflict))
+
+	if !slices.Equal(conflict.Referrers, []string{keyB}) {
+		t.Errorf("referrers = %v, want [%s]", conflict.Referrers, keyB)
+	}
+
+	wantDeleted := []string{keyB, "nar/" + hashB + ".nar.zst"}
+
+	// A dry run reports what would go but keeps everything.
+	result := deleteRequest(keyB, "?dry-run=true", http.StatusOK)
+	if !result.DryRun || !slices.Equal(result.Deleted, wantDeleted) {
+		t.Errorf("dry run = %+v, want %v", result, wantDeleted)
+	}
+
+	_, err = service.MinioClient.StatObject(ctx, service.Bucket, keyB, minio.StatObjectOptions{})
+	ok(t, err)
+
+	result = deleteRequest(keyB, "", http.StatusOK)
+	if result.DryRun || !slices.Equal(result.Deleted, wantDeleted) {
+		t.Errorf("delete = %+v, want %v", result, wantDeleted)
+	}
+
+	if _, err := service.MinioClient.StatObject(ctx, service.Bucket, keyB, minio.StatObjectOptions{}); err == nil {
+		t.Errorf("narinfo %s is still in S3", keyB)
+	}
+
+	if _, err := queries.GetClosure(ctx, keyB); !errors.Is(err, pgx.ErrNoRows) {
+		t.Errorf("closure %s was not deleted: %v", keyB, err)
+	}
+
+	existing, err := queries.GetExistingObjects(ctx, wantDeleted)
+	ok(t, err)
+
+	for _, obj := range existing {
+		if !obj.DeletedAt.Valid {
+			t.Errorf("%s was not marked for deletion", obj.Key)
+		}
+	}
+
+	// With B gone, nothing references A any more.
+	deleteRequest(keyA, "", http.StatusOK)
+
+	deleteRequest(keyA, "", http.StatusNotFound)
+	deleteRequest("nix-cache-info", "", http.StatusBadRequest)
+}
+
+func TestService_deleteObjectHandlerRefusesPinned(t *testing.T) {
+	t.Parallel()
+
+	service := createTestService(t)
+	defer service.Close()
+
+	queries := pg.New(service.Pool)
+
+	hash := "cccccccccccccccccccccccccccccccc"
+	key := hash + ".narinfo"
+
+	createTestClosure(t, service, queries, hash)
+
+	ok(t, queries.UpsertPin(t.Context(), pg.UpsertPinParams{Name: "release", NarinfoKey: key, StorePath: "/nix/store/" + hash + "-release"}))
+
+	check := checkStatusCode(http.StatusConflict)
+	rr := testRequest(t, &TestRequest{
+		method:        "DELETE",
+		path:          "/api/objects/" + key,
+		handler:       service.DeleteObjectHandler,
+		pathValues:    map[string]string{"key": key},
+		checkResponse: &check,
+	})
+
+	var conflict api.DeleteConflictResponse
+	ok(t, json.Unmarshal(rr.Body.Bytes(), &conflict))
+
+	if !slices.Equal(conflict.Pins, []string{"release"}) {
+		t.Errorf("pins = %v, want [release]", conflict.Pins)
+	}
+}
@@ -62,3 +62,8 @@ func (br ByteRange) End() int64   { return br.end }
 func ServerTLSConfig(clientCA string) (*tls.Config, error) {
 	return serverTLSConfig(clientCA)
 }
+
+// AddNarinfoSignatures exposes addNarinfoSignatures to tests.
+func AddNarinfoSignatures(content string, sigs []string) (string, int) {
+	return addNarinfoSignatures(content, sigs)
+}
@@ -60,6 +60,7 @@ func registerTestHandlers(mux *http.ServeMux, testService *server.Service) {
 	mux.HandleFunc("POST /api/pending_closures", testService.AuthMiddleware(testService.CreatePendingClosureHandler))
 	mux.HandleFunc("POST /api/pending_closures/{id}/sign", testService.AuthMiddleware(testService.SignNarinfosHandler))
 	mux.HandleFunc("POST /api/pending_closures/{id}/complete", testService.AuthMiddleware(testService.CommitPendingClosureHandler))
+	mux.HandleFunc("POST /api/pending_closures/complete", testService.AuthMiddleware(testService.CommitPendingClosuresHandler))
 	mux.HandleFunc("POST /api/multipart/complete", testService.AuthMiddleware(testService.CompleteMultipartUploadHandler))
 	mux.HandleFunc("GET /health", testService.HealthCheckHandler)
 }
@@ -0,0 +1,97 @@
+package server
+
+import (
+	"encoding/json"
+	"fmt"
+	"log/slog"
+	"net/http"
+	"strings"
+
+	"github.com/Mic92/niks3/api"
+	"github.com/Mic92/niks3/server/pg"
+)
+
+// MaxNarinfoExistsHashes bounds the hashes of one POST /api/narinfo_exists
+// request; clients split larger closures into several requests.
+const MaxNarinfoExistsHashes = 10000
+
+// NarinfoExistsHandler handles POST /api/narinfo_exists.
+// It reports which of the requested store path hashes have a narinfo in the
+// cache, answering from the objects table in one query instead of a HEAD
+// request per path. Narinfos marked for deletion by GC count as missing.
+//
+// Request body:
+//
+//	{"hashes": ["26xbg1ndr7hbcncrlf9nhx5is2b25d13", ...], "key_prefix": "optional"}
+//
+// Response body:
+//
+//	{"present": ["26xbg1ndr7hbcncrlf9nhx5is2b25d13"]}
+func (s *Service) NarinfoExistsHandler(w http.ResponseWriter, r *http.Request) {
+	defer func() {
+		if err := r.Body.Close(); err != nil {
+			slog.Error("Failed to close request body", "error", err)
+		}
+	}()
+
+	var req api.NarinfoExistsRequest
+	if !decodeJSONBody(w, r, maxAPIRequestBody, &req) {
+		return
+	}
+
+	if len(req.Hashes) > MaxNarinfoExistsHashes {
+		http.Error(w, fmt.Sprintf("too many hashes: %d, at most %d per request", len(req.Hashes), MaxNarinfoExistsHashes), http.StatusBadRequest)
+
+		return
+	}
+
+	if !IsValidKeyPrefix(req.KeyPrefix) {
+		http.Error(w, fmt.Sprintf("invalid key prefix %q", req.KeyPrefix), http.StatusBadRequest)
+
+		return
+	}
+
+	prefix := ""
+	if req.KeyPrefix != "" {
+		prefix = req.KeyPrefix + "/"
+	}
+
+	keys := make([]string, 0, len(req.Hashes))
+
+	for _, hash := range req.Hashes {
+		key := hash + ".narinfo"
+		if !narinfoRe.MatchString(key) {
+			http.Error(w, fmt.Sprintf("invalid store path hash %q", hash), http.StatusBadRequest)
+
+			return
+		}
+
+		keys = append(keys, prefix+key)
+	}
+
+	present := []string{}
+
+	if len(keys) > 0 {
+		existing, err := pg.New(s.Pool).GetExistingObjects(r.Context(), keys)
+		if err != nil {
+			slog.Error("Failed to look up narinfos", "error", err)
+			http.Error(w, "failed to look up narinfos", http.StatusInternalServerError)
+
+			return
+		}
+
+		for _, object := range existing {
+			if object.DeletedAt.Valid {
+				continue
+			}
+
+			present = append(present, strings.TrimSuffix(strings.TrimPrefix(object.Key, prefix), ".narinfo"))
+		}
+	}
+
+	w.Header().Set("Content-Type", "application/json")
+
+	if err := json.NewEncoder(w).Encode(api.NarinfoExistsResponse{Present: present}); err != nil {
+		slog.Error("Failed to encode response", "error", err)
+	}
+}
@@ -0,0 +1,55 @@
+package server_test
+
+import (
+	"encoding/json"
+	"net/http"
+	"slices"
+	"testing"
+
+	"github.com/Mic92/niks3/api"
+	"github.com/Mic92/niks3/server/pg"
+)
+
+func TestService_narinfoExistsHandler(t *testing.T) {
+	t.Parallel()
+
+	service := createTestService(t)
+	defer service.Close()
+
+	queries := pg.New(service.Pool)
+
+	cachedHash := "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+	missingHash := "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb"
+
+	createTestClosure(t, service, queries, cachedHash)
+
+	body, err := json.Marshal(api.NarinfoExistsRequest{Hashes: []string{cachedHash, missingHash}})
+	ok(t, err)
+
+	rr := testRequest(t, &TestRequest{
+		method:  "POST",
+		path:    "/api/narinfo_exists",
+		body:    body,
+		handler: service.NarinfoExistsHandler,
+	})
+
+	var result api.NarinfoExistsResponse
+	ok(t, json.Unmarshal(rr.Body.Bytes(), &result))
+
+	if !slices.Equal(result.Present, []string{cachedHash}) {
+		t.Errorf("present = %v, want [%s]", result.Present, cachedHash)
+	}
+
+	// Hashes are validated so they cannot name arbitrary keys.
+	body, err = json.Marshal(api.NarinfoExistsRequest{Hashes: []string{"../nix-cache-info"}})
+	ok(t, err)
+
+	check := checkStatusCode(http.StatusBadRequest)
+	testRequest(t, &TestRequest{
+		method:        "POST",
+		path:          "/api/narinfo_exists",
+		body:          body,
+		handler:       service.NarinfoExistsHandler,
+		checkResponse: &check,
+	})
+}
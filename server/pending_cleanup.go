@@ -15,7 +15,6 @@ import (
 func (s *Service) cleanupPendingClosures(ctx context.Context, duration time.Duration) (int, error) {
 	queries := pg.New(s.Pool)
 	seconds := int32(duration.Seconds())
-	coreClient := minio.Core{Client: s.MinioClient}
 
 	// 1. Get old multipart uploads to abort
 	uploads, err := queries.GetOldMultipartUploads(ctx, seconds)
@@ -24,6 +23,58 @@ func (s *Service) cleanupPendingClosures(ctx context.Context, duration time.Dura
 	}
 
 	// 2. Abort them in S3
+	if err := s.abortMultipartUploads(ctx, uploads); err != nil {
+		return 0, err
+	}
+
+	slog.Info("Aborted multipart uploads", "count", len(uploads))
+
+	// 3. Clean database (cascade deletes multipart_uploads rows)
+	count, err := queries.CleanupPendingClosures(ctx, seconds)
+	if err != nil {
+		return 0, fmt.Errorf("cleanup pending closures: %w", err)
+	}
+
+	return int(count), nil
+}
+
+// abortPendingClosure discards a single pending closure a client gave up on:
+// its multipart uploads are aborted in S3 and its pending objects are handed
+// to GC in case parts of them were already uploaded.
+func (s *Service) abortPendingClosure(ctx context.Context, pendingClosureID int64) error {
+	queries := pg.New(s.Pool)
+
+	rows, err := queries.GetPendingClosureMultipartUploads(ctx, pendingClosureID)
+	if err != nil {
+		return fmt.Errorf("get multipart uploads: %w", err)
+	}
+
+	uploads := make([]pg.GetOldMultipartUploadsRow, 0, len(rows))
+	for _, row := range rows {
+		uploads = append(uploads, pg.GetOldMultipartUploadsRow(row))
+	}
+
+	if err := s.abortMultipartUploads(ctx, uploads); err != nil {
+		return err
+	}
+
+	count, err := queries.AbortPendingClosure(ctx, pendingClosureID)
+	if err != nil {
+		return fmt.Errorf("abort pending closure: %w", err)
+	}
+
+	if count == 0 {
+		return fmt.Errorf("failed to abort pending closure: %w", errPendingClosureNotFound)
+	}
+
+	return nil
+}
+
+// abortMultipartUploads aborts the given uploads in S3. Uploads S3 no longer
+// knows about are ignored.
+func (s *Service) abortMultipartUploads(ctx context.Context, uploads []pg.GetOldMultipartUploadsRow) error {
+	coreClient := minio.Core{Client: s.MinioClient}
+
 	eg, egCtx := errgroup.WithContext(ctx)
 	eg.SetLimit(s.S3Concurrency)
 
@@ -52,16 +103,8 @@ func (s *Service) cleanupPendingClosures(ctx context.Context, duration time.Dura
 	}
 
 	if err := eg.Wait(); err != nil {
-		return 0, fmt.Errorf("abort multipart uploads: %w", err)
-	}
-
-	slog.Info("Aborted multipart uploads", "count", len(uploads))
-
-	// 3. Clean database (cascade deletes multipart_uploads rows)
-	count, err := queries.CleanupPendingClosures(ctx, seconds)
-	if err != nil {
-		return 0, fmt.Errorf("cleanup pending closures: %w", err)
+		return fmt.Errorf("abort multipart uploads: %w", err)
 	}
 
-	return int(count), nil
+	return nil
 }
@@ -73,6 +73,39 @@ DELETE FROM pending_closures
 USING old_closures
 WHERE pending_closures.id = old_closures.id;
 
+-- name: AbortPendingClosure :execrows
+WITH aborted_closure AS (
+    SELECT id
+    FROM pending_closures
+    WHERE id = $1::bigint
+),
+
+-- Objects may already be partially uploaded, so hand them to GC like
+-- CleanupPendingClosures does
+inserted_objects AS (
+    INSERT INTO objects (key, refs, deleted_at, first_deleted_at)
+    SELECT
+        po.key,
+        po.refs,
+        timezone('UTC', now()),
+        timezone('UTC', now())
+    FROM pending_objects AS po
+    JOIN aborted_closure ac ON po.pending_closure_id = ac.id
+    ON CONFLICT (key) DO NOTHING
+    RETURNING key
+),
+
+deleted_pending_objects AS (
+    DELETE FROM pending_objects
+    USING aborted_closure
+    WHERE pending_objects.pending_closure_id = aborted_closure.id
+    RETURNING pending_closure_id
+)
+
+DELETE FROM pending_closures
+USING aborted_closure
+WHERE pending_closures.id = aborted_closure.id;
+
 -- name: GetClosure :one
 SELECT updated_at FROM closures
 WHERE key = $1 LIMIT 1;
@@ -116,6 +149,11 @@ FROM multipart_uploads mu
 JOIN pending_closures pc ON mu.pending_closure_id = pc.id
 WHERE pc.started_at < timezone('UTC', now()) - interval '1 second' * $1::int;
 
+-- name: GetPendingClosureMultipartUploads :many
+SELECT upload_id, object_key
+FROM multipart_uploads
+WHERE pending_closure_id = $1;
+
 -- name: DeleteMultipartUpload :exec
 DELETE FROM multipart_uploads
 WHERE upload_id = $1;
@@ -213,3 +251,40 @@ WHERE name = $1;
 SELECT name, narinfo_key, store_path, created_at, updated_at
 FROM pins
 ORDER BY name;
+
+-- name: GetObjectRefsForUpdate :one
+-- Lock a live object so that its references cannot change while it is
+-- deleted.
+SELECT refs FROM objects
+WHERE key = $1 AND deleted_at IS NULL
+FOR UPDATE;
+
+-- name: GetObjectReferrers :many
+-- Live and pending objects other than the object itself that reference it.
+SELECT key FROM objects
+WHERE refs @> ARRAY[sqlc.arg(key)::varchar]
+  AND key <> sqlc.arg(key)::varchar
+  AND deleted_at IS NULL
+UNION
+SELECT key FROM pending_objects
+WHERE refs @> ARRAY[sqlc.arg(key)::varchar]
+  AND key <> sqlc.arg(key)::varchar
+ORDER BY key;
+
+-- name: ListPinNamesForClosure :many
+SELECT name FROM pins
+WHERE narinfo_key = $1
+ORDER BY name;
+
+-- name: DeleteClosure :execrows
+DELETE FROM closures
+WHERE key = $1;
+
+-- name: MarkObjectsForDeletion :exec
+-- Tombstone objects the way MarkStaleObjects does, so that GC removes them
+-- after the grace period and pushes in the meantime wait for the deletion.
+UPDATE objects
+SET
+    deleted_at = timezone('UTC', now()),
+    first_deleted_at = COALESCE(first_deleted_at, timezone('UTC', now()))
+WHERE key = any($1::varchar []) AND deleted_at IS NULL;
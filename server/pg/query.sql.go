@@ -11,6 +11,48 @@ import (
 	"github.com/jackc/pgx/v5/pgtype"
 )
 
+const abortPendingClosure = `-- name: AbortPendingClosure :execrows
+WITH aborted_closure AS (
+    SELECT id
+    FROM pending_closures
+    WHERE id = $1::bigint
+),
+
+inserted_objects AS (
+    INSERT INTO objects (key, refs, deleted_at, first_deleted_at)
+    SELECT
+        po.key,
+        po.refs,
+        timezone('UTC', now()),
+        timezone('UTC', now())
+    FROM pending_objects AS po
+    JOIN aborted_closure ac ON po.pending_closure_id = ac.id
+    ON CONFLICT (key) DO NOTHING
+    RETURNING key
+),
+
+deleted_pending_objects AS (
+    DELETE FROM pending_objects
+    USING aborted_closure
+    WHERE pending_objects.pending_closure_id = aborted_closure.id
+    RETURNING pending_closure_id
+)
+
+DELETE FROM pending_closures
+USING aborted_closure
+WHERE pending_closures.id = aborted_closure.id
+`
+
+// Objects may already be partially uploaded, so hand them to GC like
+// CleanupPendingClosures does
+func (q *Queries) AbortPendingClosure(ctx context.Context, dollar_1 int64) (int64, error) {
+	result, err := q.db.Exec(ctx, abortPendingClosure, dollar_1)
+	if err != nil {
+		return 0, err
+	}
+	return result.RowsAffected(), nil
+}
+
 const cleanupPendingClosures = `-- name: CleanupPendingClosures :execrows
 WITH cutoff_time AS (
     SELECT timezone('UTC', now()) - interval '1 second' * $1::int AS time
@@ -80,6 +122,19 @@ func (q *Queries) CountPendingClosures(ctx context.Context) (int64, error) {
 	return count, err
 }
 
+const deleteClosure = `-- name: DeleteClosure :execrows
+DELETE FROM closures
+WHERE key = $1
+`
+
+func (q *Queries) DeleteClosure(ctx context.Context, key string) (int64, error) {
+	result, err := q.db.Exec(ctx, deleteClosure, key)
+	if err != nil {
+		return 0, err
+	}
+	return result.RowsAffected(), nil
+}
+
 const deleteClosures = `-- name: DeleteClosures :execrows
 DELETE FROM closures
 WHERE closures.updated_at < $1
@@ -246,6 +301,54 @@ func (q *Queries) GetMultipartUpload(ctx context.Context, arg GetMultipartUpload
 	return i, err
 }
 
+const getObjectReferrers = `-- name: GetObjectReferrers :many
+SELECT key FROM objects
+WHERE refs @> ARRAY[$1::varchar]
+  AND key <> $1::varchar
+  AND deleted_at IS NULL
+UNION
+SELECT key FROM pending_objects
+WHERE refs @> ARRAY[$1::varchar]
+  AND key <> $1::varchar
+ORDER BY key
+`
+
+// Live and pending objects other than the object itself that reference it.
+func (q *Queries) GetObjectReferrers(ctx context.Context, key string) ([]string, error) {
+	rows, err := q.db.Query(ctx, getObjectReferrers, key)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+	var items []string
+	for rows.Next() {
+		var key string
+		if err := rows.Scan(&key); err != nil {
+			return nil, err
+		}
+		items = append(items, key)
+	}
+	if err := rows.Err(); err != nil {
+		return nil, err
+	}
+	return items, nil
+}
+
+const getObjectRefsForUpdate = `-- name: GetObjectRefsForUpdate :one
+SELECT refs FROM objects
+WHERE key = $1 AND deleted_at IS NULL
+FOR UPDATE
+`
+
+// Lock a live object so that its references cannot change while it is
+// deleted.
+func (q *Queries) GetObjectRefsForUpdate(ctx context.Context, key string) ([]string, error) {
+	row := q.db.QueryRow(ctx, getObjectRefsForUpdate, key)
+	var refs []string
+	err := row.Scan(&refs)
+	return refs, err
+}
+
 const getObjectStats = `-- name: GetObjectStats :one
 SELECT object_count, total_bytes FROM object_stats WHERE id
 `
@@ -329,6 +432,37 @@ func (q *Queries) GetOldMultipartUploads(ctx context.Context, dollar_1 int32) ([
 	return items, nil
 }
 
+const getPendingClosureMultipartUploads = `-- name: GetPendingClosureMultipartUploads :many
+SELECT upload_id, object_key
+FROM multipart_uploads
+WHERE pending_closure_id = $1
+`
+
+type GetPendingClosureMultipartUploadsRow struct {
+	UploadID  string `json:"upload_id"`
+	ObjectKey string `json:"object_key"`
+}
+
+func (q *Queries) GetPendingClosureMultipartUploads(ctx context.Context, pendingClosureID int64) ([]GetPendingClosureMultipartUploadsRow, error) {
+	rows, err := q.db.Query(ctx, getPendingClosureMultipartUploads, pendingClosureID)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+	var items []GetPendingClosureMultipartUploadsRow
+	for rows.Next() {
+		var i GetPendingClosureMultipartUploadsRow
+		if err := rows.Scan(&i.UploadID, &i.ObjectKey); err != nil {
+			return nil, err
+		}
+		items = append(items, i)
+	}
+	if err := rows.Err(); err != nil {
+		return nil, err
+	}
+	return items, nil
+}
+
 const getPendingObjectKeys = `-- name: GetPendingObjectKeys :many
 SELECT key FROM pending_objects
 WHERE pending_closure_id = $1
@@ -442,6 +576,32 @@ type InsertPendingObjectsParams struct {
 	Size             pgtype.Int8 `json:"size"`
 }
 
+const listPinNamesForClosure = `-- name: ListPinNamesForClosure :many
+SELECT name FROM pins
+WHERE narinfo_key = $1
+ORDER BY name
+`
+
+func (q *Queries) ListPinNamesForClosure(ctx context.Context, narinfoKey string) ([]string, error) {
+	rows, err := q.db.Query(ctx, listPinNamesForClosure, narinfoKey)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+	var items []string
+	for rows.Next() {
+		var name string
+		if err := rows.Scan(&name); err != nil {
+			return nil, err
+		}
+		items = append(items, name)
+	}
+	if err := rows.Err(); err != nil {
+		return nil, err
+	}
+	return items, nil
+}
+
 const listPins = `-- name: ListPins :many
 SELECT name, narinfo_key, store_path, created_at, updated_at
 FROM pins
@@ -484,6 +644,21 @@ func (q *Queries) MarkObjectsAsActive(ctx context.Context, dollar_1 []string) er
 	return err
 }
 
+const markObjectsForDeletion = `-- name: MarkObjectsForDeletion :exec
+UPDATE objects
+SET
+    deleted_at = timezone('UTC', now()),
+    first_deleted_at = COALESCE(first_deleted_at, timezone('UTC', now()))
+WHERE key = any($1::varchar []) AND deleted_at IS NULL
+`
+
+// Tombstone objects the way MarkStaleObjects does, so that GC removes them
+// after the grace period and pushes in the meantime wait for the deletion.
+func (q *Queries) MarkObjectsForDeletion(ctx context.Context, dollar_1 []string) error {
+	_, err := q.db.Exec(ctx, markObjectsForDeletion, dollar_1)
+	return err
+}
+
 const markStaleObjects = `-- name: MarkStaleObjects :execrows
 WITH RECURSIVE ct AS (
     SELECT timezone('UTC', now()) AS now
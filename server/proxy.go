@@ -151,8 +151,8 @@ var (
 	// narinfo: {32-char nix-base32 hash}.narinfo
 	narinfoRe = regexp.MustCompile(`^[` + nixBase32Alphabet + `]{32}\.narinfo$`)
 
-	// nar: nar/{52-char nix-base32 hash}.nar[.zst|.xz|.bz2]
-	narRe = regexp.MustCompile(`^nar/[` + nixBase32Alphabet + `]{52}\.nar(\.zst|\.xz|\.bz2)?$`)
+	// nar: nar/{52-char nix-base32 hash}.nar[.zst|.xz|.bz2|.br]
+	narRe = regexp.MustCompile(`^nar/[` + nixBase32Alphabet + `]{52}\.nar(\.zst|\.xz|\.bz2|\.br)?$`)
 
 	// ls: {32-char nix-base32 hash}.ls
 	lsRe = regexp.MustCompile(`^[` + nixBase32Alphabet + `]{32}\.ls$`)
@@ -165,7 +165,8 @@ var (
 	realisationsRe = regexp.MustCompile(`^realisations/[a-z0-9]+:[a-zA-Z0-9+/=]+![a-zA-Z0-9+._?=-]+\.doi$`)
 )
 
-// IsValidCachePath checks whether a path matches a known Nix binary cache object pattern.
+// IsValidCachePath checks whether a path matches a known Nix binary cache object pattern,
+// optionally below a key prefix (see IsValidKeyPrefix).
 // It rejects path traversal, leading slashes, and any pattern outside the allowlist.
 func IsValidCachePath(path string) bool {
 	if path == "" {
@@ -184,6 +185,10 @@ func IsValidCachePath(path string) bool {
 		return false
 	}
 
+	return matchPrefixed(path, isCacheObjectPath)
+}
+
+func isCacheObjectPath(path string) bool {
 	// Special files
 	if path == "nix-cache-info" || path == "index.html" {
 		return true
@@ -240,6 +245,11 @@ func (s *Service) ReadProxyHandler(w http.ResponseWriter, r *http.Request) {
 		return
 	}
 
+	// Caches below a key prefix share the bucket's nix-cache-info.
+	if strings.HasSuffix(key, "/nix-cache-info") {
+		key = "nix-cache-info"
+	}
+
 	// Wait for rate limiter
 	if err := s.S3RateLimiter.Wait(r.Context()); err != nil {
 		http.Error(w, "service unavailable", http.StatusServiceUnavailable)
@@ -392,6 +402,34 @@ func (s *Service) handleProxyGet(w http.ResponseWriter, r *http.Request, key str
 	}
 }
 
+// decodeStoredNarinfo returns the plain text of a narinfo object read from
+// S3, decompressing it if it still carries Content-Encoding: zstd.
+func decodeStoredNarinfo(data []byte, info *minio.ObjectInfo) ([]byte, error) {
+	// S3 stores Content-Encoding either as a standard header or as user
+	// metadata (X-Amz-Meta-Content-Encoding) depending on the implementation.
+	contentEncoding := info.Metadata.Get("Content-Encoding")
+	if contentEncoding == "" {
+		contentEncoding = info.Metadata.Get("X-Amz-Meta-Content-Encoding")
+	}
+
+	if !strings.EqualFold(contentEncoding, "zstd") {
+		return data, nil
+	}
+
+	decoder, ok := zstdDecoderPool.Get().(*zstd.Decoder)
+	if !ok {
+		return nil, errors.New("failed to get zstd decoder from pool")
+	}
+	defer zstdDecoderPool.Put(decoder)
+
+	plain, err := decoder.DecodeAll(data, nil)
+	if err != nil {
+		return nil, fmt.Errorf("decompressing narinfo: %w", err)
+	}
+
+	return plain, nil
+}
+
 // serveDecompressedNarinfo reads a narinfo from S3 and writes the decompressed
 // content to the response. Narinfos are tiny (~500 bytes compressed) so
 // buffering the whole thing is fine.
@@ -409,32 +447,12 @@ func (s *Service) serveDecompressedNarinfo(w http.ResponseWriter, obj *minio.Obj
 		return
 	}
 
-	plain := data
-
-	// S3 stores Content-Encoding either as a standard header or as user
-	// metadata (X-Amz-Meta-Content-Encoding) depending on the implementation.
-	contentEncoding := info.Metadata.Get("Content-Encoding")
-	if contentEncoding == "" {
-		contentEncoding = info.Metadata.Get("X-Amz-Meta-Content-Encoding")
-	}
-
-	if strings.EqualFold(contentEncoding, "zstd") {
-		decoder, ok := zstdDecoderPool.Get().(*zstd.Decoder)
-		if !ok {
-			slog.Error("Failed to get zstd decoder from pool")
-			http.Error(w, "Internal Server Error", http.StatusInternalServerError)
-
-			return
-		}
-		defer zstdDecoderPool.Put(decoder)
-
-		plain, err = decoder.DecodeAll(data, nil)
-		if err != nil {
-			slog.Error("Failed to decompress narinfo", "error", err)
-			http.Error(w, "Bad Gateway", http.StatusBadGateway)
+	plain, err := decodeStoredNarinfo(data, info)
+	if err != nil {
+		slog.Error("Failed to decompress narinfo", "error", err)
+		http.Error(w, "Bad Gateway", http.StatusBadGateway)
 
-			return
-		}
+		return
 	}
 
 	w.Header().Set("Content-Type", "text/x-nix-narinfo")
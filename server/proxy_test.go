@@ -28,12 +28,16 @@ func TestIsValidCachePath(t *testing.T) {
 		{"nar zst", "nar/1ngi2dxw1f7khrrjamzkkdai393lwcm8s78gvs1ag8k3n82w7bvp.nar.zst", true},
 		{"nar xz", "nar/1ngi2dxw1f7khrrjamzkkdai393lwcm8s78gvs1ag8k3n82w7bvp.nar.xz", true},
 		{"nar bz2", "nar/1ngi2dxw1f7khrrjamzkkdai393lwcm8s78gvs1ag8k3n82w7bvp.nar.bz2", true},
+		{"nar br", "nar/1ngi2dxw1f7khrrjamzkkdai393lwcm8s78gvs1ag8k3n82w7bvp.nar.br", true},
 		{"nar uncompressed", "nar/1ngi2dxw1f7khrrjamzkkdai393lwcm8s78gvs1ag8k3n82w7bvp.nar", true},
 		{"ls", "26xbg1ndr7hbcncrlf9nhx5is2b25d13.ls", true},
 		{"log", "log/k3b2gg5n0p2q8r9t1v4w6x7y-my-package-1.0.drv", true},
 		{"realisation", "realisations/sha256:abc123def456!out.doi", true},
 		{"nix-cache-info", "nix-cache-info", true},
 		{"index.html", "index.html", true},
+		{"prefixed narinfo", "team-a/26xbg1ndr7hbcncrlf9nhx5is2b25d13.narinfo", true},
+		{"prefixed nar", "org/team-a/nar/1ngi2dxw1f7khrrjamzkkdai393lwcm8s78gvs1ag8k3n82w7bvp.nar.zst", true},
+		{"prefixed nix-cache-info", "team-a/nix-cache-info", true},
 
 		// Path traversal
 		{"traversal parent", "../etc/passwd", false},
@@ -49,6 +53,7 @@ func TestIsValidCachePath(t *testing.T) {
 		{"leading slash", "/26xbg1ndr7hbcncrlf9nhx5is2b25d13.narinfo", false},
 		{"wrong extension", "26xbg1ndr7hbcncrlf9nhx5is2b25d13.narinfo.bak", false},
 		{"short hash", "abc.narinfo", false},
+		{"invalid prefix", "-team/26xbg1ndr7hbcncrlf9nhx5is2b25d13.narinfo", false},
 	}
 
 	for _, tc := range tests {
@@ -10,7 +10,6 @@ import (
 	"log/slog"
 	"net"
 	"net/http"
-	"os"
 	"os/signal"
 	"strings"
 	"syscall"
@@ -285,14 +284,21 @@ func runServer(opts *options) error {
 	mux.Handle("GET /metrics", service.Metrics.Handler())
 	mux.HandleFunc("GET /api/cache-config", service.CacheConfigHandler)
 	mux.HandleFunc("GET /api/cache-stats", service.CacheStatsHandler)
+	mux.HandleFunc("PUT /api/cache-info", service.AuthMiddleware(service.PutCacheInfoHandler))
 
 	mux.HandleFunc("POST /api/pending_closures", service.AuthMiddleware(service.CreatePendingClosureHandler))
 	mux.HandleFunc("DELETE /api/pending_closures", service.AuthMiddleware(service.CleanupPendingClosuresHandler))
+	mux.HandleFunc("DELETE /api/pending_closures/{id}", service.AuthMiddleware(service.AbortPendingClosureHandler))
 	mux.HandleFunc("POST /api/pending_closures/{id}/sign", service.AuthMiddleware(service.SignNarinfosHandler))
+	mux.HandleFunc("POST /api/pending_closures/{id}/presign", service.AuthMiddleware(service.PresignObjectHandler))
 	mux.HandleFunc("POST /api/pending_closures/{id}/complete", service.AuthMiddleware(service.CommitPendingClosureHandler))
+	mux.HandleFunc("POST /api/pending_closures/complete", service.AuthMiddleware(service.CommitPendingClosuresHandler))
 	mux.HandleFunc("POST /api/multipart/complete", service.AuthMiddleware(service.CompleteMultipartUploadHandler))
 	mux.HandleFunc("POST /api/multipart/request-parts", service.AuthMiddleware(service.RequestMorePartsHandler))
 	mux.HandleFunc("HEAD /api/objects/{key...}", service.AuthMiddleware(service.ObjectExistsHandler))
+	mux.HandleFunc("POST /api/narinfo_exists", service.AuthMiddleware(service.NarinfoExistsHandler))
+	mux.HandleFunc("DELETE /api/objects/{key...}", service.AuthMiddleware(service.DeleteObjectHandler))
+	mux.HandleFunc("POST /api/signatures/{key...}", service.AuthMiddleware(service.AddSignaturesHandler))
 	mux.HandleFunc("GET /api/closures/{key}", service.AuthMiddleware(service.GetClosureHandler))
 	mux.HandleFunc("DELETE /api/closures", service.AuthMiddleware(service.CleanupClosuresOlder))
 	mux.HandleFunc("GET /api/gc/status", service.AuthMiddleware(service.GCStatusHandler))
@@ -475,17 +481,12 @@ func (s *Service) InitializeBucket(ctx context.Context) error {
 		}
 
 		// Object doesn't exist, create it
-		// Priority 30 is higher than the default nixos.org cache (priority 40)
-		// Use NIX_STORE_DIR from environment if set, otherwise default to /nix/store
-		storeDir := os.Getenv("NIX_STORE_DIR")
-		if storeDir == "" {
-			storeDir = "/nix/store"
-		}
+		var cacheInfo string
 
-		cacheInfo := fmt.Sprintf(`StoreDir: %s
-WantMassQuery: 1
-Priority: 30
-`, storeDir)
+		cacheInfo, err = RenderCacheInfo(defaultCacheInfo())
+		if err != nil {
+			return fmt.Errorf("invalid NIX_STORE_DIR for nix-cache-info: %w", err)
+		}
 
 		// Wait for rate limiter before PutObject
 		if err := s.S3RateLimiter.Wait(ctx); err != nil {
@@ -0,0 +1,244 @@
+package server
+
+import (
+	"bytes"
+	"context"
+	"encoding/base64"
+	"encoding/json"
+	"fmt"
+	"io"
+	"log/slog"
+	"net/http"
+	"slices"
+	"strings"
+
+	"github.com/Mic92/niks3/api"
+	"github.com/jackc/pgx/v5/pgxpool"
+	"github.com/klauspost/compress/zstd"
+	minio "github.com/minio/minio-go/v7"
+)
+
+// AddSignaturesHandler handles POST /api/signatures/{key}.
+// It adds Sig lines to a narinfo already in the cache without touching its
+// NAR, so a new signing key can be rolled out over existing paths. The
+// narinfo is rewritten here rather than uploaded through a presigned URL,
+// so a client can add signatures but not change any other field. Existing
+// signatures are kept.
+func (s *Service) AddSignaturesHandler(w http.ResponseWriter, r *http.Request) {
+	defer func() {
+		if err := r.Body.Close(); err != nil {
+			slog.Error("Failed to close request body", "error", err)
+		}
+	}()
+
+	key := r.PathValue("key")
+	if !IsValidUploadKey(key, "narinfo") {
+		http.Error(w, "invalid key: signatures can only be added to narinfos", http.StatusBadRequest)
+
+		return
+	}
+
+	var req api.AddSignaturesRequest
+	if !decodeJSONBody(w, r, maxAPIRequestBody, &req) {
+		return
+	}
+
+	for _, sig := range req.Signatures {
+		if err := validateSignature(sig); err != nil {
+			http.Error(w, "invalid signature: "+err.Error(), http.StatusBadRequest)
+
+			return
+		}
+	}
+
+	// Read, append and write back under a per-narinfo lock, or two
+	// concurrent requests would each drop the other's signatures.
+	unlock, err := lockNarinfo(r.Context(), s.Pool, key)
+	if err != nil {
+		slog.Error("Failed to lock narinfo", "key", key, "error", err)
+		http.Error(w, "failed to lock narinfo: "+err.Error(), http.StatusServiceUnavailable)
+
+		return
+	}
+	defer unlock()
+
+	if err := s.S3RateLimiter.Wait(r.Context()); err != nil {
+		http.Error(w, "request cancelled", http.StatusServiceUnavailable)
+
+		return
+	}
+
+	content, err := s.readNarinfo(r, key)
+	if err != nil {
+		if minio.ToErrorResponse(err).Code == minio.NoSuchKey {
+			http.Error(w, "narinfo not found", http.StatusNotFound)
+
+			return
+		}
+
+		if isRateLimitError(err) {
+			s.S3RateLimiter.RecordThrottle()
+		}
+
+		slog.Error("Failed to read narinfo", "key", key, "error", err)
+		http.Error(w, "failed to read narinfo: "+err.Error(), http.StatusInternalServerError)
+
+		return
+	}
+
+	updated, added := addNarinfoSignatures(string(content), req.Signatures)
+	if added > 0 {
+		compressed, err := compressNarinfo([]byte(updated))
+		if err != nil {
+			http.Error(w, err.Error(), http.StatusInternalServerError)
+
+			return
+		}
+
+		_, err = s.MinioClient.PutObject(r.Context(), s.Bucket, key,
+			bytes.NewReader(compressed), int64(len(compressed)),
+			minio.PutObjectOptions{ContentType: "text/x-nix-narinfo", ContentEncoding: "zstd"})
+		if err != nil {
+			if isRateLimitError(err) {
+				s.S3RateLimiter.RecordThrottle()
+			}
+
+			slog.Error("Failed to write narinfo", "key", key, "error", err)
+			http.Error(w, "failed to write narinfo: "+err.Error(), http.StatusInternalServerError)
+
+			return
+		}
+
+		slog.Info("Added narinfo signatures", "key", key, "added", added)
+	}
+
+	s.S3RateLimiter.RecordSuccess()
+
+	w.Header().Set("Content-Type", "application/json")
+
+	if err := json.NewEncoder(w).Encode(api.AddSignaturesResponse{Added: added}); err != nil {
+		slog.Error("Failed to encode response", "error", err)
+	}
+}
+
+// narinfoLockClass namespaces the per-narinfo advisory locks. Locks taken
+// with two int4 keys never collide with single-bigint ones like
+// gcAdvisoryLockKey.
+const narinfoLockClass int32 = 0x6e697369 // "nisi"
+
+// lockNarinfo takes an advisory lock on key on a dedicated connection, so
+// signatures are added to one narinfo by one request at a time across all
+// replicas sharing the database. The returned func unlocks and releases
+// the connection.
+func lockNarinfo(ctx context.Context, pool *pgxpool.Pool, key string) (func(), error) {
+	conn, err := pool.Acquire(ctx)
+	if err != nil {
+		return nil, fmt.Errorf("failed to acquire connection for narinfo lock: %w", err)
+	}
+
+	if _, err := conn.Exec(ctx, "SELECT pg_advisory_lock($1, hashtext($2))", narinfoLockClass, key); err != nil {
+		conn.Release()
+
+		return nil, fmt.Errorf("failed to take narinfo advisory lock: %w", err)
+	}
+
+	// Fresh context so unlock runs even if the request context is done.
+	//nolint:contextcheck // detached on purpose
+	unlock := func() {
+		if _, err := conn.Exec(context.Background(), "SELECT pg_advisory_unlock($1, hashtext($2))", narinfoLockClass, key); err != nil {
+			slog.Error("failed to release narinfo advisory lock", "key", key, "error", err)
+		}
+
+		conn.Release()
+	}
+
+	return unlock, nil
+}
+
+// readNarinfo returns the plain text of the narinfo at key.
+func (s *Service) readNarinfo(r *http.Request, key string) ([]byte, error) {
+	obj, err := s.MinioClient.GetObject(r.Context(), s.Bucket, key, minio.GetObjectOptions{})
+	if err != nil {
+		return nil, fmt.Errorf("getting %s: %w", key, err)
+	}
+
+	defer func() {
+		if err := obj.Close(); err != nil {
+			slog.Warn("Failed to close S3 object", "key", key, "error", err)
+		}
+	}()
+
+	info, err := obj.Stat()
+	if err != nil {
+		return nil, fmt.Errorf("getting %s: %w", key, err)
+	}
+
+	data, err := io.ReadAll(obj)
+	if err != nil {
+		return nil, fmt.Errorf("reading %s: %w", key, err)
+	}
+
+	return decodeStoredNarinfo(data, &info)
+}
+
+// validateSignature checks that sig has the "<key name>:<base64>" form of a
+// narinfo Sig value, so it cannot inject other lines.
+func validateSignature(sig string) error {
+	name, value, ok := strings.Cut(sig, ":")
+	if !ok || name == "" || strings.ContainsAny(name, " \t\r\n") {
+		return fmt.Errorf("%q is not of the form <key name>:<signature>", sig)
+	}
+
+	if _, err := base64.StdEncoding.DecodeString(value); err != nil {
+		return fmt.Errorf("%q: signature is not base64: %w", sig, err)
+	}
+
+	return nil
+}
+
+// addNarinfoSignatures appends a Sig line for each of sigs that content
+// does not already have, and returns the new content and how many were
+// added.
+func addNarinfoSignatures(content string, sigs []string) (string, int) {
+	var existing []string
+
+	for line := range strings.Lines(content) {
+		if sig, ok := strings.CutPrefix(strings.TrimRight(line, "\r\n"), "Sig: "); ok {
+			existing = append(existing, sig)
+		}
+	}
+
+	var sb strings.Builder
+
+	sb.WriteString(content)
+
+	if content != "" && !strings.HasSuffix(content, "\n") {
+		sb.WriteString("\n")
+	}
+
+	added := 0
+
+	for _, sig := range sigs {
+		if slices.Contains(existing, sig) {
+			continue
+		}
+
+		existing = append(existing, sig)
+		added++
+
+		sb.WriteString("Sig: " + sig + "\n")
+	}
+
+	return sb.String(), added
+}
+
+// compressNarinfo compresses a narinfo with zstd, as clients store them.
+func compressNarinfo(content []byte) ([]byte, error) {
+	encoder, err := zstd.NewWriter(nil)
+	if err != nil {
+		return nil, fmt.Errorf("creating zstd encoder: %w", err)
+	}
+	defer func() { _ = encoder.Close() }()
+
+	return encoder.EncodeAll(content, nil), nil
+}
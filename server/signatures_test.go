@@ -0,0 +1,110 @@
+package server_test
+
+import (
+	"bytes"
+	"encoding/base64"
+	"encoding/json"
+	"fmt"
+	"io"
+	"net/http"
+	"net/http/httptest"
+	"strings"
+	"sync"
+	"testing"
+
+	"github.com/Mic92/niks3/api"
+	"github.com/Mic92/niks3/server"
+	"github.com/klauspost/compress/zstd"
+	minio "github.com/minio/minio-go/v7"
+)
+
+func TestAddNarinfoSignatures(t *testing.T) {
+	t.Parallel()
+
+	const narinfo = "StorePath: /nix/store/aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa-foo\nNarSize: 1\nSig: old-1:b2xk\n"
+
+	got, added := server.AddNarinfoSignatures(narinfo, []string{"old-1:b2xk", "new-1:bmV3"})
+	if added != 1 {
+		t.Errorf("added %d signatures, want 1", added)
+	}
+
+	if want := narinfo + "Sig: new-1:bmV3\n"; got != want {
+		t.Errorf("got %q, want %q", got, want)
+	}
+
+	// Adding the same signature again changes nothing.
+	if again, added := server.AddNarinfoSignatures(got, []string{"new-1:bmV3"}); added != 0 || again != got {
+		t.Errorf("re-adding a signature gave %q (%d added)", again, added)
+	}
+}
+
+func TestService_AddSignaturesHandlerConcurrent(t *testing.T) {
+	t.Parallel()
+
+	ctx := t.Context()
+	service := createTestService(t)
+
+	defer service.Close()
+
+	const key = "26xbg1ndr7hbcncrlf9nhx5is2b25d13.narinfo"
+
+	putTestObject(ctx, t, service, key,
+		[]byte("StorePath: /nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-foo\nNarSize: 1\n"),
+		minio.PutObjectOptions{ContentType: "text/x-nix-narinfo"})
+
+	// Every request reads the narinfo before any has written it back
+	// unless the handler serializes them, so a lost update shows up as a
+	// missing signature.
+	const requests = 8
+
+	bodies := make([][]byte, requests)
+
+	for i := range requests {
+		sig := fmt.Sprintf("key-%d:%s", i, base64.StdEncoding.EncodeToString([]byte{byte(i)}))
+
+		body, err := json.Marshal(api.AddSignaturesRequest{Signatures: []string{sig}})
+		ok(t, err)
+
+		bodies[i] = body
+	}
+
+	codes := make([]int, requests)
+
+	var wg sync.WaitGroup
+
+	for i, body := range bodies {
+		wg.Go(func() {
+			req := httptest.NewRequestWithContext(ctx, http.MethodPost, "/api/signatures/"+key, bytes.NewReader(body))
+			req.SetPathValue("key", key)
+
+			rr := httptest.NewRecorder()
+			service.AddSignaturesHandler(rr, req)
+			codes[i] = rr.Code
+		})
+	}
+
+	wg.Wait()
+
+	for i, code := range codes {
+		if code != http.StatusOK {
+			t.Errorf("request %d: status %d", i, code)
+		}
+	}
+
+	obj, err := service.MinioClient.GetObject(ctx, service.Bucket, key, minio.GetObjectOptions{})
+	ok(t, err)
+
+	defer func() { _ = obj.Close() }()
+
+	decoder, err := zstd.NewReader(obj)
+	ok(t, err)
+
+	defer decoder.Close()
+
+	content, err := io.ReadAll(decoder)
+	ok(t, err)
+
+	if got := strings.Count(string(content), "\nSig: "); got != requests {
+		t.Errorf("narinfo has %d signatures, want %d:\n%s", got, requests, content)
+	}
+}
@@ -3,6 +3,7 @@ package signing
 import (
 	"errors"
 	"fmt"
+	"slices"
 	"sort"
 	"strconv"
 	"strings"
@@ -46,10 +47,12 @@ func GenerateFingerprint(info *NarInfo) ([]byte, error) {
 		}
 	}
 
-	// Sort references to ensure deterministic fingerprints
+	// Sort references to ensure deterministic fingerprints; a duplicate
+	// would make the fingerprint differ from the one Nix computes
 	sortedRefs := make([]string, len(info.References))
 	copy(sortedRefs, info.References)
 	sort.Strings(sortedRefs)
+	sortedRefs = slices.Compact(sortedRefs)
 
 	// Build the fingerprint
 	var builder strings.Builder
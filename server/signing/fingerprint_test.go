@@ -54,6 +54,19 @@ func TestGenerateFingerprint(t *testing.T) {
 			expected:    "1;/nix/store/test;sha256:1mkvday29m2qxg1fnbv8xh9s6151bh8a2xzhh0k86j7lqhyfwibh;100;/nix/store/aaa-package,/nix/store/mmm-package,/nix/store/zzz-package",
 			shouldError: false,
 		},
+		{
+			name:      "duplicate references appear once",
+			storePath: "/nix/store/test",
+			narHash:   "sha256:1mkvday29m2qxg1fnbv8xh9s6151bh8a2xzhh0k86j7lqhyfwibh",
+			narSize:   100,
+			references: []string{
+				"/nix/store/mmm-package",
+				"/nix/store/aaa-package",
+				"/nix/store/mmm-package",
+			},
+			expected:    "1;/nix/store/test;sha256:1mkvday29m2qxg1fnbv8xh9s6151bh8a2xzhh0k86j7lqhyfwibh;100;/nix/store/aaa-package,/nix/store/mmm-package",
+			shouldError: false,
+		},
 		{
 			name:        "invalid nar hash prefix",
 			storePath:   "/nix/store/test",
@@ -5,6 +5,7 @@ import (
 	"encoding/base64"
 	"errors"
 	"fmt"
+	"io"
 	"os"
 	"strings"
 )
@@ -66,6 +67,35 @@ func ParseKey(s string) (*Key, error) {
 	}, nil
 }
 
+// GenerateKey creates a new Ed25519 signing key called name, like
+// `nix key generate-secret --key-name name`. Randomness is read from random,
+// or from crypto/rand if it is nil.
+func GenerateKey(name string, random io.Reader) (*Key, error) {
+	if name == "" {
+		return nil, errors.New("empty key name")
+	}
+
+	if strings.ContainsAny(name, ": \t\n") {
+		return nil, fmt.Errorf("key name %q must not contain ':' or whitespace", name)
+	}
+
+	_, privateKey, err := ed25519.GenerateKey(random)
+	if err != nil {
+		return nil, fmt.Errorf("failed to generate Ed25519 key: %w", err)
+	}
+
+	return &Key{
+		Name: name,
+		key:  privateKey,
+	}, nil
+}
+
+// SecretKey returns the key in the format "name:base64-keypair" written by
+// `nix key generate-secret` and accepted by ParseKey.
+func (k *Key) SecretKey() string {
+	return fmt.Sprintf("%s:%s", k.Name, base64.StdEncoding.EncodeToString(k.key))
+}
+
 // LoadKeyFromFile loads a signing key from a file
 // The file should contain a key in the format "name:base64-key".
 func LoadKeyFromFile(path string) (*Key, error) {
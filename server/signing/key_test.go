@@ -163,3 +163,54 @@ func TestSignNarinfo(t *testing.T) {
 		t.Errorf("Signatures should be deterministic")
 	}
 }
+
+func TestGenerateKeyRoundTrip(t *testing.T) {
+	t.Parallel()
+
+	key, err := signing.GenerateKey("cache.example.com-1", nil)
+	if err != nil {
+		t.Fatalf("GenerateKey failed: %v", err)
+	}
+
+	secret := key.SecretKey()
+
+	// nix writes the 64-byte keypair: 88 base64 characters after the name.
+	if name, encoded, _ := strings.Cut(secret, ":"); name != "cache.example.com-1" || len(encoded) != 88 {
+		t.Fatalf("unexpected secret key format %q", secret)
+	}
+
+	public, err := key.PublicKey()
+	if err != nil {
+		t.Fatalf("PublicKey failed: %v", err)
+	}
+
+	parsed, err := signing.ParseKey(secret)
+	if err != nil {
+		t.Fatalf("ParseKey of generated secret failed: %v", err)
+	}
+
+	sigs, err := signing.SignNarinfo([]*signing.Key{parsed}, testNarInfo())
+	if err != nil {
+		t.Fatalf("SignNarinfo failed: %v", err)
+	}
+
+	pub, err := signing.ParsePublicKey(public)
+	if err != nil {
+		t.Fatalf("ParsePublicKey of generated public key failed: %v", err)
+	}
+
+	ok, err := signing.VerifyNarinfo([]*signing.PublicKey{pub}, testNarInfo(), sigs)
+	if err != nil {
+		t.Fatalf("VerifyNarinfo failed: %v", err)
+	}
+
+	if !ok {
+		t.Fatal("signature by generated key did not verify")
+	}
+
+	for _, name := range []string{"", "a:b", "a b"} {
+		if _, err := signing.GenerateKey(name, nil); err == nil {
+			t.Errorf("GenerateKey(%q) should fail", name)
+		}
+	}
+}
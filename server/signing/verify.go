@@ -0,0 +1,75 @@
+package signing
+
+import (
+	"crypto/ed25519"
+	"encoding/base64"
+	"errors"
+	"fmt"
+	"strings"
+)
+
+// PublicKey is a named Ed25519 verification key as it appears in
+// trusted-public-keys ("name:base64-public-key").
+type PublicKey struct {
+	Name string
+	key  ed25519.PublicKey
+}
+
+// ParsePublicKey parses a public key in the format "name:base64-key".
+func ParsePublicKey(s string) (*PublicKey, error) {
+	name, keyBase64, ok := strings.Cut(strings.TrimSpace(s), ":")
+	if !ok {
+		return nil, errors.New("public key does not contain a ':'")
+	}
+
+	if name == "" {
+		return nil, errors.New("empty key name")
+	}
+
+	keyBytes, err := base64.StdEncoding.DecodeString(keyBase64)
+	if err != nil {
+		return nil, fmt.Errorf("failed to decode base64: %w", err)
+	}
+
+	if len(keyBytes) != ed25519.PublicKeySize {
+		return nil, fmt.Errorf("invalid public key length: expected %d bytes, got %d", ed25519.PublicKeySize, len(keyBytes))
+	}
+
+	return &PublicKey{Name: name, key: ed25519.PublicKey(keyBytes)}, nil
+}
+
+// Verify checks a "name:base64-signature" string against msg. Signatures
+// made by a key with a different name never verify.
+func (k *PublicKey) Verify(msg []byte, sig string) bool {
+	name, sigBase64, ok := strings.Cut(sig, ":")
+	if !ok || name != k.Name {
+		return false
+	}
+
+	sigBytes, err := base64.StdEncoding.DecodeString(sigBase64)
+	if err != nil || len(sigBytes) != ed25519.SignatureSize {
+		return false
+	}
+
+	return ed25519.Verify(k.key, msg, sigBytes)
+}
+
+// VerifyNarinfo reports whether at least one of sigs is a valid signature
+// over the fingerprint of info by one of the trusted keys, mirroring how
+// Nix accepts a substitute.
+func VerifyNarinfo(keys []*PublicKey, info *NarInfo, sigs []string) (bool, error) {
+	fingerprint, err := GenerateFingerprint(info)
+	if err != nil {
+		return false, err
+	}
+
+	for _, sig := range sigs {
+		for _, key := range keys {
+			if key != nil && key.Verify(fingerprint, sig) {
+				return true, nil
+			}
+		}
+	}
+
+	return false, nil
+}
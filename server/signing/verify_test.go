@@ -0,0 +1,108 @@
+package signing_test
+
+import (
+	"testing"
+
+	"github.com/Mic92/niks3/server/signing"
+)
+
+// Signature of the narinfo below by the all-zero seed, computed with an
+// independent Ed25519 implementation to pin the fingerprint format.
+const (
+	testPublicKey = "cache.example.com-1:O2onvM62pC1io6jQKm8Nc2UyFXcd4kOmOsBIoYtZ2ik="
+	testSignature = "cache.example.com-1:PMlHFC2gthYgh9n5KQwkkdRt42I2R0IwgSPs2Y0pVn5pTZCCN4LTvlcL8iD8vicKounZZpAsu0A458r6hu+rDg=="
+)
+
+func testNarInfo() *signing.NarInfo {
+	return &signing.NarInfo{
+		StorePath: "/nix/store/26xbg1ndr7hbcncrlf9nhx5is2b25d13-hello-2.12.1",
+		NarHash:   "sha256:1mkvday29m2qxg1fnbv8xh9s6151bh8a2xzhh0k86j7lqhyfwibh",
+		NarSize:   226560,
+		References: []string{
+			"/nix/store/sl141d1g77wvhr050ah87lcyz2czdxa3-glibc-2.40-36",
+		},
+	}
+}
+
+func TestVerifyNarinfo(t *testing.T) {
+	t.Parallel()
+
+	pub, err := signing.ParsePublicKey(testPublicKey)
+	if err != nil {
+		t.Fatalf("ParsePublicKey failed: %v", err)
+	}
+
+	// #nosec G101 -- test key with a dummy all-zero seed
+	key, err := signing.ParseKey("cache.example.com-1:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=")
+	if err != nil {
+		t.Fatalf("ParseKey failed: %v", err)
+	}
+
+	derived, err := key.PublicKey()
+	if err != nil {
+		t.Fatalf("PublicKey failed: %v", err)
+	}
+
+	if derived != testPublicKey {
+		t.Fatalf("derived public key %q, want %q", derived, testPublicKey)
+	}
+
+	sigs, err := signing.SignNarinfo([]*signing.Key{key}, testNarInfo())
+	if err != nil {
+		t.Fatalf("SignNarinfo failed: %v", err)
+	}
+
+	if sigs[0] != testSignature {
+		t.Fatalf("signature %q, want %q", sigs[0], testSignature)
+	}
+
+	ok, err := signing.VerifyNarinfo([]*signing.PublicKey{pub}, testNarInfo(), []string{"other:AAAA", testSignature})
+	if err != nil {
+		t.Fatalf("VerifyNarinfo failed: %v", err)
+	}
+
+	if !ok {
+		t.Error("expected valid signature to verify")
+	}
+
+	tampered := testNarInfo()
+	tampered.NarSize++
+
+	ok, err = signing.VerifyNarinfo([]*signing.PublicKey{pub}, tampered, []string{testSignature})
+	if err != nil {
+		t.Fatalf("VerifyNarinfo failed: %v", err)
+	}
+
+	if ok {
+		t.Error("signature verified for tampered narinfo")
+	}
+
+	other, err := signing.ParsePublicKey("cache.example.com-2:O2onvM62pC1io6jQKm8Nc2UyFXcd4kOmOsBIoYtZ2ik=")
+	if err != nil {
+		t.Fatalf("ParsePublicKey failed: %v", err)
+	}
+
+	ok, err = signing.VerifyNarinfo([]*signing.PublicKey{other}, testNarInfo(), []string{testSignature})
+	if err != nil {
+		t.Fatalf("VerifyNarinfo failed: %v", err)
+	}
+
+	if ok {
+		t.Error("signature verified under a key with a different name")
+	}
+}
+
+func TestParsePublicKeyErrors(t *testing.T) {
+	t.Parallel()
+
+	for _, s := range []string{
+		"no-colon",
+		":O2onvM62pC1io6jQKm8Nc2UyFXcd4kOmOsBIoYtZ2ik=",
+		"name:not-base64!",
+		"name:AAAA",
+	} {
+		if _, err := signing.ParsePublicKey(s); err == nil {
+			t.Errorf("ParsePublicKey(%q) succeeded, want error", s)
+		}
+	}
+}
@@ -1,6 +1,56 @@
 package server
 
-import "strings"
+import (
+	"regexp"
+	"strings"
+)
+
+// maxKeyPrefixLen bounds key prefixes, leaving S3's 1024-byte key limit to
+// the object key itself.
+const maxKeyPrefixLen = 256
+
+// keyPrefixSegmentRe matches one segment of a key prefix.
+var keyPrefixSegmentRe = regexp.MustCompile(`^[a-zA-Z0-9][a-zA-Z0-9._-]*$`)
+
+// IsValidKeyPrefix reports whether prefix can namespace object keys, so that
+// several logical caches share one bucket: each is served from
+// <bucket>/<prefix>/ with the usual layout below it. A prefix is one or more
+// slash-separated segments of letters, digits, '.', '_' and '-' that start
+// with a letter or digit. The empty prefix is the bucket root.
+func IsValidKeyPrefix(prefix string) bool {
+	if prefix == "" {
+		return true
+	}
+
+	if len(prefix) > maxKeyPrefixLen {
+		return false
+	}
+
+	for segment := range strings.SplitSeq(prefix, "/") {
+		if !keyPrefixSegmentRe.MatchString(segment) {
+			return false
+		}
+	}
+
+	return true
+}
+
+// matchPrefixed reports whether key is an object key match accepts, either
+// bare or below a valid key prefix.
+func matchPrefixed(key string, match func(string) bool) bool {
+	rest := key
+
+	for {
+		if match(rest) {
+			return IsValidKeyPrefix(strings.TrimSuffix(key[:len(key)-len(rest)], "/"))
+		}
+
+		var ok bool
+		if _, rest, ok = strings.Cut(rest, "/"); !ok {
+			return false
+		}
+	}
+}
 
 // IsValidUploadKey reports whether a client may request a presigned upload
 // for the given object key and declared type.
@@ -10,7 +60,8 @@ import "strings"
 // server-owned files (nix-cache-info, index.html) are never client-writable.
 // Without this check an authenticated client could obtain presigned PUT URLs
 // for arbitrary S3 keys — overwriting nix-cache-info, hosting attacker HTML
-// under the cache origin, or poisoning unrelated objects.
+// under the cache origin, or poisoning unrelated objects. Keys may carry a
+// key prefix (see IsValidKeyPrefix).
 func IsValidUploadKey(key, objType string) bool {
 	if key == "" {
 		return false
@@ -21,6 +72,10 @@ func IsValidUploadKey(key, objType string) bool {
 		return false
 	}
 
+	return matchPrefixed(key, func(rest string) bool { return isUploadKey(rest, objType) })
+}
+
+func isUploadKey(key, objType string) bool {
 	switch objType {
 	case "narinfo":
 		return narinfoRe.MatchString(key)
@@ -23,6 +23,7 @@ func TestIsValidUploadKey(t *testing.T) {
 		{"narinfo", "26xbg1ndr7hbcncrlf9nhx5is2b25d13.narinfo", "narinfo", true},
 		{"nar zst", "nar/1ngi2dxw1f7khrrjamzkkdai393lwcm8s78gvs1ag8k3n82w7bvp.nar.zst", "nar", true},
 		{"nar xz", "nar/1ngi2dxw1f7khrrjamzkkdai393lwcm8s78gvs1ag8k3n82w7bvp.nar.xz", "nar", true},
+		{"nar br", "nar/1ngi2dxw1f7khrrjamzkkdai393lwcm8s78gvs1ag8k3n82w7bvp.nar.br", "nar", true},
 		{"nar plain", "nar/1ngi2dxw1f7khrrjamzkkdai393lwcm8s78gvs1ag8k3n82w7bvp.nar", "nar", true},
 		{"listing", "26xbg1ndr7hbcncrlf9nhx5is2b25d13.ls", "listing", true},
 		{"build log", "log/abcd1234-hello-1.0.drv", "build_log", true},
@@ -32,10 +33,14 @@ func TestIsValidUploadKey(t *testing.T) {
 		{"build log equals", "log/abcd1234-foo=bar.drv", "build_log", true},
 		{"realisation", "realisations/sha256:abc123!out.doi", "realisation", true},
 		{"realisation plus in output", "realisations/sha256:abc123!out+dev.doi", "realisation", true},
+		{"prefixed narinfo", "team-a/26xbg1ndr7hbcncrlf9nhx5is2b25d13.narinfo", "narinfo", true},
+		{"nested prefix nar", "org/team.a/nar/1ngi2dxw1f7khrrjamzkkdai393lwcm8s78gvs1ag8k3n82w7bvp.nar.zst", "nar", true},
+		{"prefixed build log", "team-a/log/abcd1234-hello-1.0.drv", "build_log", true},
 
 		// Server-owned files: never client-writable
 		{"nix-cache-info", "nix-cache-info", "narinfo", false},
 		{"index.html", "index.html", "narinfo", false},
+		{"prefixed nix-cache-info", "team-a/nix-cache-info", "narinfo", false},
 
 		// Type/key mismatch
 		{"narinfo key, nar type", "26xbg1ndr7hbcncrlf9nhx5is2b25d13.narinfo", "nar", false},
@@ -48,6 +53,9 @@ func TestIsValidUploadKey(t *testing.T) {
 		{"absolute", "/26xbg1ndr7hbcncrlf9nhx5is2b25d13.narinfo", "narinfo", false},
 		{"empty key", "", "narinfo", false},
 		{"unknown type", "26xbg1ndr7hbcncrlf9nhx5is2b25d13.narinfo", "weird", false},
+		{"hidden prefix", ".team/26xbg1ndr7hbcncrlf9nhx5is2b25d13.narinfo", "narinfo", false},
+		{"empty prefix segment", "team//26xbg1ndr7hbcncrlf9nhx5is2b25d13.narinfo", "narinfo", false},
+		{"prefix with space", "my team/26xbg1ndr7hbcncrlf9nhx5is2b25d13.narinfo", "narinfo", false},
 	}
 
 	for _, tc := range tests {
@@ -113,6 +121,36 @@ func TestUploadHandlersRejectInvalidKeys(t *testing.T) {
 		}
 	})
 
+	t.Run("create pending closure rejects keys outside the key prefix", func(t *testing.T) {
+		t.Parallel()
+
+		rr := post(t, svc.CreatePendingClosureHandler, map[string]any{
+			"closure":    "team-a/00000000000000000000000000000000.narinfo",
+			"key_prefix": "team-a",
+			"objects": []map[string]any{
+				{"key": "team-b/00000000000000000000000000000000.narinfo", "type": "narinfo", "refs": []string{}},
+			},
+		})
+		if rr.Code != http.StatusBadRequest {
+			t.Fatalf("expected 400, got %d: %s", rr.Code, rr.Body.String())
+		}
+	})
+
+	t.Run("create pending closure rejects invalid key prefix", func(t *testing.T) {
+		t.Parallel()
+
+		rr := post(t, svc.CreatePendingClosureHandler, map[string]any{
+			"closure":    "../00000000000000000000000000000000.narinfo",
+			"key_prefix": "..",
+			"objects": []map[string]any{
+				{"key": "../00000000000000000000000000000000.narinfo", "type": "narinfo", "refs": []string{}},
+			},
+		})
+		if rr.Code != http.StatusBadRequest {
+			t.Fatalf("expected 400, got %d: %s", rr.Code, rr.Body.String())
+		}
+	})
+
 	t.Run("complete multipart rejects non-NAR key", func(t *testing.T) {
 		t.Parallel()
 
@@ -157,6 +195,7 @@ func TestUploadHandlersRejectOversizedBody(t *testing.T) {
 		"create pending closure": svc.CreatePendingClosureHandler,
 		"complete multipart":     svc.CompleteMultipartUploadHandler,
 		"request more parts":     svc.RequestMorePartsHandler,
+		"put cache info":         svc.PutCacheInfoHandler,
 	}
 
 	for name, h := range handlers {
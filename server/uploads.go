@@ -7,11 +7,13 @@ import (
 	"fmt"
 	"log/slog"
 	"net/http"
+	"slices"
 	"sort"
 	"strconv"
 	"strings"
 	"time"
 
+	"github.com/Mic92/niks3/api"
 	"github.com/Mic92/niks3/server/pg"
 	"github.com/Mic92/niks3/server/signing"
 	"github.com/minio/minio-go/v7"
@@ -27,6 +29,10 @@ const (
 	// maxAPIRequestBody bounds small JSON endpoints (complete, request-parts).
 	// Worst case: complete with 10,000 parts (S3 hard max) ≈ 700 kB. 12× headroom.
 	maxAPIRequestBody = 8 << 20
+
+	// MaxCompletePendingClosures bounds the ids of one POST
+	// /api/pending_closures/complete request; clients split larger pushes.
+	MaxCompletePendingClosures = 10000
 )
 
 // decodeJSONBody decodes a size-limited JSON request body. It writes a
@@ -58,9 +64,10 @@ type objectWithRefs struct {
 }
 
 type createPendingClosureRequest struct {
-	Closure  *string          `json:"closure"`
-	Objects  []objectWithRefs `json:"objects"`
-	VerifyS3 bool             `json:"verify_s3,omitempty"`
+	Closure   *string          `json:"closure"`
+	Objects   []objectWithRefs `json:"objects"`
+	VerifyS3  bool             `json:"verify_s3,omitempty"`
+	KeyPrefix string           `json:"key_prefix,omitempty"` // Every key must start with "<key_prefix>/"
 }
 
 // CreatePendingClosureHandler handles POST /pending_closures endpoint.
@@ -74,6 +81,10 @@ type createPendingClosureRequest struct {
 //	 ]
 //	}
 //
+// An optional "key_prefix" (see IsValidKeyPrefix) namespaces the closure:
+// the closure and object keys must then all start with "<key_prefix>/", and
+// presigned URLs are minted for those keys.
+//
 // Response body:
 //
 //	{
@@ -110,6 +121,18 @@ func (s *Service) CreatePendingClosureHandler(w http.ResponseWriter, r *http.Req
 		return
 	}
 
+	if !IsValidKeyPrefix(req.KeyPrefix) {
+		http.Error(w, fmt.Sprintf("invalid key prefix %q", req.KeyPrefix), http.StatusBadRequest)
+
+		return
+	}
+
+	if !hasKeyPrefix(*req.Closure, req.KeyPrefix) {
+		http.Error(w, fmt.Sprintf("closure key %q is not below key prefix %q", *req.Closure, req.KeyPrefix), http.StatusBadRequest)
+
+		return
+	}
+
 	if len(req.Objects) == 0 {
 		http.Error(w, "missing objects key", http.StatusBadRequest)
 
@@ -120,7 +143,7 @@ func (s *Service) CreatePendingClosureHandler(w http.ResponseWriter, r *http.Req
 
 	for _, object := range req.Objects {
 		// Security gate: must run before any DB or S3 work.
-		if !IsValidUploadKey(object.Key, object.Type) {
+		if !IsValidUploadKey(object.Key, object.Type) || !hasKeyPrefix(object.Key, req.KeyPrefix) {
 			http.Error(w, fmt.Sprintf("invalid object key %q for type %q", object.Key, object.Type), http.StatusBadRequest)
 
 			return
@@ -150,6 +173,12 @@ func (s *Service) CreatePendingClosureHandler(w http.ResponseWriter, r *http.Req
 	}
 }
 
+// hasKeyPrefix reports whether key is below prefix; every key is below the
+// empty prefix.
+func hasKeyPrefix(key, prefix string) bool {
+	return prefix == "" || strings.HasPrefix(key, prefix+"/")
+}
+
 type completedPart struct {
 	PartNumber int    `json:"part_number"`
 	ETag       string `json:"etag"`
@@ -163,12 +192,15 @@ type completeMultipartRequest struct {
 
 type NarinfoMetadata struct {
 	StorePath   string   `json:"store_path"`
-	URL         string   `json:"url"`         // e.g., "nar/xxxxx.nar.zst"
-	Compression string   `json:"compression"` // e.g., "zstd"
-	NarHash     string   `json:"nar_hash"`    // e.g., "sha256:xxxxx"
-	NarSize     uint64   `json:"nar_size"`    // Uncompressed NAR size
-	References  []string `json:"references"`  // Store paths (with /nix/store prefix)
+	URL         string   `json:"url"`                 // e.g., "nar/xxxxx.nar.zst"
+	Compression string   `json:"compression"`         // e.g., "zstd"
+	FileHash    string   `json:"file_hash,omitempty"` // Compressed NAR hash (nix32), unset for deduplicated NARs
+	FileSize    uint64   `json:"file_size,omitempty"` // Compressed NAR size
+	NarHash     string   `json:"nar_hash"`            // e.g., "sha256:xxxxx"
+	NarSize     uint64   `json:"nar_size"`            // Uncompressed NAR size
+	References  []string `json:"references"`          // Store paths (with /nix/store prefix)
 	Deriver     *string  `json:"deriver,omitempty"`
+	System      string   `json:"system,omitempty"`
 	Signatures  []string `json:"signatures,omitempty"`
 	CA          *string  `json:"ca,omitempty"`
 }
@@ -594,6 +626,91 @@ func (s *Service) SignNarinfosHandler(w http.ResponseWriter, r *http.Request) {
 	}
 }
 
+type presignObjectRequest struct {
+	ObjectKey string `json:"object_key"`
+}
+
+type presignObjectResponse struct {
+	PresignedURL string `json:"presigned_url"`
+}
+
+// PresignObjectHandler handles POST /api/pending_closures/{id}/presign endpoint.
+// Mints a fresh presigned PUT URL for an object of the pending closure, for
+// clients whose original URL expired while they were still compressing.
+// Multipart parts are refreshed through /api/multipart/request-parts instead.
+// Request body: JSON with object_key
+// Response body: JSON with presigned_url.
+func (s *Service) PresignObjectHandler(w http.ResponseWriter, r *http.Request) {
+	slog.Info("Received presign request", "method", r.Method, "path", r.URL.Path)
+
+	defer func() {
+		if err := r.Body.Close(); err != nil {
+			slog.Error("Failed to close request body", "error", err)
+		}
+	}()
+
+	pendingClosureValue := r.PathValue("id")
+	if pendingClosureValue == "" {
+		http.Error(w, "missing id", http.StatusBadRequest)
+
+		return
+	}
+
+	parsedUploadID, err := strconv.ParseInt(pendingClosureValue, 10, 32)
+	if err != nil {
+		http.Error(w, fmt.Sprintf("invalid id: %v", err), http.StatusBadRequest)
+
+		return
+	}
+
+	req := &presignObjectRequest{}
+	if !decodeJSONBody(w, r, maxAPIRequestBody, req) {
+		return
+	}
+
+	if req.ObjectKey == "" {
+		http.Error(w, "missing object_key", http.StatusBadRequest)
+
+		return
+	}
+
+	// Only keys the pending closure was created with are signable; they were
+	// checked with IsValidUploadKey then.
+	validObjectKeys, err := pg.New(s.Pool).GetPendingObjectKeys(r.Context(), parsedUploadID)
+	if err != nil {
+		slog.Error("Failed to get pending objects", "id", parsedUploadID, "error", err)
+		http.Error(w, "failed to get pending objects", http.StatusInternalServerError)
+
+		return
+	}
+
+	if !slices.Contains(validObjectKeys, req.ObjectKey) {
+		http.Error(w, "object key is not part of this pending closure", http.StatusNotFound)
+
+		return
+	}
+
+	po, err := s.makePresignedURL(r.Context(), req.ObjectKey, "")
+	if err != nil {
+		if s.handleS3Error(w, err, "presign object") {
+			return
+		}
+
+		slog.Error("Failed to create presigned URL", "object_key", req.ObjectKey, "error", err)
+		http.Error(w, fmt.Sprintf("failed to create presigned URL: %v", err), http.StatusInternalServerError)
+
+		return
+	}
+
+	slog.Info("Refreshed presigned URL", "id", parsedUploadID, "object_key", req.ObjectKey)
+
+	w.Header().Set("Content-Type", "application/json")
+
+	if err := json.NewEncoder(w).Encode(presignObjectResponse{PresignedURL: po.PresignedURL}); err != nil {
+		slog.Error("Failed to encode response", "error", err)
+	}
+}
+
 // CommitPendingClosureHandler handles POST /api/pending_closures/{id}/complete endpoint.
 // Commits the pending closure to the database after all objects have been uploaded.
 // Request body: empty (all uploads should be complete before calling this)
@@ -641,6 +758,137 @@ func (s *Service) CommitPendingClosureHandler(w http.ResponseWriter, r *http.Req
 	w.WriteHeader(http.StatusNoContent)
 }
 
+// CommitPendingClosuresHandler handles POST /api/pending_closures/complete.
+// It completes several pending closures in one request. Each is committed
+// on its own, so one that cannot be completed does not hold back the others;
+// those are reported per id.
+// Request body:
+//
+//	{"ids": ["1", "2"]}
+//
+// Response body:
+//
+//	{"completed": ["1"], "failed": {"2": "pending closure not found"}}
+//
+// Ids in neither list were not tried because the request was cancelled.
+func (s *Service) CommitPendingClosuresHandler(w http.ResponseWriter, r *http.Request) {
+	slog.Info("Received batch complete upload request", "method", r.Method, "path", r.URL.Path)
+
+	defer func() {
+		if err := r.Body.Close(); err != nil {
+			slog.Error("Failed to close request body", "error", err)
+		}
+	}()
+
+	var req api.CompletePendingClosuresRequest
+	if !decodeJSONBody(w, r, maxAPIRequestBody, &req) {
+		return
+	}
+
+	if len(req.IDs) > MaxCompletePendingClosures {
+		http.Error(w, fmt.Sprintf("too many ids: %d, at most %d per request", len(req.IDs), MaxCompletePendingClosures), http.StatusBadRequest)
+
+		return
+	}
+
+	ids := make([]int64, 0, len(req.IDs))
+
+	for _, value := range req.IDs {
+		id, err := strconv.ParseInt(value, 10, 32)
+		if err != nil {
+			http.Error(w, fmt.Sprintf("invalid id %q: %v", value, err), http.StatusBadRequest)
+
+			return
+		}
+
+		ids = append(ids, id)
+	}
+
+	resp := api.CompletePendingClosuresResponse{Completed: []string{}}
+
+	// A commit cannot be undone, so each one runs to the end even if the
+	// request is cancelled, and the ids committed so far are still reported.
+	// The ids after it are left out and stay pending for the client to send
+	// again.
+	commitCtx := context.WithoutCancel(r.Context())
+
+	for i, id := range ids {
+		if r.Context().Err() != nil {
+			slog.Warn("Request cancelled, leaving pending closures uncompleted", "remaining", len(ids)-i)
+
+			break
+		}
+
+		if err := commitPendingClosure(commitCtx, s.Pool, id); err != nil {
+			msg := "pending closure not found"
+			if !errors.Is(err, errPendingClosureNotFound) {
+				slog.Error("Failed to complete upload", "id", id, "error", err)
+
+				msg = fmt.Sprintf("failed to complete upload: %v", err)
+			}
+
+			if resp.Failed == nil {
+				resp.Failed = make(map[string]string)
+			}
+
+			resp.Failed[req.IDs[i]] = msg
+
+			continue
+		}
+
+		resp.Completed = append(resp.Completed, req.IDs[i])
+	}
+
+	slog.Info("Completed uploads", "completed", len(resp.Completed), "failed", len(resp.Failed))
+
+	w.Header().Set("Content-Type", "application/json")
+
+	if err := json.NewEncoder(w).Encode(resp); err != nil {
+		slog.Error("Failed to encode response", "error", err)
+	}
+}
+
+// AbortPendingClosureHandler handles DELETE /api/pending_closures/{id} endpoint.
+// Clients call it when an upload fails so the pending closure does not linger
+// until the periodic cleanup.
+// Request body: -
+// Response body: -.
+func (s *Service) AbortPendingClosureHandler(w http.ResponseWriter, r *http.Request) {
+	slog.Info("Received abort upload request", "method", r.Method, "path", r.URL.Path)
+
+	pendingClosureValue := r.PathValue("id")
+	if pendingClosureValue == "" {
+		http.Error(w, "missing id", http.StatusBadRequest)
+
+		return
+	}
+
+	parsedUploadID, err := strconv.ParseInt(pendingClosureValue, 10, 32)
+	if err != nil {
+		http.Error(w, fmt.Sprintf("invalid id: %v", err), http.StatusBadRequest)
+
+		return
+	}
+
+	if err = s.abortPendingClosure(r.Context(), parsedUploadID); err != nil {
+		if errors.Is(err, errPendingClosureNotFound) {
+			http.Error(w, "pending closure not found", http.StatusNotFound)
+
+			return
+		}
+
+		slog.Error("Failed to abort upload", "id", parsedUploadID, "error", err)
+
+		http.Error(w, fmt.Sprintf("failed to abort upload: %v", err), http.StatusInternalServerError)
+
+		return
+	}
+
+	slog.Info("Aborted upload", "id", parsedUploadID)
+
+	w.WriteHeader(http.StatusNoContent)
+}
+
 // CleanupPendingClosuresHandler handles DELETE /api/pending_closures?older-than=1h endpoint.
 // Request body: -
 // Response body: -.
@@ -7,11 +7,14 @@ import (
 	"fmt"
 	"net/http"
 	"net/http/httptest"
+	"strconv"
 	"strings"
 	"testing"
 	"time"
 
+	"github.com/Mic92/niks3/api"
 	"github.com/Mic92/niks3/server"
+	"github.com/Mic92/niks3/server/pg"
 	"github.com/minio/minio-go/v7"
 )
 
@@ -93,6 +96,198 @@ func TestService_cleanupPendingClosuresHandler(t *testing.T) {
 	})
 }
 
+func TestService_commitPendingClosuresHandler(t *testing.T) {
+	t.Parallel()
+
+	service := createTestService(t)
+	defer service.Close()
+
+	ctx := t.Context()
+	queries := pg.New(service.Pool)
+
+	hash := "cccccccccccccccccccccccccccccccc"
+
+	pendingClosure, err := queries.InsertPendingClosure(ctx, hash+".narinfo")
+	ok(t, err)
+
+	_, err = queries.InsertPendingObjects(ctx, []pg.InsertPendingObjectsParams{
+		{PendingClosureID: pendingClosure.ID, Key: hash + ".narinfo", Refs: []string{}},
+	})
+	ok(t, err)
+
+	id := strconv.FormatInt(pendingClosure.ID, 10)
+
+	body, err := json.Marshal(api.CompletePendingClosuresRequest{IDs: []string{id, "999999"}})
+	ok(t, err)
+
+	rr := testRequest(t, &TestRequest{
+		method:  "POST",
+		path:    "/api/pending_closures/complete",
+		body:    body,
+		handler: service.CommitPendingClosuresHandler,
+	})
+
+	var result api.CompletePendingClosuresResponse
+	ok(t, json.Unmarshal(rr.Body.Bytes(), &result))
+
+	// The unknown id is reported without holding back the other one.
+	if len(result.Completed) != 1 || result.Completed[0] != id {
+		t.Errorf("completed = %v, want [%s]", result.Completed, id)
+	}
+
+	if len(result.Failed) != 1 || result.Failed["999999"] == "" {
+		t.Errorf("failed = %v, want 999999 to fail", result.Failed)
+	}
+
+	body, err = json.Marshal(api.CompletePendingClosuresRequest{IDs: []string{"not-a-number"}})
+	ok(t, err)
+
+	check := checkStatusCode(http.StatusBadRequest)
+	testRequest(t, &TestRequest{
+		method:        "POST",
+		path:          "/api/pending_closures/complete",
+		body:          body,
+		handler:       service.CommitPendingClosuresHandler,
+		checkResponse: &check,
+	})
+}
+
+// cancelAfterChecks is a context that reports itself cancelled once Err was
+// called more than checks times, i.e. partway through a batch.
+type cancelAfterChecks struct {
+	context.Context //nolint:containedctx // wraps the request context
+
+	checks int
+}
+
+func (c *cancelAfterChecks) Err() error {
+	c.checks--
+	if c.checks < 0 {
+		return context.Canceled
+	}
+
+	return nil
+}
+
+// TestService_commitPendingClosuresHandlerCancelled cancels a batch after
+// its first commit. The committed id is still reported and the others stay
+// pending, so sending them again completes them.
+func TestService_commitPendingClosuresHandlerCancelled(t *testing.T) {
+	t.Parallel()
+
+	service := createTestService(t)
+	defer service.Close()
+
+	ctx := t.Context()
+	queries := pg.New(service.Pool)
+
+	ids := make([]string, 0, 3)
+
+	for _, hash := range []string{
+		"dddddddddddddddddddddddddddddddd",
+		"eeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee",
+		"ffffffffffffffffffffffffffffffff",
+	} {
+		pendingClosure, err := queries.InsertPendingClosure(ctx, hash+".narinfo")
+		ok(t, err)
+
+		ids = append(ids, strconv.FormatInt(pendingClosure.ID, 10))
+	}
+
+	complete := func(reqCtx context.Context, batch []string) api.CompletePendingClosuresResponse {
+		t.Helper()
+
+		body, err := json.Marshal(api.CompletePendingClosuresRequest{IDs: batch})
+		ok(t, err)
+
+		req := httptest.NewRequestWithContext(reqCtx, http.MethodPost, "/api/pending_closures/complete", bytes.NewReader(body))
+		rr := httptest.NewRecorder()
+		service.CommitPendingClosuresHandler(rr, req)
+
+		if rr.Code != http.StatusOK {
+			t.Fatalf("status = %d, body: %s", rr.Code, rr.Body.String())
+		}
+
+		var result api.CompletePendingClosuresResponse
+		ok(t, json.Unmarshal(rr.Body.Bytes(), &result))
+
+		return result
+	}
+
+	result := complete(&cancelAfterChecks{Context: ctx, checks: 1}, ids)
+
+	if len(result.Completed) != 1 || result.Completed[0] != ids[0] || len(result.Failed) != 0 {
+		t.Fatalf("first attempt = %+v, want only %s completed", result, ids[0])
+	}
+
+	result = complete(ctx, ids[1:])
+
+	if len(result.Completed) != 2 || len(result.Failed) != 0 {
+		t.Errorf("second attempt = %+v, want %v completed", result, ids[1:])
+	}
+}
+
+func TestService_abortPendingClosureHandler(t *testing.T) {
+	t.Parallel()
+
+	service := createTestService(t)
+	defer service.Close()
+
+	closureHash := "11111111111111111111111111111111"
+	closureKey := closureHash + ".narinfo"
+	narKey := narKeyFor(closureHash)
+	objects := []map[string]any{
+		{"key": closureKey, "type": "narinfo", "refs": []string{narKey}},
+		{"key": narKey, "type": "nar", "refs": []string{}},
+	}
+	body, err := json.Marshal(map[string]any{
+		"closure": closureKey,
+		"objects": objects,
+	})
+	ok(t, err)
+
+	rr := testRequest(t, &TestRequest{
+		method:  "POST",
+		path:    "/api/pending_closures",
+		body:    body,
+		handler: service.CreatePendingClosureHandler,
+	})
+
+	var pendingClosureResponse server.PendingClosureResponse
+
+	err = json.Unmarshal(rr.Body.Bytes(), &pendingClosureResponse)
+	ok(t, err)
+
+	abortPath := "/api/pending_closures/" + pendingClosureResponse.ID
+	pathValues := map[string]string{"id": pendingClosureResponse.ID}
+
+	checkNoContent := checkStatusCode(http.StatusNoContent)
+	testRequest(t, &TestRequest{
+		method:        "DELETE",
+		path:          abortPath,
+		handler:       service.AbortPendingClosureHandler,
+		pathValues:    pathValues,
+		checkResponse: &checkNoContent,
+	})
+
+	// The closure is gone, so neither a second abort nor completing it works
+	checkNotFound := checkStatusCode(http.StatusNotFound)
+	testRequest(t, &TestRequest{
+		method:        "DELETE",
+		path:          abortPath,
+		handler:       service.AbortPendingClosureHandler,
+		pathValues:    pathValues,
+		checkResponse: &checkNotFound,
+	})
+	testRequest(t, &TestRequest{
+		method:        "POST",
+		path:          abortPath + "/complete",
+		handler:       service.CommitPendingClosureHandler,
+		pathValues:    pathValues,
+		checkResponse: &checkNotFound,
+	})
+}
+
 // handleMultipartUpload handles uploading a multipart object for testing.
 func handleMultipartUpload(ctx context.Context, t *testing.T, key string, pendingObject server.PendingObject, service *server.Service) {
 	t.Helper()